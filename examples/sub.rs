@@ -31,7 +31,8 @@ pub async fn main() -> Result<()> {
     if let Some(msg) = subscriber.next_message().await? {
         println!(
             "got message from the channel: {}; message = {:?}",
-            msg.channel, msg.content
+            msg.channel(),
+            msg.content()
         );
     }
 
@@ -0,0 +1,161 @@
+//! Append-only file (AOF) persistence.
+//!
+//! When enabled, every command that modifies the data set is appended to a
+//! file, encoded the same way it was received, except that expirations are
+//! rewritten as unix timestamps. On startup, the file is replayed through the
+//! normal command path to rebuild the data set.
+
+use crate::{Command, Connection, Db, Frame, Session, Shutdown};
+
+use bytes::BytesMut;
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+use tokio::time::{self, Duration};
+use tracing::{debug, error, warn};
+
+/// How often appended commands are flushed and synced to disk.
+///
+/// Like Redis' default `appendfsync everysec`, at most about a second of
+/// writes is lost if the machine crashes.
+const FSYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Handle used to append commands to the append-only file.
+///
+/// Commands are sent to a background task, which writes them out in batches
+/// and syncs the file to disk every `FSYNC_INTERVAL`. Cloning the handle is
+/// cheap. Once every handle has been dropped, the task flushes any remaining
+/// commands and exits.
+#[derive(Debug, Clone)]
+pub(crate) struct AofWriter {
+    tx: mpsc::Sender<Frame>,
+}
+
+impl AofWriter {
+    /// Opens the append-only file at `path`, creating it if needed, and spawns
+    /// the task writing to it.
+    ///
+    /// The returned `JoinHandle` completes once every `AofWriter` handle has
+    /// been dropped and all commands have been written.
+    pub(crate) async fn open(path: &Path) -> crate::Result<(AofWriter, JoinHandle<()>)> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+
+        let (tx, rx) = mpsc::channel(1024);
+        let task = tokio::spawn(run(file, rx, path.to_path_buf()));
+
+        Ok((AofWriter { tx }, task))
+    }
+
+    /// Appends `frame`, a command that modified the data set.
+    pub(crate) async fn append(&self, frame: Frame) {
+        // Sending only fails if the writer task stopped after an error, which
+        // it has already logged.
+        let _ = self.tx.send(frame).await;
+    }
+}
+
+/// Routine executed by the writer task.
+///
+/// Commands are encoded into a buffer as they arrive. The buffer is written
+/// out whenever no more commands are immediately available, and the file is
+/// synced to disk every `FSYNC_INTERVAL`.
+async fn run(mut file: File, mut rx: mpsc::Receiver<Frame>, path: PathBuf) {
+    let mut buffer = BytesMut::new();
+    let mut fsync = time::interval(FSYNC_INTERVAL);
+    let mut dirty = false;
+
+    loop {
+        tokio::select! {
+            frame = rx.recv() => {
+                let frame = match frame {
+                    Some(frame) => frame,
+                    // Every handle has been dropped.
+                    None => break,
+                };
+
+                frame.encode(&mut buffer);
+
+                // Batch together the commands that are already queued.
+                while let Ok(frame) = rx.try_recv() {
+                    frame.encode(&mut buffer);
+                }
+
+                if let Err(err) = file.write_all(&buffer).await {
+                    error!(cause = %err, path = %path.display(), "failed to write append-only file");
+                    return;
+                }
+
+                buffer.clear();
+                dirty = true;
+            }
+            _ = fsync.tick(), if dirty => {
+                if let Err(err) = file.sync_data().await {
+                    error!(cause = %err, path = %path.display(), "failed to sync append-only file");
+                    return;
+                }
+
+                dirty = false;
+            }
+        }
+    }
+
+    if let Err(err) = file.sync_data().await {
+        error!(cause = %err, path = %path.display(), "failed to sync append-only file");
+    }
+
+    debug!("append-only file writer shut down");
+}
+
+/// Replays the commands stored in the append-only file at `path` into `db`,
+/// returning the number of commands applied.
+///
+/// Commands are applied with `Command::apply`, exactly as if a client had sent
+/// them, and their responses are discarded. A missing file is treated as an
+/// empty one. If the last command was only partially written, for example
+/// because the server crashed, it is ignored.
+pub(crate) async fn replay(path: &Path, db: &Db) -> crate::Result<usize> {
+    let data = match tokio::fs::read(path).await {
+        Ok(data) => data,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(err) => return Err(err.into()),
+    };
+
+    // Commands are applied on behalf of a client which has nothing to read
+    // and no interest in the responses.
+    let mut dst = Connection::discard();
+    let (notify_shutdown, _) = broadcast::channel(1);
+    let mut shutdown = Shutdown::new(notify_shutdown.subscribe());
+    let mut session = Session::new(false);
+
+    let mut pos = 0;
+    let mut applied = 0;
+
+    while pos < data.len() {
+        let (frame, len) = match Frame::parse_from(&data[pos..]) {
+            Ok(parsed) => parsed,
+            Err(crate::frame::Error::Incomplete) => {
+                warn!(
+                    path = %path.display(),
+                    "ignoring truncated command at the end of the append-only file"
+                );
+                break;
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        pos += len;
+
+        let cmd = Command::from_frame(frame)?;
+        cmd.apply(db, &mut dst, &mut shutdown, &mut session).await?;
+        applied += 1;
+    }
+
+    Ok(applied)
+}
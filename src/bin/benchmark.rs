@@ -0,0 +1,207 @@
+//! mini-redis benchmark.
+//!
+//! Measures the throughput and latency of a mini-redis server, in the spirit
+//! of `redis-benchmark`. A number of clients run concurrently, each on its own
+//! connection, sending the same command until the requested number of
+//! requests has been sent. With `--pipeline`, each client sends several
+//! requests before waiting for their responses.
+//!
+//! The `clap` crate is used for parsing arguments.
+
+use mini_redis::{cmd::Get, cmd::Set, Connection, Frame, DEFAULT_PORT};
+
+use bytes::Bytes;
+use clap::{Parser, ValueEnum};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "mini-redis-benchmark",
+    version,
+    author,
+    about = "Benchmark a mini-redis server"
+)]
+struct Cli {
+    #[arg(id = "hostname", long, default_value = "127.0.0.1")]
+    host: String,
+
+    #[arg(long, default_value_t = DEFAULT_PORT)]
+    port: u16,
+
+    /// Number of concurrent connections
+    #[arg(long, default_value_t = 50)]
+    clients: usize,
+
+    /// Total number of requests
+    #[arg(long, default_value_t = 100_000)]
+    requests: usize,
+
+    /// Number of requests each client sends before reading the responses
+    #[arg(long, default_value_t = 1)]
+    pipeline: usize,
+
+    /// Size of the value written by SET, in bytes
+    #[arg(long, default_value_t = 3)]
+    payload_size: usize,
+
+    /// Command to benchmark
+    #[arg(long, value_enum, default_value_t = BenchCommand::Set)]
+    command: BenchCommand,
+}
+
+/// The commands that can be benchmarked.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum BenchCommand {
+    Get,
+    Set,
+}
+
+/// Key read or written by every request.
+const KEY: &str = "key:__rand_int__";
+
+#[tokio::main]
+async fn main() -> mini_redis::Result<()> {
+    // Enable logging
+    tracing_subscriber::fmt::try_init()?;
+
+    // Parse command line arguments
+    let cli = Cli::parse();
+
+    if cli.clients == 0 || cli.pipeline == 0 {
+        return Err("--clients and --pipeline must be at least 1".into());
+    }
+
+    let host = cli
+        .host
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(&cli.host)
+        .to_string();
+
+    let value = Bytes::from(vec![b'x'; cli.payload_size]);
+    let frame = match cli.command {
+        BenchCommand::Get => Get::new(KEY).into_frame(),
+        BenchCommand::Set => Set::new(KEY, value, None).into_frame(),
+    };
+
+    // Clients claim requests from this counter, so the requests are spread
+    // over the clients however fast each one is.
+    let claimed = Arc::new(AtomicUsize::new(0));
+
+    let start = Instant::now();
+
+    let mut tasks = Vec::with_capacity(cli.clients);
+    for _ in 0..cli.clients {
+        let socket = TcpStream::connect((host.as_str(), cli.port)).await?;
+        let connection = Connection::new(socket);
+
+        tasks.push(tokio::spawn(run_client(
+            connection,
+            frame.clone(),
+            claimed.clone(),
+            cli.requests,
+            cli.pipeline,
+        )));
+    }
+
+    let mut latencies = Vec::with_capacity(cli.requests);
+    for task in tasks {
+        latencies.extend(task.await??);
+    }
+
+    let elapsed = start.elapsed();
+
+    report(&cli, elapsed, latencies);
+
+    Ok(())
+}
+
+/// Sends `frame` on `connection`, `pipeline` requests at a time, until
+/// `requests` requests have been claimed across all clients.
+///
+/// Returns the latency of each request. Requests sent in the same pipeline
+/// all get the latency of the whole pipeline, as `redis-benchmark` does.
+async fn run_client(
+    mut connection: Connection,
+    frame: Frame,
+    claimed: Arc<AtomicUsize>,
+    requests: usize,
+    pipeline: usize,
+) -> mini_redis::Result<Vec<Duration>> {
+    let mut latencies = vec![];
+
+    loop {
+        let first = claimed.fetch_add(pipeline, Ordering::Relaxed);
+        if first >= requests {
+            return Ok(latencies);
+        }
+        let count = pipeline.min(requests - first);
+
+        let frames = vec![frame.clone(); count];
+        let start = Instant::now();
+
+        connection.write_frames(&frames).await?;
+
+        for _ in 0..count {
+            match connection.read_frame().await? {
+                Some(Frame::Error(msg)) => return Err(msg.into()),
+                Some(_) => {}
+                None => return Err("connection reset by server".into()),
+            }
+        }
+
+        let latency = start.elapsed();
+        latencies.resize(latencies.len() + count, latency);
+    }
+}
+
+/// Prints the throughput and latency distribution of the run.
+fn report(cli: &Cli, elapsed: Duration, mut latencies: Vec<Duration>) {
+    latencies.sort_unstable();
+
+    let seconds = elapsed.as_secs_f64();
+    let total: Duration = latencies.iter().sum();
+    let average = total / latencies.len().max(1) as u32;
+
+    let name = format!("{:?}", cli.command).to_uppercase();
+
+    println!("====== {} ======", name);
+    println!(
+        "  {} requests completed in {:.2} seconds",
+        latencies.len(),
+        seconds
+    );
+    println!("  {} parallel clients", cli.clients);
+    println!("  {} bytes payload", cli.payload_size);
+    println!("  {} requests per pipeline", cli.pipeline);
+    println!();
+    println!(
+        "throughput: {:.2} requests per second",
+        latencies.len() as f64 / seconds
+    );
+    println!(
+        "latency (msec): avg {:.3} p50 {:.3} p95 {:.3} p99 {:.3} max {:.3}",
+        millis(average),
+        millis(percentile(&latencies, 0.50)),
+        millis(percentile(&latencies, 0.95)),
+        millis(percentile(&latencies, 0.99)),
+        millis(latencies.last().copied().unwrap_or_default()),
+    );
+}
+
+/// Returns the latency below which `p` of the `sorted` latencies fall.
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::default();
+    }
+
+    let rank = (sorted.len() as f64 * p).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
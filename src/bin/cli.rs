@@ -79,11 +79,13 @@ async fn main() -> mini_redis::Result<()> {
     // Parse command line arguments
     let cli = Cli::parse();
 
-    // Get the remote address to connect to
-    let addr = format!("{}:{}", cli.host, cli.port);
+    // Get the remote address to connect to. IPv6 literals may be given with or
+    // without brackets, e.g. `::1` or `[::1]`. Passing the host and port as a
+    // pair, rather than formatting them as `host:port`, lets both forms work.
+    let host = strip_brackets(&cli.host);
 
     // Establish a connection
-    let mut client = Client::connect(&addr).await?;
+    let mut client = Client::connect((host, cli.port)).await?;
 
     // Process the requested command
     match cli.command {
@@ -136,7 +138,8 @@ async fn main() -> mini_redis::Result<()> {
             while let Some(msg) = subscriber.next_message().await? {
                 println!(
                     "got message from the channel: {}; message = {:?}",
-                    msg.channel, msg.content
+                    msg.channel(),
+                    msg.content()
                 );
             }
         }
@@ -149,3 +152,10 @@ fn duration_from_ms_str(src: &str) -> Result<Duration, ParseIntError> {
     let ms = src.parse::<u64>()?;
     Ok(Duration::from_millis(ms))
 }
+
+/// Removes the brackets around an IPv6 literal such as `[::1]`.
+fn strip_brackets(host: &str) -> &str {
+    host.strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host)
+}
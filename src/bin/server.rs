@@ -6,11 +6,20 @@
 //!
 //! The `clap` crate is used for parsing arguments.
 
-use mini_redis::{server, DEFAULT_PORT};
+use mini_redis::{
+    server::{KeyspaceEvents, ServerBuilder},
+    DEFAULT_PORT,
+};
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::net::{AddrParseError, IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tokio::net::TcpListener;
 use tokio::signal;
+use tracing_subscriber::{filter::LevelFilter, EnvFilter};
 
 #[cfg(feature = "otel")]
 // To be able to set the XrayPropagator
@@ -25,20 +34,87 @@ use opentelemetry_aws::trace::XrayPropagator;
 // The `Ext` traits are to allow the Registry to accept the
 // OpenTelemetry-specific types (such as `OpenTelemetryLayer`)
 use tracing_subscriber::{
-    fmt, layer::SubscriberExt, util::SubscriberInitExt, util::TryInitError, EnvFilter,
+    fmt, layer::SubscriberExt, util::SubscriberInitExt, util::TryInitError, Layer,
 };
 
 #[tokio::main]
 pub async fn main() -> mini_redis::Result<()> {
-    set_up_logging()?;
-
     let cli = Cli::parse();
+
+    set_up_logging(cli.log_format, cli.log_level)?;
     let port = cli.port.unwrap_or(DEFAULT_PORT);
 
-    // Bind a TCP listener
-    let listener = TcpListener::bind(&format!("127.0.0.1:{}", port)).await?;
+    // Bind a TCP listener. The address has already been validated by clap, so
+    // only errors binding the socket itself remain.
+    let listener = TcpListener::bind(SocketAddr::new(cli.bind, port)).await?;
+
+    let mut server = ServerBuilder::new();
+
+    if let Some(password) = cli.requirepass {
+        server = server.requirepass(password);
+    }
+
+    if let Some(bytes) = cli.maxmemory {
+        server = server.maxmemory(bytes);
+    }
+
+    if let Some(bytes) = cli.max_value_size {
+        server = server.max_value_size(bytes);
+    }
+
+    if let Some(lags) = cli.max_subscriber_lags {
+        server = server.max_subscriber_lags(lags);
+    }
+
+    if let Some(events) = cli.notify_keyspace_events {
+        server = server.notify_keyspace_events(events);
+    }
+
+    server = server.enable_debug(cli.enable_debug);
+
+    server = server.read_only(cli.read_only);
+
+    if let Some(path) = cli.appendonly {
+        server = server.appendonly(path);
+    }
+
+    if let Some(path) = cli.dbfilename {
+        server = server.dbfilename(path);
+    }
+
+    if let Some(ms) = cli.slowlog_threshold_ms {
+        server = server.slowlog_threshold(Duration::from_millis(ms));
+    }
+
+    if let Some(max_len) = cli.slowlog_max_len {
+        server = server.slowlog_max_len(max_len);
+    }
+
+    server = server.tcp_nodelay(cli.tcp_nodelay);
+
+    if let Some(secs) = cli.tcp_keepalive {
+        server = server.tcp_keepalive(Duration::from_secs(secs));
+    }
 
-    server::run(listener, signal::ctrl_c()).await;
+    if let Some(primary) = cli.replicaof {
+        // clap ensures exactly two values are given.
+        let port = primary[1].parse().map_err(|_| "invalid primary port")?;
+        server = server.replicaof(primary[0].clone(), port);
+    }
+
+    // Written once the server is about to run, so a server failing to start
+    // does not leave a pidfile behind.
+    if let Some(path) = &cli.pidfile {
+        write_pidfile(path, cli.overwrite_pidfile)?;
+    }
+
+    server.run(listener, signal::ctrl_c()).await;
+
+    if let Some(path) = &cli.pidfile {
+        if let Err(err) = fs::remove_file(path) {
+            tracing::error!(cause = %err, path = %path.display(), "failed to remove pidfile");
+        }
+    }
 
     Ok(())
 }
@@ -48,16 +124,158 @@ pub async fn main() -> mini_redis::Result<()> {
 struct Cli {
     #[arg(long)]
     port: Option<u16>,
+
+    /// Listen on this address, e.g. `0.0.0.0` or `::` for all interfaces
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1", value_parser = parse_ip_addr)]
+    bind: IpAddr,
+
+    /// Require clients to authenticate with this password
+    #[arg(long)]
+    requirepass: Option<String>,
+
+    /// Reject writes once the data set uses more than this many bytes
+    #[arg(long)]
+    maxmemory: Option<usize>,
+
+    /// Reject SET commands whose value is larger than this many bytes
+    #[arg(long)]
+    max_value_size: Option<usize>,
+
+    /// Disconnect subscribers falling behind this many times in a row
+    #[arg(long)]
+    max_subscriber_lags: Option<usize>,
+
+    /// Publish keyspace events for these classes, using Redis' flags, e.g. KEA
+    #[arg(long, value_name = "FLAGS", value_parser = str::parse::<KeyspaceEvents>)]
+    notify_keyspace_events: Option<KeyspaceEvents>,
+
+    /// Allow clients to use the DEBUG command, meant for testing
+    #[arg(long)]
+    enable_debug: bool,
+
+    /// Reject commands that modify the data set
+    #[arg(long)]
+    read_only: bool,
+
+    /// Log writes to this file, and replay it on startup
+    #[arg(long)]
+    appendonly: Option<PathBuf>,
+
+    /// Save snapshots to this file with SAVE and BGSAVE, and load it on startup
+    #[arg(long)]
+    dbfilename: Option<PathBuf>,
+
+    /// Replicate the primary server listening on this host and port
+    #[arg(long, num_args = 2, value_names = ["HOST", "PORT"])]
+    replicaof: Option<Vec<String>>,
+
+    /// Log a warning for commands taking longer than this many milliseconds
+    #[arg(long, value_name = "MS")]
+    slowlog_threshold_ms: Option<u64>,
+
+    /// Keep at most this many slow commands for SLOWLOG GET
+    #[arg(long)]
+    slowlog_max_len: Option<usize>,
+
+    /// Set TCP_NODELAY on client connections
+    #[arg(long)]
+    tcp_nodelay: bool,
+
+    /// Send TCP keepalive probes after client connections are idle for this
+    /// many seconds
+    #[arg(long, value_name = "SECS")]
+    tcp_keepalive: Option<u64>,
+
+    /// Format of the log lines written to stdout
+    #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+
+    /// Log events at this level and above, unless `RUST_LOG` is set
+    #[arg(long, value_name = "LEVEL", default_value_t = LevelFilter::ERROR)]
+    log_level: LevelFilter,
+
+    /// Write the process ID to this file, removed on clean shutdown
+    #[arg(long)]
+    pidfile: Option<PathBuf>,
+
+    /// Replace the pidfile if it already exists, e.g. left over by a crash
+    #[arg(long, requires = "pidfile")]
+    overwrite_pidfile: bool,
+}
+
+/// Format of the log lines, see `--log-format`.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum LogFormat {
+    /// Human-readable lines
+    Text,
+
+    /// One JSON object per line, for log aggregation
+    Json,
+}
+
+/// Parses an IP address, accepting IPv6 literals in brackets such as `[::1]`
+/// as well.
+fn parse_ip_addr(src: &str) -> Result<IpAddr, AddrParseError> {
+    src.strip_prefix('[')
+        .and_then(|src| src.strip_suffix(']'))
+        .unwrap_or(src)
+        .parse()
+}
+
+/// Writes the ID of the process to the file at `path`.
+///
+/// An existing file was likely left by another server, still running or not
+/// shut down cleanly, so it is an error unless `overwrite` is set.
+fn write_pidfile(path: &Path, overwrite: bool) -> mini_redis::Result<()> {
+    let mut options = OpenOptions::new();
+    if overwrite {
+        options.write(true).create(true).truncate(true);
+    } else {
+        options.write(true).create_new(true);
+    }
+
+    let mut file = match options.open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+            return Err(format!(
+                "pidfile {} already exists, pass --overwrite-pidfile to replace it",
+                path.display()
+            )
+            .into());
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    writeln!(file, "{}", std::process::id())?;
+
+    Ok(())
+}
+
+/// Builds the filter selecting which events are logged.
+///
+/// `RUST_LOG` takes precedence, so its finer-grained directives, such as
+/// `mini_redis::server=debug`, remain available. Otherwise, events are logged
+/// from `level` up.
+fn env_filter(level: LevelFilter) -> EnvFilter {
+    match std::env::var(EnvFilter::DEFAULT_ENV) {
+        Ok(directives) => EnvFilter::new(directives),
+        Err(_) => EnvFilter::new(level.to_string()),
+    }
 }
 
 #[cfg(not(feature = "otel"))]
-fn set_up_logging() -> mini_redis::Result<()> {
+fn set_up_logging(format: LogFormat, level: LevelFilter) -> mini_redis::Result<()> {
     // See https://docs.rs/tracing for more info
-    tracing_subscriber::fmt::try_init()
+    let subscriber = tracing_subscriber::fmt().with_env_filter(env_filter(level));
+
+    match format {
+        LogFormat::Text => subscriber.try_init(),
+        LogFormat::Json => subscriber.json().try_init(),
+    }
 }
 
 #[cfg(feature = "otel")]
-fn set_up_logging() -> Result<(), TryInitError> {
+fn set_up_logging(format: LogFormat, level: LevelFilter) -> Result<(), TryInitError> {
     // Set the global propagator to X-Ray propagator
     // Note: If you need to pass the x-amzn-trace-id across services in the same trace,
     // you will need this line. However, this requires additional code not pictured here.
@@ -81,14 +299,21 @@ fn set_up_logging() -> Result<(), TryInitError> {
     let opentelemetry = tracing_opentelemetry::layer().with_tracer(tracer);
 
     // Parse an `EnvFilter` configuration from the `RUST_LOG`
-    // environment variable.
-    let filter = EnvFilter::from_default_env();
+    // environment variable, or from `--log-level` if it is unset.
+    let filter = env_filter(level);
+
+    // Write logs to stdout in the requested format. The two layers have
+    // different types, so they are boxed to be used interchangeably.
+    let fmt_layer = match format {
+        LogFormat::Text => fmt::layer().boxed(),
+        LogFormat::Json => fmt::layer().json().boxed(),
+    };
 
     // Use the tracing subscriber `Registry`, or any other subscriber
     // that impls `LookupSpan`
     tracing_subscriber::registry()
         .with(opentelemetry)
         .with(filter)
-        .with(fmt::Layer::default())
+        .with(fmt_layer)
         .try_init()
 }
@@ -2,8 +2,12 @@
 //!
 //! Provides a blocking connect and methods for issuing the supported commands.
 
+use crate::clients::Client;
+use crate::SocketOptions;
+
 use bytes::Bytes;
-use std::time::Duration;
+use std::future::Future;
+use std::time::{Duration, SystemTime};
 use tokio::net::ToSocketAddrs;
 use tokio::runtime::Runtime;
 
@@ -18,7 +22,7 @@ pub use crate::clients::Message;
 /// Requests are issued using the various methods of `Client`.
 pub struct BlockingClient {
     /// The asynchronous `Client`.
-    inner: crate::clients::Client,
+    inner: Client,
 
     /// A `current_thread` runtime for executing operations on the asynchronous
     /// client in a blocking manner.
@@ -40,8 +44,11 @@ pub struct BlockingSubscriber {
     rt: Runtime,
 }
 
-/// The iterator returned by `Subscriber::into_iter`.
-struct SubscriberIterator {
+/// An `Iterator` yielding new messages published on subscribed channels,
+/// blocking until the next one is received.
+///
+/// Returned by `BlockingSubscriber::into_iter`.
+pub struct SubscriberIterator {
     /// The asynchronous `Subscriber`.
     inner: crate::clients::Subscriber,
 
@@ -71,15 +78,54 @@ impl BlockingClient {
     /// }
     /// ```
     pub fn connect<T: ToSocketAddrs>(addr: T) -> crate::Result<BlockingClient> {
+        BlockingClient::connect_with(Client::connect(addr))
+    }
+
+    /// Establish a connection with the Redis server located at `addr`, with
+    /// `options` applied to the socket.
+    ///
+    /// See [`Client::connect_with_options`].
+    pub fn connect_with_options<T: ToSocketAddrs>(
+        addr: T,
+        options: &SocketOptions,
+    ) -> crate::Result<BlockingClient> {
+        BlockingClient::connect_with(Client::connect_with_options(addr, options))
+    }
+
+    /// Establish a connection with the Redis server located at `addr`, giving
+    /// up once `timeout` has elapsed.
+    ///
+    /// See [`Client::connect_timeout`].
+    pub fn connect_timeout<T: ToSocketAddrs>(
+        addr: T,
+        timeout: Duration,
+    ) -> crate::Result<BlockingClient> {
+        BlockingClient::connect_with(Client::connect_timeout(addr, timeout))
+    }
+
+    /// Builds the runtime, then drives `connect` on it.
+    fn connect_with(
+        connect: impl Future<Output = crate::Result<Client>>,
+    ) -> crate::Result<BlockingClient> {
         let rt = tokio::runtime::Builder::new_current_thread()
             .enable_all()
             .build()?;
 
-        let inner = rt.block_on(crate::clients::Client::connect(addr))?;
+        let inner = rt.block_on(connect)?;
 
         Ok(BlockingClient { inner, rt })
     }
 
+    /// Authenticate the connection with `password`.
+    pub fn auth(&mut self, password: &str) -> crate::Result<()> {
+        self.rt.block_on(self.inner.auth(password))
+    }
+
+    /// Ping the server, returning `msg` if given, or `PONG`.
+    pub fn ping(&mut self, msg: Option<Bytes>) -> crate::Result<Bytes> {
+        self.rt.block_on(self.inner.ping(msg))
+    }
+
     /// Get the value of key.
     ///
     /// If the key does not exist the special value `None` is returned.
@@ -180,6 +226,188 @@ impl BlockingClient {
             .block_on(self.inner.set_expires(key, value, expiration))
     }
 
+    /// Set `key` to hold `value`, returning the value it held before.
+    pub fn set_get(&mut self, key: &str, value: Bytes) -> crate::Result<Option<Bytes>> {
+        self.rt.block_on(self.inner.set_get(key, value))
+    }
+
+    /// Returns the remaining time to live of `key`, in seconds.
+    ///
+    /// See [`Client::ttl`] for the special values returned.
+    pub fn ttl(&mut self, key: &str) -> crate::Result<i64> {
+        self.rt.block_on(self.inner.ttl(key))
+    }
+
+    /// Returns the remaining time to live of `key`, in milliseconds.
+    pub fn pttl(&mut self, key: &str) -> crate::Result<i64> {
+        self.rt.block_on(self.inner.pttl(key))
+    }
+
+    /// Returns the Unix time, in seconds, at which `key` expires.
+    pub fn expiretime(&mut self, key: &str) -> crate::Result<i64> {
+        self.rt.block_on(self.inner.expiretime(key))
+    }
+
+    /// Returns the Unix time, in milliseconds, at which `key` expires.
+    pub fn pexpiretime(&mut self, key: &str) -> crate::Result<i64> {
+        self.rt.block_on(self.inner.pexpiretime(key))
+    }
+
+    /// Overwrite part of the string stored at `key`, starting at `offset`,
+    /// returning the length of the string.
+    pub fn setrange(&mut self, key: &str, offset: u64, value: Bytes) -> crate::Result<u64> {
+        self.rt.block_on(self.inner.setrange(key, offset, value))
+    }
+
+    /// Returns the part of the string stored at `key` between the `start` and
+    /// `end` offsets, both included.
+    pub fn getrange(&mut self, key: &str, start: i64, end: i64) -> crate::Result<Bytes> {
+        self.rt.block_on(self.inner.getrange(key, start, end))
+    }
+
+    /// Set the bit at `offset` in the string stored at `key`, returning the
+    /// bit's previous value.
+    pub fn setbit(&mut self, key: &str, offset: u32, value: bool) -> crate::Result<bool> {
+        self.rt.block_on(self.inner.setbit(key, offset, value))
+    }
+
+    /// Returns the bit at `offset` in the string stored at `key`.
+    pub fn getbit(&mut self, key: &str, offset: u32) -> crate::Result<bool> {
+        self.rt.block_on(self.inner.getbit(key, offset))
+    }
+
+    /// Returns the number of bits set in the string stored at `key`.
+    pub fn bitcount(&mut self, key: &str, range: Option<(i64, i64)>) -> crate::Result<u64> {
+        self.rt.block_on(self.inner.bitcount(key, range))
+    }
+
+    /// Set `key` to hold `value` only if it currently holds `expected`,
+    /// returning whether the value was set.
+    pub fn cas(&mut self, key: &str, expected: Bytes, value: Bytes) -> crate::Result<bool> {
+        self.rt.block_on(self.inner.cas(key, expected, value))
+    }
+
+    /// Serialize the value stored at `key`, or return `None` if the key does
+    /// not exist.
+    pub fn dump(&mut self, key: &str) -> crate::Result<Option<Bytes>> {
+        self.rt.block_on(self.inner.dump(key))
+    }
+
+    /// Create `key` holding the value serialized in `payload` by `dump`,
+    /// expiring after `ttl` if given.
+    pub fn restore(
+        &mut self,
+        key: &str,
+        ttl: Option<Duration>,
+        payload: Bytes,
+        replace: bool,
+    ) -> crate::Result<()> {
+        self.rt
+            .block_on(self.inner.restore(key, ttl, payload, replace))
+    }
+
+    /// Adds `members` to the set stored at `key`, returning the number of
+    /// members that were not already in the set.
+    pub fn sadd(&mut self, key: &str, members: Vec<Bytes>) -> crate::Result<u64> {
+        self.rt.block_on(self.inner.sadd(key, members))
+    }
+
+    /// Removes `members` from the set stored at `key`, returning the number
+    /// of members that were in the set.
+    pub fn srem(&mut self, key: &str, members: Vec<Bytes>) -> crate::Result<u64> {
+        self.rt.block_on(self.inner.srem(key, members))
+    }
+
+    /// Returns the number of members of the set stored at `key`.
+    pub fn scard(&mut self, key: &str) -> crate::Result<u64> {
+        self.rt.block_on(self.inner.scard(key))
+    }
+
+    /// Returns `true` if `member` is in the set stored at `key`.
+    pub fn sismember(&mut self, key: &str, member: Bytes) -> crate::Result<bool> {
+        self.rt.block_on(self.inner.sismember(key, member))
+    }
+
+    /// Returns the members of the set stored at `key`, in no particular
+    /// order.
+    pub fn smembers(&mut self, key: &str) -> crate::Result<Vec<Bytes>> {
+        self.rt.block_on(self.inner.smembers(key))
+    }
+
+    /// Returns the members of every set stored at `keys`.
+    pub fn sinter(&mut self, keys: Vec<String>) -> crate::Result<Vec<Bytes>> {
+        self.rt.block_on(self.inner.sinter(keys))
+    }
+
+    /// Returns the members of any set stored at `keys`.
+    pub fn sunion(&mut self, keys: Vec<String>) -> crate::Result<Vec<Bytes>> {
+        self.rt.block_on(self.inner.sunion(keys))
+    }
+
+    /// Returns the members of the set stored at the first of `keys` that are
+    /// in none of the sets stored at the others.
+    pub fn sdiff(&mut self, keys: Vec<String>) -> crate::Result<Vec<Bytes>> {
+        self.rt.block_on(self.inner.sdiff(keys))
+    }
+
+    /// Stores the intersection of the sets stored at `keys` at
+    /// `destination`, returning the number of members stored.
+    pub fn sinterstore(&mut self, destination: &str, keys: Vec<String>) -> crate::Result<u64> {
+        self.rt.block_on(self.inner.sinterstore(destination, keys))
+    }
+
+    /// Stores the union of the sets stored at `keys` at `destination`,
+    /// returning the number of members stored.
+    pub fn sunionstore(&mut self, destination: &str, keys: Vec<String>) -> crate::Result<u64> {
+        self.rt.block_on(self.inner.sunionstore(destination, keys))
+    }
+
+    /// Stores the difference of the sets stored at `keys` at `destination`,
+    /// returning the number of members stored.
+    pub fn sdiffstore(&mut self, destination: &str, keys: Vec<String>) -> crate::Result<u64> {
+        self.rt.block_on(self.inner.sdiffstore(destination, keys))
+    }
+
+    /// Adds `members`, with their scores, to the sorted set stored at `key`,
+    /// returning the number of members that were not already in the set.
+    pub fn zadd(&mut self, key: &str, members: Vec<(f64, String)>) -> crate::Result<u64> {
+        self.rt.block_on(self.inner.zadd(key, members))
+    }
+
+    /// Returns the score of `member` in the sorted set stored at `key`, or
+    /// `None` if the key or the member does not exist.
+    pub fn zscore(&mut self, key: &str, member: &str) -> crate::Result<Option<f64>> {
+        self.rt.block_on(self.inner.zscore(key, member))
+    }
+
+    /// Returns the members of the sorted set stored at `key` ranked between
+    /// `start` and `stop`, both included, ordered by score.
+    pub fn zrange(&mut self, key: &str, start: i64, stop: i64) -> crate::Result<Vec<String>> {
+        self.rt.block_on(self.inner.zrange(key, start, stop))
+    }
+
+    /// Same as `zrange`, but each member is returned along with its score.
+    pub fn zrange_withscores(
+        &mut self,
+        key: &str,
+        start: i64,
+        stop: i64,
+    ) -> crate::Result<Vec<(String, f64)>> {
+        self.rt
+            .block_on(self.inner.zrange_withscores(key, start, stop))
+    }
+
+    /// Increment the number stored at `key` by `increment`, returning the new
+    /// value.
+    pub fn incrbyfloat(&mut self, key: &str, increment: f64) -> crate::Result<f64> {
+        self.rt.block_on(self.inner.incrbyfloat(key, increment))
+    }
+
+    /// Returns the current time of the server.
+    pub fn time(&mut self) -> crate::Result<SystemTime> {
+        self.rt.block_on(self.inner.time())
+    }
+
     /// Posts `message` to the given `channel`.
     ///
     /// Returns the number of subscribers currently listening on the channel.
@@ -219,6 +447,18 @@ impl BlockingClient {
             rt: self.rt,
         })
     }
+
+    /// Subscribes the client to the specified glob-style patterns.
+    ///
+    /// Like `subscribe`, but messages published to any channel matching one
+    /// of the patterns are received.
+    pub fn psubscribe(self, patterns: Vec<String>) -> crate::Result<BlockingSubscriber> {
+        let subscriber = self.rt.block_on(self.inner.psubscribe(patterns))?;
+        Ok(BlockingSubscriber {
+            inner: subscriber,
+            rt: self.rt,
+        })
+    }
 }
 
 impl BlockingSubscriber {
@@ -227,6 +467,17 @@ impl BlockingSubscriber {
         self.inner.get_subscribed()
     }
 
+    /// Returns the set of patterns currently subscribed to.
+    pub fn get_subscribed_patterns(&self) -> &[String] {
+        self.inner.get_subscribed_patterns()
+    }
+
+    /// Returns the number of subscriptions the connection has, as reported by
+    /// the server when the subscriptions last changed.
+    pub fn subscription_count(&self) -> u64 {
+        self.inner.subscription_count()
+    }
+
     /// Receive the next message published on a subscribed channel, waiting if
     /// necessary.
     ///
@@ -235,26 +486,36 @@ impl BlockingSubscriber {
         self.rt.block_on(self.inner.next_message())
     }
 
-    /// Convert the subscriber into an `Iterator` yielding new messages published
-    /// on subscribed channels.
-    pub fn into_iter(self) -> impl Iterator<Item = crate::Result<Message>> {
-        SubscriberIterator {
-            inner: self.inner,
-            rt: self.rt,
-        }
-    }
-
     /// Subscribe to a list of new channels
     pub fn subscribe(&mut self, channels: &[String]) -> crate::Result<()> {
         self.rt.block_on(self.inner.subscribe(channels))
     }
 
+    /// Subscribe to a list of new patterns
+    pub fn psubscribe(&mut self, patterns: &[String]) -> crate::Result<()> {
+        self.rt.block_on(self.inner.psubscribe(patterns))
+    }
+
     /// Unsubscribe to a list of new channels
     pub fn unsubscribe(&mut self, channels: &[String]) -> crate::Result<()> {
         self.rt.block_on(self.inner.unsubscribe(channels))
     }
 }
 
+impl IntoIterator for BlockingSubscriber {
+    type Item = crate::Result<Message>;
+    type IntoIter = SubscriberIterator;
+
+    /// Convert the subscriber into an `Iterator` yielding new messages published
+    /// on subscribed channels.
+    fn into_iter(self) -> SubscriberIterator {
+        SubscriberIterator {
+            inner: self.inner,
+            rt: self.rt,
+        }
+    }
+}
+
 impl Iterator for SubscriberIterator {
     type Item = crate::Result<Message>;
 
@@ -1,15 +1,32 @@
 use crate::clients::Client;
-use crate::Result;
+use crate::{MiniRedisError, Result};
 
 use bytes::Bytes;
+use std::io::{Error, ErrorKind};
+use std::net::SocketAddr;
+use std::time::Duration;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
 use tokio::sync::oneshot;
+use tokio::time;
 
 // Enum used to message pass the requested command from the `BufferedClient` handle
 #[derive(Debug)]
 enum Command {
     Get(String),
     Set(String, Bytes),
+    Publish(String, Bytes),
+    Ping(Option<Bytes>),
+    Flush,
+}
+
+// Response to a `Command`, sent back from the connection task. Each command
+// has its own variant, so replies of different types stay distinct.
+#[derive(Debug)]
+enum Response {
+    Value(Option<Bytes>),
+    Done,
+    Count(u64),
+    Pong(Bytes),
 }
 
 // Message type sent over the channel to the connection task.
@@ -19,19 +36,180 @@ enum Command {
 // `oneshot::Sender` is a channel type that sends a **single** value. It is used
 // here to send the response received from the connection back to the original
 // requester.
-type Message = (Command, oneshot::Sender<Result<Option<Bytes>>>);
+type Message = (Command, oneshot::Sender<Result<Response>>);
+
+/// Controls when `SET` requests sent through a `BufferedClient` are written to
+/// the server.
+///
+/// By default, every request is written as soon as the connection task
+/// receives it. A policy that buffers writes lets the connection task send
+/// several `SET` commands together, pipelined, which amortizes the cost of
+/// writing to the socket in write heavy workloads.
+///
+/// Buffered writes are flushed once `max_writes` are pending, once the oldest
+/// one has been pending for `interval`, before any other request is sent, and
+/// when the last `BufferedClient` handle is dropped. If neither limit is set,
+/// writes are not buffered.
+///
+/// A buffered `SET` returns `Ok` as soon as it has been buffered, before it is
+/// written to the server. If writing it later fails, the error is returned by
+/// the next `set` or `flush` made through the `BufferedClient`. Other requests
+/// only return their own errors.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FlushPolicy {
+    /// Number of pending writes that triggers a flush
+    max_writes: Option<usize>,
+
+    /// Maximum amount of time a write may be pending
+    interval: Option<Duration>,
+}
+
+impl FlushPolicy {
+    /// Create a policy which writes every request immediately.
+    pub fn immediate() -> FlushPolicy {
+        FlushPolicy::default()
+    }
+
+    /// Flush once `max_writes` writes are pending.
+    pub fn max_writes(mut self, max_writes: usize) -> FlushPolicy {
+        self.max_writes = Some(max_writes);
+        self
+    }
+
+    /// Flush once the oldest pending write has been pending for `interval`.
+    pub fn interval(mut self, interval: Duration) -> FlushPolicy {
+        self.interval = Some(interval);
+        self
+    }
+
+    /// Returns `true` if writes are buffered.
+    fn buffers_writes(&self) -> bool {
+        self.max_writes.map(|max| max > 1).unwrap_or(false) || self.interval.is_some()
+    }
+}
+
+/// The connection a connection task forwards commands to.
+struct Conn {
+    /// The connection, or `None` once it failed and before it is
+    /// re-established.
+    client: Option<Client>,
+
+    /// Addresses to reconnect to once the connection failed. If `None`, the
+    /// connection is used until the `BufferedClient` is dropped, even if it
+    /// failed.
+    reconnect: Option<Vec<SocketAddr>>,
+}
+
+impl Conn {
+    /// Returns the connection, re-establishing it first if it failed.
+    ///
+    /// A new connection is checked with a `PING` before it is used.
+    async fn get(&mut self) -> Result<&mut Client> {
+        if self.client.is_none() {
+            let addrs = self.reconnect.as_deref().unwrap_or_default();
+            let mut client = Client::connect(addrs).await?;
+            client.ping(None).await?;
+            self.client = Some(client);
+        }
+
+        Ok(self.client.as_mut().unwrap())
+    }
+
+    /// Drops the connection if `result` shows it failed, and it can be
+    /// re-established.
+    fn check<T>(&mut self, result: &Result<T>) {
+        if let Err(err) = result {
+            if self.reconnect.is_some() && MiniRedisError::is_connection_error(err) {
+                self.client = None;
+            }
+        }
+    }
+}
 
 /// Receive commands sent through the channel and forward them to client. The
 /// response is returned back to the caller via a `oneshot`.
-async fn run(mut client: Client, mut rx: Receiver<Message>) {
-    // Repeatedly pop messages from the channel. A return value of `None`
-    // indicates that all `BufferedClient` handles have dropped and there will never be
-    // another message sent on the channel.
-    while let Some((cmd, tx)) = rx.recv().await {
-        // The command is forwarded to the connection
+async fn run(mut conn: Conn, mut rx: Receiver<Message>, policy: FlushPolicy) {
+    // `SET` commands buffered according to `policy`, and not yet written.
+    let mut pending = vec![];
+
+    // When the oldest pending write must be flushed, if the policy has an
+    // interval.
+    let mut deadline = None;
+
+    // Error from flushing pending writes, reported to the next `SET` or
+    // flush.
+    let mut flush_error = None;
+
+    loop {
+        // Wait for the next message, flushing pending writes if their
+        // deadline is reached first.
+        let message = match deadline {
+            Some(when) => tokio::select! {
+                message = rx.recv() => message,
+                _ = time::sleep_until(when) => {
+                    deadline = None;
+                    flush(&mut conn, &mut pending, &mut flush_error).await;
+                    continue;
+                }
+            },
+            None => rx.recv().await,
+        };
+
+        // A value of `None` indicates that all `BufferedClient` handles have
+        // dropped and there will never be another message sent on the channel.
+        let (cmd, tx) = match message {
+            Some(message) => message,
+            None => break,
+        };
+
+        // Only writes learn whether earlier writes failed. A `GET` is not
+        // answered with the failure of a `SET` issued by another task.
+        let reports_flush = matches!(cmd, Command::Set(..) | Command::Flush);
+
         let response = match cmd {
-            Command::Get(key) => client.get(&key).await,
-            Command::Set(key, value) => client.set(&key, value).await.map(|_| None),
+            Command::Set(key, value) if policy.buffers_writes() => {
+                pending.push((key, value));
+
+                let full = policy
+                    .max_writes
+                    .map(|max| pending.len() >= max)
+                    .unwrap_or(false);
+
+                if full {
+                    deadline = None;
+                    flush(&mut conn, &mut pending, &mut flush_error).await;
+                } else if deadline.is_none() {
+                    deadline = policy
+                        .interval
+                        .map(|interval| time::Instant::now() + interval);
+                }
+
+                Ok(Response::Done)
+            }
+            cmd => {
+                // Pending writes are sent first, so requests observe them.
+                deadline = None;
+                flush(&mut conn, &mut pending, &mut flush_error).await;
+
+                // The command is forwarded to the connection
+                let response = match cmd {
+                    Command::Flush => Ok(Response::Done),
+                    cmd => forward(&mut conn, cmd).await,
+                };
+
+                conn.check(&response);
+                response
+            }
+        };
+
+        // A failure to flush earlier writes takes precedence, as the
+        // writer would otherwise never learn of it.
+        let response = match flush_error.take() {
+            Some(err) if reports_flush => Err(err),
+            err => {
+                flush_error = err;
+                response
+            }
         };
 
         // Send the response back to the caller.
@@ -40,11 +218,60 @@ async fn run(mut client: Client, mut rx: Receiver<Message>) {
         // before receiving the message. This is a normal runtime event.
         let _ = tx.send(response);
     }
+
+    // Write anything still pending before the connection is closed. There is
+    // no one left to report an error to.
+    flush(&mut conn, &mut pending, &mut flush_error).await;
+}
+
+/// Sends `cmd` to the server and returns its response.
+async fn forward(conn: &mut Conn, cmd: Command) -> Result<Response> {
+    let client = conn.get().await?;
+
+    match cmd {
+        Command::Get(key) => client.get(&key).await.map(Response::Value),
+        Command::Set(key, value) => client.set(&key, value).await.map(|_| Response::Done),
+        Command::Publish(channel, message) => {
+            client.publish(&channel, message).await.map(Response::Count)
+        }
+        Command::Ping(msg) => client.ping(msg).await.map(Response::Pong),
+        Command::Flush => Ok(Response::Done),
+    }
+}
+
+/// Writes the pending `SET` commands to the server, storing the first error
+/// in `flush_error` unless an earlier error has not been reported yet.
+async fn flush(
+    conn: &mut Conn,
+    pending: &mut Vec<(String, Bytes)>,
+    flush_error: &mut Option<crate::Error>,
+) {
+    if pending.is_empty() {
+        return;
+    }
+
+    let result = match conn.get().await {
+        Ok(client) => client.set_pipelined(std::mem::take(pending)).await,
+        Err(err) => {
+            pending.clear();
+            Err(err)
+        }
+    };
+
+    conn.check(&result);
+
+    if let Err(err) = result {
+        flush_error.get_or_insert(err);
+    }
 }
 
 #[derive(Clone)]
 pub struct BufferedClient {
     tx: Sender<Message>,
+
+    /// Maximum amount of time to wait for the connection task to respond to a
+    /// request. `None` waits forever.
+    timeout: Option<Duration>,
 }
 
 impl BufferedClient {
@@ -65,15 +292,59 @@ impl BufferedClient {
     /// The returned `BufferedClient` handle may be cloned before passing the new handle to
     /// separate tasks.
     pub fn buffer(client: Client) -> BufferedClient {
+        BufferedClient::with_policy(client, FlushPolicy::immediate())
+    }
+
+    /// Create a new client request buffer which writes `SET` requests to the
+    /// server according to `policy`.
+    ///
+    /// See `buffer` for details.
+    pub fn with_policy(client: Client, policy: FlushPolicy) -> BufferedClient {
+        let conn = Conn {
+            client: Some(client),
+            reconnect: None,
+        };
+
+        BufferedClient::spawn(conn, policy)
+    }
+
+    /// Create a new client request buffer which re-establishes the connection
+    /// to `addrs` if it fails.
+    ///
+    /// The request that fails is not retried, its error is returned. The
+    /// connection is re-established before the next request is sent.
+    pub(crate) fn reconnecting(client: Client, addrs: Vec<SocketAddr>) -> BufferedClient {
+        let conn = Conn {
+            client: Some(client),
+            reconnect: Some(addrs),
+        };
+
+        BufferedClient::spawn(conn, FlushPolicy::immediate())
+    }
+
+    fn spawn(conn: Conn, policy: FlushPolicy) -> BufferedClient {
         // Setting the message limit to a hard coded value of 32. in a real-app, the
         // buffer size should be configurable, but we don't need to do that here.
         let (tx, rx) = channel(32);
 
         // Spawn a task to process requests for the connection.
-        tokio::spawn(async move { run(client, rx).await });
+        tokio::spawn(async move { run(conn, rx, policy).await });
 
         // Return the `BufferedClient` handle.
-        BufferedClient { tx }
+        BufferedClient { tx, timeout: None }
+    }
+
+    /// Set the maximum amount of time a request may take.
+    ///
+    /// The timeout covers both waiting for room in the request buffer and
+    /// waiting for the connection task to respond. If it elapses, the request
+    /// fails with an error of kind `ErrorKind::TimedOut`. This prevents callers
+    /// from hanging forever when the connection to the server is stuck.
+    ///
+    /// By default, requests do not time out.
+    pub fn timeout(mut self, timeout: Duration) -> BufferedClient {
+        self.timeout = Some(timeout);
+        self
     }
 
     /// Get the value of a key.
@@ -84,16 +355,9 @@ impl BufferedClient {
         // Initialize a new `Get` command to send via the channel.
         let get = Command::Get(key.into());
 
-        // Initialize a new oneshot to be used to receive the response back from the connection.
-        let (tx, rx) = oneshot::channel();
-
-        // Send the request
-        self.tx.send((get, tx)).await?;
-
-        // Await the response
-        match rx.await {
-            Ok(res) => res,
-            Err(err) => Err(err.into()),
+        match self.request(get).await? {
+            Response::Value(value) => Ok(value),
+            response => Err(unexpected(response)),
         }
     }
 
@@ -101,20 +365,88 @@ impl BufferedClient {
     ///
     /// Same as `Client::set` but requests are **buffered** until the associated
     /// connection has the ability to send the request
+    ///
+    /// If the `FlushPolicy` buffers writes, `Ok` is returned once the request
+    /// is buffered, before it is written to the server. An error writing
+    /// earlier buffered requests is returned instead, see `FlushPolicy`.
     pub async fn set(&mut self, key: &str, value: Bytes) -> Result<()> {
         // Initialize a new `Set` command to send via the channel.
         let set = Command::Set(key.into(), value);
 
+        match self.request(set).await? {
+            Response::Done => Ok(()),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Posts `message` to the given `channel`.
+    ///
+    /// Same as `Client::publish` but requests are **buffered** until the
+    /// associated connection has the ability to send the request.
+    pub async fn publish(&mut self, channel: &str, message: Bytes) -> Result<u64> {
+        // Initialize a new `Publish` command to send via the channel.
+        let publish = Command::Publish(channel.into(), message);
+
+        match self.request(publish).await? {
+            Response::Count(count) => Ok(count),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Ping the server.
+    ///
+    /// Same as `Client::ping` but requests are **buffered** until the
+    /// associated connection has the ability to send the request.
+    pub async fn ping(&mut self, msg: Option<Bytes>) -> Result<Bytes> {
+        // Initialize a new `Ping` command to send via the channel.
+        let ping = Command::Ping(msg);
+
+        match self.request(ping).await? {
+            Response::Pong(pong) => Ok(pong),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Write any buffered `SET` requests to the server.
+    ///
+    /// Returns an error if writing buffered requests failed, now or since the
+    /// last `set` or `flush`.
+    pub async fn flush(&mut self) -> Result<()> {
+        match self.request(Command::Flush).await? {
+            Response::Done => Ok(()),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Send `cmd` to the connection task and wait for the response, giving up
+    /// once the configured timeout elapses.
+    async fn request(&mut self, cmd: Command) -> Result<Response> {
         // Initialize a new oneshot to be used to receive the response back from the connection.
         let (tx, rx) = oneshot::channel();
 
-        // Send the request
-        self.tx.send((set, tx)).await?;
+        let round_trip = async {
+            // Send the request
+            self.tx.send((cmd, tx)).await?;
+
+            // Await the response
+            match rx.await {
+                Ok(res) => res,
+                Err(err) => Err(err.into()),
+            }
+        };
 
-        // Await the response
-        match rx.await {
-            Ok(res) => res.map(|_| ()),
-            Err(err) => Err(err.into()),
+        match self.timeout {
+            Some(timeout) => match time::timeout(timeout, round_trip).await {
+                Ok(res) => res,
+                Err(_) => Err(Error::new(ErrorKind::TimedOut, "request timed out").into()),
+            },
+            None => round_trip.await,
         }
     }
 }
+
+/// Error returned when the connection task replies to a command with the
+/// response of another command. This indicates a bug in the connection task.
+fn unexpected(response: Response) -> crate::Error {
+    format!("unexpected response: {:?}", response).into()
+}
@@ -2,14 +2,19 @@
 //!
 //! Provides an async connect and methods for issuing the supported commands.
 
-use crate::cmd::{Get, Ping, Publish, Set, Subscribe, Unsubscribe};
-use crate::{Connection, Frame};
+use crate::cmd::{
+    Auth, Bitcount, Cas, Dump, Get, Getbit, Getrange, Incrbyfloat, Ping, Psubscribe, Publish,
+    Restore, Sadd, Scard, Set, SetOp, SetOpKind, Setbit, Setrange, Sismember, Smembers, Srem,
+    Subscribe, Time, Ttl, TtlKind, Unsubscribe, Zadd, Zrange, Zscore,
+};
+use crate::{Connection, Frame, MiniRedisError, SocketOptions};
 
 use async_stream::try_stream;
 use bytes::Bytes;
 use std::io::{Error, ErrorKind};
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio::time;
 use tokio_stream::Stream;
 use tracing::{debug, instrument};
 
@@ -42,13 +47,67 @@ pub struct Subscriber {
 
     /// The set of channels to which the `Subscriber` is currently subscribed.
     subscribed_channels: Vec<String>,
+
+    /// The set of patterns to which the `Subscriber` is currently subscribed.
+    subscribed_patterns: Vec<String>,
+
+    /// Number of subscriptions the connection has, as last reported by the
+    /// server.
+    subscription_count: u64,
 }
 
-/// A message received on a subscribed channel.
+/// A message received by a `Subscriber`.
 #[derive(Debug, Clone)]
-pub struct Message {
-    pub channel: String,
-    pub content: Bytes,
+pub enum Message {
+    /// A message published to a channel the subscriber is subscribed to.
+    Channel { channel: String, content: Bytes },
+
+    /// A message published to a channel matching a pattern the subscriber is
+    /// subscribed to.
+    Pattern {
+        pattern: String,
+        channel: String,
+        content: Bytes,
+    },
+}
+
+impl Message {
+    /// Returns the channel the message was published to.
+    pub fn channel(&self) -> &str {
+        match self {
+            Message::Channel { channel, .. } => channel,
+            Message::Pattern { channel, .. } => channel,
+        }
+    }
+
+    /// Returns the content of the message.
+    pub fn content(&self) -> &Bytes {
+        match self {
+            Message::Channel { content, .. } => content,
+            Message::Pattern { content, .. } => content,
+        }
+    }
+
+    /// Returns the pattern that matched the channel, if the message was
+    /// received through a pattern subscription.
+    pub fn pattern(&self) -> Option<&str> {
+        match self {
+            Message::Channel { .. } => None,
+            Message::Pattern { pattern, .. } => Some(pattern),
+        }
+    }
+
+    /// Returns the channel and content of the message, the fields of
+    /// `Message` before pattern subscriptions were supported.
+    #[deprecated(note = "match on `Message`, or use `channel` and `content`")]
+    pub fn into_parts(self) -> (String, Bytes) {
+        match self {
+            Message::Channel { channel, content } => (channel, content),
+            Message::Pattern {
+                channel, content, ..
+            } => (channel, content),
+        }
+    }
 }
 
 impl Client {
@@ -87,6 +146,104 @@ impl Client {
         Ok(Client { connection })
     }
 
+    /// Establish a connection with the Redis server located at `addr`, then
+    /// apply `options` to the socket.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use mini_redis::{clients::Client, SocketOptions};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let options = SocketOptions::new().nodelay(true);
+    ///     let client = Client::connect_with_options("localhost:6379", &options)
+    ///         .await
+    ///         .unwrap();
+    /// # drop(client);
+    /// }
+    /// ```
+    pub async fn connect_with_options<T: ToSocketAddrs>(
+        addr: T,
+        options: &SocketOptions,
+    ) -> crate::Result<Client> {
+        let socket = TcpStream::connect(addr).await?;
+        options.apply(&socket)?;
+
+        let connection = Connection::new(socket);
+
+        Ok(Client { connection })
+    }
+
+    /// Establish a connection with the Redis server located at `addr`, giving
+    /// up once `timeout` has elapsed.
+    ///
+    /// `connect` waits for as long as the operating system keeps trying to
+    /// establish the connection, which may take more than a minute when the
+    /// host is unreachable. The timeout covers the DNS lookup and the TCP
+    /// handshake. When it elapses, an error of kind `TimedOut` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    /// use std::time::Duration;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let client = Client::connect_timeout("localhost:6379", Duration::from_secs(1))
+    ///         .await
+    ///         .unwrap();
+    /// # drop(client);
+    /// }
+    /// ```
+    pub async fn connect_timeout<T: ToSocketAddrs>(
+        addr: T,
+        timeout: Duration,
+    ) -> crate::Result<Client> {
+        match time::timeout(timeout, Client::connect(addr)).await {
+            Ok(res) => res,
+            Err(_) => Err(Error::new(
+                ErrorKind::TimedOut,
+                format!("connect timed out after {:?}", timeout),
+            )
+            .into()),
+        }
+    }
+
+    /// Authenticate the connection with `password`.
+    ///
+    /// This is required before issuing other commands if the server is
+    /// configured with a password. An error is returned if the password is
+    /// wrong.
+    ///
+    /// # Examples
+    ///
+    /// Demonstrates basic usage.
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     client.auth("secret").await.unwrap();
+    /// }
+    /// ```
+    #[instrument(skip(self, password))]
+    pub async fn auth(&mut self, password: &str) -> crate::Result<()> {
+        let frame = Auth::new(password).into_frame();
+
+        // The frame holds the password, so it is not logged.
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(response) if response == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
     /// Ping to the server.
     ///
     /// Returns PONG if no argument is provided, otherwise
@@ -105,26 +262,592 @@ impl Client {
     /// async fn main() {
     ///     let mut client = Client::connect("localhost:6379").await.unwrap();
     ///
-    ///     let pong = client.ping(None).await.unwrap();
-    ///     assert_eq!(b"PONG", &pong[..]);
+    ///     let pong = client.ping(None).await.unwrap();
+    ///     assert_eq!(b"PONG", &pong[..]);
+    /// }
+    /// ```
+    #[instrument(skip(self))]
+    pub async fn ping(&mut self, msg: Option<Bytes>) -> crate::Result<Bytes> {
+        let frame = Ping::new(msg).into_frame();
+        debug!(request = ?frame);
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(value) => Ok(value.into()),
+            Frame::Bulk(value) => Ok(value),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Get the value of key.
+    ///
+    /// If the key does not exist the special value `None` is returned.
+    ///
+    /// # Examples
+    ///
+    /// Demonstrates basic usage.
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     let val = client.get("foo").await.unwrap();
+    ///     println!("Got = {:?}", val);
+    /// }
+    /// ```
+    #[instrument(skip(self))]
+    pub async fn get(&mut self, key: &str) -> crate::Result<Option<Bytes>> {
+        // Create a `Get` command for the `key` and convert it to a frame.
+        let frame = Get::new(key).into_frame();
+
+        debug!(request = ?frame);
+
+        // Write the frame to the socket. This writes the full frame to the
+        // socket, waiting if necessary.
+        self.connection.write_frame(&frame).await?;
+
+        // Wait for the response from the server
+        //
+        // Both `Simple` and `Bulk` frames are accepted. `Null` represents the
+        // key not being present and `None` is returned.
+        match self.read_response().await? {
+            Frame::Simple(value) => Ok(Some(value.into())),
+            Frame::Bulk(value) => Ok(Some(value)),
+            Frame::Null => Ok(None),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Set `key` to hold the given `value`.
+    ///
+    /// The `value` is associated with `key` until it is overwritten by the next
+    /// call to `set` or it is removed.
+    ///
+    /// If key already holds a value, it is overwritten. Any previous time to
+    /// live associated with the key is discarded on successful SET operation.
+    ///
+    /// # Examples
+    ///
+    /// Demonstrates basic usage.
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     client.set("foo", "bar".into()).await.unwrap();
+    ///
+    ///     // Getting the value immediately works
+    ///     let val = client.get("foo").await.unwrap().unwrap();
+    ///     assert_eq!(val, "bar");
+    /// }
+    /// ```
+    #[instrument(skip(self))]
+    pub async fn set(&mut self, key: &str, value: Bytes) -> crate::Result<()> {
+        // Create a `Set` command and pass it to `set_cmd`. A separate method is
+        // used to set a value with an expiration. The common parts of both
+        // functions are implemented by `set_cmd`.
+        self.set_cmd(Set::new(key, value, None)).await
+    }
+
+    /// Set `key` to hold the given `value`. The value expires after `expiration`
+    ///
+    /// The `value` is associated with `key` until one of the following:
+    /// - it expires.
+    /// - it is overwritten by the next call to `set`.
+    /// - it is removed.
+    ///
+    /// If key already holds a value, it is overwritten. Any previous time to
+    /// live associated with the key is discarded on a successful SET operation.
+    ///
+    /// # Examples
+    ///
+    /// Demonstrates basic usage. This example is not **guaranteed** to always
+    /// work as it relies on time based logic and assumes the client and server
+    /// stay relatively synchronized in time. The real world tends to not be so
+    /// favorable.
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    /// use tokio::time;
+    /// use std::time::Duration;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let ttl = Duration::from_millis(500);
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     client.set_expires("foo", "bar".into(), ttl).await.unwrap();
+    ///
+    ///     // Getting the value immediately works
+    ///     let val = client.get("foo").await.unwrap().unwrap();
+    ///     assert_eq!(val, "bar");
+    ///
+    ///     // Wait for the TTL to expire
+    ///     time::sleep(ttl).await;
+    ///
+    ///     let val = client.get("foo").await.unwrap();
+    ///     assert!(val.is_some());
+    /// }
+    /// ```
+    #[instrument(skip(self))]
+    pub async fn set_expires(
+        &mut self,
+        key: &str,
+        value: Bytes,
+        expiration: Duration,
+    ) -> crate::Result<()> {
+        // Create a `Set` command and pass it to `set_cmd`. A separate method is
+        // used to set a value with an expiration. The common parts of both
+        // functions are implemented by `set_cmd`.
+        self.set_cmd(Set::new(key, value, Some(expiration))).await
+    }
+
+    /// Set `key` to hold the given `value`, returning the value it held before.
+    ///
+    /// The previous value is read and replaced atomically. If the key did not
+    /// exist, `None` is returned. Any previous time to live associated with
+    /// the key is discarded.
+    ///
+    /// # Examples
+    ///
+    /// Demonstrates basic usage.
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     client.set("foo", "bar".into()).await.unwrap();
+    ///
+    ///     let prev = client.set_get("foo", "baz".into()).await.unwrap();
+    ///     assert_eq!(prev.unwrap(), "bar");
+    /// }
+    /// ```
+    #[instrument(skip(self))]
+    pub async fn set_get(&mut self, key: &str, value: Bytes) -> crate::Result<Option<Bytes>> {
+        // Convert the `Set` command into a frame
+        let frame = Set::new(key, value, None).with_get().into_frame();
+
+        debug!(request = ?frame);
+
+        // Write the frame to the socket.
+        self.connection.write_frame(&frame).await?;
+
+        // Wait for the response from the server. With the `GET` option, the
+        // server responds with the previous value, or `Null` if there was
+        // none.
+        match self.read_response().await? {
+            Frame::Bulk(value) => Ok(Some(value)),
+            Frame::Null => Ok(None),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Returns the remaining time to live of `key`, in seconds.
+    ///
+    /// Returns `-2` if the key does not exist and `-1` if the key exists but
+    /// has no associated expiration.
+    ///
+    /// # Examples
+    ///
+    /// Demonstrates basic usage.
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    /// use std::time::Duration;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     let ttl = Duration::from_secs(60);
+    ///     client.set_expires("foo", "bar".into(), ttl).await.unwrap();
+    ///
+    ///     let remaining = client.ttl("foo").await.unwrap();
+    ///     println!("Expires in {}s", remaining);
+    /// }
+    /// ```
+    #[instrument(skip(self))]
+    pub async fn ttl(&mut self, key: &str) -> crate::Result<i64> {
+        self.ttl_cmd(Ttl::new(key, TtlKind::Ttl)).await
+    }
+
+    /// Returns the remaining time to live of `key`, in milliseconds.
+    ///
+    /// Same as `ttl`, including the special `-2` and `-1` values.
+    #[instrument(skip(self))]
+    pub async fn pttl(&mut self, key: &str) -> crate::Result<i64> {
+        self.ttl_cmd(Ttl::new(key, TtlKind::Pttl)).await
+    }
+
+    /// Returns the unix timestamp, in seconds, at which `key` expires.
+    ///
+    /// Returns `-2` if the key does not exist and `-1` if the key exists but
+    /// has no associated expiration.
+    #[instrument(skip(self))]
+    pub async fn expiretime(&mut self, key: &str) -> crate::Result<i64> {
+        self.ttl_cmd(Ttl::new(key, TtlKind::ExpireTime)).await
+    }
+
+    /// Returns the unix timestamp, in milliseconds, at which `key` expires.
+    ///
+    /// Same as `expiretime`, including the special `-2` and `-1` values.
+    #[instrument(skip(self))]
+    pub async fn pexpiretime(&mut self, key: &str) -> crate::Result<i64> {
+        self.ttl_cmd(Ttl::new(key, TtlKind::PexpireTime)).await
+    }
+
+    /// Overwrites part of the string stored at `key`, starting at `offset`,
+    /// with `value`. Returns the length of the string after the change.
+    ///
+    /// If the string is shorter than `offset`, it is padded with zero bytes.
+    /// A missing key is treated as an empty string.
+    ///
+    /// # Examples
+    ///
+    /// Demonstrates basic usage.
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     client.set("greeting", "Hello World".into()).await.unwrap();
+    ///
+    ///     let len = client.setrange("greeting", 6, "Redis".into()).await.unwrap();
+    ///     assert_eq!(len, 11);
+    /// }
+    /// ```
+    #[instrument(skip(self))]
+    pub async fn setrange(&mut self, key: &str, offset: u64, value: Bytes) -> crate::Result<u64> {
+        let frame = Setrange::new(key, offset, value).into_frame();
+
+        debug!(request = ?frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(len) => Ok(len as u64),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Returns the part of the string stored at `key` between `start` and
+    /// `end`, both inclusive.
+    ///
+    /// Negative offsets count from the end of the string, so `-1` is the last
+    /// byte. A missing key is treated as an empty string.
+    ///
+    /// # Examples
+    ///
+    /// Demonstrates basic usage.
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     client.set("greeting", "Hello World".into()).await.unwrap();
+    ///
+    ///     let world = client.getrange("greeting", -5, -1).await.unwrap();
+    ///     assert_eq!(&world[..], b"World");
+    /// }
+    /// ```
+    #[instrument(skip(self))]
+    pub async fn getrange(&mut self, key: &str, start: i64, end: i64) -> crate::Result<Bytes> {
+        let frame = Getrange::new(key, start, end).into_frame();
+
+        debug!(request = ?frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Bulk(value) => Ok(value),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Sets the bit at `offset` in the string stored at `key` to `value`,
+    /// returning the previous value of the bit.
+    ///
+    /// Bits are numbered from the most significant bit of the first byte. The
+    /// string is grown with zero bytes if it is too short.
+    ///
+    /// # Examples
+    ///
+    /// Demonstrates basic usage.
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     let prev = client.setbit("flags", 7, true).await.unwrap();
+    ///     assert!(!prev);
+    ///
+    ///     assert!(client.getbit("flags", 7).await.unwrap());
+    /// }
+    /// ```
+    #[instrument(skip(self))]
+    pub async fn setbit(&mut self, key: &str, offset: u32, value: bool) -> crate::Result<bool> {
+        self.bit_cmd(Setbit::new(key, offset, value).into_frame())
+            .await
+            .map(|bit| bit != 0)
+    }
+
+    /// Returns the bit at `offset` in the string stored at `key`.
+    ///
+    /// Bits past the end of the string, or of a missing key, are `false`.
+    #[instrument(skip(self))]
+    pub async fn getbit(&mut self, key: &str, offset: u32) -> crate::Result<bool> {
+        self.bit_cmd(Getbit::new(key, offset).into_frame())
+            .await
+            .map(|bit| bit != 0)
+    }
+
+    /// Returns the number of bits set in the string stored at `key`.
+    ///
+    /// When `range` is given, only the bytes between its `start` and `end`
+    /// offsets are counted. Negative offsets count from the end of the string.
+    #[instrument(skip(self))]
+    pub async fn bitcount(&mut self, key: &str, range: Option<(i64, i64)>) -> crate::Result<u64> {
+        self.bit_cmd(Bitcount::new(key, range).into_frame())
+            .await
+            .map(|count| count as u64)
+    }
+
+    /// Set `key` to hold `value` only if it currently holds `expected`,
+    /// returning whether the value was set.
+    ///
+    /// The comparison and the write happen atomically on the server. A missing
+    /// key never matches. `CAS` is not a Redis command, so this only works
+    /// against mini-redis.
+    ///
+    /// # Examples
+    ///
+    /// Demonstrates basic usage.
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     client.set("counter", "1".into()).await.unwrap();
+    ///
+    ///     assert!(client.cas("counter", "1".into(), "2".into()).await.unwrap());
+    ///     assert!(!client.cas("counter", "1".into(), "3".into()).await.unwrap());
+    /// }
+    /// ```
+    #[instrument(skip(self))]
+    pub async fn cas(&mut self, key: &str, expected: Bytes, value: Bytes) -> crate::Result<bool> {
+        let frame = Cas::new(key, expected, value).into_frame();
+
+        debug!(request = ?frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(set) => Ok(set != 0),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Serialize the value stored at `key`, or return `None` if the key does
+    /// not exist.
+    ///
+    /// The payload is passed to `restore` to recreate the value, possibly on
+    /// another mini-redis server. The key's expiration is not included.
+    ///
+    /// # Examples
+    ///
+    /// Demonstrates basic usage.
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut source = Client::connect("localhost:6379").await.unwrap();
+    ///     let mut target = Client::connect("localhost:6380").await.unwrap();
+    ///
+    ///     let payload = source.dump("foo").await.unwrap().unwrap();
+    ///     target.restore("foo", None, payload, false).await.unwrap();
+    /// }
+    /// ```
+    #[instrument(skip(self))]
+    pub async fn dump(&mut self, key: &str) -> crate::Result<Option<Bytes>> {
+        let frame = Dump::new(key).into_frame();
+
+        debug!(request = ?frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Bulk(payload) => Ok(Some(payload)),
+            Frame::Null => Ok(None),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Create `key` holding the value serialized in `payload` by `dump`,
+    /// expiring after `ttl` if given.
+    ///
+    /// If the key already exists, a `BUSYKEY` error is returned, unless
+    /// `replace` is set.
+    #[instrument(skip(self, payload))]
+    pub async fn restore(
+        &mut self,
+        key: &str,
+        ttl: Option<Duration>,
+        payload: Bytes,
+        replace: bool,
+    ) -> crate::Result<()> {
+        let mut cmd = Restore::new(key, ttl, payload);
+        if replace {
+            cmd = cmd.replace();
+        }
+
+        let frame = cmd.into_frame();
+
+        debug!(request = ?frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(response) if response == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Adds `members` to the set stored at `key`, returning the number of
+    /// members that were not already in the set.
+    ///
+    /// # Examples
+    ///
+    /// Demonstrates basic usage.
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     client.sadd("tags", vec!["rust".into(), "tokio".into()]).await.unwrap();
+    ///
+    ///     assert!(client.sismember("tags", "rust".into()).await.unwrap());
     /// }
     /// ```
     #[instrument(skip(self))]
-    pub async fn ping(&mut self, msg: Option<Bytes>) -> crate::Result<Bytes> {
-        let frame = Ping::new(msg).into_frame();
-        debug!(request = ?frame);
-        self.connection.write_frame(&frame).await?;
+    pub async fn sadd(&mut self, key: &str, members: Vec<Bytes>) -> crate::Result<u64> {
+        self.set_count_cmd(Sadd::new(key, members).into_frame())
+            .await
+    }
 
-        match self.read_response().await? {
-            Frame::Simple(value) => Ok(value.into()),
-            Frame::Bulk(value) => Ok(value),
-            frame => Err(frame.to_error()),
-        }
+    /// Removes `members` from the set stored at `key`, returning the number
+    /// of members that were in the set.
+    #[instrument(skip(self))]
+    pub async fn srem(&mut self, key: &str, members: Vec<Bytes>) -> crate::Result<u64> {
+        self.set_count_cmd(Srem::new(key, members).into_frame())
+            .await
     }
 
-    /// Get the value of key.
-    ///
-    /// If the key does not exist the special value `None` is returned.
+    /// Returns the number of members of the set stored at `key`.
+    #[instrument(skip(self))]
+    pub async fn scard(&mut self, key: &str) -> crate::Result<u64> {
+        self.set_count_cmd(Scard::new(key).into_frame()).await
+    }
+
+    /// Returns `true` if `member` is in the set stored at `key`.
+    #[instrument(skip(self))]
+    pub async fn sismember(&mut self, key: &str, member: Bytes) -> crate::Result<bool> {
+        self.set_count_cmd(Sismember::new(key, member).into_frame())
+            .await
+            .map(|found| found != 0)
+    }
+
+    /// Returns the members of the set stored at `key`, in no particular
+    /// order.
+    #[instrument(skip(self))]
+    pub async fn smembers(&mut self, key: &str) -> crate::Result<Vec<Bytes>> {
+        self.set_members_cmd(Smembers::new(key).into_frame()).await
+    }
+
+    /// Returns the members of every set stored at `keys`, in no particular
+    /// order. Missing keys are treated as empty sets.
+    #[instrument(skip(self))]
+    pub async fn sinter(&mut self, keys: Vec<String>) -> crate::Result<Vec<Bytes>> {
+        self.set_members_cmd(SetOp::new(SetOpKind::Inter, keys).into_frame())
+            .await
+    }
+
+    /// Returns the members of any set stored at `keys`, in no particular
+    /// order.
+    #[instrument(skip(self))]
+    pub async fn sunion(&mut self, keys: Vec<String>) -> crate::Result<Vec<Bytes>> {
+        self.set_members_cmd(SetOp::new(SetOpKind::Union, keys).into_frame())
+            .await
+    }
+
+    /// Returns the members of the set stored at the first of `keys` that are
+    /// in none of the sets stored at the others, in no particular order.
+    #[instrument(skip(self))]
+    pub async fn sdiff(&mut self, keys: Vec<String>) -> crate::Result<Vec<Bytes>> {
+        self.set_members_cmd(SetOp::new(SetOpKind::Diff, keys).into_frame())
+            .await
+    }
+
+    /// Stores the intersection of the sets stored at `keys` at
+    /// `destination`, returning the number of members stored.
+    #[instrument(skip(self))]
+    pub async fn sinterstore(
+        &mut self,
+        destination: &str,
+        keys: Vec<String>,
+    ) -> crate::Result<u64> {
+        self.set_count_cmd(SetOp::store(SetOpKind::Inter, destination, keys).into_frame())
+            .await
+    }
+
+    /// Stores the union of the sets stored at `keys` at `destination`,
+    /// returning the number of members stored.
+    #[instrument(skip(self))]
+    pub async fn sunionstore(
+        &mut self,
+        destination: &str,
+        keys: Vec<String>,
+    ) -> crate::Result<u64> {
+        self.set_count_cmd(SetOp::store(SetOpKind::Union, destination, keys).into_frame())
+            .await
+    }
+
+    /// Stores the difference of the sets stored at `keys` at `destination`,
+    /// returning the number of members stored.
+    #[instrument(skip(self))]
+    pub async fn sdiffstore(&mut self, destination: &str, keys: Vec<String>) -> crate::Result<u64> {
+        self.set_count_cmd(SetOp::store(SetOpKind::Diff, destination, keys).into_frame())
+            .await
+    }
+
+    /// Adds `members`, given as `(score, member)` pairs, to the sorted set
+    /// stored at `key`. Returns the number of members that were added, not
+    /// counting members whose score was updated.
     ///
     /// # Examples
     ///
@@ -137,40 +860,92 @@ impl Client {
     /// async fn main() {
     ///     let mut client = Client::connect("localhost:6379").await.unwrap();
     ///
-    ///     let val = client.get("foo").await.unwrap();
-    ///     println!("Got = {:?}", val);
+    ///     let members = vec![(100.0, "alice".to_string()), (85.0, "bob".to_string())];
+    ///     client.zadd("leaderboard", members).await.unwrap();
+    ///
+    ///     let top = client.zrange("leaderboard", -1, -1).await.unwrap();
+    ///     assert_eq!(top, ["alice"]);
     /// }
     /// ```
     #[instrument(skip(self))]
-    pub async fn get(&mut self, key: &str) -> crate::Result<Option<Bytes>> {
-        // Create a `Get` command for the `key` and convert it to a frame.
-        let frame = Get::new(key).into_frame();
+    pub async fn zadd(&mut self, key: &str, members: Vec<(f64, String)>) -> crate::Result<u64> {
+        let frame = Zadd::new(key, members).into_frame();
 
         debug!(request = ?frame);
 
-        // Write the frame to the socket. This writes the full frame to the
-        // socket, waiting if necessary.
         self.connection.write_frame(&frame).await?;
 
-        // Wait for the response from the server
-        //
-        // Both `Simple` and `Bulk` frames are accepted. `Null` represents the
-        // key not being present and `None` is returned.
         match self.read_response().await? {
-            Frame::Simple(value) => Ok(Some(value.into())),
-            Frame::Bulk(value) => Ok(Some(value)),
+            Frame::Integer(added) => Ok(added as u64),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Returns the score of `member` in the sorted set stored at `key`, or
+    /// `None` if the key or the member does not exist.
+    #[instrument(skip(self))]
+    pub async fn zscore(&mut self, key: &str, member: &str) -> crate::Result<Option<f64>> {
+        let frame = Zscore::new(key, member).into_frame();
+
+        debug!(request = ?frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Bulk(score) => parse_float(&score).map(Some),
             Frame::Null => Ok(None),
             frame => Err(frame.to_error()),
         }
     }
 
-    /// Set `key` to hold the given `value`.
+    /// Returns the members ranked between `start` and `stop`, both inclusive,
+    /// in the sorted set stored at `key`, ordered by ascending score.
     ///
-    /// The `value` is associated with `key` until it is overwritten by the next
-    /// call to `set` or it is removed.
+    /// Negative ranks count from the end of the set, so `-1` is the member
+    /// with the highest score.
+    #[instrument(skip(self))]
+    pub async fn zrange(&mut self, key: &str, start: i64, stop: i64) -> crate::Result<Vec<String>> {
+        let frames = self.zrange_cmd(Zrange::new(key, start, stop)).await?;
+
+        frames
+            .into_iter()
+            .map(|frame| match frame {
+                Frame::Bulk(member) => Ok(String::from_utf8(member.to_vec())?),
+                frame => Err(frame.to_error()),
+            })
+            .collect()
+    }
+
+    /// Same as `zrange`, but each member is returned along with its score.
+    #[instrument(skip(self))]
+    pub async fn zrange_withscores(
+        &mut self,
+        key: &str,
+        start: i64,
+        stop: i64,
+    ) -> crate::Result<Vec<(String, f64)>> {
+        let frames = self
+            .zrange_cmd(Zrange::new(key, start, stop).with_scores())
+            .await?;
+
+        // Each member is followed by its score.
+        frames
+            .chunks(2)
+            .map(|pair| match pair {
+                [Frame::Bulk(member), Frame::Bulk(score)] => {
+                    Ok((String::from_utf8(member.to_vec())?, parse_float(score)?))
+                }
+                _ => Err("protocol error; invalid ZRANGE response".into()),
+            })
+            .collect()
+    }
+
+    /// Increments the floating point number stored at `key` by `increment`,
+    /// returning the new value.
     ///
-    /// If key already holds a value, it is overwritten. Any previous time to
-    /// live associated with the key is discarded on successful SET operation.
+    /// A missing key is treated as `0`. Use a negative `increment` to
+    /// decrement the value. An error is returned if the stored value is not a
+    /// valid float.
     ///
     /// # Examples
     ///
@@ -183,72 +958,136 @@ impl Client {
     /// async fn main() {
     ///     let mut client = Client::connect("localhost:6379").await.unwrap();
     ///
-    ///     client.set("foo", "bar".into()).await.unwrap();
+    ///     client.set("price", "10.5".into()).await.unwrap();
     ///
-    ///     // Getting the value immediately works
-    ///     let val = client.get("foo").await.unwrap().unwrap();
-    ///     assert_eq!(val, "bar");
+    ///     let price = client.incrbyfloat("price", 0.1).await.unwrap();
+    ///     println!("price = {}", price);
     /// }
     /// ```
     #[instrument(skip(self))]
-    pub async fn set(&mut self, key: &str, value: Bytes) -> crate::Result<()> {
-        // Create a `Set` command and pass it to `set_cmd`. A separate method is
-        // used to set a value with an expiration. The common parts of both
-        // functions are implemented by `set_cmd`.
-        self.set_cmd(Set::new(key, value, None)).await
+    pub async fn incrbyfloat(&mut self, key: &str, increment: f64) -> crate::Result<f64> {
+        let frame = Incrbyfloat::new(key, increment).into_frame();
+
+        debug!(request = ?frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        // The new value is returned as a bulk string.
+        match self.read_response().await? {
+            Frame::Bulk(value) => parse_float(&value),
+            frame => Err(frame.to_error()),
+        }
     }
 
-    /// Set `key` to hold the given `value`. The value expires after `expiration`
-    ///
-    /// The `value` is associated with `key` until one of the following:
-    /// - it expires.
-    /// - it is overwritten by the next call to `set`.
-    /// - it is removed.
-    ///
-    /// If key already holds a value, it is overwritten. Any previous time to
-    /// live associated with the key is discarded on a successful SET operation.
+    /// Returns the current time of the server.
     ///
     /// # Examples
     ///
-    /// Demonstrates basic usage. This example is not **guaranteed** to always
-    /// work as it relies on time based logic and assumes the client and server
-    /// stay relatively synchronized in time. The real world tends to not be so
-    /// favorable.
+    /// Demonstrates basic usage.
     ///
     /// ```no_run
     /// use mini_redis::clients::Client;
-    /// use tokio::time;
-    /// use std::time::Duration;
     ///
     /// #[tokio::main]
     /// async fn main() {
-    ///     let ttl = Duration::from_millis(500);
     ///     let mut client = Client::connect("localhost:6379").await.unwrap();
     ///
-    ///     client.set_expires("foo", "bar".into(), ttl).await.unwrap();
-    ///
-    ///     // Getting the value immediately works
-    ///     let val = client.get("foo").await.unwrap().unwrap();
-    ///     assert_eq!(val, "bar");
-    ///
-    ///     // Wait for the TTL to expire
-    ///     time::sleep(ttl).await;
-    ///
-    ///     let val = client.get("foo").await.unwrap();
-    ///     assert!(val.is_some());
+    ///     let time = client.time().await.unwrap();
+    ///     println!("server time = {:?}", time);
     /// }
     /// ```
     #[instrument(skip(self))]
-    pub async fn set_expires(
-        &mut self,
-        key: &str,
-        value: Bytes,
-        expiration: Duration,
-    ) -> crate::Result<()> {
-        // Create a `Set` command and pass it to `set_cmd`. A separate method is
-        // used to set a value with an expiration. The common parts of both
-        // functions are implemented by `set_cmd`.
-        self.set_cmd(Set::new(key, value, Some(expiration))).await
+    pub async fn time(&mut self) -> crate::Result<SystemTime> {
+        let frame = Time::new().into_frame();
+
+        debug!(request = ?frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        // The seconds and microseconds are returned as bulk strings.
+        match self.read_response().await? {
+            Frame::Array(frames) => match &frames[..] {
+                [Frame::Bulk(secs), Frame::Bulk(micros)] => {
+                    let secs = parse_u64(secs)?;
+                    let micros = parse_u64(micros)?;
+
+                    Ok(UNIX_EPOCH + Duration::from_secs(secs) + Duration::from_micros(micros))
+                }
+                _ => Err("protocol error; invalid TIME response".into()),
+            },
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// The core logic shared by `ttl`, `pttl`, `expiretime` and `pexpiretime`.
+    async fn ttl_cmd(&mut self, cmd: Ttl) -> crate::Result<i64> {
+        let frame = cmd.into_frame();
+
+        debug!(request = ?frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(response) => Ok(response),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// The core `ZRANGE` logic, used by both `zrange` and
+    /// `zrange_withscores`. Returns the entries of the response array.
+    async fn zrange_cmd(&mut self, cmd: Zrange) -> crate::Result<Vec<Frame>> {
+        let frame = cmd.into_frame();
+
+        debug!(request = ?frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Array(frames) => Ok(frames),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// The core logic of the set commands replying with an integer.
+    async fn set_count_cmd(&mut self, frame: Frame) -> crate::Result<u64> {
+        debug!(request = ?frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(response) => Ok(response as u64),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// The core logic of the set commands replying with members.
+    async fn set_members_cmd(&mut self, frame: Frame) -> crate::Result<Vec<Bytes>> {
+        debug!(request = ?frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Array(frames) => frames
+                .into_iter()
+                .map(|frame| match frame {
+                    Frame::Bulk(member) => Ok(member),
+                    frame => Err(frame.to_error()),
+                })
+                .collect(),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// The core logic of the bit commands, which all reply with an integer.
+    async fn bit_cmd(&mut self, frame: Frame) -> crate::Result<i64> {
+        debug!(request = ?frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(response) => Ok(response),
+            frame => Err(frame.to_error()),
+        }
     }
 
     /// The core `SET` logic, used by both `set` and `set_expires.
@@ -270,6 +1109,42 @@ impl Client {
         }
     }
 
+    /// Set each key to hold its value, sending all `SET` commands before
+    /// waiting for any response.
+    ///
+    /// Pipelining the commands saves a round trip per command. Every response
+    /// is read, even if some commands fail, so the connection remains usable.
+    /// The first error encountered is returned.
+    pub(crate) async fn set_pipelined(
+        &mut self,
+        entries: Vec<(String, Bytes)>,
+    ) -> crate::Result<()> {
+        let frames: Vec<Frame> = entries
+            .into_iter()
+            .map(|(key, value)| Set::new(key, value, None).into_frame())
+            .collect();
+
+        debug!(requests = ?frames);
+
+        self.connection.write_frames(&frames).await?;
+
+        let mut result = Ok(());
+
+        for _ in &frames {
+            let response = match self.read_response().await {
+                Ok(Frame::Simple(response)) if response == "OK" => Ok(()),
+                Ok(frame) => Err(frame.to_error()),
+                Err(err) => Err(err),
+            };
+
+            if result.is_ok() {
+                result = response;
+            }
+        }
+
+        result
+    }
+
     /// Posts `message` to the given `channel`.
     ///
     /// Returns the number of subscribers currently listening on the channel.
@@ -303,7 +1178,7 @@ impl Client {
 
         // Read the response
         match self.read_response().await? {
-            Frame::Integer(response) => Ok(response),
+            Frame::Integer(response) if response >= 0 => Ok(response as u64),
             frame => Err(frame.to_error()),
         }
     }
@@ -320,28 +1195,57 @@ impl Client {
         // Issue the subscribe command to the server and wait for confirmation.
         // The client will then have been transitioned into the "subscriber"
         // state and may only issue pub/sub commands from that point on.
-        self.subscribe_cmd(&channels).await?;
+        let frame = Subscribe::new(channels.clone()).into_frame();
+        let subscription_count = self.subscribe_cmd(frame, "subscribe", &channels).await?;
 
         // Return the `Subscriber` type
         Ok(Subscriber {
             client: self,
             subscribed_channels: channels,
+            subscribed_patterns: vec![],
+            subscription_count,
         })
     }
 
-    /// The core `SUBSCRIBE` logic, used by misc subscribe fns
-    async fn subscribe_cmd(&mut self, channels: &[String]) -> crate::Result<()> {
-        // Convert the `Subscribe` command into a frame
-        let frame = Subscribe::new(channels.to_vec()).into_frame();
+    /// Subscribes the client to the specified glob-style patterns.
+    ///
+    /// Like `subscribe`, but messages published to any channel matching one
+    /// of the patterns are received.
+    #[instrument(skip(self))]
+    pub async fn psubscribe(mut self, patterns: Vec<String>) -> crate::Result<Subscriber> {
+        let frame = Psubscribe::new(patterns.clone()).into_frame();
+        let subscription_count = self.subscribe_cmd(frame, "psubscribe", &patterns).await?;
 
+        Ok(Subscriber {
+            client: self,
+            subscribed_channels: vec![],
+            subscribed_patterns: patterns,
+            subscription_count,
+        })
+    }
+
+    /// The core `SUBSCRIBE` and `PSUBSCRIBE` logic, used by misc subscribe fns
+    ///
+    /// `frame` is the command subscribing to `names`, and `kind` is the name
+    /// of the command, which the server includes in its confirmations.
+    ///
+    /// Returns the number of subscriptions reported by the last confirmation.
+    async fn subscribe_cmd(
+        &mut self,
+        frame: Frame,
+        kind: &str,
+        names: &[String],
+    ) -> crate::Result<u64> {
         debug!(request = ?frame);
 
         // Write the frame to the socket
         self.connection.write_frame(&frame).await?;
 
+        let mut count = 0;
+
         // For each channel being subscribed to, the server responds with a
         // message confirming subscription to that channel.
-        for channel in channels {
+        for name in names {
             // Read the response
             let response = self.read_response().await?;
 
@@ -351,43 +1255,47 @@ impl Client {
                     // The server responds with an array frame in the form of:
                     //
                     // ```
-                    // [ "subscribe", channel, num-subscribed ]
+                    // [ kind, name, num-subscribed ]
                     // ```
                     //
-                    // where channel is the name of the channel and
-                    // num-subscribed is the number of channels that the client
-                    // is currently subscribed to.
-                    [subscribe, schannel, ..]
-                        if *subscribe == "subscribe" && *schannel == channel => {}
+                    // where name is the name of the channel or pattern and
+                    // num-subscribed is the number of channels and patterns
+                    // that the client is currently subscribed to.
+                    [skind, sname, Frame::Integer(n)]
+                        if *skind == kind && *sname == name.as_str() =>
+                    {
+                        count = *n as u64;
+                    }
                     _ => return Err(response.to_error()),
                 },
                 frame => return Err(frame.to_error()),
             };
         }
 
-        Ok(())
+        Ok(count)
     }
 
     /// Reads a response frame from the socket.
     ///
-    /// If an `Error` frame is received, it is converted to `Err`.
+    /// If an `Error` frame is received, it is converted to `Err`. Errors are
+    /// `MiniRedisError` values, so callers can tell them apart.
     async fn read_response(&mut self) -> crate::Result<Frame> {
-        let response = self.connection.read_frame().await?;
+        let response = self
+            .connection
+            .read_frame()
+            .await
+            .map_err(MiniRedisError::from_read)?;
 
         debug!(?response);
 
         match response {
             // Error frames are converted to `Err`
-            Some(Frame::Error(msg)) => Err(msg.into()),
+            Some(frame @ Frame::Error(_)) => Err(frame.to_error()),
             Some(frame) => Ok(frame),
-            None => {
-                // Receiving `None` here indicates the server has closed the
-                // connection without sending a frame. This is unexpected and is
-                // represented as a "connection reset by peer" error.
-                let err = Error::new(ErrorKind::ConnectionReset, "connection reset by server");
-
-                Err(err.into())
-            }
+            // Receiving `None` here indicates the server has closed the
+            // connection without sending a frame. This is unexpected and is
+            // represented as a "connection reset" error.
+            None => Err(MiniRedisError::ConnectionReset.into()),
         }
     }
 }
@@ -398,6 +1306,17 @@ impl Subscriber {
         &self.subscribed_channels
     }
 
+    /// Returns the set of patterns currently subscribed to.
+    pub fn get_subscribed_patterns(&self) -> &[String] {
+        &self.subscribed_patterns
+    }
+
+    /// Returns the number of subscriptions the connection has, as reported by
+    /// the server when the subscriptions last changed.
+    pub fn subscription_count(&self) -> u64 {
+        self.subscription_count
+    }
+
     /// Receive the next message published on a subscribed channel, waiting if
     /// necessary.
     ///
@@ -409,10 +1328,19 @@ impl Subscriber {
 
                 match mframe {
                     Frame::Array(ref frame) => match frame.as_slice() {
-                        [message, channel, content] if *message == "message" => Ok(Some(Message {
-                            channel: channel.to_string(),
-                            content: Bytes::from(content.to_string()),
-                        })),
+                        [message, channel, content] if *message == "message" => {
+                            Ok(Some(Message::Channel {
+                                channel: channel.to_string(),
+                                content: Bytes::from(content.to_string()),
+                            }))
+                        }
+                        [message, pattern, channel, content] if *message == "pmessage" => {
+                            Ok(Some(Message::Pattern {
+                                pattern: pattern.to_string(),
+                                channel: channel.to_string(),
+                                content: Bytes::from(content.to_string()),
+                            }))
+                        }
                         _ => Err(mframe.to_error()),
                     },
                     frame => Err(frame.to_error()),
@@ -446,7 +1374,11 @@ impl Subscriber {
     #[instrument(skip(self))]
     pub async fn subscribe(&mut self, channels: &[String]) -> crate::Result<()> {
         // Issue the subscribe command
-        self.client.subscribe_cmd(channels).await?;
+        let frame = Subscribe::new(channels.to_vec()).into_frame();
+        self.subscription_count = self
+            .client
+            .subscribe_cmd(frame, "subscribe", channels)
+            .await?;
 
         // Update the set of subscribed channels.
         self.subscribed_channels
@@ -455,6 +1387,21 @@ impl Subscriber {
         Ok(())
     }
 
+    /// Subscribe to a list of new patterns
+    #[instrument(skip(self))]
+    pub async fn psubscribe(&mut self, patterns: &[String]) -> crate::Result<()> {
+        let frame = Psubscribe::new(patterns.to_vec()).into_frame();
+        self.subscription_count = self
+            .client
+            .subscribe_cmd(frame, "psubscribe", patterns)
+            .await?;
+
+        self.subscribed_patterns
+            .extend(patterns.iter().map(Clone::clone));
+
+        Ok(())
+    }
+
     /// Unsubscribe to a list of new channels
     #[instrument(skip(self))]
     pub async fn unsubscribe(&mut self, channels: &[String]) -> crate::Result<()> {
@@ -467,9 +1414,10 @@ impl Subscriber {
 
         // if the input channel list is empty, server acknowledges as unsubscribing
         // from all subscribed channels, so we assert that the unsubscribe list received
-        // matches the client subscribed one
+        // matches the client subscribed one. Without any subscribed channel, the
+        // server still sends a single acknowledgment, with a nil channel.
         let num = if channels.is_empty() {
-            self.subscribed_channels.len()
+            self.subscribed_channels.len().max(1)
         } else {
             channels.len()
         };
@@ -480,7 +1428,14 @@ impl Subscriber {
 
             match response {
                 Frame::Array(ref frame) => match frame.as_slice() {
-                    [unsubscribe, channel, ..] if *unsubscribe == "unsubscribe" => {
+                    [unsubscribe, Frame::Null, Frame::Integer(count)]
+                        if *unsubscribe == "unsubscribe" && self.subscribed_channels.is_empty() =>
+                    {
+                        self.subscription_count = *count as u64;
+                    }
+                    [unsubscribe, channel, Frame::Integer(count)]
+                        if *unsubscribe == "unsubscribe" =>
+                    {
                         let len = self.subscribed_channels.len();
 
                         if len == 0 {
@@ -496,6 +1451,8 @@ impl Subscriber {
                         if self.subscribed_channels.len() != len - 1 {
                             return Err(response.to_error());
                         }
+
+                        self.subscription_count = *count as u64;
                     }
                     _ => return Err(response.to_error()),
                 },
@@ -506,3 +1463,21 @@ impl Subscriber {
         Ok(())
     }
 }
+
+/// Parses a floating point number sent by the server as a bulk string, such
+/// as the reply to `INCRBYFLOAT` or a sorted set score.
+fn parse_float(data: &[u8]) -> crate::Result<f64> {
+    std::str::from_utf8(data)
+        .ok()
+        .and_then(|data| data.parse().ok())
+        .ok_or_else(|| "protocol error; invalid float".into())
+}
+
+/// Parses an unsigned integer sent by the server as a bulk string, such as
+/// the fields of the reply to `TIME`.
+fn parse_u64(data: &[u8]) -> crate::Result<u64> {
+    std::str::from_utf8(data)
+        .ok()
+        .and_then(|data| data.parse().ok())
+        .ok_or_else(|| "protocol error; invalid integer".into())
+}
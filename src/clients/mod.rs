@@ -2,7 +2,10 @@ mod client;
 pub use client::{Client, Message, Subscriber};
 
 mod blocking_client;
-pub use blocking_client::BlockingClient;
+pub use blocking_client::{BlockingClient, BlockingSubscriber, SubscriberIterator};
 
 mod buffered_client;
-pub use buffered_client::BufferedClient;
+pub use buffered_client::{BufferedClient, FlushPolicy};
+
+mod pool;
+pub use pool::Pool;
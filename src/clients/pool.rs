@@ -0,0 +1,119 @@
+use crate::clients::{BufferedClient, Client};
+use crate::Result;
+
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{lookup_host, ToSocketAddrs};
+
+/// A pool of connections to a Redis server.
+///
+/// A `BufferedClient` allows many tasks to share a connection, but the
+/// requests are still sent one at a time, as the connection only supports a
+/// single in-flight request. A `Pool` maintains several connections, each
+/// managed by its own `BufferedClient`, so requests issued through different
+/// connections run in parallel.
+///
+/// Connections are handed out in a round-robin fashion by `get_connection`.
+///
+/// A connection that fails, for instance because the server closed it, is
+/// re-established before the next request is sent through it. The request
+/// that failed is not retried, its error is returned to the caller.
+///
+/// Cloning a `Pool` is cheap, and clones share the same connections.
+#[derive(Clone)]
+pub struct Pool {
+    /// The pooled connections
+    connections: Vec<BufferedClient>,
+
+    /// Index, modulo the number of connections, of the connection returned by
+    /// the next call to `get_connection`. Shared between clones so they spread
+    /// the load together.
+    next: Arc<AtomicUsize>,
+}
+
+impl Pool {
+    /// Establish `size` connections to the Redis server located at `addr`.
+    ///
+    /// `addr` may be any type that can be asynchronously converted to a
+    /// `SocketAddr`. This includes `SocketAddr` and strings. The `ToSocketAddrs`
+    /// trait is the Tokio version and not the `std` version.
+    ///
+    /// Each connection is checked with a `PING` before the pool is returned,
+    /// so a server that accepts connections but does not respond is detected
+    /// up front. Connections re-established later are checked the same way.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Pool;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let pool = match Pool::connect("localhost:6379", 4).await {
+    ///         Ok(pool) => pool,
+    ///         Err(_) => panic!("failed to establish connections"),
+    ///     };
+    /// # drop(pool);
+    /// }
+    /// ```
+    pub async fn connect<T: ToSocketAddrs + Clone>(addr: T, size: usize) -> Result<Pool> {
+        assert!(size > 0, "a pool needs at least one connection");
+
+        // The address is resolved once, so failed connections are
+        // re-established to the same server.
+        let addrs: Vec<SocketAddr> = lookup_host(addr).await?.collect();
+        let mut connections = Vec::with_capacity(size);
+
+        for _ in 0..size {
+            let mut client = Client::connect(&addrs[..]).await?;
+
+            // Health check the connection before handing it out.
+            client.ping(None).await?;
+
+            connections.push(BufferedClient::reconnecting(client, addrs.clone()));
+        }
+
+        Ok(Pool {
+            connections,
+            next: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    /// Set the maximum amount of time a request through any of the pooled
+    /// connections may take.
+    ///
+    /// See `BufferedClient::timeout` for details. If the timeout elapses, the
+    /// request fails with an error of kind `ErrorKind::TimedOut`. The request
+    /// may still be sent to the server, in which case its response is
+    /// discarded when it arrives.
+    ///
+    /// By default, requests do not time out.
+    pub fn timeout(mut self, timeout: Duration) -> Pool {
+        self.connections = self
+            .connections
+            .into_iter()
+            .map(|connection| connection.timeout(timeout))
+            .collect();
+        self
+    }
+
+    /// Returns a handle to one of the pooled connections.
+    ///
+    /// Each call returns the next connection in turn, so requests issued
+    /// through handles from successive calls are spread across connections.
+    pub fn get_connection(&self) -> BufferedClient {
+        let next = self.next.fetch_add(1, Ordering::Relaxed);
+        self.connections[next % self.connections.len()].clone()
+    }
+
+    /// Returns the number of connections in the pool.
+    pub fn size(&self) -> usize {
+        self.connections.len()
+    }
+}
@@ -0,0 +1,129 @@
+use crate::{Connection, Frame, Parse, ParseError};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// Authenticate the connection.
+///
+/// When the server is configured with a password, every other command is
+/// rejected until the connection has authenticated with `AUTH`. mini-redis has
+/// a single user, `default`, so a username is only accepted if it is
+/// `default`.
+#[derive(Debug)]
+pub struct Auth {
+    /// The username, if one was given
+    username: Option<String>,
+
+    /// The password
+    password: String,
+}
+
+impl Auth {
+    /// Create a new `Auth` command which authenticates with `password`.
+    pub fn new(password: impl ToString) -> Auth {
+        Auth {
+            username: None,
+            password: password.to_string(),
+        }
+    }
+
+    /// Get the password
+    pub fn password(&self) -> &str {
+        &self.password
+    }
+
+    /// Parse an `Auth` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `AUTH` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Auth` value on success. If the frame is malformed, `Err`
+    /// is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing two or three entries.
+    ///
+    /// ```text
+    /// AUTH [username] password
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Auth> {
+        let first = parse.next_string()?;
+
+        // With a single argument, it is the password. With two, the username
+        // comes first.
+        match parse.next_string() {
+            Ok(password) => Ok(Auth {
+                username: Some(first),
+                password,
+            }),
+            Err(ParseError::EndOfStream) => Ok(Auth {
+                username: None,
+                password: first,
+            }),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Apply the `Auth` command, checking the password against `requirepass`,
+    /// the password the server is configured with.
+    ///
+    /// The response is written to `dst`. Returns `true` if the connection is
+    /// now authenticated. This is called by the connection handler, which
+    /// tracks whether the connection is authenticated.
+    #[instrument(skip(self, requirepass, dst))]
+    pub(crate) async fn apply(
+        self,
+        requirepass: Option<&str>,
+        dst: &mut Connection,
+    ) -> crate::Result<bool> {
+        let username_ok = match &self.username {
+            Some(username) => username == "default",
+            None => true,
+        };
+
+        let (response, authenticated) = match requirepass {
+            None => (
+                Frame::Error(
+                    "ERR AUTH <password> called without any password configured for the default user. Are you sure your configuration is correct?"
+                        .to_string(),
+                ),
+                false,
+            ),
+            Some(requirepass) if username_ok && requirepass == self.password => {
+                (Frame::Simple("OK".to_string()), true)
+            }
+            Some(_) => (
+                Frame::Error(
+                    "WRONGPASS invalid username-password pair or user is disabled.".to_string(),
+                ),
+                false,
+            ),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(authenticated)
+    }
+
+    /// Converts the command into an equivalent `Frame`.
+    ///
+    /// This is called by the client when encoding an `Auth` command to send to
+    /// the server.
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("auth".as_bytes()));
+        if let Some(username) = self.username {
+            frame.push_bulk(Bytes::from(username.into_bytes()));
+        }
+        frame.push_bulk(Bytes::from(self.password.into_bytes()));
+        frame
+    }
+}
@@ -0,0 +1,87 @@
+use crate::{Connection, Db, Frame, Parse};
+
+use std::path::Path;
+use tracing::{debug, error, info, instrument};
+
+/// Save the data set to disk in the background.
+///
+/// Like `SAVE`, but the reply is sent as soon as the snapshot has been taken.
+/// The snapshot is written to the file by a separate task, so the connection
+/// does not wait for the disk.
+#[derive(Debug, Default)]
+pub struct Bgsave;
+
+impl Bgsave {
+    /// Create a new `Bgsave` command.
+    pub fn new() -> Bgsave {
+        Bgsave
+    }
+
+    /// Parse a `Bgsave` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `BGSAVE` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Bgsave` value on success. If the frame is malformed, `Err`
+    /// is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing a single entry.
+    ///
+    /// ```text
+    /// BGSAVE
+    /// ```
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Bgsave> {
+        Ok(Bgsave)
+    }
+
+    /// Apply the `Bgsave` command, writing a snapshot of `db` to `path`, the
+    /// snapshot file the server is configured with, in the background.
+    ///
+    /// The response is written to `dst`. This is called by the connection
+    /// handler, which knows the configured path.
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(
+        self,
+        db: &Db,
+        path: Option<&Path>,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = match path {
+            None => Frame::Error("ERR no snapshot file configured".to_string()),
+            Some(path) => {
+                // Taking the snapshot only copies references to the values,
+                // so it is done right away. Writing it out can take a while.
+                let snapshot = db.snapshot();
+                let path = path.to_path_buf();
+                let db = db.clone();
+
+                tokio::spawn(async move {
+                    match snapshot.write_to_file(&path).await {
+                        Ok(()) => {
+                            db.record_save();
+                            info!(path = %path.display(), "background save done")
+                        }
+                        Err(err) => {
+                            error!(cause = %err, path = %path.display(), "background save failed")
+                        }
+                    }
+                });
+
+                Frame::Simple("Background saving started".to_string())
+            }
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+use crate::cmd::ParseError;
+use crate::{Connection, Db, Frame, Parse};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// Count the number of bits set in the string stored at `key`.
+///
+/// By default, the whole string is counted. A range of bytes, between the
+/// `start` and `end` offsets, can be given to only count those bytes. As with
+/// `GETRANGE`, negative offsets count from the end of the string.
+#[derive(Debug)]
+pub struct Bitcount {
+    /// Name of the key to read
+    key: String,
+
+    /// Offsets of the first and last bytes to count
+    range: Option<(i64, i64)>,
+}
+
+impl Bitcount {
+    /// Create a new `Bitcount` command which counts the bits set in `key`,
+    /// optionally only in the bytes between `range`'s offsets.
+    pub fn new(key: impl ToString, range: Option<(i64, i64)>) -> Bitcount {
+        Bitcount {
+            key: key.to_string(),
+            range,
+        }
+    }
+
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Get the range
+    pub fn range(&self) -> Option<(i64, i64)> {
+        self.range
+    }
+
+    /// Parse a `Bitcount` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `BITCOUNT` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Bitcount` value on success. If the frame is malformed,
+    /// `Err` is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing two or four entries.
+    ///
+    /// ```text
+    /// BITCOUNT key [start end]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Bitcount> {
+        use ParseError::EndOfStream;
+
+        let key = parse.next_string()?;
+
+        let range = match parse.next_signed_int() {
+            // The end offset is required once a start offset is given.
+            Ok(start) => Some((start, parse.next_signed_int()?)),
+            Err(EndOfStream) => None,
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Bitcount { key, range })
+    }
+
+    /// Apply the `Bitcount` command to the specified `Db` instance.
+    ///
+    /// The response is written to `dst`. This is called by the server in order
+    /// to execute a received command.
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = match db.bitcount(&self.key, self.range) {
+            Ok(count) => Frame::Integer(count as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Converts the command into an equivalent `Frame`.
+    ///
+    /// This is called by the client when encoding a `Bitcount` command to send
+    /// to the server.
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("bitcount".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+
+        if let Some((start, end)) = self.range {
+            frame.push_int(start);
+            frame.push_int(end);
+        }
+
+        frame
+    }
+}
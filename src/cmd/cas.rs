@@ -0,0 +1,117 @@
+use crate::{Connection, Db, Frame, Parse};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// Set `key` to hold `value` only if it currently holds `expected`.
+///
+/// This is not a Redis command. It gives clients optimistic concurrency on a
+/// single key without a `WATCH`/`MULTI`/`EXEC` round trip: read the value,
+/// compute the new one, then `CAS` it, retrying if another client changed the
+/// value in between.
+///
+/// The comparison and the write happen atomically. A missing key never
+/// matches, and the key's expiration, if any, is kept.
+///
+/// The response is `1` if the value was set and `0` otherwise.
+#[derive(Debug)]
+pub struct Cas {
+    /// Name of the key to set
+    key: String,
+
+    /// Value the key must hold for the write to happen
+    expected: Bytes,
+
+    /// Value to set
+    value: Bytes,
+}
+
+impl Cas {
+    /// Create a new `Cas` command which sets `key` to `value` if it holds
+    /// `expected`.
+    pub fn new(key: impl ToString, expected: Bytes, value: Bytes) -> Cas {
+        Cas {
+            key: key.to_string(),
+            expected,
+            value,
+        }
+    }
+
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Get the expected value
+    pub fn expected(&self) -> &Bytes {
+        &self.expected
+    }
+
+    /// Get the value
+    pub fn value(&self) -> &Bytes {
+        &self.value
+    }
+
+    /// Parse a `Cas` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `CAS` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Cas` value on success. If the frame is malformed, `Err`
+    /// is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing four entries.
+    ///
+    /// ```text
+    /// CAS key expected value
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Cas> {
+        let key = parse.next_string()?;
+        let expected = parse.next_bytes()?;
+        let value = parse.next_bytes()?;
+
+        Ok(Cas {
+            key,
+            expected,
+            value,
+        })
+    }
+
+    /// Apply the `Cas` command to the specified `Db` instance.
+    ///
+    /// The response is written to `dst`. This is called by the server in order
+    /// to execute a received command.
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = match db.cas(&self.key, &self.expected, self.value) {
+            Ok(set) => Frame::Integer(set as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Converts the command into an equivalent `Frame`.
+    ///
+    /// This is called by the client when encoding a `Cas` command to send to
+    /// the server.
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("cas".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_bulk(self.expected);
+        frame.push_bulk(self.value);
+        frame
+    }
+}
@@ -0,0 +1,216 @@
+use crate::db::glob_match;
+use crate::server::ConnectionLimit;
+use crate::{Connection, Db, Frame, Parse, ParseError};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// Read and change the server configuration at runtime.
+///
+/// Client libraries and tools commonly issue `CONFIG GET maxmemory` when
+/// connecting, so the parameters they ask for are reported even when they
+/// cannot be changed.
+///
+/// # Subcommands
+///
+/// Currently, the following subcommands are supported:
+///
+/// * GET `parameter [parameter ...]` -- Return the name and value of every
+///   parameter matching one of the glob-style patterns, e.g. `max*`.
+/// * SET `parameter value` -- Change the value of a parameter. `appendonly`
+///   is fixed when the server starts.
+///
+/// # Parameters
+///
+/// * `maxmemory` -- The memory limit of the data set, in bytes, see
+///   `ServerBuilder::maxmemory`. Like Redis, `CONFIG SET` accepts a unit:
+///   `k`, `m` and `g` are powers of 1000, `kb`, `mb` and `gb` powers of 1024.
+/// * `maxclients` -- The maximum number of connections. Lowering it does not
+///   close connections, new ones wait until enough of them are closed.
+/// * `timeout` -- Seconds after which idle connections are closed. Idle
+///   connections are never closed, so this is always `0`, and setting any
+///   other value is an error.
+/// * `appendonly` -- `yes` if writes are logged to an append-only file, see
+///   `ServerBuilder::appendonly`.
+#[derive(Debug)]
+pub struct Config {
+    subcommand: Subcommand,
+}
+
+#[derive(Debug)]
+enum Subcommand {
+    Get(Vec<String>),
+    Set(String, String),
+    Unknown(String),
+}
+
+/// Every parameter `CONFIG GET` reports, in the order they are reported.
+const PARAMETERS: &[&str] = &["maxmemory", "maxclients", "timeout", "appendonly"];
+
+impl Config {
+    /// Parse a `Config` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `CONFIG` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Config` value on success. If the frame is malformed, `Err`
+    /// is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing `CONFIG`, a subcommand and the
+    /// subcommand's arguments.
+    ///
+    /// ```text
+    /// CONFIG GET parameter [parameter ...]
+    /// CONFIG SET parameter value
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Config> {
+        // Subcommands are matched case insensitively, like command names.
+        let subcommand = match &parse.next_string()?.to_lowercase()[..] {
+            "get" => {
+                let mut patterns = vec![];
+
+                loop {
+                    match parse.next_string() {
+                        Ok(pattern) => patterns.push(pattern.to_lowercase()),
+                        Err(ParseError::EndOfStream) => break,
+                        Err(err) => return Err(err.into()),
+                    }
+                }
+
+                if patterns.is_empty() {
+                    return Err("ERR wrong number of arguments for 'config|get' command".into());
+                }
+
+                Subcommand::Get(patterns)
+            }
+            "set" => match (parse.next_string(), parse.next_string(), parse.finish()) {
+                (Ok(parameter), Ok(value), Ok(())) => {
+                    Subcommand::Set(parameter.to_lowercase(), value)
+                }
+                _ => return Err("ERR wrong number of arguments for 'config|set' command".into()),
+            },
+            subcommand => Subcommand::Unknown(subcommand.to_string()),
+        };
+
+        Ok(Config { subcommand })
+    }
+
+    /// Apply the `Config` command.
+    ///
+    /// `limit` holds `maxclients`, and `appendonly` is the value of the
+    /// parameter of the same name. The response is written to `dst`. This is
+    /// called by the connection handler, which knows the server
+    /// configuration.
+    #[instrument(skip(self, db, limit, dst))]
+    pub(crate) async fn apply(
+        self,
+        db: &Db,
+        limit: &ConnectionLimit,
+        appendonly: bool,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = match self.subcommand {
+            // The response alternates between a parameter name and its value.
+            Subcommand::Get(patterns) => Frame::Array(
+                PARAMETERS
+                    .iter()
+                    .filter(|name| {
+                        patterns
+                            .iter()
+                            .any(|pattern| glob_match(pattern.as_bytes(), name.as_bytes()))
+                    })
+                    .flat_map(|name| {
+                        let value = match *name {
+                            "maxmemory" => db.maxmemory().to_string(),
+                            "maxclients" => limit.max().to_string(),
+                            "timeout" => "0".to_string(),
+                            _ => if appendonly { "yes" } else { "no" }.to_string(),
+                        };
+
+                        vec![Frame::Bulk(Bytes::from(*name)), Frame::Bulk(value.into())]
+                    })
+                    .collect(),
+            ),
+            Subcommand::Set(parameter, value) => match &parameter[..] {
+                "maxmemory" => match parse_memory(&value) {
+                    Some(bytes) => {
+                        db.set_maxmemory(bytes);
+                        Frame::Simple("OK".to_string())
+                    }
+                    None => Frame::Error(format!(
+                        "ERR CONFIG SET failed (possibly related to argument '{}') - argument must be a memory value",
+                        parameter
+                    )),
+                },
+                "maxclients" => match value.parse::<u32>() {
+                    Ok(max) if max > 0 => {
+                        limit.set_max(max as usize);
+                        Frame::Simple("OK".to_string())
+                    }
+                    _ => Frame::Error(format!(
+                        "ERR CONFIG SET failed (possibly related to argument '{}') - argument must be between 1 and {} inclusive",
+                        parameter,
+                        u32::MAX
+                    )),
+                },
+                // Idle connections are never closed, so there is nothing to
+                // change. Accepting `0` lets clients disable the timeout.
+                "timeout" => match value.parse::<u64>() {
+                    Ok(0) => Frame::Simple("OK".to_string()),
+                    _ => Frame::Error(format!(
+                        "ERR CONFIG SET failed (possibly related to argument '{}') - idle connections are never closed, only 0 is supported",
+                        parameter
+                    )),
+                },
+                parameter if PARAMETERS.contains(&parameter) => Frame::Error(format!(
+                    "ERR CONFIG SET failed (possibly related to argument '{}') - can't set immutable config",
+                    parameter
+                )),
+                parameter => Frame::Error(format!(
+                    "ERR Unknown option or number of arguments for CONFIG SET - '{}'",
+                    parameter
+                )),
+            },
+            Subcommand::Unknown(subcommand) => Frame::Error(format!(
+                "ERR unknown subcommand '{}'. Try CONFIG HELP.",
+                subcommand
+            )),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
+
+/// Parses a memory value as Redis does: a number of bytes, optionally followed
+/// by a case insensitive unit, e.g. `100mb`.
+fn parse_memory(value: &str) -> Option<usize> {
+    let value = value.to_lowercase();
+    let digits = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(digits);
+
+    let scale: usize = match unit {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+
+    number.parse::<usize>().ok()?.checked_mul(scale)
+}
@@ -0,0 +1,55 @@
+use crate::{Connection, Db, Frame, Parse};
+
+use tracing::{debug, instrument};
+
+/// Return the number of keys in the database.
+///
+/// Keys that expired but have not been purged yet are included in the count.
+#[derive(Debug, Default)]
+pub struct Dbsize;
+
+impl Dbsize {
+    /// Create a new `Dbsize` command.
+    pub fn new() -> Dbsize {
+        Dbsize
+    }
+
+    /// Parse a `Dbsize` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `DBSIZE` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Dbsize` value on success. If the frame is malformed, `Err`
+    /// is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing a single entry.
+    ///
+    /// ```text
+    /// DBSIZE
+    /// ```
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Dbsize> {
+        Ok(Dbsize)
+    }
+
+    /// Apply the `Dbsize` command to the specified `Db` instance.
+    ///
+    /// The response is written to `dst`. This is called by the server in order
+    /// to execute a received command.
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = Frame::Integer(db.dbsize() as i64);
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+use crate::{Connection, Db, Frame, Parse};
+
+use std::time::Duration;
+use tracing::{debug, instrument};
+
+/// Commands useful for testing the server.
+///
+/// `DEBUG` is only available when the server is started with debugging
+/// enabled, see `ServerBuilder::enable_debug`.
+///
+/// # Subcommands
+///
+/// Currently, the following subcommands are supported:
+///
+/// * SLEEP `seconds` -- Wait for the given number of seconds, which may be
+///   fractional, before replying `OK`. This provides a deterministic slow
+///   command for testing timeouts.
+/// * SET-ACTIVE-EXPIRE `0|1` -- Disable or enable purging expired keys in the
+///   background. While disabled, expired keys are hidden from reads but still
+///   counted by `DBSIZE`, so lazy and active expiration can be tested
+///   separately.
+#[derive(Debug)]
+pub struct Debug {
+    subcommand: Subcommand,
+}
+
+#[derive(Debug)]
+enum Subcommand {
+    Sleep(Duration),
+    SetActiveExpire(bool),
+    Unknown(String),
+}
+
+impl Debug {
+    /// Parse a `Debug` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `DEBUG` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Debug` value on success. If the frame is malformed, `Err`
+    /// is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing `DEBUG`, a subcommand and the
+    /// subcommand's arguments.
+    ///
+    /// ```text
+    /// DEBUG SLEEP seconds
+    /// DEBUG SET-ACTIVE-EXPIRE 0|1
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Debug> {
+        // Subcommands are matched case insensitively, like command names.
+        let subcommand = match &parse.next_string()?.to_lowercase()[..] {
+            "sleep" => {
+                let seconds: f64 = parse
+                    .next_string()?
+                    .parse()
+                    .map_err(|_| "protocol error; invalid number of seconds")?;
+
+                if !seconds.is_finite() || seconds < 0.0 {
+                    return Err("protocol error; invalid number of seconds".into());
+                }
+
+                Subcommand::Sleep(Duration::from_secs_f64(seconds))
+            }
+            "set-active-expire" => match parse.next_int()? {
+                0 => Subcommand::SetActiveExpire(false),
+                1 => Subcommand::SetActiveExpire(true),
+                _ => return Err("protocol error; expected 0 or 1".into()),
+            },
+            subcommand => Subcommand::Unknown(subcommand.to_string()),
+        };
+
+        Ok(Debug { subcommand })
+    }
+
+    /// Apply the `Debug` command to the specified `Db` instance.
+    ///
+    /// The response is written to `dst`. This is called by the server in order
+    /// to execute a received command.
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = match self.subcommand {
+            Subcommand::Sleep(duration) => {
+                tokio::time::sleep(duration).await;
+                Frame::Simple("OK".to_string())
+            }
+            Subcommand::SetActiveExpire(enabled) => {
+                db.set_active_expire(enabled);
+                Frame::Simple("OK".to_string())
+            }
+            Subcommand::Unknown(subcommand) => Frame::Error(format!(
+                "ERR unknown subcommand '{}'. Try DEBUG HELP.",
+                subcommand
+            )),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
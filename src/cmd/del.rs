@@ -0,0 +1,78 @@
+use crate::cmd::ParseError;
+use crate::{Connection, Frame, Parse, Store};
+
+use tracing::{debug, instrument};
+
+/// Removes the specified keys. A key is ignored if it does not exist.
+///
+/// The response is the number of keys that were removed.
+#[derive(Debug)]
+pub struct Del {
+    /// Names of the keys to remove
+    keys: Vec<String>,
+}
+
+impl Del {
+    /// Create a new `Del` command which removes `keys`.
+    pub fn new(keys: Vec<String>) -> Del {
+        Del { keys }
+    }
+
+    /// Get the keys
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    /// Parse a `Del` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `DEL` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Del` value on success. If the frame is malformed, `Err` is
+    /// returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing two or more entries.
+    ///
+    /// ```text
+    /// DEL key [key ...]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Del> {
+        use ParseError::EndOfStream;
+
+        // At least one key is required.
+        let mut keys = vec![parse.next_string()?];
+
+        loop {
+            match parse.next_string() {
+                Ok(key) => keys.push(key),
+                Err(EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(Del { keys })
+    }
+
+    /// Apply the `Del` command to the specified `Store`.
+    ///
+    /// The response is written to `dst`. This is called by the server in order
+    /// to execute a received command, with its `Db`.
+    #[instrument(skip(self, db, dst))]
+    pub async fn apply(self, db: &impl Store, dst: &mut Connection) -> crate::Result<()> {
+        let removed = db.del(&self.keys);
+        let response = Frame::Integer(removed as i64);
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
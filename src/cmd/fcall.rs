@@ -0,0 +1,231 @@
+use crate::cmd::Set;
+use crate::{Db, Frame, Parse, Store};
+
+use bytes::Bytes;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{debug, instrument};
+
+/// Invoke a function registered with `ServerBuilder::register_function`.
+///
+/// Functions are Rust closures compiled into the server, which gives clients
+/// custom atomic operations, such as rate limiting, without a scripting
+/// engine. No other command is applied while a function runs.
+///
+/// Like Redis, the arguments are split into the keys the function accesses
+/// and other arguments. The response is the frame the function returns, or
+/// the error it returns.
+#[derive(Debug)]
+pub struct Fcall {
+    /// Name of the function
+    function: String,
+
+    /// Keys the function accesses
+    keys: Vec<String>,
+
+    /// Other arguments
+    args: Vec<Bytes>,
+}
+
+/// A function callable with `FCALL`. It is passed the store, the keys and the
+/// other arguments.
+pub(crate) type Function =
+    Arc<dyn Fn(&dyn Store, &[String], &[Bytes]) -> crate::Result<Frame> + Send + Sync>;
+
+/// The functions registered on the server, by name.
+#[derive(Default, Clone)]
+pub(crate) struct Functions {
+    functions: HashMap<String, Function>,
+}
+
+impl Fcall {
+    /// Create a new `Fcall` command which invokes `function` with `keys` and
+    /// `args`.
+    pub fn new(function: impl ToString, keys: Vec<String>, args: Vec<Bytes>) -> Fcall {
+        Fcall {
+            function: function.to_string(),
+            keys,
+            args,
+        }
+    }
+
+    /// Get the function name
+    pub fn function(&self) -> &str {
+        &self.function
+    }
+
+    /// Get the keys
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    /// Get the other arguments
+    pub fn args(&self) -> &[Bytes] {
+        &self.args
+    }
+
+    /// Parse a `Fcall` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `FCALL` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Fcall` value on success. If the frame is malformed, `Err`
+    /// is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing at least three entries.
+    ///
+    /// ```text
+    /// FCALL function numkeys [key ...] [arg ...]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Fcall> {
+        let function = parse.next_string()?;
+
+        let numkeys = usize::try_from(parse.next_int()?)
+            .ok()
+            .filter(|&numkeys| numkeys <= parse.remaining())
+            .ok_or("ERR Number of keys can't be greater than number of args")?;
+
+        let mut keys = Vec::with_capacity(numkeys);
+
+        for _ in 0..numkeys {
+            keys.push(parse.next_string()?);
+        }
+
+        let mut args = vec![];
+
+        while parse.remaining() > 0 {
+            args.push(parse.next_bytes()?);
+        }
+
+        Ok(Fcall {
+            function,
+            keys,
+            args,
+        })
+    }
+
+    /// Apply the `Fcall` command to the specified `Db` instance.
+    ///
+    /// Returns the response, along with the `SET` and `DEL` commands
+    /// equivalent to the writes of the function, which is what replicas and
+    /// the append-only file must apply instead of `FCALL`, as they may not
+    /// have the function. This is called by the connection handler, which
+    /// writes the response once no longer holding the lock, and logs those
+    /// commands.
+    #[instrument(skip(self, db, functions))]
+    pub(crate) fn apply(self, db: &Db, functions: &Functions) -> (Frame, Vec<Frame>) {
+        let store = RecordingStore {
+            db,
+            writes: RefCell::new(vec![]),
+        };
+
+        let response = match functions.functions.get(&self.function) {
+            Some(function) => match function(&store, &self.keys, &self.args) {
+                Ok(response) => response,
+                Err(err) => Frame::Error(err.to_string()),
+            },
+            None => Frame::Error("ERR Function not found".to_string()),
+        };
+
+        debug!(?response);
+
+        (response, store.writes.into_inner())
+    }
+
+    /// Converts the command into an equivalent `Frame`.
+    ///
+    /// This is called by the client when encoding a `Fcall` command to send to
+    /// the server.
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("fcall".as_bytes()));
+        frame.push_bulk(Bytes::from(self.function.into_bytes()));
+        frame.push_bulk(Bytes::from(self.keys.len().to_string()));
+
+        for key in self.keys {
+            frame.push_bulk(Bytes::from(key.into_bytes()));
+        }
+
+        for arg in self.args {
+            frame.push_bulk(arg);
+        }
+
+        frame
+    }
+}
+
+impl Functions {
+    /// Registers `function` under `name`, replacing any function registered
+    /// under the same name.
+    pub(crate) fn register(&mut self, name: String, function: Function) {
+        self.functions.insert(name, function);
+    }
+}
+
+impl fmt::Debug for Functions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Closures cannot be printed, so only their names are.
+        f.debug_set().entries(self.functions.keys()).finish()
+    }
+}
+
+/// The `Store` passed to functions. Writes are applied to the `Db`, and
+/// recorded as the equivalent commands.
+struct RecordingStore<'a> {
+    db: &'a Db,
+    writes: RefCell<Vec<Frame>>,
+}
+
+impl Store for RecordingStore<'_> {
+    fn get(&self, key: &str) -> crate::Result<Option<Bytes>> {
+        self.db.get(key)
+    }
+
+    fn set(&self, key: String, value: Bytes, expire: Option<Duration>) -> Option<Bytes> {
+        let frame = Set::new(&key, value.clone(), expire).into_frame();
+        self.writes.borrow_mut().push(frame);
+
+        self.db.set(key, value, expire)
+    }
+
+    fn del(&self, keys: &[String]) -> usize {
+        let removed = self.db.del(keys);
+
+        if removed > 0 {
+            let mut frame = Frame::array();
+            frame.push_bulk(Bytes::from("del".as_bytes()));
+
+            for key in keys {
+                frame.push_bulk(Bytes::from(key.clone().into_bytes()));
+            }
+
+            self.writes.borrow_mut().push(frame);
+        }
+
+        removed
+    }
+
+    fn subscribe(&self, channel: String) -> broadcast::Receiver<Bytes> {
+        self.db.subscribe(channel)
+    }
+
+    fn publish(&self, channel: &str, message: Bytes) -> usize {
+        self.db.publish(channel, message)
+    }
+
+    fn max_value_size(&self) -> usize {
+        self.db.max_value_size()
+    }
+}
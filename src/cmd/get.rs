@@ -1,4 +1,4 @@
-use crate::{Connection, Db, Frame, Parse};
+use crate::{Connection, Frame, Parse, Store};
 
 use bytes::Bytes;
 use tracing::{debug, instrument};
@@ -56,20 +56,21 @@ impl Get {
         Ok(Get { key })
     }
 
-    /// Apply the `Get` command to the specified `Db` instance.
+    /// Apply the `Get` command to the specified `Store`.
     ///
     /// The response is written to `dst`. This is called by the server in order
-    /// to execute a received command.
+    /// to execute a received command, with its `Db`.
     #[instrument(skip(self, db, dst))]
-    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+    pub async fn apply(self, db: &impl Store, dst: &mut Connection) -> crate::Result<()> {
         // Get the value from the shared database state
-        let response = if let Some(value) = db.get(&self.key) {
+        let response = match db.get(&self.key) {
             // If a value is present, it is written to the client in "bulk"
             // format.
-            Frame::Bulk(value)
-        } else {
+            Ok(Some(value)) => Frame::Bulk(value),
             // If there is no value, `Null` is written.
-            Frame::Null
+            Ok(None) => Frame::Null,
+            // The key holds a value that is not a string.
+            Err(err) => Frame::Error(err.to_string()),
         };
 
         debug!(?response);
@@ -84,7 +85,7 @@ impl Get {
     ///
     /// This is called by the client when encoding a `Get` command to send to
     /// the server.
-    pub(crate) fn into_frame(self) -> Frame {
+    pub fn into_frame(self) -> Frame {
         let mut frame = Frame::array();
         frame.push_bulk(Bytes::from("get".as_bytes()));
         frame.push_bulk(Bytes::from(self.key.into_bytes()));
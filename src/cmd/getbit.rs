@@ -0,0 +1,95 @@
+use crate::cmd::setbit::parse_offset;
+use crate::{Connection, Db, Frame, Parse};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// Get the bit at `offset` in the string stored at `key`.
+///
+/// Bits are numbered from the most significant bit of the first byte. Bits
+/// past the end of the string, or of a missing key, are `0`.
+#[derive(Debug)]
+pub struct Getbit {
+    /// Name of the key to read
+    key: String,
+
+    /// Offset of the bit to read
+    offset: u32,
+}
+
+impl Getbit {
+    /// Create a new `Getbit` command which reads the bit at `offset` in `key`.
+    pub fn new(key: impl ToString, offset: u32) -> Getbit {
+        Getbit {
+            key: key.to_string(),
+            offset,
+        }
+    }
+
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Get the offset
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// Parse a `Getbit` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `GETBIT` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Getbit` value on success. If the frame is malformed, `Err`
+    /// is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing three entries.
+    ///
+    /// ```text
+    /// GETBIT key offset
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Getbit> {
+        let key = parse.next_string()?;
+        let offset = parse_offset(parse)?;
+
+        Ok(Getbit { key, offset })
+    }
+
+    /// Apply the `Getbit` command to the specified `Db` instance.
+    ///
+    /// The response is written to `dst`. This is called by the server in order
+    /// to execute a received command.
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = match db.getbit(&self.key, self.offset) {
+            Ok(bit) => Frame::Integer(bit as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Converts the command into an equivalent `Frame`.
+    ///
+    /// This is called by the client when encoding a `Getbit` command to send
+    /// to the server.
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("getbit".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_int(self.offset as i64);
+        frame
+    }
+}
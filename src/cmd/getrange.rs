@@ -0,0 +1,108 @@
+use crate::{Connection, Db, Frame, Parse};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// Get the part of the string stored at `key` between the `start` and `end`
+/// offsets, both inclusive.
+///
+/// Negative offsets count from the end of the string, so `-1` is the last
+/// byte. The range is clamped to the string, and a missing key is treated as
+/// an empty string.
+#[derive(Debug)]
+pub struct Getrange {
+    /// Name of the key to read
+    key: String,
+
+    /// Offset of the first byte to return
+    start: i64,
+
+    /// Offset of the last byte to return
+    end: i64,
+}
+
+impl Getrange {
+    /// Create a new `Getrange` command which reads `key` from `start` to
+    /// `end`.
+    pub fn new(key: impl ToString, start: i64, end: i64) -> Getrange {
+        Getrange {
+            key: key.to_string(),
+            start,
+            end,
+        }
+    }
+
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Get the start offset
+    pub fn start(&self) -> i64 {
+        self.start
+    }
+
+    /// Get the end offset
+    pub fn end(&self) -> i64 {
+        self.end
+    }
+
+    /// Parse a `Getrange` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `GETRANGE` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Getrange` value on success. If the frame is malformed,
+    /// `Err` is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing four entries.
+    ///
+    /// ```text
+    /// GETRANGE key start end
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Getrange> {
+        let key = parse.next_string()?;
+        let start = parse.next_signed_int()?;
+        let end = parse.next_signed_int()?;
+
+        Ok(Getrange { key, start, end })
+    }
+
+    /// Apply the `Getrange` command to the specified `Db` instance.
+    ///
+    /// The response is written to `dst`. This is called by the server in order
+    /// to execute a received command.
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = match db.getrange(&self.key, self.start, self.end) {
+            Ok(value) => Frame::Bulk(value),
+            Err(err) => Frame::Error(err.to_string()),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Converts the command into an equivalent `Frame`.
+    ///
+    /// This is called by the client when encoding a `Getrange` command to send
+    /// to the server.
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("getrange".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_bulk(Bytes::from(self.start.to_string()));
+        frame.push_bulk(Bytes::from(self.end.to_string()));
+        frame
+    }
+}
@@ -0,0 +1,101 @@
+use crate::{Connection, Db, Frame, Parse};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// Increment the floating point number stored at `key` by `increment`.
+///
+/// If the key does not exist, it is set to `0` before performing the
+/// operation. An error is returned if the value stored at key cannot be parsed
+/// as a floating point number. The key's expiration, if any, is kept.
+///
+/// The response is the value of the key after the increment.
+#[derive(Debug)]
+pub struct Incrbyfloat {
+    /// Name of the key to increment
+    key: String,
+
+    /// Amount to add to the value
+    increment: f64,
+}
+
+impl Incrbyfloat {
+    /// Create a new `Incrbyfloat` command which adds `increment` to `key`.
+    pub fn new(key: impl ToString, increment: f64) -> Incrbyfloat {
+        Incrbyfloat {
+            key: key.to_string(),
+            increment,
+        }
+    }
+
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Get the increment
+    pub fn increment(&self) -> f64 {
+        self.increment
+    }
+
+    /// Parse an `Incrbyfloat` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `INCRBYFLOAT` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Incrbyfloat` value on success. If the frame is malformed,
+    /// `Err` is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing three entries.
+    ///
+    /// ```text
+    /// INCRBYFLOAT key increment
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Incrbyfloat> {
+        let key = parse.next_string()?;
+
+        let increment = parse
+            .next_string()?
+            .parse()
+            .map_err(|_| "ERR value is not a valid float")?;
+
+        Ok(Incrbyfloat { key, increment })
+    }
+
+    /// Apply the `Incrbyfloat` command to the specified `Db` instance.
+    ///
+    /// The response is written to `dst`. This is called by the server in order
+    /// to execute a received command.
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = match db.incr_by_float(&self.key, self.increment) {
+            Ok(value) => Frame::Bulk(Bytes::from(value.to_string())),
+            Err(err) => Frame::Error(err.to_string()),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Converts the command into an equivalent `Frame`.
+    ///
+    /// This is called by the client when encoding an `Incrbyfloat` command to
+    /// send to the server.
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("incrbyfloat".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_bulk(Bytes::from(self.increment.to_string()));
+        frame
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{Connection, Db, Frame, Parse, ParseError};
+
+use bytes::Bytes;
+use std::fmt::Write;
+use tracing::{debug, instrument};
+
+/// Returns information and statistics about the server.
+///
+/// The reply is a bulk string made of sections. Each section starts with a
+/// `# Name` header line, followed by `field:value` lines.
+///
+/// # Sections
+///
+/// Currently, the following sections are supported:
+///
+/// * server -- General information about the server.
+/// * memory -- Memory used by the data set, and the configured limit.
+/// * stats -- Number of bytes read from and written to clients.
+/// * keyspace -- Number of keys in the database.
+///
+/// Without an argument, or with `all` or `default`, every section is
+/// returned. Unknown sections produce an empty reply.
+#[derive(Debug, Default)]
+pub struct Info {
+    /// The section to return, in lower case
+    section: Option<String>,
+}
+
+/// The sections returned when no section is requested, in order.
+const SECTIONS: &[&str] = &["server", "memory", "stats", "keyspace"];
+
+impl Info {
+    /// Create a new `Info` command returning `section`, or every section if
+    /// `None`.
+    pub fn new(section: Option<String>) -> Info {
+        Info {
+            section: section.map(|section| section.to_lowercase()),
+        }
+    }
+
+    /// Parse an `Info` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `INFO` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Info` value on success. If the frame is malformed, `Err` is
+    /// returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing `INFO` and an optional section name.
+    ///
+    /// ```text
+    /// INFO [section]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Info> {
+        match parse.next_string() {
+            Ok(section) => Ok(Info::new(Some(section))),
+            Err(ParseError::EndOfStream) => Ok(Info::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Apply the `Info` command to the specified `Db` instance.
+    ///
+    /// The response is written to `dst`. This is called by the server in order
+    /// to execute a received command.
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let sections = match self.section.as_deref() {
+            None | Some("all") | Some("default") | Some("everything") => SECTIONS.to_vec(),
+            Some(section) => vec![section],
+        };
+
+        let mut info = String::new();
+
+        for section in sections {
+            // Sections are separated by an empty line.
+            if !info.is_empty() {
+                info.push_str("\r\n");
+            }
+
+            write_section(&mut info, section, db);
+        }
+
+        let response = Frame::Bulk(Bytes::from(info));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
+
+/// Appends the contents of `section` to `info`. Nothing is appended for
+/// unknown sections.
+fn write_section(info: &mut String, section: &str, db: &Db) {
+    // Writing to a `String` cannot fail.
+    match section {
+        "server" => {
+            info.push_str("# Server\r\n");
+            let _ = write!(info, "mini_redis_version:{}\r\n", env!("CARGO_PKG_VERSION"));
+        }
+        "memory" => {
+            info.push_str("# Memory\r\n");
+            let _ = write!(info, "used_memory:{}\r\n", db.used_memory());
+            let _ = write!(info, "maxmemory:{}\r\n", db.maxmemory());
+            info.push_str("maxmemory_policy:noeviction\r\n");
+        }
+        "stats" => {
+            let net_stats = db.net_stats();
+            info.push_str("# Stats\r\n");
+            let _ = write!(info, "total_net_input_bytes:{}\r\n", net_stats.input());
+            let _ = write!(info, "total_net_output_bytes:{}\r\n", net_stats.output());
+        }
+        "keyspace" => {
+            info.push_str("# Keyspace\r\n");
+
+            // Like Redis, empty databases are not listed.
+            let (keys, expires) = db.keyspace();
+
+            if keys > 0 {
+                let _ = write!(info, "db0:keys={},expires={},avg_ttl=0\r\n", keys, expires);
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,159 @@
+use crate::cmd::{CommandSpec, COMMANDS};
+use crate::{Connection, Frame, Parse, ParseError};
+
+use tracing::{debug, instrument};
+
+/// Describes the commands supported by the server.
+///
+/// This is the `COMMAND` command. Client libraries, including `redis-cli`,
+/// issue it when connecting in order to discover which commands are available.
+///
+/// # Subcommands
+///
+/// Currently, the following subcommands are supported:
+///
+/// * COUNT -- Return the number of supported commands.
+/// * INFO `[name ...]` -- Return the name, arity, flags and key positions of
+///   each named command, or of every command if no names are given. Unknown
+///   commands are reported as `Null`.
+/// * DOCS `[name ...]` -- Return the documentation of each named command, or of
+///   every command if no names are given. Unknown commands are omitted.
+///
+/// `COMMAND` without a subcommand is equivalent to `COMMAND INFO`.
+#[derive(Debug)]
+pub struct Introspect {
+    /// The subcommand, in lower case
+    subcommand: String,
+
+    /// Names of the commands to describe
+    names: Vec<String>,
+}
+
+impl Introspect {
+    /// Parse an `Introspect` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `COMMAND` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Introspect` value on success. If the frame is malformed,
+    /// `Err` is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing `COMMAND`, an optional subcommand and
+    /// the subcommand's arguments.
+    ///
+    /// ```text
+    /// COMMAND [subcommand [name ...]]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Introspect> {
+        use ParseError::EndOfStream;
+
+        let subcommand = match parse.next_string() {
+            Ok(subcommand) => subcommand.to_lowercase(),
+            Err(EndOfStream) => "info".to_string(),
+            Err(err) => return Err(err.into()),
+        };
+
+        // Any remaining entries are command names.
+        let mut names = vec![];
+
+        loop {
+            match parse.next_string() {
+                Ok(name) => names.push(name),
+                Err(EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(Introspect { subcommand, names })
+    }
+
+    /// Apply the `Introspect` command.
+    ///
+    /// The response is written to `dst`. This is called by the server in order
+    /// to execute a received command.
+    #[instrument(skip(self, dst))]
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = match &self.subcommand[..] {
+            "count" if self.names.is_empty() => Frame::Integer(COMMANDS.len() as i64),
+            "count" => {
+                Frame::Error("ERR wrong number of arguments for 'command|count' command".into())
+            }
+            "info" => Frame::Array(
+                self.specs()
+                    .into_iter()
+                    .map(|spec| match spec {
+                        Some(spec) => info(spec),
+                        None => Frame::Null,
+                    })
+                    .collect(),
+            ),
+            // The response alternates between a command name and its
+            // documentation.
+            "docs" => Frame::Array(
+                self.specs()
+                    .into_iter()
+                    .flatten()
+                    .flat_map(|spec| vec![Frame::Bulk(spec.name.into()), docs(spec)])
+                    .collect(),
+            ),
+            subcommand => Frame::Error(format!(
+                "ERR unknown subcommand '{}'. Try COMMAND HELP.",
+                subcommand
+            )),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Returns the spec of each requested command, or of every command if none
+    /// were named. Unknown names are returned as `None`.
+    fn specs(&self) -> Vec<Option<&'static CommandSpec>> {
+        if self.names.is_empty() {
+            COMMANDS.iter().map(Some).collect()
+        } else {
+            self.names
+                .iter()
+                .map(|name| CommandSpec::lookup(name))
+                .collect()
+        }
+    }
+}
+
+/// The `COMMAND INFO` entry for a command:
+/// `[name, arity, [flags], first_key, last_key, step]`.
+fn info(spec: &CommandSpec) -> Frame {
+    let flags = spec
+        .flags
+        .iter()
+        .map(|flag| Frame::Simple(flag.to_string()))
+        .collect();
+
+    Frame::Array(vec![
+        Frame::Bulk(spec.name.into()),
+        Frame::Integer(spec.arity),
+        Frame::Array(flags),
+        Frame::Integer(spec.first_key),
+        Frame::Integer(spec.last_key),
+        Frame::Integer(spec.step),
+    ])
+}
+
+/// The `COMMAND DOCS` entry for a command, as a flattened map of field names to
+/// values.
+fn docs(spec: &CommandSpec) -> Frame {
+    let mut frame = Frame::array();
+    frame.push_bulk("arity".into());
+    frame.push_int(spec.arity);
+    frame
+}
@@ -0,0 +1,58 @@
+use crate::{Connection, Db, Frame, Parse};
+
+use tracing::{debug, instrument};
+
+/// Return the Unix time, in seconds, of the last successful `SAVE` or
+/// `BGSAVE`.
+///
+/// If the data set was never saved, the time the server started is returned.
+/// A client can call `BGSAVE`, then poll `LASTSAVE` to learn when the
+/// background save is done.
+#[derive(Debug, Default)]
+pub struct Lastsave;
+
+impl Lastsave {
+    /// Create a new `Lastsave` command.
+    pub fn new() -> Lastsave {
+        Lastsave
+    }
+
+    /// Parse a `Lastsave` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `LASTSAVE` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Lastsave` value on success. If the frame is malformed,
+    /// `Err` is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing a single entry.
+    ///
+    /// ```text
+    /// LASTSAVE
+    /// ```
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Lastsave> {
+        Ok(Lastsave)
+    }
+
+    /// Apply the `Lastsave` command to the specified `Db` instance.
+    ///
+    /// The response is written to `dst`. This is called by the server in order
+    /// to execute a received command.
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = Frame::Integer(db.last_save() as i64);
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+use crate::{Connection, Db, Frame, Parse};
+
+use tracing::{debug, instrument};
+
+/// Inspect the memory used by the server.
+///
+/// # Subcommands
+///
+/// Currently, the following subcommands are supported:
+///
+/// * USAGE `key` [SAMPLES `count`] -- Return the approximate number of bytes
+///   used to store the key and its value, or `nil` if the key does not exist.
+///   The size of every member of a set is counted, so `SAMPLES` is accepted
+///   but ignored.
+#[derive(Debug)]
+pub struct Memory {
+    subcommand: Subcommand,
+}
+
+#[derive(Debug)]
+enum Subcommand {
+    Usage(String),
+    Unknown(String),
+}
+
+impl Memory {
+    /// Parse a `Memory` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `MEMORY` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Memory` value on success. If the frame is malformed, `Err`
+    /// is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing `MEMORY`, a subcommand and the
+    /// subcommand's arguments.
+    ///
+    /// ```text
+    /// MEMORY USAGE key [SAMPLES count]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Memory> {
+        // Subcommands are matched case insensitively, like command names.
+        let subcommand = match &parse.next_string()?.to_lowercase()[..] {
+            "usage" => {
+                let key = parse.next_string()?;
+
+                if parse.remaining() > 0 {
+                    if parse.next_string()?.to_lowercase() != "samples" {
+                        return Err("ERR syntax error".into());
+                    }
+                    parse.next_int()?;
+                }
+
+                Subcommand::Usage(key)
+            }
+            subcommand => {
+                // The arguments of an unknown subcommand are not looked at.
+                while parse.remaining() > 0 {
+                    parse.next_bytes()?;
+                }
+
+                Subcommand::Unknown(subcommand.to_string())
+            }
+        };
+
+        Ok(Memory { subcommand })
+    }
+
+    /// Apply the `Memory` command to the specified `Db` instance.
+    ///
+    /// The response is written to `dst`. This is called by the server in order
+    /// to execute a received command.
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = match self.subcommand {
+            Subcommand::Usage(key) => match db.memory_usage(&key) {
+                Some(bytes) => Frame::Integer(bytes as i64),
+                None => Frame::Null,
+            },
+            Subcommand::Unknown(subcommand) => Frame::Error(format!(
+                "ERR unknown subcommand '{}'. Try MEMORY HELP.",
+                subcommand
+            )),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
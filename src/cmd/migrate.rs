@@ -0,0 +1,204 @@
+use crate::clients::Client;
+use crate::{Connection, Db, Frame, MiniRedisError, Parse};
+
+use bytes::Bytes;
+use std::convert::TryFrom;
+use std::time::Duration;
+use tokio::sync::OwnedMutexGuard;
+use tokio::time::{self, Instant};
+use tracing::{debug, instrument};
+
+/// Move `key` to another mini-redis instance.
+///
+/// The value is serialized as with `DUMP`, and restored on the target, at
+/// `host` and `port`, with `RESTORE`, keeping its time to live. Once the target
+/// accepted it, the key is deleted, unless `COPY` is given. If the key already
+/// exists on the target, it is only replaced if `REPLACE` is given.
+///
+/// `timeout` is the most time, in milliseconds, connecting to the target and
+/// waiting for its reply may take. On any failure, the key is left untouched.
+///
+/// The response is `OK`, or `NOKEY` if the key does not exist. Only database
+/// `0` exists, and the `AUTH`, `AUTH2` and `KEYS` options are not supported.
+#[derive(Debug)]
+pub struct Migrate {
+    /// Host of the target instance
+    host: String,
+
+    /// Port of the target instance
+    port: u16,
+
+    /// Name of the key to move
+    key: String,
+
+    /// Database of the target instance
+    db: u64,
+
+    /// Most time the transfer may take
+    timeout: Duration,
+
+    /// Whether the key is kept
+    copy: bool,
+
+    /// Whether an existing key on the target is replaced
+    replace: bool,
+}
+
+impl Migrate {
+    /// Parse a `Migrate` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `MIGRATE` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Migrate` value on success. If the frame is malformed,
+    /// `Err` is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing at least six entries.
+    ///
+    /// ```text
+    /// MIGRATE host port key destination-db timeout [COPY] [REPLACE]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Migrate> {
+        let host = parse.next_string()?;
+        let port = u16::try_from(parse.next_int()?).map_err(|_| "ERR Invalid port")?;
+        let key = parse.next_string()?;
+        let db = parse.next_int()?;
+
+        // Like Redis, a timeout that is not positive is one second.
+        let timeout = match parse.next_signed_int()? {
+            ms if ms <= 0 => Duration::from_secs(1),
+            ms => Duration::from_millis(ms as u64),
+        };
+
+        let mut copy = false;
+        let mut replace = false;
+
+        while parse.remaining() > 0 {
+            match &parse.next_string()?.to_uppercase()[..] {
+                "COPY" => copy = true,
+                "REPLACE" => replace = true,
+                _ => return Err("ERR syntax error".into()),
+            }
+        }
+
+        Ok(Migrate {
+            host,
+            port,
+            key,
+            db,
+            timeout,
+            copy,
+            replace,
+        })
+    }
+
+    /// Apply the `Migrate` command to the specified `Db` instance.
+    ///
+    /// The response is written to `dst`. Returns the `DEL` command deleting
+    /// the key, if it was deleted, which is what replicas and the append-only
+    /// file must apply instead of `MIGRATE`, along with the log lock taken
+    /// before deleting it, see `Db::lock_log`. This is called by the
+    /// connection handler, which logs that command before releasing the lock.
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+    ) -> crate::Result<Option<(Frame, OwnedMutexGuard<()>)>> {
+        let (response, deleted) = match self.migrate(db).await {
+            Ok(Some(deleted)) => (Frame::Simple("OK".to_string()), deleted),
+            Ok(None) => (Frame::Simple("NOKEY".to_string()), None),
+            Err(err) => (Frame::Error(err.to_string()), None),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        let log = match deleted {
+            Some(log) => log,
+            None => return Ok(None),
+        };
+
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("del".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+
+        Ok(Some((frame, log)))
+    }
+
+    /// Restores the key on the target, then deletes it unless `COPY` was
+    /// given. Returns the log lock if the key was deleted, or `None` if there
+    /// is no such key.
+    async fn migrate(&self, db: &Db) -> crate::Result<Option<Option<OwnedMutexGuard<()>>>> {
+        if self.db != 0 {
+            return Err("ERR DB index is out of range".into());
+        }
+
+        // The key is read while no transaction is applied, but the lock is
+        // released before contacting the target: a slow target must not hold
+        // up transactions, and every command queued behind them. The version
+        // is read along with the value, so a value written while the key is
+        // transferred is detected, and kept.
+        let (version, payload, ttl) = {
+            let _lock = db.lock_shared().await;
+
+            let version = match db.key_version(&self.key) {
+                Some(version) => version,
+                None => return Ok(None),
+            };
+
+            match (db.dump(&self.key), db.expires_at(&self.key)) {
+                (Some(payload), Some(expires_at)) => (
+                    version,
+                    payload,
+                    expires_at.map(|when| when.saturating_duration_since(Instant::now())),
+                ),
+                _ => return Ok(None),
+            }
+        };
+
+        let connect = Client::connect_timeout((self.host.as_str(), self.port), self.timeout);
+        let mut client = connect
+            .await
+            .map_err(|_| "IOERR error or timeout connecting to the client")?;
+
+        let restore = client.restore(&self.key, ttl, payload, self.replace);
+
+        match time::timeout(self.timeout, restore).await {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => match err.downcast_ref::<MiniRedisError>() {
+                Some(
+                    MiniRedisError::Io(_)
+                    | MiniRedisError::ConnectionReset
+                    | MiniRedisError::Protocol(_),
+                )
+                | None => return Err("IOERR error or timeout reading to target instance".into()),
+                Some(err) => {
+                    return Err(format!("ERR Target instance replied with error: {}", err).into())
+                }
+            },
+            Err(_) => return Err("IOERR error or timeout reading to target instance".into()),
+        }
+
+        if self.copy {
+            return Ok(Some(None));
+        }
+
+        let log = db.lock_log().await;
+        let _lock = db.lock_shared().await;
+
+        if !db.del_if_version(&self.key, version) {
+            return Ok(Some(None));
+        }
+
+        Ok(Some(Some(log)))
+    }
+}
@@ -1,22 +1,173 @@
+mod auth;
+pub use auth::Auth;
+
+mod bgsave;
+pub use bgsave::Bgsave;
+
+mod lastsave;
+pub use lastsave::Lastsave;
+
+mod config;
+pub use config::Config;
+
+mod time;
+pub use time::Time;
+
+mod dbsize;
+pub use dbsize::Dbsize;
+
+mod debug;
+pub use debug::Debug;
+
+mod del;
+pub use del::Del;
+
 mod get;
 pub use get::Get;
 
+mod info;
+pub use info::Info;
+
+mod introspect;
+pub use introspect::Introspect;
+
+mod key_type;
+pub use key_type::Type;
+
+mod monitor;
+pub use monitor::Monitor;
+pub(crate) use monitor::MonitoredCommand;
+
+mod object;
+pub use object::Object;
+
+mod memory;
+pub use memory::Memory;
+
+mod psync;
+pub use psync::Psync;
+
 mod publish;
 pub use publish::Publish;
 
+mod quit;
+pub use quit::Quit;
+
+mod randomkey;
+pub use randomkey::Randomkey;
+
+mod reset;
+pub use reset::Reset;
+
+mod role;
+pub use role::{Failover, Role};
+
+mod save;
+pub use save::Save;
+
+mod select;
+pub use select::Select;
+
 mod set;
-pub use set::Set;
+pub use set::{Set, SetCondition};
 
 mod subscribe;
-pub use subscribe::{Subscribe, Unsubscribe};
+pub use subscribe::{Psubscribe, Punsubscribe, Subscribe, Unsubscribe};
 
 mod ping;
 pub use ping::Ping;
 
+mod ttl;
+pub use ttl::{Ttl, TtlKind};
+
+mod unlink;
+pub use unlink::Unlink;
+
+mod touch;
+pub use touch::Touch;
+
+mod incrbyfloat;
+pub use incrbyfloat::Incrbyfloat;
+
+mod setrange;
+pub use setrange::Setrange;
+
+mod getrange;
+pub use getrange::Getrange;
+
+mod setbit;
+pub use setbit::Setbit;
+
+mod getbit;
+pub use getbit::Getbit;
+
+mod bitcount;
+pub use bitcount::Bitcount;
+
+mod cas;
+pub use cas::Cas;
+
+mod dump;
+pub use dump::Dump;
+
+mod restore;
+pub use restore::Restore;
+
+mod migrate;
+pub use migrate::Migrate;
+
+mod fcall;
+pub use fcall::Fcall;
+pub(crate) use fcall::{Function, Functions};
+
+mod zadd;
+pub use zadd::Zadd;
+
+mod zscore;
+pub use zscore::Zscore;
+
+mod zrange;
+pub use zrange::Zrange;
+
+mod sadd;
+pub use sadd::Sadd;
+
+mod srem;
+pub use srem::Srem;
+
+mod smembers;
+pub use smembers::Smembers;
+
+mod sismember;
+pub use sismember::Sismember;
+
+mod scard;
+pub use scard::Scard;
+
+mod setop;
+pub use setop::{SetOp, SetOpKind};
+
+mod slowlog;
+pub(crate) use slowlog::SlowCommands;
+pub use slowlog::Slowlog;
+
+mod wait;
+pub use wait::Wait;
+
+mod multi;
+pub(crate) use multi::Transaction;
+pub use multi::{Discard, Exec, Multi};
+
+mod watch;
+pub use watch::{Unwatch, Watch};
+
 mod unknown;
 pub use unknown::Unknown;
 
-use crate::{Connection, Db, Frame, Parse, ParseError, Shutdown};
+use crate::{Connection, Db, Frame, Parse, ParseError, Session, Shutdown};
+
+use std::convert::TryFrom;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// Enumeration of supported Redis commands.
 ///
@@ -28,10 +179,204 @@ pub enum Command {
     Set(Set),
     Subscribe(Subscribe),
     Unsubscribe(Unsubscribe),
+    Psubscribe(Psubscribe),
+    Punsubscribe(Punsubscribe),
     Ping(Ping),
+    Type(Type),
+    Object(Object),
+    Introspect(Introspect),
+    Select(Select),
+    Auth(Auth),
+    Del(Del),
+    Randomkey(Randomkey),
+    Quit(Quit),
+    Info(Info),
+    Unlink(Unlink),
+    Touch(Touch),
+    Reset(Reset),
+    Debug(Debug),
+    Dbsize(Dbsize),
+    Ttl(Ttl),
+    Save(Save),
+    Bgsave(Bgsave),
+    Wait(Wait),
+    Psync(Psync),
+    Monitor(Monitor),
+    Incrbyfloat(Incrbyfloat),
+    Setrange(Setrange),
+    Getrange(Getrange),
+    Setbit(Setbit),
+    Getbit(Getbit),
+    Bitcount(Bitcount),
+    Cas(Cas),
+    Dump(Dump),
+    Restore(Restore),
+    Migrate(Migrate),
+    Fcall(Fcall),
+    Zadd(Zadd),
+    Zscore(Zscore),
+    Zrange(Zrange),
+    Sadd(Sadd),
+    Srem(Srem),
+    Smembers(Smembers),
+    Sismember(Sismember),
+    Scard(Scard),
+    SetOp(SetOp),
+    Slowlog(Slowlog),
+    Multi(Multi),
+    Exec(Exec),
+    Discard(Discard),
+    Watch(Watch),
+    Unwatch(Unwatch),
+    Config(Config),
+    Time(Time),
+    Lastsave(Lastsave),
+    Role(Role),
+    Failover(Failover),
+    Memory(Memory),
     Unknown(Unknown),
 }
 
+/// Static information about a command, as reported by the `COMMAND` command.
+#[derive(Debug)]
+pub(crate) struct CommandSpec {
+    /// The command name, in lower case
+    pub(crate) name: &'static str,
+
+    /// The number of arguments, including the command name. A negative value
+    /// `-n` means the command takes at least `n` arguments.
+    pub(crate) arity: i64,
+
+    /// Flags describing the command's behavior, using the names Redis reports,
+    /// e.g. `readonly` or `write`.
+    pub(crate) flags: &'static [&'static str],
+
+    /// Position of the first key argument, or `0` if the command takes no
+    /// keys.
+    pub(crate) first_key: i64,
+
+    /// Position of the last key argument. Negative values count from the end
+    /// of the arguments, so `-1` means the keys continue to the end.
+    pub(crate) last_key: i64,
+
+    /// Step between key arguments, between `first_key` and `last_key`.
+    pub(crate) step: i64,
+}
+
+/// Every command supported by mini-redis.
+///
+/// When a command is added to `Command`, it must be added here as well.
+#[rustfmt::skip]
+pub(crate) const COMMANDS: &[CommandSpec] = &[
+    spec("get", 2, &["readonly", "fast"], (1, 1, 1)),
+    spec("publish", 3, &["pubsub", "loading", "stale", "fast"], (0, 0, 0)),
+    spec("set", -3, &["write", "denyoom"], (1, 1, 1)),
+    spec("subscribe", -2, &["pubsub", "noscript", "loading", "stale"], (0, 0, 0)),
+    spec("unsubscribe", -1, &["pubsub", "noscript", "loading", "stale"], (0, 0, 0)),
+    spec("psubscribe", -2, &["pubsub", "noscript", "loading", "stale"], (0, 0, 0)),
+    spec("punsubscribe", -1, &["pubsub", "noscript", "loading", "stale"], (0, 0, 0)),
+    spec("ping", -1, &["fast", "stale"], (0, 0, 0)),
+    spec("type", 2, &["readonly", "fast"], (1, 1, 1)),
+    spec("object", 3, &["readonly"], (2, 2, 1)),
+    spec("command", -1, &["loading", "stale"], (0, 0, 0)),
+    spec("select", 2, &["loading", "stale", "fast"], (0, 0, 0)),
+    spec("auth", -2, &["noscript", "loading", "stale", "fast", "no_auth"], (0, 0, 0)),
+    spec("del", -2, &["write"], (1, -1, 1)),
+    spec("randomkey", 1, &["readonly"], (0, 0, 0)),
+    spec("quit", -1, &["loading", "stale", "fast", "no_auth"], (0, 0, 0)),
+    spec("info", -1, &["loading", "stale"], (0, 0, 0)),
+    spec("unlink", -2, &["write", "fast"], (1, -1, 1)),
+    spec("touch", -2, &["readonly", "fast"], (1, -1, 1)),
+    spec("reset", 1, &["noscript", "loading", "stale", "fast", "no_auth"], (0, 0, 0)),
+    spec("debug", -2, &["admin", "noscript", "loading", "stale"], (0, 0, 0)),
+    spec("dbsize", 1, &["readonly", "fast"], (0, 0, 0)),
+    spec("ttl", 2, &["readonly", "fast"], (1, 1, 1)),
+    spec("pttl", 2, &["readonly", "fast"], (1, 1, 1)),
+    spec("expiretime", 2, &["readonly", "fast"], (1, 1, 1)),
+    spec("pexpiretime", 2, &["readonly", "fast"], (1, 1, 1)),
+    spec("save", 1, &["admin", "noscript"], (0, 0, 0)),
+    spec("bgsave", 1, &["admin", "noscript"], (0, 0, 0)),
+    spec("wait", 3, &["noscript"], (0, 0, 0)),
+    spec("psync", 3, &["admin", "noscript"], (0, 0, 0)),
+    spec("monitor", 1, &["admin", "noscript", "loading", "stale"], (0, 0, 0)),
+    spec("slowlog", -2, &["admin", "random", "loading", "stale"], (0, 0, 0)),
+    spec("incrbyfloat", 3, &["write", "denyoom", "fast"], (1, 1, 1)),
+    spec("setrange", 4, &["write", "denyoom"], (1, 1, 1)),
+    spec("getrange", 4, &["readonly"], (1, 1, 1)),
+    spec("setbit", 4, &["write", "denyoom"], (1, 1, 1)),
+    spec("getbit", 3, &["readonly", "fast"], (1, 1, 1)),
+    spec("bitcount", -2, &["readonly"], (1, 1, 1)),
+    spec("cas", 4, &["write", "denyoom"], (1, 1, 1)),
+    spec("dump", 2, &["readonly", "random"], (1, 1, 1)),
+    spec("restore", -4, &["write", "denyoom"], (1, 1, 1)),
+    spec("migrate", -6, &["write", "random"], (3, 3, 1)),
+    spec("fcall", -3, &["write", "denyoom"], (0, 0, 0)),
+    spec("zadd", -4, &["write", "denyoom", "fast"], (1, 1, 1)),
+    spec("zscore", 3, &["readonly", "fast"], (1, 1, 1)),
+    spec("zrange", -4, &["readonly"], (1, 1, 1)),
+    spec("sadd", -3, &["write", "denyoom", "fast"], (1, 1, 1)),
+    spec("srem", -3, &["write", "fast"], (1, 1, 1)),
+    spec("smembers", 2, &["readonly"], (1, 1, 1)),
+    spec("sismember", 3, &["readonly", "fast"], (1, 1, 1)),
+    spec("scard", 2, &["readonly", "fast"], (1, 1, 1)),
+    spec("sinter", -2, &["readonly"], (1, -1, 1)),
+    spec("sunion", -2, &["readonly"], (1, -1, 1)),
+    spec("sdiff", -2, &["readonly"], (1, -1, 1)),
+    spec("sinterstore", -3, &["write", "denyoom"], (1, -1, 1)),
+    spec("sunionstore", -3, &["write", "denyoom"], (1, -1, 1)),
+    spec("sdiffstore", -3, &["write", "denyoom"], (1, -1, 1)),
+    spec("multi", 1, &["noscript", "loading", "stale", "fast"], (0, 0, 0)),
+    spec("exec", 1, &["noscript", "loading", "stale"], (0, 0, 0)),
+    spec("discard", 1, &["noscript", "loading", "stale", "fast"], (0, 0, 0)),
+    spec("watch", -2, &["noscript", "loading", "stale", "fast"], (1, -1, 1)),
+    spec("unwatch", 1, &["noscript", "loading", "stale", "fast"], (0, 0, 0)),
+    spec("config", -2, &["admin", "noscript", "loading", "stale"], (0, 0, 0)),
+    spec("time", 1, &["random", "loading", "stale", "fast"], (0, 0, 0)),
+    spec("lastsave", 1, &["random", "loading", "stale", "fast"], (0, 0, 0)),
+    spec("role", 1, &["noscript", "loading", "stale", "fast"], (0, 0, 0)),
+    spec("failover", -1, &["admin", "noscript", "stale"], (0, 0, 0)),
+    spec("memory", -2, &["readonly"], (2, 2, 1)),
+];
+
+/// Shorthand for the entries of `COMMANDS`. `keys` holds the first key
+/// position, the last key position and the step between keys.
+const fn spec(
+    name: &'static str,
+    arity: i64,
+    flags: &'static [&'static str],
+    keys: (i64, i64, i64),
+) -> CommandSpec {
+    CommandSpec {
+        name,
+        arity,
+        flags,
+        first_key: keys.0,
+        last_key: keys.1,
+        step: keys.2,
+    }
+}
+
+impl CommandSpec {
+    /// Returns the spec of the command named `name`, ignoring case.
+    pub(crate) fn lookup(name: &str) -> Option<&'static CommandSpec> {
+        COMMANDS
+            .iter()
+            .find(|spec| spec.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Returns `true` if the command may be called with `len` arguments,
+    /// including the command name.
+    pub(crate) fn accepts(&self, len: usize) -> bool {
+        let len = len as i64;
+
+        if self.arity < 0 {
+            len >= -self.arity
+        } else {
+            len == self.arity
+        }
+    }
+}
+
 impl Command {
     /// Parse a command from a received frame.
     ///
@@ -54,6 +399,19 @@ impl Command {
         // matching.
         let command_name = parse.next_string()?.to_lowercase();
 
+        // The number of arguments is checked against the command's arity
+        // before parsing, so every command rejects a missing or extra argument
+        // with the same error, like Redis does.
+        if let Some(spec) = CommandSpec::lookup(&command_name) {
+            if !spec.accepts(parse.remaining() + 1) {
+                return Err(format!(
+                    "ERR wrong number of arguments for '{}' command",
+                    command_name
+                )
+                .into());
+            }
+        }
+
         // Match the command name, delegating the rest of the parsing to the
         // specific command.
         let command = match &command_name[..] {
@@ -62,7 +420,73 @@ impl Command {
             "set" => Command::Set(Set::parse_frames(&mut parse)?),
             "subscribe" => Command::Subscribe(Subscribe::parse_frames(&mut parse)?),
             "unsubscribe" => Command::Unsubscribe(Unsubscribe::parse_frames(&mut parse)?),
+            "psubscribe" => Command::Psubscribe(Psubscribe::parse_frames(&mut parse)?),
+            "punsubscribe" => Command::Punsubscribe(Punsubscribe::parse_frames(&mut parse)?),
             "ping" => Command::Ping(Ping::parse_frames(&mut parse)?),
+            "type" => Command::Type(Type::parse_frames(&mut parse)?),
+            "object" => Command::Object(Object::parse_frames(&mut parse)?),
+            "command" => Command::Introspect(Introspect::parse_frames(&mut parse)?),
+            "select" => Command::Select(Select::parse_frames(&mut parse)?),
+            "auth" => Command::Auth(Auth::parse_frames(&mut parse)?),
+            "del" => Command::Del(Del::parse_frames(&mut parse)?),
+            "randomkey" => Command::Randomkey(Randomkey::parse_frames(&mut parse)?),
+            "quit" => Command::Quit(Quit::parse_frames(&mut parse)?),
+            "info" => Command::Info(Info::parse_frames(&mut parse)?),
+            "unlink" => Command::Unlink(Unlink::parse_frames(&mut parse)?),
+            "touch" => Command::Touch(Touch::parse_frames(&mut parse)?),
+            "reset" => Command::Reset(Reset::parse_frames(&mut parse)?),
+            "debug" => Command::Debug(Debug::parse_frames(&mut parse)?),
+            "dbsize" => Command::Dbsize(Dbsize::parse_frames(&mut parse)?),
+            "ttl" => Command::Ttl(Ttl::parse_frames(&mut parse, TtlKind::Ttl)?),
+            "pttl" => Command::Ttl(Ttl::parse_frames(&mut parse, TtlKind::Pttl)?),
+            "expiretime" => Command::Ttl(Ttl::parse_frames(&mut parse, TtlKind::ExpireTime)?),
+            "pexpiretime" => Command::Ttl(Ttl::parse_frames(&mut parse, TtlKind::PexpireTime)?),
+            "save" => Command::Save(Save::parse_frames(&mut parse)?),
+            "bgsave" => Command::Bgsave(Bgsave::parse_frames(&mut parse)?),
+            "wait" => Command::Wait(Wait::parse_frames(&mut parse)?),
+            "psync" => Command::Psync(Psync::parse_frames(&mut parse)?),
+            "monitor" => Command::Monitor(Monitor::parse_frames(&mut parse)?),
+            "slowlog" => Command::Slowlog(Slowlog::parse_frames(&mut parse)?),
+            "incrbyfloat" => Command::Incrbyfloat(Incrbyfloat::parse_frames(&mut parse)?),
+            "setrange" => Command::Setrange(Setrange::parse_frames(&mut parse)?),
+            "getrange" => Command::Getrange(Getrange::parse_frames(&mut parse)?),
+            "setbit" => Command::Setbit(Setbit::parse_frames(&mut parse)?),
+            "getbit" => Command::Getbit(Getbit::parse_frames(&mut parse)?),
+            "bitcount" => Command::Bitcount(Bitcount::parse_frames(&mut parse)?),
+            "cas" => Command::Cas(Cas::parse_frames(&mut parse)?),
+            "dump" => Command::Dump(Dump::parse_frames(&mut parse)?),
+            "restore" => Command::Restore(Restore::parse_frames(&mut parse)?),
+            "migrate" => Command::Migrate(Migrate::parse_frames(&mut parse)?),
+            "fcall" => Command::Fcall(Fcall::parse_frames(&mut parse)?),
+            "zadd" => Command::Zadd(Zadd::parse_frames(&mut parse)?),
+            "zscore" => Command::Zscore(Zscore::parse_frames(&mut parse)?),
+            "zrange" => Command::Zrange(Zrange::parse_frames(&mut parse)?),
+            "sadd" => Command::Sadd(Sadd::parse_frames(&mut parse)?),
+            "srem" => Command::Srem(Srem::parse_frames(&mut parse)?),
+            "smembers" => Command::Smembers(Smembers::parse_frames(&mut parse)?),
+            "sismember" => Command::Sismember(Sismember::parse_frames(&mut parse)?),
+            "scard" => Command::Scard(Scard::parse_frames(&mut parse)?),
+            "sinter" => Command::SetOp(SetOp::parse_frames(&mut parse, SetOpKind::Inter, false)?),
+            "sunion" => Command::SetOp(SetOp::parse_frames(&mut parse, SetOpKind::Union, false)?),
+            "sdiff" => Command::SetOp(SetOp::parse_frames(&mut parse, SetOpKind::Diff, false)?),
+            "sinterstore" => {
+                Command::SetOp(SetOp::parse_frames(&mut parse, SetOpKind::Inter, true)?)
+            }
+            "sunionstore" => {
+                Command::SetOp(SetOp::parse_frames(&mut parse, SetOpKind::Union, true)?)
+            }
+            "sdiffstore" => Command::SetOp(SetOp::parse_frames(&mut parse, SetOpKind::Diff, true)?),
+            "multi" => Command::Multi(Multi::parse_frames(&mut parse)?),
+            "exec" => Command::Exec(Exec::parse_frames(&mut parse)?),
+            "discard" => Command::Discard(Discard::parse_frames(&mut parse)?),
+            "watch" => Command::Watch(Watch::parse_frames(&mut parse)?),
+            "unwatch" => Command::Unwatch(Unwatch::parse_frames(&mut parse)?),
+            "config" => Command::Config(Config::parse_frames(&mut parse)?),
+            "time" => Command::Time(Time::parse_frames(&mut parse)?),
+            "lastsave" => Command::Lastsave(Lastsave::parse_frames(&mut parse)?),
+            "role" => Command::Role(Role::parse_frames(&mut parse)?),
+            "failover" => Command::Failover(Failover::parse_frames(&mut parse)?),
+            "memory" => Command::Memory(Memory::parse_frames(&mut parse)?),
             _ => {
                 // The command is not recognized and an Unknown command is
                 // returned.
@@ -87,11 +511,16 @@ impl Command {
     ///
     /// The response is written to `dst`. This is called by the server in order
     /// to execute a received command.
+    ///
+    /// Most commands use operations specific to `Db`, so this takes the `Db`
+    /// rather than any `Store`. Commands only using `Store` operations, such
+    /// as `Get`, can be applied to another `Store` directly.
     pub(crate) async fn apply(
         self,
         db: &Db,
         dst: &mut Connection,
         shutdown: &mut Shutdown,
+        session: &mut Session,
     ) -> crate::Result<()> {
         use Command::*;
 
@@ -99,25 +528,277 @@ impl Command {
             Get(cmd) => cmd.apply(db, dst).await,
             Publish(cmd) => cmd.apply(db, dst).await,
             Set(cmd) => cmd.apply(db, dst).await,
-            Subscribe(cmd) => cmd.apply(db, dst, shutdown).await,
+            Subscribe(cmd) => cmd.apply(db, dst, shutdown, session).await,
+            Psubscribe(cmd) => cmd.apply(db, dst, shutdown, session).await,
             Ping(cmd) => cmd.apply(dst).await,
+            Type(cmd) => cmd.apply(db, dst).await,
+            Object(cmd) => cmd.apply(db, dst).await,
+            Introspect(cmd) => cmd.apply(dst).await,
+            Select(cmd) => cmd.apply(dst).await,
+            Del(cmd) => cmd.apply(db, dst).await,
+            Randomkey(cmd) => cmd.apply(db, dst).await,
+            Quit(cmd) => cmd.apply(dst).await,
+            Info(cmd) => cmd.apply(db, dst).await,
+            Unlink(cmd) => cmd.apply(db, dst).await,
+            Touch(cmd) => cmd.apply(db, dst).await,
+            Reset(cmd) => cmd.apply(session, dst).await,
+            Multi(cmd) => cmd.apply(session, dst).await,
+            Discard(cmd) => cmd.apply(session, dst).await,
+            Watch(cmd) => cmd.apply(db, session, dst).await,
+            Unwatch(cmd) => cmd.apply(session, dst).await,
+            Debug(cmd) => cmd.apply(db, dst).await,
+            Dbsize(cmd) => cmd.apply(db, dst).await,
+            Ttl(cmd) => cmd.apply(db, dst).await,
+            Wait(cmd) => cmd.apply(dst).await,
+            Incrbyfloat(cmd) => cmd.apply(db, dst).await,
+            Setrange(cmd) => cmd.apply(db, dst).await,
+            Getrange(cmd) => cmd.apply(db, dst).await,
+            Setbit(cmd) => cmd.apply(db, dst).await,
+            Getbit(cmd) => cmd.apply(db, dst).await,
+            Bitcount(cmd) => cmd.apply(db, dst).await,
+            Cas(cmd) => cmd.apply(db, dst).await,
+            Dump(cmd) => cmd.apply(db, dst).await,
+            Restore(cmd) => cmd.apply(db, dst).await,
+            Zadd(cmd) => cmd.apply(db, dst).await,
+            Zscore(cmd) => cmd.apply(db, dst).await,
+            Zrange(cmd) => cmd.apply(db, dst).await,
+            Sadd(cmd) => cmd.apply(db, dst).await,
+            Srem(cmd) => cmd.apply(db, dst).await,
+            Smembers(cmd) => cmd.apply(db, dst).await,
+            Sismember(cmd) => cmd.apply(db, dst).await,
+            Scard(cmd) => cmd.apply(db, dst).await,
+            SetOp(cmd) => cmd.apply(db, dst).await,
+            Time(cmd) => cmd.apply(dst).await,
+            Lastsave(cmd) => cmd.apply(db, dst).await,
+            Memory(cmd) => cmd.apply(db, dst).await,
             Unknown(cmd) => cmd.apply(dst).await,
-            // `Unsubscribe` cannot be applied. It may only be received from the
-            // context of a `Subscribe` command.
-            Unsubscribe(_) => Err("`Unsubscribe` is unsupported in this context".into()),
+            // Outside of the subscribed state, there is nothing to unsubscribe
+            // from, but each request is still confirmed.
+            Unsubscribe(cmd) => cmd.apply(dst).await,
+            Punsubscribe(cmd) => cmd.apply(dst).await,
+            // `Auth` changes the state of the connection, so it is applied by
+            // the connection handler.
+            Auth(_) => Err("`Auth` is unsupported in this context".into()),
+            // `Save` and `Bgsave` need the configured snapshot file, so they
+            // are applied by the connection handler.
+            Save(_) => Err("`Save` is unsupported in this context".into()),
+            Bgsave(_) => Err("`Bgsave` is unsupported in this context".into()),
+            // `Psync` needs the replication channel, so it is applied by the
+            // connection handler.
+            Psync(_) => Err("`Psync` is unsupported in this context".into()),
+            // `Monitor` needs the channel commands are reported on, so it is
+            // applied by the connection handler.
+            Monitor(_) => Err("`Monitor` is unsupported in this context".into()),
+            // `Slowlog` needs the slow commands recorded by the server, so it
+            // is applied by the connection handler.
+            Slowlog(_) => Err("`Slowlog` is unsupported in this context".into()),
+            // `Config` needs the server configuration, so it is applied by the
+            // connection handler.
+            Config(_) => Err("`Config` is unsupported in this context".into()),
+            // `Role` and `Failover` need the replication configuration, so
+            // they are applied by the connection handler.
+            Role(_) => Err("`Role` is unsupported in this context".into()),
+            Failover(_) => Err("`Failover` is unsupported in this context".into()),
+            // `Exec` returns the commands to log and forward, so it is applied
+            // by the connection handler.
+            Exec(_) => Err("`Exec` is unsupported in this context".into()),
+            // `Migrate` returns the command to log and forward in its place,
+            // so it is applied by the connection handler.
+            Migrate(_) => Err("`Migrate` is unsupported in this context".into()),
+            // `Fcall` needs the functions registered on the server, and
+            // returns the commands to log and forward in its place, so it is
+            // applied by the connection handler.
+            Fcall(_) => Err("`Fcall` is unsupported in this context".into()),
+        }
+    }
+
+    /// Returns `true` if the command is queued when received inside a
+    /// transaction, rather than applied right away.
+    pub(crate) fn is_queued(&self) -> bool {
+        use Command::*;
+
+        !matches!(
+            self,
+            Multi(_) | Exec(_) | Discard(_) | Watch(_) | Reset(_) | Quit(_)
+        )
+    }
+
+    /// Returns `true` if the command may be queued in a transaction.
+    ///
+    /// Commands that take over the connection, such as `SUBSCRIBE`, and those
+    /// applied by the connection handler cannot be applied by `EXEC`.
+    pub(crate) fn is_allowed_in_transaction(&self) -> bool {
+        use Command::*;
+
+        !matches!(
+            self,
+            Subscribe(_)
+                | Psubscribe(_)
+                | Unsubscribe(_)
+                | Punsubscribe(_)
+                | Save(_)
+                | Bgsave(_)
+                | Psync(_)
+                | Monitor(_)
+                | Slowlog(_)
+                | Config(_)
+                | Role(_)
+                | Failover(_)
+                | Migrate(_)
+                | Fcall(_)
+        )
+    }
+
+    /// Returns `true` if the connection is closed once the command has been
+    /// applied.
+    pub(crate) fn is_terminal(&self) -> bool {
+        matches!(self, Command::Quit(_))
+    }
+
+    /// Returns `true` if the command may add data, and so is rejected while
+    /// the data set is over the memory limit.
+    pub(crate) fn is_denyoom(&self) -> bool {
+        CommandSpec::lookup(self.get_name())
+            .map(|spec| spec.flags.contains(&"denyoom"))
+            .unwrap_or(false)
+    }
+
+    /// Returns `true` if the command is accepted before the connection has
+    /// authenticated, e.g. `QUIT`.
+    pub(crate) fn is_no_auth(&self) -> bool {
+        CommandSpec::lookup(self.get_name())
+            .map(|spec| spec.flags.contains(&"no_auth"))
+            .unwrap_or(false)
+    }
+
+    /// Returns `true` if the command may modify the data set, and so is
+    /// logged to the append-only file.
+    pub(crate) fn is_write(&self) -> bool {
+        CommandSpec::lookup(self.get_name())
+            .map(|spec| spec.flags.contains(&"write"))
+            .unwrap_or(false)
+    }
+
+    /// Returns `frame`, a command that modified the data set, with its
+    /// expiration, if any, rewritten as a unix timestamp.
+    ///
+    /// Commands are logged to the append-only file and forwarded to replicas
+    /// once applied. A relative expiration, as in `SET key value EX 10`, would
+    /// give the key a new lease each time the command is applied again, and
+    /// bring back keys that have already expired. `SET` expirations are logged
+    /// with `PXAT` instead, and `RESTORE` with `ABSTTL`.
+    pub(crate) fn absolute_expiration(frame: Frame) -> Frame {
+        let mut args = match frame {
+            Frame::Array(args) => args,
+            frame => return frame,
+        };
+
+        let name = match args.first() {
+            Some(Frame::Bulk(name)) => name.to_ascii_lowercase(),
+            _ => return Frame::Array(args),
+        };
+
+        match &name[..] {
+            b"set" => Set::absolute_expiration(&mut args),
+            b"restore" => Restore::absolute_expiration(&mut args),
+            _ => {}
         }
+
+        Frame::Array(args)
     }
 
     /// Returns the command name
     pub(crate) fn get_name(&self) -> &str {
         match self {
             Command::Get(_) => "get",
-            Command::Publish(_) => "pub",
+            Command::Publish(_) => "publish",
             Command::Set(_) => "set",
             Command::Subscribe(_) => "subscribe",
             Command::Unsubscribe(_) => "unsubscribe",
+            Command::Psubscribe(_) => "psubscribe",
+            Command::Punsubscribe(_) => "punsubscribe",
             Command::Ping(_) => "ping",
+            Command::Type(_) => "type",
+            Command::Object(_) => "object",
+            Command::Introspect(_) => "command",
+            Command::Select(_) => "select",
+            Command::Auth(_) => "auth",
+            Command::Del(_) => "del",
+            Command::Randomkey(_) => "randomkey",
+            Command::Quit(_) => "quit",
+            Command::Info(_) => "info",
+            Command::Unlink(_) => "unlink",
+            Command::Touch(_) => "touch",
+            Command::Reset(_) => "reset",
+            Command::Debug(_) => "debug",
+            Command::Dbsize(_) => "dbsize",
+            Command::Ttl(cmd) => cmd.get_name(),
+            Command::Save(_) => "save",
+            Command::Bgsave(_) => "bgsave",
+            Command::Wait(_) => "wait",
+            Command::Psync(_) => "psync",
+            Command::Monitor(_) => "monitor",
+            Command::Slowlog(_) => "slowlog",
+            Command::Incrbyfloat(_) => "incrbyfloat",
+            Command::Setrange(_) => "setrange",
+            Command::Getrange(_) => "getrange",
+            Command::Setbit(_) => "setbit",
+            Command::Getbit(_) => "getbit",
+            Command::Bitcount(_) => "bitcount",
+            Command::Cas(_) => "cas",
+            Command::Dump(_) => "dump",
+            Command::Restore(_) => "restore",
+            Command::Migrate(_) => "migrate",
+            Command::Fcall(_) => "fcall",
+            Command::Zadd(_) => "zadd",
+            Command::Zscore(_) => "zscore",
+            Command::Zrange(_) => "zrange",
+            Command::Sadd(_) => "sadd",
+            Command::Srem(_) => "srem",
+            Command::Smembers(_) => "smembers",
+            Command::Sismember(_) => "sismember",
+            Command::Scard(_) => "scard",
+            Command::SetOp(cmd) => cmd.get_name(),
+            Command::Multi(_) => "multi",
+            Command::Exec(_) => "exec",
+            Command::Discard(_) => "discard",
+            Command::Watch(_) => "watch",
+            Command::Unwatch(_) => "unwatch",
+            Command::Config(_) => "config",
+            Command::Time(_) => "time",
+            Command::Lastsave(_) => "lastsave",
+            Command::Role(_) => "role",
+            Command::Failover(_) => "failover",
+            Command::Memory(_) => "memory",
             Command::Unknown(cmd) => cmd.get_name(),
         }
     }
 }
+
+/// Returns the unix timestamp, in milliseconds, `delay` from now.
+fn unix_time_ms(delay: Duration) -> u64 {
+    (SystemTime::now() + delay)
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Returns the time left until the unix timestamp `ms`, in milliseconds, or
+/// zero if it has passed.
+fn until_unix_time_ms(ms: u64) -> Duration {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+    Duration::from_millis(ms).saturating_sub(now)
+}
+
+/// Returns the non-negative integer held by `frame`, sent either as an integer
+/// or as a bulk string.
+fn frame_to_u64(frame: &Frame) -> Option<u64> {
+    match frame {
+        Frame::Integer(n) => u64::try_from(*n).ok(),
+        Frame::Bulk(data) => std::str::from_utf8(data).ok()?.parse().ok(),
+        _ => None,
+    }
+}
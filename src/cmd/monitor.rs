@@ -0,0 +1,178 @@
+use crate::{Connection, Frame, Parse, Shutdown};
+
+use bytes::Bytes;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+use tracing::{debug, instrument, warn};
+
+/// Stream every command processed by the server.
+///
+/// Once `MONITOR` is issued, the connection receives a line for each command
+/// any connection sends, formatted the way Redis does:
+///
+/// ```text
+/// 1339518083.107412 [0 127.0.0.1:60866] "set" "key" "value"
+/// ```
+///
+/// The connection keeps streaming until it is closed.
+#[derive(Debug, Default)]
+pub struct Monitor;
+
+/// A command received by the server, as reported to monitoring connections.
+#[derive(Debug, Clone)]
+pub(crate) struct MonitoredCommand {
+    /// When the command was received
+    time: SystemTime,
+
+    /// Address of the client that sent the command
+    addr: SocketAddr,
+
+    /// The command name and its arguments
+    args: Vec<Bytes>,
+}
+
+impl Monitor {
+    /// Create a new `Monitor` command.
+    pub fn new() -> Monitor {
+        Monitor
+    }
+
+    /// Parse a `Monitor` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `MONITOR` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Monitor` value on success. If the frame is malformed, `Err`
+    /// is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing a single entry.
+    ///
+    /// ```text
+    /// MONITOR
+    /// ```
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Monitor> {
+        Ok(Monitor)
+    }
+
+    /// Apply the `Monitor` command, streaming the commands sent on `monitors`
+    /// to the connection.
+    ///
+    /// This is called by the connection handler, which owns the channel
+    /// commands are reported on. It only returns once the connection is
+    /// closed or the server shuts down.
+    #[instrument(skip(self, monitors, dst, shutdown))]
+    pub(crate) async fn apply(
+        self,
+        monitors: &broadcast::Sender<MonitoredCommand>,
+        dst: &mut Connection,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let mut commands = monitors.subscribe();
+
+        let response = Frame::Simple("OK".to_string());
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        loop {
+            let command = tokio::select! {
+                res = commands.recv() => match res {
+                    Ok(command) => command,
+                    // Monitoring is best effort. If the connection cannot keep
+                    // up, the commands it missed are skipped.
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!(skipped, "monitor fell behind");
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return Ok(()),
+                },
+                res = dst.read_frame() => match res? {
+                    // Commands sent while monitoring are ignored, this only
+                    // detects the connection closing.
+                    Some(_) => continue,
+                    None => return Ok(()),
+                },
+                _ = shutdown.recv() => return Ok(()),
+            };
+
+            dst.write_frame(&Frame::Bulk(command.format().into()))
+                .await?;
+        }
+    }
+}
+
+impl MonitoredCommand {
+    /// Record `frame`, a command just received from `addr`.
+    pub(crate) fn new(addr: SocketAddr, frame: &Frame) -> MonitoredCommand {
+        let args = match frame {
+            Frame::Array(parts) => parts
+                .iter()
+                .filter_map(|part| match part {
+                    Frame::Bulk(data) => Some(data.clone()),
+                    Frame::Simple(data) => Some(Bytes::from(data.clone())),
+                    Frame::Integer(value) => Some(Bytes::from(value.to_string())),
+                    _ => None,
+                })
+                .collect(),
+            _ => vec![],
+        };
+
+        MonitoredCommand {
+            time: SystemTime::now(),
+            addr,
+            args,
+        }
+    }
+
+    /// Formats the command as a line of `MONITOR` output.
+    fn format(&self) -> String {
+        let since_epoch = self.time.duration_since(UNIX_EPOCH).unwrap_or_default();
+
+        // Writing to a `String` cannot fail.
+        let mut line = String::new();
+        let _ = write!(
+            line,
+            "{}.{:06} [0 {}]",
+            since_epoch.as_secs(),
+            since_epoch.subsec_micros(),
+            self.addr
+        );
+
+        for arg in &self.args {
+            line.push(' ');
+            quote(&mut line, arg);
+        }
+
+        line
+    }
+}
+
+/// Appends `data` to `line` as a quoted string, escaping quotes, backslashes
+/// and non-printable bytes.
+fn quote(line: &mut String, data: &[u8]) {
+    line.push('"');
+
+    for &byte in data {
+        match byte {
+            b'\\' => line.push_str("\\\\"),
+            b'"' => line.push_str("\\\""),
+            b'\n' => line.push_str("\\n"),
+            b'\r' => line.push_str("\\r"),
+            b'\t' => line.push_str("\\t"),
+            byte if byte.is_ascii_graphic() || byte == b' ' => line.push(byte as char),
+            byte => {
+                let _ = write!(line, "\\x{:02x}", byte);
+            }
+        }
+    }
+
+    line.push('"');
+}
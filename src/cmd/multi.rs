@@ -0,0 +1,231 @@
+use crate::{Command, Connection, Db, Frame, Parse, Session, Shutdown};
+
+use tracing::{debug, instrument};
+
+/// Start a transaction.
+///
+/// Commands received after `MULTI` are queued instead of being applied, and
+/// replied to with `QUEUED`. `EXEC` applies them, and `DISCARD` drops them.
+#[derive(Debug, Default)]
+pub struct Multi;
+
+/// Apply the commands queued since `MULTI`.
+///
+/// The queued commands are applied in order, without commands from other
+/// connections being applied in between. The reply is an array holding the
+/// reply to each queued command.
+///
+/// If a command was rejected while queuing, e.g. because it was malformed,
+/// none of the commands are applied and an `EXECABORT` error is returned. If a
+/// key watched with `WATCH` was modified, none of the commands are applied and
+/// `nil` is returned.
+#[derive(Debug, Default)]
+pub struct Exec;
+
+/// Drop the commands queued since `MULTI`, ending the transaction. Keys
+/// watched with `WATCH` stop being watched.
+#[derive(Debug, Default)]
+pub struct Discard;
+
+/// Commands queued by a connection between `MULTI` and `EXEC`.
+#[derive(Debug, Default)]
+pub(crate) struct Transaction {
+    /// The queued commands, in order. Each command is kept along with the
+    /// frame it was received as, so the commands that modify the data set
+    /// can be logged and forwarded.
+    commands: Vec<(Command, Frame)>,
+
+    /// Set when a command is rejected while queuing. The transaction is then
+    /// discarded by `EXEC`.
+    aborted: bool,
+}
+
+impl Multi {
+    /// Create a new `Multi` command.
+    pub fn new() -> Multi {
+        Multi
+    }
+
+    /// Parse a `Multi` instance from a received frame.
+    ///
+    /// The `MULTI` string has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing a single entry.
+    ///
+    /// ```text
+    /// MULTI
+    /// ```
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Multi> {
+        Ok(Multi)
+    }
+
+    /// Apply the `Multi` command, starting a transaction on the connection.
+    ///
+    /// The response is written to `dst`.
+    #[instrument(skip(self, session, dst))]
+    pub(crate) async fn apply(
+        self,
+        session: &mut Session,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = if session.begin_transaction() {
+            Frame::Simple("OK".to_string())
+        } else {
+            Frame::Error("ERR MULTI calls can not be nested".to_string())
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
+
+impl Exec {
+    /// Create a new `Exec` command.
+    pub fn new() -> Exec {
+        Exec
+    }
+
+    /// Parse an `Exec` instance from a received frame.
+    ///
+    /// The `EXEC` string has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing a single entry.
+    ///
+    /// ```text
+    /// EXEC
+    /// ```
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Exec> {
+        Ok(Exec)
+    }
+
+    /// Apply the commands queued on the connection, ending the transaction.
+    ///
+    /// The response is written to `dst`. This is called by the connection
+    /// handler, which logs and forwards the commands that modified the data
+    /// set. Their frames are returned, in the order they were applied.
+    #[instrument(skip(self, db, dst, shutdown, session))]
+    pub(crate) async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        shutdown: &mut Shutdown,
+        session: &mut Session,
+    ) -> crate::Result<Vec<Frame>> {
+        let mut applied = vec![];
+
+        // Keys are only watched until the next `EXEC`, whatever its outcome.
+        let watched = session.unwatch();
+
+        let response = match session.take_transaction() {
+            None => Frame::Error("ERR EXEC without MULTI".to_string()),
+            Some(transaction) if transaction.aborted => Frame::Error(
+                "EXECABORT Transaction discarded because of previous errors.".to_string(),
+            ),
+            Some(transaction) => {
+                // Every other command is applied while holding the lock shared,
+                // so none is applied until the transaction is done.
+                let _lock = db.lock_exclusive().await;
+
+                // Checked under the lock, so no watched key can be modified
+                // between the check and the commands being applied.
+                if watched
+                    .iter()
+                    .any(|(key, version)| db.key_version(key) != *version)
+                {
+                    Frame::Null
+                } else {
+                    // The commands write their replies to `replies`, from where
+                    // they are read back to build the array reply.
+                    let mut replies = Connection::loopback();
+
+                    for (cmd, frame) in transaction.commands {
+                        let is_write = cmd.is_write();
+
+                        cmd.apply(db, &mut replies, shutdown, session).await?;
+
+                        if is_write {
+                            applied.push(frame);
+                        }
+                    }
+
+                    let mut response = vec![];
+                    while let Some(reply) = replies.read_frame().await? {
+                        response.push(reply);
+                    }
+
+                    Frame::Array(response)
+                }
+            }
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(applied)
+    }
+}
+
+impl Discard {
+    /// Create a new `Discard` command.
+    pub fn new() -> Discard {
+        Discard
+    }
+
+    /// Parse a `Discard` instance from a received frame.
+    ///
+    /// The `DISCARD` string has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing a single entry.
+    ///
+    /// ```text
+    /// DISCARD
+    /// ```
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Discard> {
+        Ok(Discard)
+    }
+
+    /// Apply the `Discard` command, dropping the queued commands.
+    ///
+    /// The response is written to `dst`.
+    #[instrument(skip(self, session, dst))]
+    pub(crate) async fn apply(
+        self,
+        session: &mut Session,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        session.unwatch();
+
+        let response = match session.take_transaction() {
+            Some(_) => Frame::Simple("OK".to_string()),
+            None => Frame::Error("ERR DISCARD without MULTI".to_string()),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
+
+impl Transaction {
+    /// Queue `cmd`, received as `frame`, to be applied by `EXEC`.
+    pub(crate) fn queue(&mut self, cmd: Command, frame: Frame) {
+        self.commands.push((cmd, frame));
+    }
+
+    /// Mark the transaction as aborted, so `EXEC` discards it.
+    pub(crate) fn abort(&mut self) {
+        self.aborted = true;
+    }
+}
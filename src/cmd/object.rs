@@ -0,0 +1,85 @@
+use crate::{Connection, Db, Frame, Parse};
+
+use tracing::{debug, instrument};
+
+/// Inspect the internals of the value stored at a key.
+///
+/// # Subcommands
+///
+/// Currently, the following subcommands are supported:
+///
+/// * ENCODING `key` -- Return the internal encoding of the value.
+/// * IDLETIME `key` -- Return the number of seconds since the value was last
+///   read or written.
+/// * REFCOUNT `key` -- Return the number of references to the value. Values
+///   are never shared, so this is always `1`.
+#[derive(Debug)]
+pub struct Object {
+    /// The subcommand, in lower case
+    subcommand: String,
+
+    /// Name of the key to inspect
+    key: String,
+}
+
+impl Object {
+    /// Parse an `Object` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `OBJECT` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Object` value on success. If the frame is malformed, `Err`
+    /// is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing three entries.
+    ///
+    /// ```text
+    /// OBJECT subcommand key
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Object> {
+        // Subcommands are matched case insensitively, like command names.
+        let subcommand = parse.next_string()?.to_lowercase();
+        let key = parse.next_string()?;
+
+        Ok(Object { subcommand, key })
+    }
+
+    /// Apply the `Object` command to the specified `Db` instance.
+    ///
+    /// The response is written to `dst`. This is called by the server in order
+    /// to execute a received command.
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = match &self.subcommand[..] {
+            "encoding" => match db.encoding(&self.key) {
+                Some(encoding) => Frame::Bulk(encoding.into()),
+                None => Frame::Null,
+            },
+            "idletime" => match db.idle_time(&self.key) {
+                Some(idle) => Frame::Integer(idle.as_secs() as i64),
+                None => Frame::Null,
+            },
+            "refcount" => match db.value_type(&self.key) {
+                Some(_) => Frame::Integer(1),
+                None => Frame::Null,
+            },
+            subcommand => Frame::Error(format!(
+                "ERR unknown subcommand '{}'. Try OBJECT HELP.",
+                subcommand
+            )),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
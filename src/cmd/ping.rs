@@ -70,7 +70,7 @@ impl Ping {
     ///
     /// This is called by the client when encoding a `Ping` command to send
     /// to the server.
-    pub(crate) fn into_frame(self) -> Frame {
+    pub fn into_frame(self) -> Frame {
         let mut frame = Frame::array();
         frame.push_bulk(Bytes::from("ping".as_bytes()));
         if let Some(msg) = self.msg {
@@ -0,0 +1,140 @@
+use crate::{Connection, Db, Frame, Parse, Shutdown};
+
+use bytes::Bytes;
+use tokio::sync::broadcast;
+use tracing::{debug, info, instrument};
+
+/// Turn the connection into a replication stream, as used by replicas to
+/// follow a primary.
+///
+/// mini-redis always performs a full resynchronization: the reply is
+/// `+FULLRESYNC`, followed by a bulk string holding a snapshot of the data set,
+/// encoded by `DbSnapshot::encode`. Every command that modifies the data set is
+/// then forwarded to the connection, as it was received but with its
+/// expiration as a unix timestamp, until the connection is closed.
+#[derive(Debug)]
+pub struct Psync {
+    /// The replication ID the replica last followed, `?` if none
+    replication_id: String,
+
+    /// The replication offset the replica reached, `-1` if none
+    offset: String,
+}
+
+impl Psync {
+    /// Create a new `Psync` command requesting a full resynchronization.
+    pub fn new() -> Psync {
+        Psync {
+            replication_id: "?".to_string(),
+            offset: "-1".to_string(),
+        }
+    }
+
+    /// Parse a `Psync` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `PSYNC` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Psync` value on success. If the frame is malformed, `Err`
+    /// is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing three entries.
+    ///
+    /// ```text
+    /// PSYNC replicationid offset
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Psync> {
+        let replication_id = parse.next_string()?;
+        let offset = parse.next_string()?;
+
+        Ok(Psync {
+            replication_id,
+            offset,
+        })
+    }
+
+    /// Apply the `Psync` command, streaming the commands sent on `replicas`
+    /// to the replica.
+    ///
+    /// This is called by the connection handler, which owns the replication
+    /// channel. It only returns once the replica disconnects, falls too far
+    /// behind, or the server shuts down.
+    #[instrument(skip(self, db, replicas, dst, shutdown))]
+    pub(crate) async fn apply(
+        self,
+        db: &Db,
+        replicas: &broadcast::Sender<Frame>,
+        dst: &mut Connection,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        // The replica only asks for a partial resynchronization when it has
+        // followed a primary before. mini-redis keeps no backlog to resume
+        // from, so the request is only logged.
+        debug!(replication_id = %self.replication_id, offset = %self.offset);
+
+        // Subscribe and take the snapshot while no command is applied nor
+        // being logged, see `Db::lock_log`. Every command is then either in
+        // the snapshot or forwarded, never both: applying a command such as
+        // `INCRBYFLOAT` twice would make the replica diverge.
+        let (mut commands, snapshot) = {
+            let _log = db.lock_log().await;
+            let _lock = db.lock_exclusive().await;
+
+            (replicas.subscribe(), db.snapshot())
+        };
+
+        dst.write_frame(&Frame::Simple("FULLRESYNC".to_string()))
+            .await?;
+        dst.write_frame(&Frame::Bulk(snapshot.encode())).await?;
+
+        info!(keys = snapshot.len(), "replica synchronized");
+
+        loop {
+            let frame = tokio::select! {
+                res = commands.recv() => match res {
+                    Ok(frame) => frame,
+                    // The replica missed commands, so it can no longer follow.
+                    // Closing the connection makes it resynchronize.
+                    Err(broadcast::error::RecvError::Lagged(_)) => {
+                        return Err("replica fell behind the replication stream".into())
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return Ok(()),
+                },
+                res = dst.read_frame() => match res? {
+                    // Replicas have nothing to say, this only detects the
+                    // connection closing.
+                    Some(_) => continue,
+                    None => return Ok(()),
+                },
+                _ = shutdown.recv() => return Ok(()),
+            };
+
+            dst.write_frame(&frame).await?;
+        }
+    }
+
+    /// Converts the command into an equivalent `Frame`.
+    ///
+    /// This is called by a replica when encoding a `Psync` command to send to
+    /// its primary.
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("psync".as_bytes()));
+        frame.push_bulk(Bytes::from(self.replication_id.into_bytes()));
+        frame.push_bulk(Bytes::from(self.offset.into_bytes()));
+        frame
+    }
+}
+
+impl Default for Psync {
+    fn default() -> Psync {
+        Psync::new()
+    }
+}
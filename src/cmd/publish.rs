@@ -1,4 +1,4 @@
-use crate::{Connection, Db, Frame, Parse};
+use crate::{Connection, Frame, Parse, Store};
 
 use bytes::Bytes;
 
@@ -60,11 +60,11 @@ impl Publish {
         Ok(Publish { channel, message })
     }
 
-    /// Apply the `Publish` command to the specified `Db` instance.
+    /// Apply the `Publish` command to the specified `Store`.
     ///
     /// The response is written to `dst`. This is called by the server in order
-    /// to execute a received command.
-    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+    /// to execute a received command, with its `Db`.
+    pub async fn apply(self, db: &impl Store, dst: &mut Connection) -> crate::Result<()> {
         // The shared state contains the `tokio::sync::broadcast::Sender` for
         // all active channels. Calling `db.publish` dispatches the message into
         // the appropriate channel.
@@ -78,7 +78,7 @@ impl Publish {
 
         // The number of subscribers is returned as the response to the publish
         // request.
-        let response = Frame::Integer(num_subscribers as u64);
+        let response = Frame::Integer(num_subscribers as i64);
 
         // Write the frame to the client.
         dst.write_frame(&response).await?;
@@ -90,7 +90,7 @@ impl Publish {
     ///
     /// This is called by the client when encoding a `Publish` command to send
     /// to the server.
-    pub(crate) fn into_frame(self) -> Frame {
+    pub fn into_frame(self) -> Frame {
         let mut frame = Frame::array();
         frame.push_bulk(Bytes::from("publish".as_bytes()));
         frame.push_bulk(Bytes::from(self.channel.into_bytes()));
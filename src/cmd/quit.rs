@@ -0,0 +1,55 @@
+use crate::{Connection, Frame, Parse};
+
+use tracing::{debug, instrument};
+
+/// Ask the server to close the connection.
+///
+/// The server replies `OK` and then closes the connection.
+#[derive(Debug, Default)]
+pub struct Quit;
+
+impl Quit {
+    /// Create a new `Quit` command.
+    pub fn new() -> Quit {
+        Quit
+    }
+
+    /// Parse a `Quit` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `QUIT` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Quit` value on success. If the frame is malformed, `Err`
+    /// is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing a single entry.
+    ///
+    /// ```text
+    /// QUIT
+    /// ```
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Quit> {
+        Ok(Quit)
+    }
+
+    /// Apply the `Quit` command.
+    ///
+    /// The response is written to `dst`. Closing the connection is left to the
+    /// connection handler, which stops once a terminal command is applied.
+    #[instrument(skip(self, dst))]
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = Frame::Simple("OK".to_string());
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
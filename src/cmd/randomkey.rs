@@ -0,0 +1,59 @@
+use crate::{Connection, Db, Frame, Parse};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// Return a random key from the keyspace.
+///
+/// If the database is empty, the special value nil is returned.
+#[derive(Debug, Default)]
+pub struct Randomkey;
+
+impl Randomkey {
+    /// Create a new `Randomkey` command.
+    pub fn new() -> Randomkey {
+        Randomkey
+    }
+
+    /// Parse a `Randomkey` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `RANDOMKEY` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Randomkey` value on success. If the frame is malformed,
+    /// `Err` is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing a single entry.
+    ///
+    /// ```text
+    /// RANDOMKEY
+    /// ```
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Randomkey> {
+        Ok(Randomkey)
+    }
+
+    /// Apply the `Randomkey` command to the specified `Db` instance.
+    ///
+    /// The response is written to `dst`. This is called by the server in order
+    /// to execute a received command.
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = match db.random_key() {
+            Some(key) => Frame::Bulk(Bytes::from(key)),
+            None => Frame::Null,
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
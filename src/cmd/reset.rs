@@ -0,0 +1,69 @@
+use crate::{Connection, Frame, Parse, Session};
+
+use tracing::{debug, instrument};
+
+/// Return the connection to the state it was in when it was established.
+///
+/// The connection is unsubscribed from all channels and patterns, leaving the
+/// subscribed state, and must authenticate again if the server requires a
+/// password. mini-redis only supports database 0, so there is no selected
+/// database to reset.
+///
+/// This is used by connection pools to reuse connections between clients.
+#[derive(Debug, Default)]
+pub struct Reset;
+
+impl Reset {
+    /// Create a new `Reset` command.
+    pub fn new() -> Reset {
+        Reset
+    }
+
+    /// Parse a `Reset` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `RESET` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Reset` value on success. If the frame is malformed, `Err`
+    /// is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing a single entry.
+    ///
+    /// ```text
+    /// RESET
+    /// ```
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Reset> {
+        Ok(Reset)
+    }
+
+    /// Apply the `Reset` command to the connection's `Session`.
+    ///
+    /// Subscriptions are dropped by the caller, as they only exist while the
+    /// connection is in the subscribed state.
+    ///
+    /// The response is written to `dst`. This is called by the server in order
+    /// to execute a received command.
+    #[instrument(skip(self, session, dst))]
+    pub(crate) async fn apply(
+        self,
+        session: &mut Session,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        session.reset();
+
+        let response = Frame::Simple("RESET".to_string());
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,182 @@
+use crate::{Connection, Db, Frame, Parse};
+
+use bytes::Bytes;
+use std::time::Duration;
+use tracing::{debug, instrument};
+
+/// Create `key` holding the value serialized by `DUMP`.
+///
+/// `ttl` is the time to live of the key, in milliseconds, or `0` for a key
+/// that does not expire. With `ABSTTL`, it is the unix time at which the key
+/// expires instead, in milliseconds. If the key already exists, an error is
+/// returned, unless `REPLACE` is given. The `IDLETIME` and `FREQ` options are
+/// not supported.
+#[derive(Debug)]
+pub struct Restore {
+    /// Name of the key to create
+    key: String,
+
+    /// When to expire the key
+    ttl: Option<Duration>,
+
+    /// Payload returned by `DUMP`
+    payload: Bytes,
+
+    /// Whether an existing key is replaced
+    replace: bool,
+}
+
+impl Restore {
+    /// Create a new `Restore` command which creates `key` from `payload`,
+    /// expiring after `ttl` if given.
+    pub fn new(key: impl ToString, ttl: Option<Duration>, payload: Bytes) -> Restore {
+        Restore {
+            key: key.to_string(),
+            ttl,
+            payload,
+            replace: false,
+        }
+    }
+
+    /// Replace the key if it already exists.
+    pub fn replace(mut self) -> Restore {
+        self.replace = true;
+        self
+    }
+
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Get the time to live
+    pub fn ttl(&self) -> Option<Duration> {
+        self.ttl
+    }
+
+    /// Get the payload
+    pub fn payload(&self) -> &Bytes {
+        &self.payload
+    }
+
+    /// Parse a `Restore` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `RESTORE` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Restore` value on success. If the frame is malformed,
+    /// `Err` is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing at least four entries.
+    ///
+    /// ```text
+    /// RESTORE key ttl serialized-value [REPLACE] [ABSTTL]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Restore> {
+        let key = parse.next_string()?;
+
+        let ttl = match parse.next_signed_int()? {
+            ms if ms < 0 => return Err("ERR Invalid TTL value, must be >= 0".into()),
+            ms => ms as u64,
+        };
+
+        let payload = parse.next_bytes()?;
+
+        let mut replace = false;
+        let mut absttl = false;
+
+        while parse.remaining() > 0 {
+            match &parse.next_string()?.to_uppercase()[..] {
+                "REPLACE" => replace = true,
+                "ABSTTL" => absttl = true,
+                _ => return Err("ERR syntax error".into()),
+            }
+        }
+
+        // An absolute time that has passed expires the key right away.
+        let ttl = match ttl {
+            0 => None,
+            ms if absttl => Some(super::until_unix_time_ms(ms)),
+            ms => Some(Duration::from_millis(ms)),
+        };
+
+        Ok(Restore {
+            key,
+            ttl,
+            payload,
+            replace,
+        })
+    }
+
+    /// Rewrites the time to live in `args`, the frame of a `RESTORE` command
+    /// that was just applied, as a unix timestamp, adding `ABSTTL`.
+    ///
+    /// See `Command::absolute_expiration`.
+    pub(crate) fn absolute_expiration(args: &mut Vec<Frame>) {
+        let absttl = args.iter().skip(4).any(
+            |arg| matches!(arg, Frame::Bulk(option) if option.eq_ignore_ascii_case(b"absttl")),
+        );
+
+        if absttl {
+            return;
+        }
+
+        if let Some(ttl) = args
+            .get(2)
+            .and_then(super::frame_to_u64)
+            .filter(|ttl| *ttl > 0)
+        {
+            let ttl = super::unix_time_ms(Duration::from_millis(ttl));
+
+            args[2] = Frame::Bulk(Bytes::from(ttl.to_string()));
+            args.push(Frame::Bulk(Bytes::from("absttl".as_bytes())));
+        }
+    }
+
+    /// Apply the `Restore` command to the specified `Db` instance.
+    ///
+    /// The response is written to `dst`. This is called by the server in order
+    /// to execute a received command.
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = match db.restore_dump(self.key, &self.payload, self.ttl, self.replace) {
+            Ok(()) => Frame::Simple("OK".to_string()),
+            Err(err) => Frame::Error(err.to_string()),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Converts the command into an equivalent `Frame`.
+    ///
+    /// This is called by the client when encoding a `Restore` command to send
+    /// to the server.
+    pub fn into_frame(self) -> Frame {
+        // A time to live shorter than a millisecond must not become `0`,
+        // which means the key does not expire.
+        let ttl = self.ttl.map_or(0, |ttl| (ttl.as_millis() as u64).max(1));
+
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("restore".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_bulk(Bytes::from(ttl.to_string()));
+        frame.push_bulk(self.payload);
+
+        if self.replace {
+            frame.push_bulk(Bytes::from("replace".as_bytes()));
+        }
+
+        frame
+    }
+}
@@ -0,0 +1,139 @@
+use crate::{Connection, Frame, Parse};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// Report whether the server is a primary or a replica.
+///
+/// A primary replies with `master`, its replication offset and the replicas
+/// connected to it. A replica replies with `slave`, the host and port of its
+/// primary, the state of the link and the replication offset.
+///
+/// mini-redis does not track replication offsets, so the offset is always
+/// `0`, and replicas connected to a primary are not listed. A replica always
+/// reports its link as `connected`, including while it reconnects.
+#[derive(Debug, Default)]
+pub struct Role;
+
+/// Hand the primary role over to a replica.
+///
+/// mini-redis cannot coordinate a failover, as replicas do not report how far
+/// they followed the replication stream. The command is accepted, so clients
+/// probing for it do not fail on an unknown command, but no failover is ever
+/// started and an error is returned.
+#[derive(Debug, Default)]
+pub struct Failover;
+
+impl Role {
+    /// Create a new `Role` command.
+    pub fn new() -> Role {
+        Role
+    }
+
+    /// Parse a `Role` instance from a received frame.
+    ///
+    /// The `ROLE` string has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing a single entry.
+    ///
+    /// ```text
+    /// ROLE
+    /// ```
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Role> {
+        Ok(Role)
+    }
+
+    /// Apply the `Role` command. `replicaof` is the address of the primary,
+    /// as `host:port`, if the server is a replica.
+    ///
+    /// The response is written to `dst`. This is called by the connection
+    /// handler, which knows the server configuration.
+    #[instrument(skip(self, dst))]
+    pub(crate) async fn apply(
+        self,
+        replicaof: Option<&str>,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = match replicaof.and_then(|addr| addr.rsplit_once(':')) {
+            Some((host, port)) => {
+                // IPv6 addresses are reported without their brackets.
+                let host = host.trim_start_matches('[').trim_end_matches(']');
+
+                Frame::Array(vec![
+                    Frame::Bulk(Bytes::from("slave")),
+                    Frame::Bulk(Bytes::from(host.to_string())),
+                    Frame::Integer(port.parse().unwrap_or(0)),
+                    Frame::Bulk(Bytes::from("connected")),
+                    Frame::Integer(0),
+                ])
+            }
+            None => Frame::Array(vec![
+                Frame::Bulk(Bytes::from("master")),
+                Frame::Integer(0),
+                Frame::Array(vec![]),
+            ]),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
+
+impl Failover {
+    /// Create a new `Failover` command.
+    pub fn new() -> Failover {
+        Failover
+    }
+
+    /// Parse a `Failover` instance from a received frame.
+    ///
+    /// The `FAILOVER` string has already been consumed. The options are
+    /// ignored, as no failover is ever started.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing `FAILOVER` and its options.
+    ///
+    /// ```text
+    /// FAILOVER [TO host port [FORCE]] [ABORT] [TIMEOUT milliseconds]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Failover> {
+        while parse.remaining() > 0 {
+            parse.next_bytes()?;
+        }
+
+        Ok(Failover)
+    }
+
+    /// Apply the `Failover` command. `is_replica` is `true` if the server is
+    /// a replica, and `replicas` the number of replicas connected to it.
+    ///
+    /// The response is written to `dst`. This is called by the connection
+    /// handler, which knows the server configuration.
+    #[instrument(skip(self, dst))]
+    pub(crate) async fn apply(
+        self,
+        is_replica: bool,
+        replicas: usize,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = if is_replica {
+            Frame::Error("ERR FAILOVER is not valid when server is a replica.".to_string())
+        } else if replicas == 0 {
+            Frame::Error("ERR FAILOVER requires connected replicas.".to_string())
+        } else {
+            Frame::Error("ERR FAILOVER is not supported".to_string())
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+use crate::cmd::ParseError;
+use crate::{Connection, Db, Frame, Parse};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// Add members to the set stored at `key`.
+///
+/// Members already in the set are ignored. A missing key is created as an
+/// empty set first. An error is returned if the key holds a value of another
+/// type.
+///
+/// The response is the number of members that were added.
+#[derive(Debug)]
+pub struct Sadd {
+    /// Name of the set
+    key: String,
+
+    /// Members to add
+    members: Vec<Bytes>,
+}
+
+impl Sadd {
+    /// Create a new `Sadd` command which adds `members` to the set `key`.
+    pub fn new(key: impl ToString, members: Vec<Bytes>) -> Sadd {
+        Sadd {
+            key: key.to_string(),
+            members,
+        }
+    }
+
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Get the members
+    pub fn members(&self) -> &[Bytes] {
+        &self.members
+    }
+
+    /// Parse a `Sadd` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `SADD` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Sadd` value on success. If the frame is malformed, `Err` is
+    /// returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing three or more entries.
+    ///
+    /// ```text
+    /// SADD key member [member ...]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Sadd> {
+        use ParseError::EndOfStream;
+
+        let key = parse.next_string()?;
+
+        // At least one member is required.
+        let mut members = vec![parse.next_bytes()?];
+
+        loop {
+            match parse.next_bytes() {
+                Ok(member) => members.push(member),
+                Err(EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(Sadd { key, members })
+    }
+
+    /// Apply the `Sadd` command to the specified `Db` instance.
+    ///
+    /// The response is written to `dst`. This is called by the server in order
+    /// to execute a received command.
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = match db.sadd(&self.key, self.members) {
+            Ok(count) => Frame::Integer(count as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Converts the command into an equivalent `Frame`.
+    ///
+    /// This is called by the client when encoding a `Sadd` command to send to
+    /// the server.
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("sadd".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+
+        for member in self.members {
+            frame.push_bulk(member);
+        }
+
+        frame
+    }
+}
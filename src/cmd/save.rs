@@ -0,0 +1,76 @@
+use crate::{Connection, Db, Frame, Parse};
+
+use std::path::Path;
+use tracing::{debug, error, instrument};
+
+/// Save the data set to disk.
+///
+/// A snapshot of the data set is written to the file the server is configured
+/// with, see `ServerBuilder::dbfilename`. The reply is sent once the file has
+/// been written, so the connection waits for the whole save.
+#[derive(Debug, Default)]
+pub struct Save;
+
+impl Save {
+    /// Create a new `Save` command.
+    pub fn new() -> Save {
+        Save
+    }
+
+    /// Parse a `Save` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `SAVE` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Save` value on success. If the frame is malformed, `Err`
+    /// is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing a single entry.
+    ///
+    /// ```text
+    /// SAVE
+    /// ```
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Save> {
+        Ok(Save)
+    }
+
+    /// Apply the `Save` command, writing a snapshot of `db` to `path`, the
+    /// snapshot file the server is configured with.
+    ///
+    /// The response is written to `dst`. This is called by the connection
+    /// handler, which knows the configured path.
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(
+        self,
+        db: &Db,
+        path: Option<&Path>,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = match path {
+            None => Frame::Error("ERR no snapshot file configured".to_string()),
+            Some(path) => match db.snapshot().write_to_file(path).await {
+                Ok(()) => {
+                    db.record_save();
+                    Frame::Simple("OK".to_string())
+                }
+                Err(err) => {
+                    error!(cause = %err, path = %path.display(), "failed to save snapshot");
+                    Frame::Error(format!("ERR failed to save snapshot: {}", err))
+                }
+            },
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,71 @@
+use crate::{Connection, Frame, Parse};
+
+use tracing::{debug, instrument};
+
+/// Select the logical database for the current connection.
+///
+/// mini-redis only has a single database, index `0`. Selecting it is accepted
+/// so that clients which issue `SELECT 0` when connecting work unchanged. Any
+/// other index is rejected.
+#[derive(Debug)]
+pub struct Select {
+    /// The index of the database to select
+    index: u64,
+}
+
+impl Select {
+    /// Create a new `Select` command which selects database `index`.
+    pub fn new(index: u64) -> Select {
+        Select { index }
+    }
+
+    /// Get the index
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// Parse a `Select` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `SELECT` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Select` value on success. If the frame is malformed, `Err`
+    /// is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing two entries.
+    ///
+    /// ```text
+    /// SELECT index
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Select> {
+        let index = parse.next_int()?;
+
+        Ok(Select { index })
+    }
+
+    /// Apply the `Select` command.
+    ///
+    /// The response is written to `dst`. This is called by the server in order
+    /// to execute a received command.
+    #[instrument(skip(self, dst))]
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = if self.index == 0 {
+            Frame::Simple("OK".to_string())
+        } else {
+            Frame::Error("ERR DB index is out of range".to_string())
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
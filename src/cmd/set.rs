@@ -1,5 +1,5 @@
 use crate::cmd::{Parse, ParseError};
-use crate::{Connection, Db, Frame};
+use crate::{Connection, Frame, Store};
 
 use bytes::Bytes;
 use std::time::Duration;
@@ -17,6 +17,18 @@ use tracing::{debug, instrument};
 ///
 /// * EX `seconds` -- Set the specified expire time, in seconds.
 /// * PX `milliseconds` -- Set the specified expire time, in milliseconds.
+/// * EXAT `timestamp` -- Set the unix time at which the key expires, in
+///   seconds.
+/// * PXAT `timestamp` -- Set the unix time at which the key expires, in
+///   milliseconds.
+/// * NX -- Only set the key if it does not already exist.
+/// * XX -- Only set the key if it already exists.
+/// * GET -- Return the value previously stored at `key`, or `Null` if there
+///   was none, instead of `OK`. This is the response even if `NX` or `XX`
+///   prevented the write. If the key holds a value that is not a string, an
+///   error is returned and the key is left untouched.
+///
+/// Without `GET`, the response is `Null` if `NX` or `XX` prevented the write.
 #[derive(Debug)]
 pub struct Set {
     /// the lookup key
@@ -27,6 +39,23 @@ pub struct Set {
 
     /// When to expire the key
     expire: Option<Duration>,
+
+    /// When the key is set
+    condition: SetCondition,
+
+    /// Whether the previous value is returned
+    get: bool,
+}
+
+/// When `SET` stores its value, depending on whether the key exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetCondition {
+    /// Always, the default
+    Always,
+    /// `NX`, only if the key does not exist
+    IfAbsent,
+    /// `XX`, only if the key exists
+    IfPresent,
 }
 
 impl Set {
@@ -39,9 +68,24 @@ impl Set {
             key: key.to_string(),
             value,
             expire,
+            condition: SetCondition::Always,
+            get: false,
         }
     }
 
+    /// Only set the key under `condition`, as with the `NX` and `XX` options.
+    pub fn with_condition(mut self, condition: SetCondition) -> Set {
+        self.condition = condition;
+        self
+    }
+
+    /// Request the value previously stored at the key as the response, as
+    /// with the `GET` option.
+    pub fn with_get(mut self) -> Set {
+        self.get = true;
+        self
+    }
+
     /// Get the key
     pub fn key(&self) -> &str {
         &self.key
@@ -57,6 +101,16 @@ impl Set {
         self.expire
     }
 
+    /// Get the condition under which the key is set
+    pub fn condition(&self) -> SetCondition {
+        self.condition
+    }
+
+    /// Returns `true` if the previous value is requested
+    pub fn get(&self) -> bool {
+        self.get
+    }
+
     /// Parse a `Set` instance from a received frame.
     ///
     /// The `Parse` argument provides a cursor-like API to read fields from the
@@ -75,7 +129,7 @@ impl Set {
     /// Expects an array frame containing at least 3 entries.
     ///
     /// ```text
-    /// SET key value [EX seconds|PX milliseconds]
+    /// SET key value [NX|XX] [GET] [EX seconds|PX milliseconds|EXAT timestamp|PXAT timestamp]
     /// ```
     pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Set> {
         use ParseError::EndOfStream;
@@ -90,58 +144,132 @@ impl Set {
         // `None`.
         let mut expire = None;
 
-        // Attempt to parse another string.
-        match parse.next_string() {
-            Ok(s) if s.to_uppercase() == "EX" => {
-                // An expiration is specified in seconds. The next value is an
-                // integer.
-                let secs = parse.next_int()?;
-                expire = Some(Duration::from_secs(secs));
-            }
-            Ok(s) if s.to_uppercase() == "PX" => {
-                // An expiration is specified in milliseconds. The next value is
-                // an integer.
-                let ms = parse.next_int()?;
-                expire = Some(Duration::from_millis(ms));
+        // Whether the key must exist, and whether the previous value is
+        // returned, are optional as well.
+        let mut condition = SetCondition::Always;
+        let mut get = false;
+
+        // Options may be given in any order, so keep parsing strings until
+        // the end of the frame is reached.
+        loop {
+            match parse.next_string() {
+                Ok(s) if s.to_uppercase() == "EX" && expire.is_none() => {
+                    // An expiration is specified in seconds. The next value is
+                    // an integer.
+                    let secs = parse.next_int()?;
+                    expire = Some(Duration::from_secs(secs));
+                }
+                Ok(s) if s.to_uppercase() == "PX" && expire.is_none() => {
+                    // An expiration is specified in milliseconds. The next
+                    // value is an integer.
+                    let ms = parse.next_int()?;
+                    expire = Some(Duration::from_millis(ms));
+                }
+                Ok(s) if s.to_uppercase() == "EXAT" && expire.is_none() => {
+                    // An expiration is specified as a unix time, in seconds.
+                    // A time that has passed expires the key right away.
+                    let secs = parse.next_int()?;
+                    expire = Some(super::until_unix_time_ms(secs.saturating_mul(1000)));
+                }
+                Ok(s) if s.to_uppercase() == "PXAT" && expire.is_none() => {
+                    // An expiration is specified as a unix time, in
+                    // milliseconds.
+                    let ms = parse.next_int()?;
+                    expire = Some(super::until_unix_time_ms(ms));
+                }
+                Ok(s) if s.to_uppercase() == "NX" && condition == SetCondition::Always => {
+                    condition = SetCondition::IfAbsent;
+                }
+                Ok(s) if s.to_uppercase() == "XX" && condition == SetCondition::Always => {
+                    condition = SetCondition::IfPresent;
+                }
+                Ok(s) if s.to_uppercase() == "GET" && !get => {
+                    get = true;
+                }
+                // Currently, mini-redis does not support any of the other SET
+                // options, and each option may only be given once. Like Redis,
+                // this is reported to the client as a syntax error.
+                Ok(_) => return Err("ERR syntax error".into()),
+                // The `EndOfStream` error indicates there is no further data to
+                // parse. In this case, it is a normal run time situation and
+                // indicates there are no further `SET` options.
+                Err(EndOfStream) => break,
+                // All other errors are bubbled up and reported to the client.
+                Err(err) => return Err(err.into()),
             }
-            // Currently, mini-redis does not support any of the other SET
-            // options. An error here results in the connection being
-            // terminated. Other connections will continue to operate normally.
-            Ok(_) => return Err("currently `SET` only supports the expiration option".into()),
-            // The `EndOfStream` error indicates there is no further data to
-            // parse. In this case, it is a normal run time situation and
-            // indicates there are no specified `SET` options.
-            Err(EndOfStream) => {}
-            // All other errors are bubbled up, resulting in the connection
-            // being terminated.
-            Err(err) => return Err(err.into()),
         }
 
-        Ok(Set { key, value, expire })
+        Ok(Set {
+            key,
+            value,
+            expire,
+            condition,
+            get,
+        })
     }
 
-    /// Apply the `Set` command to the specified `Db` instance.
+    /// Apply the `Set` command to the specified `Store`.
     ///
     /// The response is written to `dst`. This is called by the server in order
-    /// to execute a received command.
+    /// to execute a received command, with its `Db`.
     #[instrument(skip(self, db, dst))]
-    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+    pub async fn apply(self, db: &impl Store, dst: &mut Connection) -> crate::Result<()> {
+        // Values larger than the configured limit are not stored.
+        let max_value_size = db.max_value_size();
+        if max_value_size != 0 && self.value.len() > max_value_size {
+            let response = Frame::Error("ERR value exceeds maximum allowed size".to_string());
+            debug!(?response);
+            dst.write_frame(&response).await?;
+            return Ok(());
+        }
+
         // Set the value in the shared database state.
-        db.set(self.key, self.value, self.expire);
+        let set = db.set_with(self.key, self.value, self.expire, self.condition, self.get);
 
-        // Create a success response and write it to `dst`.
-        let response = Frame::Simple("OK".to_string());
+        // Create the response and write it to `dst`. With the `GET` option,
+        // the response is the previous value rather than `OK`, whether or not
+        // the value was set.
+        let response = match set {
+            Ok((_, Some(prev))) if self.get => Frame::Bulk(prev),
+            Ok((_, None)) if self.get => Frame::Null,
+            Ok((true, _)) => Frame::Simple("OK".to_string()),
+            Ok((false, _)) => Frame::Null,
+            // With `GET`, the key holds a value that is not a string.
+            Err(err) => Frame::Error(err.to_string()),
+        };
         debug!(?response);
         dst.write_frame(&response).await?;
 
         Ok(())
     }
 
+    /// Rewrites an `EX` or `PX` expiration in `args`, the frame of a `SET`
+    /// command that was just applied, as a `PXAT` unix timestamp.
+    ///
+    /// See `Command::absolute_expiration`.
+    pub(crate) fn absolute_expiration(args: &mut [Frame]) {
+        // Options follow the key and the value.
+        for i in 3..args.len().saturating_sub(1) {
+            let scale = match &args[i] {
+                Frame::Bulk(option) if option.eq_ignore_ascii_case(b"ex") => 1000,
+                Frame::Bulk(option) if option.eq_ignore_ascii_case(b"px") => 1,
+                _ => continue,
+            };
+
+            if let Some(delay) = super::frame_to_u64(&args[i + 1]) {
+                let delay = Duration::from_millis(delay.saturating_mul(scale));
+
+                args[i] = Frame::Bulk(Bytes::from("pxat".as_bytes()));
+                args[i + 1] = Frame::Bulk(Bytes::from(super::unix_time_ms(delay).to_string()));
+            }
+        }
+    }
+
     /// Converts the command into an equivalent `Frame`.
     ///
     /// This is called by the client when encoding a `Set` command to send to
     /// the server.
-    pub(crate) fn into_frame(self) -> Frame {
+    pub fn into_frame(self) -> Frame {
         let mut frame = Frame::array();
         frame.push_bulk(Bytes::from("set".as_bytes()));
         frame.push_bulk(Bytes::from(self.key.into_bytes()));
@@ -154,8 +282,27 @@ impl Set {
             // src/bin/cli.rs parses the expiration argument as milliseconds
             // in duration_from_ms_str()
             frame.push_bulk(Bytes::from("px".as_bytes()));
-            frame.push_int(ms.as_millis() as u64);
+            frame.push_int(ms.as_millis() as i64);
+        }
+        match self.condition {
+            SetCondition::Always => {}
+            SetCondition::IfAbsent => frame.push_bulk(Bytes::from("nx".as_bytes())),
+            SetCondition::IfPresent => frame.push_bulk(Bytes::from("xx".as_bytes())),
+        }
+        if self.get {
+            frame.push_bulk(Bytes::from("get".as_bytes()));
         }
         frame
     }
 }
+
+impl SetCondition {
+    /// Returns `true` if the key is set, given whether it `exists`.
+    pub(crate) fn holds(self, exists: bool) -> bool {
+        match self {
+            SetCondition::Always => true,
+            SetCondition::IfAbsent => !exists,
+            SetCondition::IfPresent => exists,
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use crate::{Connection, Db, Frame, Parse};
+
+use bytes::Bytes;
+use std::convert::TryFrom;
+use tracing::{debug, instrument};
+
+/// Set or clear the bit at `offset` in the string stored at `key`.
+///
+/// Bits are numbered from the most significant bit of the first byte. The
+/// string is grown with zero bytes if it is too short, and a missing key is
+/// treated as an empty string.
+///
+/// The response is the previous value of the bit.
+#[derive(Debug)]
+pub struct Setbit {
+    /// Name of the key to modify
+    key: String,
+
+    /// Offset of the bit to set
+    offset: u32,
+
+    /// New value of the bit
+    value: bool,
+}
+
+impl Setbit {
+    /// Create a new `Setbit` command which sets the bit at `offset` in `key`
+    /// to `value`.
+    pub fn new(key: impl ToString, offset: u32, value: bool) -> Setbit {
+        Setbit {
+            key: key.to_string(),
+            offset,
+            value,
+        }
+    }
+
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Get the offset
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// Get the value
+    pub fn value(&self) -> bool {
+        self.value
+    }
+
+    /// Parse a `Setbit` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `SETBIT` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Setbit` value on success. If the frame is malformed, `Err`
+    /// is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing four entries.
+    ///
+    /// ```text
+    /// SETBIT key offset value
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Setbit> {
+        let key = parse.next_string()?;
+        let offset = parse_offset(parse)?;
+
+        let value = match parse.next_signed_int()? {
+            0 => false,
+            1 => true,
+            _ => return Err("ERR bit is not an integer or out of range".into()),
+        };
+
+        Ok(Setbit { key, offset, value })
+    }
+
+    /// Apply the `Setbit` command to the specified `Db` instance.
+    ///
+    /// The response is written to `dst`. This is called by the server in order
+    /// to execute a received command.
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = match db.setbit(&self.key, self.offset, self.value) {
+            Ok(prev) => Frame::Integer(prev as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Converts the command into an equivalent `Frame`.
+    ///
+    /// This is called by the client when encoding a `Setbit` command to send
+    /// to the server.
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("setbit".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_int(self.offset as i64);
+        frame.push_int(self.value as i64);
+        frame
+    }
+}
+
+/// Reads a bit offset, shared with `GETBIT`.
+///
+/// Like Redis, offsets are limited to 2^32 bits, which is 512MB of data.
+pub(crate) fn parse_offset(parse: &mut Parse) -> crate::Result<u32> {
+    let offset = parse.next_signed_int()?;
+    u32::try_from(offset).map_err(|_| "ERR bit offset is not an integer or out of range".into())
+}
@@ -0,0 +1,212 @@
+use crate::{Connection, Db, Frame, Parse};
+
+use bytes::Bytes;
+use std::collections::HashSet;
+use tracing::{debug, instrument};
+
+/// Combines the sets stored at the given keys.
+///
+/// This implements six closely related commands, which differ in how the sets
+/// are combined and whether the result is returned or stored:
+///
+/// * SINTER -- Members of every set.
+/// * SUNION -- Members of any set.
+/// * SDIFF -- Members of the first set that are in none of the others.
+/// * SINTERSTORE, SUNIONSTORE, SDIFFSTORE -- The same, storing the result at
+///   a destination key, replacing any value it holds, and returning the
+///   number of members stored. An empty result deletes the destination.
+///
+/// Missing keys are treated as empty sets. An error is returned if a key holds
+/// a value of another type. All the keys are read, and the destination
+/// written, atomically.
+#[derive(Debug)]
+pub struct SetOp {
+    /// How the sets are combined
+    kind: SetOpKind,
+
+    /// Key the result is stored at, for the `*STORE` commands
+    destination: Option<String>,
+
+    /// Names of the sets to combine
+    keys: Vec<String>,
+}
+
+/// How the sets of a `SetOp` command are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetOpKind {
+    /// `SINTER`, the intersection
+    Inter,
+    /// `SUNION`, the union
+    Union,
+    /// `SDIFF`, the difference
+    Diff,
+}
+
+impl SetOp {
+    /// Create a new `SetOp` command which combines the sets stored at `keys`
+    /// as described by `kind`, and returns the result.
+    pub fn new(kind: SetOpKind, keys: Vec<String>) -> SetOp {
+        SetOp {
+            kind,
+            destination: None,
+            keys,
+        }
+    }
+
+    /// Create a new `SetOp` command which combines the sets stored at `keys`
+    /// as described by `kind`, and stores the result at `destination`.
+    pub fn store(kind: SetOpKind, destination: impl ToString, keys: Vec<String>) -> SetOp {
+        SetOp {
+            kind,
+            destination: Some(destination.to_string()),
+            keys,
+        }
+    }
+
+    /// Get how the sets are combined
+    pub fn kind(&self) -> SetOpKind {
+        self.kind
+    }
+
+    /// Get the destination key, if the result is stored
+    pub fn destination(&self) -> Option<&str> {
+        self.destination.as_deref()
+    }
+
+    /// Get the keys
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    /// Parse a `SetOp` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The command name has already been consumed, and determines `kind` and
+    /// whether a destination is read.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `SetOp` value on success. If the frame is malformed, `Err`
+    /// is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing at least two entries, or three for
+    /// the `*STORE` commands.
+    ///
+    /// ```text
+    /// SINTER key [key ...]
+    /// SINTERSTORE destination key [key ...]
+    /// ```
+    pub(crate) fn parse_frames(
+        parse: &mut Parse,
+        kind: SetOpKind,
+        store: bool,
+    ) -> crate::Result<SetOp> {
+        let destination = if store {
+            Some(parse.next_string()?)
+        } else {
+            None
+        };
+
+        // At least one key is required.
+        let mut keys = vec![parse.next_string()?];
+
+        while parse.remaining() > 0 {
+            keys.push(parse.next_string()?);
+        }
+
+        Ok(SetOp {
+            kind,
+            destination,
+            keys,
+        })
+    }
+
+    /// Apply the `SetOp` command to the specified `Db` instance.
+    ///
+    /// The response is written to `dst`. This is called by the server in order
+    /// to execute a received command.
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let result = db.set_op(self.kind, &self.keys, self.destination.as_deref());
+
+        let response = match (result, &self.destination) {
+            (Ok(members), Some(_)) => Frame::Integer(members.len() as i64),
+            (Ok(members), None) => Frame::Array(members.into_iter().map(Frame::Bulk).collect()),
+            (Err(err), _) => Frame::Error(err.to_string()),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Returns the command name
+    pub(crate) fn get_name(&self) -> &'static str {
+        self.kind.name(self.destination.is_some())
+    }
+
+    /// Converts the command into an equivalent `Frame`.
+    ///
+    /// This is called by the client when encoding a `SetOp` command to send to
+    /// the server.
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from(self.get_name().as_bytes()));
+
+        if let Some(destination) = self.destination {
+            frame.push_bulk(Bytes::from(destination.into_bytes()));
+        }
+
+        for key in self.keys {
+            frame.push_bulk(Bytes::from(key.into_bytes()));
+        }
+
+        frame
+    }
+}
+
+impl SetOpKind {
+    /// Returns the name of the command, which is also the keyspace event
+    /// published for the destination when the result is `store`d.
+    pub(crate) fn name(self, store: bool) -> &'static str {
+        match (self, store) {
+            (SetOpKind::Inter, false) => "sinter",
+            (SetOpKind::Union, false) => "sunion",
+            (SetOpKind::Diff, false) => "sdiff",
+            (SetOpKind::Inter, true) => "sinterstore",
+            (SetOpKind::Union, true) => "sunionstore",
+            (SetOpKind::Diff, true) => "sdiffstore",
+        }
+    }
+
+    /// Combines `sets`, given in the order of their keys. A missing key is
+    /// passed as an empty set.
+    pub(crate) fn combine(self, sets: &[&HashSet<Bytes>]) -> HashSet<Bytes> {
+        let (first, rest) = match sets.split_first() {
+            Some(split) => split,
+            None => return HashSet::new(),
+        };
+
+        // Members are `Bytes`, so copying them is shallow.
+        match self {
+            SetOpKind::Inter => first
+                .iter()
+                .filter(|member| rest.iter().all(|set| set.contains(*member)))
+                .cloned()
+                .collect(),
+            SetOpKind::Union => sets.iter().flat_map(|set| set.iter()).cloned().collect(),
+            SetOpKind::Diff => first
+                .iter()
+                .filter(|member| !rest.iter().any(|set| set.contains(*member)))
+                .cloned()
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{Connection, Db, Frame, Parse};
+
+use bytes::Bytes;
+use std::convert::TryFrom;
+use tracing::{debug, instrument};
+
+/// Overwrite part of the string stored at `key`, starting at `offset`.
+///
+/// If the string is shorter than `offset`, it is padded with zero bytes. A
+/// missing key is treated as an empty string. The operation is binary safe.
+///
+/// The response is the length of the string after it was modified.
+#[derive(Debug)]
+pub struct Setrange {
+    /// Name of the key to modify
+    key: String,
+
+    /// Offset at which `value` is written
+    offset: u64,
+
+    /// Data to write
+    value: Bytes,
+}
+
+impl Setrange {
+    /// Create a new `Setrange` command which writes `value` to `key`, starting
+    /// at `offset`.
+    pub fn new(key: impl ToString, offset: u64, value: Bytes) -> Setrange {
+        Setrange {
+            key: key.to_string(),
+            offset,
+            value,
+        }
+    }
+
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Get the offset
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Get the value
+    pub fn value(&self) -> &Bytes {
+        &self.value
+    }
+
+    /// Parse a `Setrange` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `SETRANGE` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Setrange` value on success. If the frame is malformed,
+    /// `Err` is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing four entries.
+    ///
+    /// ```text
+    /// SETRANGE key offset value
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Setrange> {
+        let key = parse.next_string()?;
+
+        let offset = parse.next_signed_int()?;
+        let offset = u64::try_from(offset).map_err(|_| "ERR offset is out of range")?;
+
+        let value = parse.next_bytes()?;
+
+        Ok(Setrange { key, offset, value })
+    }
+
+    /// Apply the `Setrange` command to the specified `Db` instance.
+    ///
+    /// The response is written to `dst`. This is called by the server in order
+    /// to execute a received command.
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        // An offset that does not even fit in memory is reported the same way
+        // as one that exceeds the maximum string length.
+        let offset = usize::try_from(self.offset).unwrap_or(usize::MAX);
+
+        let response = match db.setrange(&self.key, offset, &self.value) {
+            Ok(len) => Frame::Integer(len as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Converts the command into an equivalent `Frame`.
+    ///
+    /// This is called by the client when encoding a `Setrange` command to send
+    /// to the server.
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("setrange".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_bulk(Bytes::from(self.offset.to_string()));
+        frame.push_bulk(self.value);
+        frame
+    }
+}
@@ -0,0 +1,95 @@
+use crate::{Connection, Db, Frame, Parse};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// Check whether `member` is in the set stored at `key`.
+///
+/// The response is `1` if it is, and `0` if it is not or the key does not
+/// exist. An error is returned if the key holds a value of another type.
+#[derive(Debug)]
+pub struct Sismember {
+    /// Name of the set
+    key: String,
+
+    /// Member to look for
+    member: Bytes,
+}
+
+impl Sismember {
+    /// Create a new `Sismember` command which checks whether `member` is in
+    /// the set `key`.
+    pub fn new(key: impl ToString, member: Bytes) -> Sismember {
+        Sismember {
+            key: key.to_string(),
+            member,
+        }
+    }
+
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Get the member
+    pub fn member(&self) -> &Bytes {
+        &self.member
+    }
+
+    /// Parse a `Sismember` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `SISMEMBER` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Sismember` value on success. If the frame is malformed,
+    /// `Err` is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing three entries.
+    ///
+    /// ```text
+    /// SISMEMBER key member
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Sismember> {
+        let key = parse.next_string()?;
+        let member = parse.next_bytes()?;
+
+        Ok(Sismember { key, member })
+    }
+
+    /// Apply the `Sismember` command to the specified `Db` instance.
+    ///
+    /// The response is written to `dst`. This is called by the server in order
+    /// to execute a received command.
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = match db.sismember(&self.key, &self.member) {
+            Ok(found) => Frame::Integer(found as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Converts the command into an equivalent `Frame`.
+    ///
+    /// This is called by the client when encoding a `Sismember` command to send
+    /// to the server.
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("sismember".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_bulk(self.member);
+        frame
+    }
+}
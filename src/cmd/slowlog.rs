@@ -0,0 +1,295 @@
+use crate::{Connection, Frame, Parse, ParseError};
+
+use bytes::Bytes;
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, instrument};
+
+/// Inspect the commands that took longer than the slow log threshold to
+/// apply, see `ServerBuilder::slowlog_threshold`.
+///
+/// # Subcommands
+///
+/// Currently, the following subcommands are supported:
+///
+/// * GET [`count`] -- Return the `count` most recent slow commands, newest
+///   first. `count` defaults to 10, and `-1` returns every entry. Each entry
+///   is an array of its id, the Unix time it was recorded at, in seconds, how
+///   long the command took, in microseconds, the command with its arguments,
+///   and the client address.
+/// * LEN -- Return the number of entries.
+/// * RESET -- Remove every entry.
+#[derive(Debug)]
+pub struct Slowlog {
+    subcommand: Subcommand,
+}
+
+#[derive(Debug)]
+enum Subcommand {
+    Get(Option<usize>),
+    Len,
+    Reset,
+    Unknown(String),
+}
+
+/// The most recent slow commands, shared by every connection.
+///
+/// Once `max_len` entries are stored, recording a new one drops the oldest.
+#[derive(Debug, Clone)]
+pub(crate) struct SlowCommands {
+    shared: Arc<Mutex<Entries>>,
+}
+
+#[derive(Debug)]
+struct Entries {
+    /// Slow commands, newest first
+    entries: VecDeque<SlowCommand>,
+
+    /// Id of the next entry. Ids keep increasing when entries are dropped or
+    /// reset, so a client polling the slow log can tell which are new.
+    next_id: u64,
+
+    /// Maximum number of entries kept
+    max_len: usize,
+}
+
+/// A command that took longer than the slow log threshold to apply.
+#[derive(Debug, Clone)]
+pub(crate) struct SlowCommand {
+    /// Unique, increasing id of the entry
+    id: u64,
+
+    /// When the command was recorded
+    time: SystemTime,
+
+    /// How long the command took to apply
+    duration: Duration,
+
+    /// The command name and its arguments, possibly truncated
+    args: Vec<Bytes>,
+
+    /// Address of the client that sent the command
+    addr: SocketAddr,
+}
+
+/// Number of entries `SLOWLOG GET` returns when no count is given.
+const DEFAULT_COUNT: usize = 10;
+
+/// Maximum number of arguments, including the command name, kept for an
+/// entry.
+const MAX_ARGS: usize = 32;
+
+/// Maximum number of bytes kept for each argument.
+const MAX_ARG_LEN: usize = 128;
+
+impl Slowlog {
+    /// Parse a `Slowlog` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `SLOWLOG` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Slowlog` value on success. If the frame is malformed,
+    /// `Err` is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing `SLOWLOG`, a subcommand and the
+    /// subcommand's arguments.
+    ///
+    /// ```text
+    /// SLOWLOG GET [count]
+    /// SLOWLOG LEN
+    /// SLOWLOG RESET
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Slowlog> {
+        // Subcommands are matched case insensitively, like command names.
+        let subcommand = match &parse.next_string()?.to_lowercase()[..] {
+            "get" => match parse.next_signed_int() {
+                Ok(-1) => Subcommand::Get(None),
+                Ok(count) => match usize::try_from(count) {
+                    Ok(count) => Subcommand::Get(Some(count)),
+                    Err(_) => return Err("ERR count should be greater than or equal to -1".into()),
+                },
+                Err(ParseError::EndOfStream) => Subcommand::Get(Some(DEFAULT_COUNT)),
+                Err(err) => return Err(err.into()),
+            },
+            "len" => Subcommand::Len,
+            "reset" => Subcommand::Reset,
+            subcommand => Subcommand::Unknown(subcommand.to_string()),
+        };
+
+        Ok(Slowlog { subcommand })
+    }
+
+    /// Apply the `Slowlog` command to the slow commands recorded by the
+    /// server.
+    ///
+    /// This is called by the connection handler, which owns the slow log.
+    #[instrument(skip(self, slow_commands, dst))]
+    pub(crate) async fn apply(
+        self,
+        slow_commands: &SlowCommands,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = match self.subcommand {
+            Subcommand::Get(count) => Frame::Array(
+                slow_commands
+                    .get(count)
+                    .into_iter()
+                    .map(SlowCommand::into_frame)
+                    .collect(),
+            ),
+            Subcommand::Len => Frame::Integer(slow_commands.len() as i64),
+            Subcommand::Reset => {
+                slow_commands.reset();
+                Frame::Simple("OK".to_string())
+            }
+            Subcommand::Unknown(subcommand) => Frame::Error(format!(
+                "ERR unknown subcommand '{}'. Try SLOWLOG HELP.",
+                subcommand
+            )),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
+
+impl SlowCommands {
+    /// Create an empty slow log keeping at most `max_len` entries.
+    pub(crate) fn new(max_len: usize) -> SlowCommands {
+        SlowCommands {
+            shared: Arc::new(Mutex::new(Entries {
+                entries: VecDeque::new(),
+                next_id: 0,
+                max_len,
+            })),
+        }
+    }
+
+    /// Record `frame`, received from `addr`, as a command that took
+    /// `duration` to apply. Returns the new entry.
+    pub(crate) fn record(
+        &self,
+        addr: SocketAddr,
+        frame: &Frame,
+        duration: Duration,
+    ) -> SlowCommand {
+        let mut state = self.shared.lock().unwrap();
+
+        let entry = SlowCommand {
+            id: state.next_id,
+            time: SystemTime::now(),
+            duration,
+            args: truncate_args(frame),
+            addr,
+        };
+        state.next_id += 1;
+
+        if state.max_len > 0 {
+            if state.entries.len() == state.max_len {
+                state.entries.pop_back();
+            }
+            state.entries.push_front(entry.clone());
+        }
+
+        entry
+    }
+
+    /// Returns the `count` most recent entries, or all of them if `count` is
+    /// `None`, newest first.
+    fn get(&self, count: Option<usize>) -> Vec<SlowCommand> {
+        let state = self.shared.lock().unwrap();
+        let count = count.unwrap_or(state.entries.len());
+        state.entries.iter().take(count).cloned().collect()
+    }
+
+    /// Returns the number of entries.
+    fn len(&self) -> usize {
+        self.shared.lock().unwrap().entries.len()
+    }
+
+    /// Removes every entry.
+    fn reset(&self) {
+        self.shared.lock().unwrap().entries.clear();
+    }
+}
+
+impl SlowCommand {
+    /// Returns the name of the command, in lower case.
+    pub(crate) fn name(&self) -> String {
+        self.args
+            .first()
+            .map(|name| String::from_utf8_lossy(name).to_lowercase())
+            .unwrap_or_default()
+    }
+
+    /// Returns the arguments of the command, separated by spaces, for logging.
+    pub(crate) fn args_summary(&self) -> String {
+        let args: Vec<_> = self
+            .args
+            .iter()
+            .skip(1)
+            .map(|arg| String::from_utf8_lossy(arg))
+            .collect();
+        args.join(" ")
+    }
+
+    /// Converts the entry into the array `SLOWLOG GET` returns.
+    fn into_frame(self) -> Frame {
+        let since_epoch = self.time.duration_since(UNIX_EPOCH).unwrap_or_default();
+
+        Frame::Array(vec![
+            Frame::Integer(self.id as i64),
+            Frame::Integer(since_epoch.as_secs() as i64),
+            Frame::Integer(self.duration.as_micros() as i64),
+            Frame::Array(self.args.into_iter().map(Frame::Bulk).collect()),
+            Frame::Bulk(Bytes::from(self.addr.to_string())),
+        ])
+    }
+}
+
+/// Returns the command name and arguments in `frame`, truncated like Redis
+/// does, so a command with many arguments or a large value does not use much
+/// memory in the slow log.
+fn truncate_args(frame: &Frame) -> Vec<Bytes> {
+    let parts = match frame {
+        Frame::Array(parts) => &parts[..],
+        _ => &[],
+    };
+
+    let mut args: Vec<Bytes> = parts
+        .iter()
+        .take(MAX_ARGS)
+        .map(|part| match part {
+            Frame::Bulk(data) if data.len() > MAX_ARG_LEN => {
+                let mut arg = data[..MAX_ARG_LEN].to_vec();
+                let more = format!("... ({} more bytes)", data.len() - MAX_ARG_LEN);
+                arg.extend_from_slice(more.as_bytes());
+                Bytes::from(arg)
+            }
+            Frame::Bulk(data) => data.clone(),
+            part => Bytes::from(part.to_string()),
+        })
+        .collect();
+
+    // The last argument kept is replaced by the number of arguments dropped,
+    // like Redis does.
+    if parts.len() > MAX_ARGS {
+        let more = parts.len() - MAX_ARGS + 1;
+        args[MAX_ARGS - 1] = Bytes::from(format!("... ({} more arguments)", more));
+    }
+
+    args
+}
@@ -0,0 +1,115 @@
+use crate::cmd::ParseError;
+use crate::{Connection, Db, Frame, Parse};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// Remove members from the set stored at `key`.
+///
+/// Members that are not in the set are ignored. The key is removed once the
+/// set is empty. An error is returned if the key holds a value of another
+/// type.
+///
+/// The response is the number of members that were removed.
+#[derive(Debug)]
+pub struct Srem {
+    /// Name of the set
+    key: String,
+
+    /// Members to remove
+    members: Vec<Bytes>,
+}
+
+impl Srem {
+    /// Create a new `Srem` command which removes `members` from the set
+    /// `key`.
+    pub fn new(key: impl ToString, members: Vec<Bytes>) -> Srem {
+        Srem {
+            key: key.to_string(),
+            members,
+        }
+    }
+
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Get the members
+    pub fn members(&self) -> &[Bytes] {
+        &self.members
+    }
+
+    /// Parse a `Srem` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `SREM` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Srem` value on success. If the frame is malformed, `Err` is
+    /// returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing three or more entries.
+    ///
+    /// ```text
+    /// SREM key member [member ...]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Srem> {
+        use ParseError::EndOfStream;
+
+        let key = parse.next_string()?;
+
+        // At least one member is required.
+        let mut members = vec![parse.next_bytes()?];
+
+        loop {
+            match parse.next_bytes() {
+                Ok(member) => members.push(member),
+                Err(EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(Srem { key, members })
+    }
+
+    /// Apply the `Srem` command to the specified `Db` instance.
+    ///
+    /// The response is written to `dst`. This is called by the server in order
+    /// to execute a received command.
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = match db.srem(&self.key, &self.members) {
+            Ok(count) => Frame::Integer(count as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Converts the command into an equivalent `Frame`.
+    ///
+    /// This is called by the client when encoding a `Srem` command to send to
+    /// the server.
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("srem".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+
+        for member in self.members {
+            frame.push_bulk(member);
+        }
+
+        frame
+    }
+}
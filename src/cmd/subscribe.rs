@@ -1,5 +1,5 @@
 use crate::cmd::{Parse, ParseError, Unknown};
-use crate::{Command, Connection, Db, Frame, Shutdown};
+use crate::{Command, Connection, Db, Frame, Session, Shutdown};
 
 use bytes::Bytes;
 use std::pin::Pin;
@@ -17,6 +17,16 @@ pub struct Subscribe {
     channels: Vec<String>,
 }
 
+/// Subscribes the client to one or more glob-style patterns.
+///
+/// The client receives messages published to every channel matching one of
+/// the patterns. The client then enters the subscribed state, like with
+/// `SUBSCRIBE`.
+#[derive(Debug)]
+pub struct Psubscribe {
+    patterns: Vec<String>,
+}
+
 /// Unsubscribes the client from one or more channels.
 ///
 /// When no channels are specified, the client is unsubscribed from all the
@@ -26,11 +36,35 @@ pub struct Unsubscribe {
     channels: Vec<String>,
 }
 
+/// Unsubscribes the client from one or more patterns.
+///
+/// When no patterns are specified, the client is unsubscribed from all the
+/// previously subscribed patterns.
+#[derive(Debug)]
+pub struct Punsubscribe {
+    patterns: Vec<String>,
+}
+
+/// A single subscription held by a client.
+///
+/// A client may subscribe to a channel and to a pattern with the same name,
+/// so the kind of subscription is part of the key.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Subscription {
+    Channel(String),
+    Pattern(String),
+}
+
 /// Stream of messages. The stream receives messages from the
-/// `broadcast::Receiver`. We use `stream!` to create a `Stream` that consumes
-/// messages. Because `stream!` values cannot be named, we box the stream using
-/// a trait object.
-type Messages = Pin<Box<dyn Stream<Item = Bytes> + Send>>;
+/// `broadcast::Receiver` and turns them into the frames sent to the client. We
+/// use `stream!` to create a `Stream` that consumes messages. Because `stream!`
+/// values cannot be named, we box the stream using a trait object.
+type Messages = Pin<Box<dyn Stream<Item = Result<Frame, TooSlow>> + Send>>;
+
+/// Yielded by a `Messages` stream when the client fell behind the published
+/// messages more times in a row than `Db::max_subscriber_lags` allows.
+#[derive(Debug)]
+struct TooSlow;
 
 impl Subscribe {
     /// Creates a new `Subscribe` command to listen on the specified channels.
@@ -80,8 +114,7 @@ impl Subscribe {
                 // The `EndOfStream` error indicates there is no further data to
                 // parse.
                 Err(EndOfStream) => break,
-                // All other errors are bubbled up, resulting in the connection
-                // being terminated.
+                // All other errors are bubbled up and reported to the client.
                 Err(err) => return Err(err.into()),
             }
         }
@@ -98,66 +131,26 @@ impl Subscribe {
     ///
     /// [here]: https://redis.io/topics/pubsub
     pub(crate) async fn apply(
-        mut self,
+        self,
         db: &Db,
         dst: &mut Connection,
         shutdown: &mut Shutdown,
+        session: &mut Session,
     ) -> crate::Result<()> {
-        // Each individual channel subscription is handled using a
-        // `sync::broadcast` channel. Messages are then fanned out to all
-        // clients currently subscribed to the channels.
-        //
-        // An individual client may subscribe to multiple channels and may
-        // dynamically add and remove channels from its subscription set. To
-        // handle this, a `StreamMap` is used to track active subscriptions. The
-        // `StreamMap` merges messages from individual broadcast channels as
-        // they are received.
-        let mut subscriptions = StreamMap::new();
+        let subscribe_to = self
+            .channels
+            .into_iter()
+            .map(Subscription::Channel)
+            .collect();
 
-        loop {
-            // `self.channels` is used to track additional channels to subscribe
-            // to. When new `SUBSCRIBE` commands are received during the
-            // execution of `apply`, the new channels are pushed onto this vec.
-            for channel_name in self.channels.drain(..) {
-                subscribe_to_channel(channel_name, &mut subscriptions, db, dst).await?;
-            }
-
-            // Wait for one of the following to happen:
-            //
-            // - Receive a message from one of the subscribed channels.
-            // - Receive a subscribe or unsubscribe command from the client.
-            // - A server shutdown signal.
-            select! {
-                // Receive messages from subscribed channels
-                Some((channel_name, msg)) = subscriptions.next() => {
-                    dst.write_frame(&make_message_frame(channel_name, msg)).await?;
-                }
-                res = dst.read_frame() => {
-                    let frame = match res? {
-                        Some(frame) => frame,
-                        // This happens if the remote client has disconnected.
-                        None => return Ok(())
-                    };
-
-                    handle_command(
-                        frame,
-                        &mut self.channels,
-                        &mut subscriptions,
-                        dst,
-                    ).await?;
-                }
-                _ = shutdown.recv() => {
-                    return Ok(());
-                }
-            };
-        }
+        run_subscribed(subscribe_to, db, dst, shutdown, session).await
     }
 
     /// Converts the command into an equivalent `Frame`.
     ///
     /// This is called by the client when encoding a `Subscribe` command to send
     /// to the server.
-    pub(crate) fn into_frame(self) -> Frame {
+    pub fn into_frame(self) -> Frame {
         let mut frame = Frame::array();
         frame.push_bulk(Bytes::from("subscribe".as_bytes()));
         for channel in self.channels {
@@ -167,104 +160,349 @@ impl Subscribe {
     }
 }
 
-async fn subscribe_to_channel(
-    channel_name: String,
-    subscriptions: &mut StreamMap<String, Messages>,
+impl Psubscribe {
+    /// Creates a new `Psubscribe` command to listen on the specified patterns.
+    pub(crate) fn new(patterns: Vec<String>) -> Psubscribe {
+        Psubscribe { patterns }
+    }
+
+    /// Parse a `Psubscribe` instance from a received frame.
+    ///
+    /// The `PSUBSCRIBE` string has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing two or more entries.
+    ///
+    /// ```text
+    /// PSUBSCRIBE pattern [pattern ...]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Psubscribe> {
+        use ParseError::EndOfStream;
+
+        // At least one pattern is required.
+        let mut patterns = vec![parse.next_string()?];
+
+        loop {
+            match parse.next_string() {
+                Ok(s) => patterns.push(s),
+                Err(EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(Psubscribe { patterns })
+    }
+
+    /// Apply the `Psubscribe` command to the specified `Db` instance.
+    ///
+    /// This enters the same subscribed state as `Subscribe::apply`, starting
+    /// with pattern subscriptions instead of channel subscriptions.
+    pub(crate) async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        shutdown: &mut Shutdown,
+        session: &mut Session,
+    ) -> crate::Result<()> {
+        let subscribe_to = self
+            .patterns
+            .into_iter()
+            .map(Subscription::Pattern)
+            .collect();
+
+        run_subscribed(subscribe_to, db, dst, shutdown, session).await
+    }
+
+    /// Converts the command into an equivalent `Frame`.
+    ///
+    /// This is called by the client when encoding a `Psubscribe` command to
+    /// send to the server.
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("psubscribe".as_bytes()));
+        for pattern in self.patterns {
+            frame.push_bulk(Bytes::from(pattern.into_bytes()));
+        }
+        frame
+    }
+}
+
+/// Runs a client in the subscribed state until it disconnects, issues
+/// `RESET`, or the server shuts down.
+///
+/// `subscribe_to` holds the initial subscriptions. Additional `subscribe`,
+/// `psubscribe`, `unsubscribe` and `punsubscribe` commands may be received from
+/// the client and the set of subscriptions is updated accordingly.
+async fn run_subscribed(
+    mut subscribe_to: Vec<Subscription>,
     db: &Db,
     dst: &mut Connection,
+    shutdown: &mut Shutdown,
+    session: &mut Session,
 ) -> crate::Result<()> {
-    let mut rx = db.subscribe(channel_name.clone());
+    // Each individual channel or pattern subscription is handled using a
+    // `sync::broadcast` channel. Messages are then fanned out to all clients
+    // currently subscribed to the channels.
+    //
+    // An individual client may subscribe to multiple channels and may
+    // dynamically add and remove channels from its subscription set. To handle
+    // this, a `StreamMap` is used to track active subscriptions. The
+    // `StreamMap` merges messages from individual broadcast channels as they
+    // are received.
+    //
+    // Channel and pattern subscriptions live in the same `StreamMap`, so its
+    // length is the combined subscription count reported to the client.
+    let mut subscriptions = StreamMap::new();
+
+    loop {
+        // `subscribe_to` is used to track additional subscriptions. When new
+        // `SUBSCRIBE` or `PSUBSCRIBE` commands are received, the new
+        // subscriptions are pushed onto this vec.
+        for subscription in subscribe_to.drain(..) {
+            subscribe(subscription, &mut subscriptions, db, dst).await?;
+        }
 
-    // Subscribe to the channel.
-    let rx = Box::pin(async_stream::stream! {
-        loop {
-            match rx.recv().await {
-                Ok(msg) => yield msg,
-                // If we lagged in consuming messages, just resume.
-                Err(broadcast::error::RecvError::Lagged(_)) => {}
-                Err(_) => break,
+        // Wait for one of the following to happen:
+        //
+        // - Receive a message from one of the subscriptions.
+        // - Receive a subscribe or unsubscribe command from the client.
+        // - A server shutdown signal.
+        select! {
+            // Receive messages from subscribed channels and patterns
+            Some((_, message)) = subscriptions.next() => match message {
+                Ok(frame) => dst.write_frame(&frame).await?,
+                // The client is told why before being disconnected, like a
+                // replica falling behind its primary.
+                Err(TooSlow) => {
+                    let response = Frame::Error(
+                        "ERR client fell behind the published messages".to_string(),
+                    );
+                    dst.write_frame(&response).await?;
+
+                    return Err("subscriber fell behind the published messages".into());
+                }
+            },
+            res = dst.read_frame() => {
+                let frame = match res? {
+                    Some(frame) => frame,
+                    // This happens if the remote client has disconnected.
+                    None => return Ok(())
+                };
+
+                let subscribed = handle_command(
+                    frame,
+                    &mut subscribe_to,
+                    &mut subscriptions,
+                    dst,
+                    session,
+                ).await?;
+
+                // Leaving the subscribed state drops all subscriptions.
+                if !subscribed {
+                    return Ok(());
+                }
             }
+            _ = shutdown.recv() => {
+                return Ok(());
+            }
+        };
+    }
+}
+
+async fn subscribe(
+    subscription: Subscription,
+    subscriptions: &mut StreamMap<Subscription, Messages>,
+    db: &Db,
+    dst: &mut Connection,
+) -> crate::Result<()> {
+    // Subscribe to the channel or pattern. Each received message is turned
+    // into the frame sent to the client.
+    let max_lags = db.max_subscriber_lags();
+    let rx = match &subscription {
+        Subscription::Channel(channel_name) => {
+            let channel_name = channel_name.clone();
+            let rx = db.subscribe(channel_name.clone());
+
+            messages(rx, max_lags, move |msg| {
+                make_message_frame(channel_name.clone(), msg)
+            })
         }
-    });
+        Subscription::Pattern(pattern) => {
+            let pattern = pattern.clone();
+            let rx = db.psubscribe(pattern.clone());
 
-    // Track subscription in this client's subscription set.
-    subscriptions.insert(channel_name.clone(), rx);
+            messages(rx, max_lags, move |(channel_name, msg)| {
+                make_pmessage_frame(pattern.clone(), channel_name, msg)
+            })
+        }
+    };
 
-    // Respond with the successful subscription
-    let response = make_subscribe_frame(channel_name, subscriptions.len());
+    // Track subscription in this client's subscription set.
+    subscriptions.insert(subscription.clone(), rx);
+
+    // Respond with the successful subscription. Messages published from now
+    // on are buffered by the receiver, which is only polled by
+    // `run_subscribed` once this returns. As this task is the only one writing
+    // to `dst`, the client always sees the confirmation before any message
+    // from the new subscription.
+    let response = make_subscribe_frame(subscription, subscriptions.len());
     dst.write_frame(&response).await?;
 
     Ok(())
 }
 
-/// Handle a command received while inside `Subscribe::apply`. Only subscribe
-/// and unsubscribe commands are permitted in this context.
+/// Turns the messages received by `rx` into frames, using `make_frame`.
+///
+/// If the client lags in consuming messages, it just resumes, skipping the
+/// messages it missed. Once it lags `max_lags` times without catching up in
+/// between, `TooSlow` is yielded and the stream ends. A `max_lags` of `0`
+/// means the client is never considered too slow.
+fn messages<T>(
+    mut rx: broadcast::Receiver<T>,
+    max_lags: usize,
+    make_frame: impl Fn(T) -> Frame + Send + 'static,
+) -> Messages
+where
+    T: Clone + Send + 'static,
+{
+    Box::pin(async_stream::stream! {
+        // Number of times the client lagged since it last caught up.
+        let mut lags = 0;
+
+        loop {
+            match rx.recv().await {
+                Ok(msg) => {
+                    if rx.is_empty() {
+                        lags = 0;
+                    }
+
+                    yield Ok(make_frame(msg));
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => {
+                    lags += 1;
+
+                    if max_lags > 0 && lags >= max_lags {
+                        yield Err(TooSlow);
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+    })
+}
+
+/// Handle a command received while inside `run_subscribed`. Only subscribe,
+/// unsubscribe and `RESET` commands are permitted in this context.
 ///
 /// Any new subscriptions are appended to `subscribe_to` instead of modifying
 /// `subscriptions`.
+///
+/// Returns `false` if the client left the subscribed state.
 async fn handle_command(
     frame: Frame,
-    subscribe_to: &mut Vec<String>,
-    subscriptions: &mut StreamMap<String, Messages>,
+    subscribe_to: &mut Vec<Subscription>,
+    subscriptions: &mut StreamMap<Subscription, Messages>,
     dst: &mut Connection,
-) -> crate::Result<()> {
+    session: &mut Session,
+) -> crate::Result<bool> {
     // A command has been received from the client.
     //
-    // Only `SUBSCRIBE` and `UNSUBSCRIBE` commands are permitted
-    // in this context.
+    // Only `SUBSCRIBE`, `PSUBSCRIBE`, `UNSUBSCRIBE`, `PUNSUBSCRIBE` and
+    // `RESET` commands are permitted in this context.
     match Command::from_frame(frame)? {
         Command::Subscribe(subscribe) => {
-            // The `apply` method will subscribe to the channels we add to this
-            // vector.
-            subscribe_to.extend(subscribe.channels.into_iter());
+            // The `run_subscribed` loop will subscribe to the channels we add
+            // to this vector.
+            subscribe_to.extend(subscribe.channels.into_iter().map(Subscription::Channel));
         }
-        Command::Unsubscribe(mut unsubscribe) => {
-            // If no channels are specified, this requests unsubscribing from
-            // **all** channels. To implement this, the `unsubscribe.channels`
-            // vec is populated with the list of channels currently subscribed
-            // to.
-            if unsubscribe.channels.is_empty() {
-                unsubscribe.channels = subscriptions
-                    .keys()
-                    .map(|channel_name| channel_name.to_string())
-                    .collect();
-            }
-
-            for channel_name in unsubscribe.channels {
-                subscriptions.remove(&channel_name);
-
-                let response = make_unsubscribe_frame(channel_name, subscriptions.len());
-                dst.write_frame(&response).await?;
-            }
+        Command::Psubscribe(psubscribe) => {
+            subscribe_to.extend(psubscribe.patterns.into_iter().map(Subscription::Pattern));
+        }
+        Command::Unsubscribe(unsubscribe) => {
+            unsubscribe.unsubscribe(subscriptions, dst).await?;
+        }
+        Command::Punsubscribe(punsubscribe) => {
+            punsubscribe.unsubscribe(subscriptions, dst).await?;
+        }
+        Command::Reset(reset) => {
+            // `RESET` leaves the subscribed state. The subscriptions are
+            // dropped by the caller, without confirming each one.
+            reset.apply(session, dst).await?;
+            return Ok(false);
         }
         command => {
             let cmd = Unknown::new(command.get_name());
             cmd.apply(dst).await?;
         }
     }
+    Ok(true)
+}
+
+/// Removes each of `to_remove` from `subscriptions`, confirming each removal
+/// to the client.
+///
+/// `to_remove` is empty when the client unsubscribes from all channels or
+/// patterns without holding any. Like Redis, a single confirmation with a nil
+/// name is sent then, using `kind`, so the client still gets a reply.
+async fn unsubscribe_from(
+    kind: &'static str,
+    to_remove: Vec<Subscription>,
+    subscriptions: &mut StreamMap<Subscription, Messages>,
+    dst: &mut Connection,
+) -> crate::Result<()> {
+    if to_remove.is_empty() {
+        let response = Frame::Array(vec![
+            Frame::Bulk(Bytes::from_static(kind.as_bytes())),
+            Frame::Null,
+            Frame::Integer(subscriptions.len() as i64),
+        ]);
+        dst.write_frame(&response).await?;
+    }
+
+    for subscription in to_remove {
+        subscriptions.remove(&subscription);
+
+        let response = make_unsubscribe_frame(subscription, subscriptions.len());
+        dst.write_frame(&response).await?;
+    }
+
     Ok(())
 }
 
 /// Creates the response to a subscribe request.
 ///
-/// All of these functions take the `channel_name` as a `String` instead of
-/// a `&str` since `Bytes::from` can reuse the allocation in the `String`, and
+/// All of these functions take the channel name as a `String` instead of a
+/// `&str` since `Bytes::from` can reuse the allocation in the `String`, and
 /// taking a `&str` would require copying the data. This allows the caller to
 /// decide whether to clone the channel name or not.
-fn make_subscribe_frame(channel_name: String, num_subs: usize) -> Frame {
+///
+/// `num_subs` counts channel and pattern subscriptions together, as Redis does.
+fn make_subscribe_frame(subscription: Subscription, num_subs: usize) -> Frame {
+    let (kind, name) = match subscription {
+        Subscription::Channel(channel_name) => (&b"subscribe"[..], channel_name),
+        Subscription::Pattern(pattern) => (&b"psubscribe"[..], pattern),
+    };
+
     let mut response = Frame::array();
-    response.push_bulk(Bytes::from_static(b"subscribe"));
-    response.push_bulk(Bytes::from(channel_name));
-    response.push_int(num_subs as u64);
+    response.push_bulk(Bytes::from_static(kind));
+    response.push_bulk(Bytes::from(name));
+    response.push_int(num_subs as i64);
     response
 }
 
 /// Creates the response to an unsubcribe request.
-fn make_unsubscribe_frame(channel_name: String, num_subs: usize) -> Frame {
+fn make_unsubscribe_frame(subscription: Subscription, num_subs: usize) -> Frame {
+    let (kind, name) = match subscription {
+        Subscription::Channel(channel_name) => (&b"unsubscribe"[..], channel_name),
+        Subscription::Pattern(pattern) => (&b"punsubscribe"[..], pattern),
+    };
+
     let mut response = Frame::array();
-    response.push_bulk(Bytes::from_static(b"unsubscribe"));
-    response.push_bulk(Bytes::from(channel_name));
-    response.push_int(num_subs as u64);
+    response.push_bulk(Bytes::from_static(kind));
+    response.push_bulk(Bytes::from(name));
+    response.push_int(num_subs as i64);
     response
 }
 
@@ -278,6 +516,17 @@ fn make_message_frame(channel_name: String, msg: Bytes) -> Frame {
     response
 }
 
+/// Creates a message informing the client about a new message on a channel
+/// matching a pattern that the client subscribes to.
+fn make_pmessage_frame(pattern: String, channel_name: String, msg: Bytes) -> Frame {
+    let mut response = Frame::array();
+    response.push_bulk(Bytes::from_static(b"pmessage"));
+    response.push_bulk(Bytes::from(pattern));
+    response.push_bulk(Bytes::from(channel_name));
+    response.push_bulk(msg);
+    response
+}
+
 impl Unsubscribe {
     /// Create a new `Unsubscribe` command with the given `channels`.
     pub(crate) fn new(channels: &[String]) -> Unsubscribe {
@@ -332,11 +581,44 @@ impl Unsubscribe {
         Ok(Unsubscribe { channels })
     }
 
+    /// Apply the `Unsubscribe` command outside of the subscribed state.
+    ///
+    /// The client holds no subscriptions, so each channel is confirmed with a
+    /// count of 0, as Redis does.
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        self.unsubscribe(&mut StreamMap::new(), dst).await
+    }
+
+    /// Removes the channels from `subscriptions`, confirming each removal to
+    /// the client.
+    async fn unsubscribe(
+        self,
+        subscriptions: &mut StreamMap<Subscription, Messages>,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        // If no channels are specified, this requests unsubscribing from
+        // **all** channels. Pattern subscriptions are left alone.
+        let channels = if self.channels.is_empty() {
+            subscriptions
+                .keys()
+                .filter(|subscription| matches!(subscription, Subscription::Channel(_)))
+                .cloned()
+                .collect()
+        } else {
+            self.channels
+                .into_iter()
+                .map(Subscription::Channel)
+                .collect()
+        };
+
+        unsubscribe_from("unsubscribe", channels, subscriptions, dst).await
+    }
+
     /// Converts the command into an equivalent `Frame`.
     ///
     /// This is called by the client when encoding an `Unsubscribe` command to
     /// send to the server.
-    pub(crate) fn into_frame(self) -> Frame {
+    pub fn into_frame(self) -> Frame {
         let mut frame = Frame::array();
         frame.push_bulk(Bytes::from("unsubscribe".as_bytes()));
 
@@ -347,3 +629,66 @@ impl Unsubscribe {
         frame
     }
 }
+
+impl Punsubscribe {
+    /// Parse a `Punsubscribe` instance from a received frame.
+    ///
+    /// The `PUNSUBSCRIBE` string has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing at least one entry.
+    ///
+    /// ```text
+    /// PUNSUBSCRIBE [pattern [pattern ...]]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> Result<Punsubscribe, ParseError> {
+        use ParseError::EndOfStream;
+
+        // There may be no patterns listed, so start with an empty vec.
+        let mut patterns = vec![];
+
+        loop {
+            match parse.next_string() {
+                Ok(s) => patterns.push(s),
+                Err(EndOfStream) => break,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(Punsubscribe { patterns })
+    }
+
+    /// Apply the `Punsubscribe` command outside of the subscribed state.
+    ///
+    /// The client holds no subscriptions, so each pattern is confirmed with a
+    /// count of 0, as Redis does.
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        self.unsubscribe(&mut StreamMap::new(), dst).await
+    }
+
+    /// Removes the patterns from `subscriptions`, confirming each removal to
+    /// the client.
+    async fn unsubscribe(
+        self,
+        subscriptions: &mut StreamMap<Subscription, Messages>,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        // If no patterns are specified, this requests unsubscribing from
+        // **all** patterns. Channel subscriptions are left alone.
+        let patterns = if self.patterns.is_empty() {
+            subscriptions
+                .keys()
+                .filter(|subscription| matches!(subscription, Subscription::Pattern(_)))
+                .cloned()
+                .collect()
+        } else {
+            self.patterns
+                .into_iter()
+                .map(Subscription::Pattern)
+                .collect()
+        };
+
+        unsubscribe_from("punsubscribe", patterns, subscriptions, dst).await
+    }
+}
@@ -0,0 +1,75 @@
+use crate::{Connection, Frame, Parse};
+
+use bytes::Bytes;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, instrument};
+
+/// Return the current server time.
+///
+/// The reply is an array of two bulk strings: the Unix time in seconds, and
+/// the microseconds elapsed in the current second.
+#[derive(Debug, Default)]
+pub struct Time;
+
+impl Time {
+    /// Create a new `Time` command.
+    pub fn new() -> Time {
+        Time
+    }
+
+    /// Parse a `Time` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `TIME` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Time` value on success. If the frame is malformed, `Err`
+    /// is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing a single entry.
+    ///
+    /// ```text
+    /// TIME
+    /// ```
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Time> {
+        Ok(Time)
+    }
+
+    /// Apply the `Time` command.
+    ///
+    /// The response is written to `dst`. This is called by the server in order
+    /// to execute a received command.
+    #[instrument(skip(self, dst))]
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        // A clock set before 1970 is reported as the epoch.
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        let mut response = Frame::array();
+        response.push_bulk(Bytes::from(now.as_secs().to_string()));
+        response.push_bulk(Bytes::from(now.subsec_micros().to_string()));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Converts the command into an equivalent `Frame`.
+    ///
+    /// This is called by the client when encoding a `Time` command to send to
+    /// the server.
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("time".as_bytes()));
+        frame
+    }
+}
@@ -0,0 +1,156 @@
+use crate::{Connection, Db, Frame, Parse};
+
+use bytes::Bytes;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::Instant;
+use tracing::{debug, instrument};
+
+/// Returns when the value stored at a key expires.
+///
+/// This implements four closely related commands, which only differ in how
+/// the expiration is reported:
+///
+/// * TTL -- Remaining time to live, in seconds.
+/// * PTTL -- Remaining time to live, in milliseconds.
+/// * EXPIRETIME -- Absolute unix timestamp of the expiration, in seconds.
+/// * PEXPIRETIME -- Absolute unix timestamp of the expiration, in
+///   milliseconds.
+///
+/// All of them return `-2` if the key does not exist and `-1` if the key
+/// exists but has no associated expiration.
+#[derive(Debug)]
+pub struct Ttl {
+    /// Name of the key
+    key: String,
+
+    /// How the expiration is reported
+    kind: TtlKind,
+}
+
+/// The command implemented by a `Ttl` instance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TtlKind {
+    /// `TTL`, the remaining time in seconds
+    Ttl,
+    /// `PTTL`, the remaining time in milliseconds
+    Pttl,
+    /// `EXPIRETIME`, the unix timestamp in seconds
+    ExpireTime,
+    /// `PEXPIRETIME`, the unix timestamp in milliseconds
+    PexpireTime,
+}
+
+impl Ttl {
+    /// Create a new `Ttl` command which reports the expiration of `key` as
+    /// described by `kind`.
+    pub fn new(key: impl ToString, kind: TtlKind) -> Ttl {
+        Ttl {
+            key: key.to_string(),
+            kind,
+        }
+    }
+
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Parse a `Ttl` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The command name has already been consumed, and determines `kind`.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Ttl` value on success. If the frame is malformed, `Err` is
+    /// returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing two entries.
+    ///
+    /// ```text
+    /// TTL key
+    /// PTTL key
+    /// EXPIRETIME key
+    /// PEXPIRETIME key
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse, kind: TtlKind) -> crate::Result<Ttl> {
+        let key = parse.next_string()?;
+
+        Ok(Ttl { key, kind })
+    }
+
+    /// Apply the `Ttl` command to the specified `Db` instance.
+    ///
+    /// The response is written to `dst`. This is called by the server in order
+    /// to execute a received command.
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = match db.expires_at(&self.key) {
+            None => Frame::Integer(-2),
+            Some(None) => Frame::Integer(-1),
+            Some(Some(when)) => {
+                let remaining = when.saturating_duration_since(Instant::now());
+                Frame::Integer(self.kind.report(remaining))
+            }
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Returns the command name
+    pub(crate) fn get_name(&self) -> &'static str {
+        match self.kind {
+            TtlKind::Ttl => "ttl",
+            TtlKind::Pttl => "pttl",
+            TtlKind::ExpireTime => "expiretime",
+            TtlKind::PexpireTime => "pexpiretime",
+        }
+    }
+
+    /// Converts the command into an equivalent `Frame`.
+    ///
+    /// This is called by the client when encoding a `Ttl` command to send to
+    /// the server.
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from(self.get_name().as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame
+    }
+}
+
+impl TtlKind {
+    /// Converts the time remaining until a key expires into the value
+    /// reported by the command.
+    fn report(self, remaining: Duration) -> i64 {
+        match self {
+            // Like Redis, the remaining seconds are rounded to the nearest
+            // second.
+            TtlKind::Ttl => ((remaining.as_millis() + 500) / 1000) as i64,
+            TtlKind::Pttl => remaining.as_millis() as i64,
+            TtlKind::ExpireTime => unix_time(remaining).as_secs() as i64,
+            TtlKind::PexpireTime => unix_time(remaining).as_millis() as i64,
+        }
+    }
+}
+
+/// Returns the wall-clock time, `remaining` from now, as a duration since the
+/// unix epoch.
+///
+/// Expirations are tracked using a monotonic `Instant`, which has no relation
+/// to the wall clock, so the time remaining is added to the current system
+/// time instead.
+fn unix_time(remaining: Duration) -> Duration {
+    (SystemTime::now() + remaining)
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
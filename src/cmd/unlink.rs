@@ -0,0 +1,82 @@
+use crate::cmd::ParseError;
+use crate::{Connection, Db, Frame, Parse};
+
+use tracing::{debug, instrument};
+
+/// Removes the specified keys. A key is ignored if it does not exist.
+///
+/// This is the same as `DEL`, except that the memory used by the removed
+/// values is freed in the background. The keys themselves are removed
+/// immediately, so they are no longer visible once the response is sent.
+///
+/// The response is the number of keys that were removed.
+#[derive(Debug)]
+pub struct Unlink {
+    /// Names of the keys to remove
+    keys: Vec<String>,
+}
+
+impl Unlink {
+    /// Create a new `Unlink` command which removes `keys`.
+    pub fn new(keys: Vec<String>) -> Unlink {
+        Unlink { keys }
+    }
+
+    /// Get the keys
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    /// Parse a `Unlink` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `UNLINK` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Unlink` value on success. If the frame is malformed, `Err` is
+    /// returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing two or more entries.
+    ///
+    /// ```text
+    /// UNLINK key [key ...]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Unlink> {
+        use ParseError::EndOfStream;
+
+        // At least one key is required.
+        let mut keys = vec![parse.next_string()?];
+
+        loop {
+            match parse.next_string() {
+                Ok(key) => keys.push(key),
+                Err(EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(Unlink { keys })
+    }
+
+    /// Apply the `Unlink` command to the specified `Db` instance.
+    ///
+    /// The response is written to `dst`. This is called by the server in order
+    /// to execute a received command.
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let removed = db.unlink(&self.keys);
+        let response = Frame::Integer(removed as i64);
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+use crate::{Connection, Frame, Parse};
+
+use tracing::{debug, instrument};
+
+/// Wait for previous writes to be acknowledged by replicas.
+///
+/// mini-redis replicas do not acknowledge the commands they apply, so there is
+/// nothing to wait for. `WAIT` replies immediately with `0`, the number of
+/// replicas that acknowledged the writes, so clients relying on it keep
+/// working.
+#[derive(Debug)]
+pub struct Wait {
+    /// Number of replicas to wait for
+    numreplicas: u64,
+
+    /// Maximum time to wait, in milliseconds. `0` waits forever.
+    timeout: u64,
+}
+
+impl Wait {
+    /// Create a new `Wait` command which waits for `numreplicas` replicas, for
+    /// at most `timeout` milliseconds.
+    pub fn new(numreplicas: u64, timeout: u64) -> Wait {
+        Wait {
+            numreplicas,
+            timeout,
+        }
+    }
+
+    /// Get the number of replicas to wait for
+    pub fn numreplicas(&self) -> u64 {
+        self.numreplicas
+    }
+
+    /// Get the timeout, in milliseconds
+    pub fn timeout(&self) -> u64 {
+        self.timeout
+    }
+
+    /// Parse a `Wait` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `WAIT` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Wait` value on success. If the frame is malformed, `Err`
+    /// is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing three entries.
+    ///
+    /// ```text
+    /// WAIT numreplicas timeout
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Wait> {
+        let numreplicas = parse.next_int()?;
+        let timeout = parse.next_int()?;
+
+        Ok(Wait {
+            numreplicas,
+            timeout,
+        })
+    }
+
+    /// Apply the `Wait` command.
+    ///
+    /// The response is written to `dst`. This is called by the server in order
+    /// to execute a received command.
+    #[instrument(skip(self, dst))]
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        // There are no replicas, so none can acknowledge the writes. Waiting
+        // would not change that, so the reply is sent right away.
+        let response = Frame::Integer(0);
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+use crate::{Connection, Db, Frame, Parse, ParseError, Session};
+
+use tracing::{debug, instrument};
+
+/// Watch keys for changes, making the next `EXEC` conditional.
+///
+/// If any of the watched keys is modified, by this connection or another one,
+/// before `EXEC`, the transaction is not applied and `EXEC` returns `nil`.
+/// Keys stop being watched once `EXEC` or `DISCARD` is called.
+#[derive(Debug)]
+pub struct Watch {
+    /// Keys to watch
+    keys: Vec<String>,
+}
+
+/// Stop watching every key watched with `WATCH`.
+#[derive(Debug, Default)]
+pub struct Unwatch;
+
+impl Watch {
+    /// Create a new `Watch` command which watches `keys`.
+    pub fn new(keys: Vec<String>) -> Watch {
+        Watch { keys }
+    }
+
+    /// Parse a `Watch` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `WATCH` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// On success, the `Watch` value is returned. If the frame is malformed,
+    /// `Err` is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing at least two entries.
+    ///
+    /// ```text
+    /// WATCH key [key ...]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Watch> {
+        let mut keys = vec![parse.next_string()?];
+
+        loop {
+            match parse.next_string() {
+                Ok(key) => keys.push(key),
+                Err(ParseError::EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(Watch { keys })
+    }
+
+    /// Apply the `Watch` command, recording the current version of each key
+    /// in the connection's session.
+    ///
+    /// The response is written to `dst`.
+    #[instrument(skip(self, db, session, dst))]
+    pub(crate) async fn apply(
+        self,
+        db: &Db,
+        session: &mut Session,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        // Keys are watched before `MULTI`, so the values read to build the
+        // transaction are the ones checked by `EXEC`.
+        let response = if session.transaction().is_some() {
+            Frame::Error("ERR WATCH inside MULTI is not allowed".to_string())
+        } else {
+            for key in self.keys {
+                let version = db.key_version(&key);
+                session.watch(key, version);
+            }
+
+            Frame::Simple("OK".to_string())
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
+
+impl Unwatch {
+    /// Create a new `Unwatch` command.
+    pub fn new() -> Unwatch {
+        Unwatch
+    }
+
+    /// Parse an `Unwatch` instance from a received frame.
+    ///
+    /// The `UNWATCH` string has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing a single entry.
+    ///
+    /// ```text
+    /// UNWATCH
+    /// ```
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Unwatch> {
+        Ok(Unwatch)
+    }
+
+    /// Apply the `Unwatch` command, forgetting the watched keys.
+    ///
+    /// The response is written to `dst`.
+    #[instrument(skip(self, session, dst))]
+    pub(crate) async fn apply(
+        self,
+        session: &mut Session,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        session.unwatch();
+
+        let response = Frame::Simple("OK".to_string());
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,126 @@
+use crate::cmd::ParseError;
+use crate::{Connection, Db, Frame, Parse};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// Add members, with their scores, to the sorted set stored at `key`.
+///
+/// If a member is already in the set, its score is updated. A missing key is
+/// created as an empty sorted set first. An error is returned if the key holds
+/// a value of another type.
+///
+/// The response is the number of members that were added, not counting
+/// members whose score was updated.
+#[derive(Debug)]
+pub struct Zadd {
+    /// Name of the sorted set
+    key: String,
+
+    /// Members to add, with their scores
+    members: Vec<(f64, String)>,
+}
+
+impl Zadd {
+    /// Create a new `Zadd` command which adds `members` to the sorted set
+    /// `key`.
+    pub fn new(key: impl ToString, members: Vec<(f64, String)>) -> Zadd {
+        Zadd {
+            key: key.to_string(),
+            members,
+        }
+    }
+
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Get the members, with their scores
+    pub fn members(&self) -> &[(f64, String)] {
+        &self.members
+    }
+
+    /// Parse a `Zadd` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `ZADD` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Zadd` value on success. If the frame is malformed, `Err` is
+    /// returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing four or more entries.
+    ///
+    /// ```text
+    /// ZADD key score member [score member ...]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Zadd> {
+        use ParseError::EndOfStream;
+
+        let key = parse.next_string()?;
+
+        // At least one member is required.
+        let mut members = vec![(parse_score(&parse.next_string()?)?, parse.next_string()?)];
+
+        loop {
+            match parse.next_string() {
+                Ok(score) => members.push((parse_score(&score)?, parse.next_string()?)),
+                Err(EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(Zadd { key, members })
+    }
+
+    /// Apply the `Zadd` command to the specified `Db` instance.
+    ///
+    /// The response is written to `dst`. This is called by the server in order
+    /// to execute a received command.
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = match db.zadd(&self.key, self.members) {
+            Ok(added) => Frame::Integer(added as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Converts the command into an equivalent `Frame`.
+    ///
+    /// This is called by the client when encoding a `Zadd` command to send to
+    /// the server.
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("zadd".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+
+        for (score, member) in self.members {
+            frame.push_bulk(Bytes::from(score.to_string()));
+            frame.push_bulk(Bytes::from(member.into_bytes()));
+        }
+
+        frame
+    }
+}
+
+/// Parses a score. Like Redis, `inf` and `-inf` are accepted, but NaN is not.
+fn parse_score(score: &str) -> crate::Result<f64> {
+    score
+        .parse::<f64>()
+        .ok()
+        .filter(|score| !score.is_nan())
+        .ok_or_else(|| "ERR value is not a valid float".into())
+}
@@ -0,0 +1,159 @@
+use crate::cmd::ParseError;
+use crate::{Connection, Db, Frame, Parse};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// Get the members ranked between `start` and `stop`, both inclusive, in the
+/// sorted set stored at `key`.
+///
+/// Members are ordered by ascending score, and members sharing a score are
+/// ordered lexicographically. Negative ranks count from the end of the set,
+/// so `-1` is the member with the highest score.
+///
+/// # Options
+///
+/// * WITHSCORES -- Reply with each member's score following the member.
+#[derive(Debug)]
+pub struct Zrange {
+    /// Name of the sorted set
+    key: String,
+
+    /// Rank of the first member to return
+    start: i64,
+
+    /// Rank of the last member to return
+    stop: i64,
+
+    /// Whether scores are returned along with the members
+    with_scores: bool,
+}
+
+impl Zrange {
+    /// Create a new `Zrange` command which fetches the members of the sorted
+    /// set `key` ranked between `start` and `stop`.
+    pub fn new(key: impl ToString, start: i64, stop: i64) -> Zrange {
+        Zrange {
+            key: key.to_string(),
+            start,
+            stop,
+            with_scores: false,
+        }
+    }
+
+    /// Request the score of each member as well.
+    pub fn with_scores(mut self) -> Zrange {
+        self.with_scores = true;
+        self
+    }
+
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Get the start rank
+    pub fn start(&self) -> i64 {
+        self.start
+    }
+
+    /// Get the stop rank
+    pub fn stop(&self) -> i64 {
+        self.stop
+    }
+
+    /// Returns `true` if scores are requested
+    pub fn scores(&self) -> bool {
+        self.with_scores
+    }
+
+    /// Parse a `Zrange` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `ZRANGE` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Zrange` value on success. If the frame is malformed, `Err`
+    /// is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing four or five entries.
+    ///
+    /// ```text
+    /// ZRANGE key start stop [WITHSCORES]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Zrange> {
+        use ParseError::EndOfStream;
+
+        let key = parse.next_string()?;
+        let start = parse.next_signed_int()?;
+        let stop = parse.next_signed_int()?;
+
+        let with_scores = match parse.next_string() {
+            Ok(s) if s.to_uppercase() == "WITHSCORES" => true,
+            Ok(_) => return Err("currently `ZRANGE` only supports the WITHSCORES option".into()),
+            Err(EndOfStream) => false,
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Zrange {
+            key,
+            start,
+            stop,
+            with_scores,
+        })
+    }
+
+    /// Apply the `Zrange` command to the specified `Db` instance.
+    ///
+    /// The response is written to `dst`. This is called by the server in order
+    /// to execute a received command.
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = match db.zrange(&self.key, self.start, self.stop) {
+            Ok(members) => {
+                let mut response = Frame::array();
+
+                for (member, score) in members {
+                    response.push_bulk(Bytes::from(member));
+
+                    if self.with_scores {
+                        response.push_bulk(Bytes::from(score.to_string()));
+                    }
+                }
+
+                response
+            }
+            Err(err) => Frame::Error(err.to_string()),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Converts the command into an equivalent `Frame`.
+    ///
+    /// This is called by the client when encoding a `Zrange` command to send
+    /// to the server.
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("zrange".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_int(self.start);
+        frame.push_int(self.stop);
+
+        if self.with_scores {
+            frame.push_bulk(Bytes::from("withscores".as_bytes()));
+        }
+
+        frame
+    }
+}
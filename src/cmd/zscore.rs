@@ -0,0 +1,97 @@
+use crate::{Connection, Db, Frame, Parse};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// Get the score of `member` in the sorted set stored at `key`.
+///
+/// If the key or the member does not exist, the special value nil is
+/// returned. An error is returned if the key holds a value of another type.
+#[derive(Debug)]
+pub struct Zscore {
+    /// Name of the sorted set
+    key: String,
+
+    /// Member whose score is returned
+    member: String,
+}
+
+impl Zscore {
+    /// Create a new `Zscore` command which fetches the score of `member` in
+    /// the sorted set `key`.
+    pub fn new(key: impl ToString, member: impl ToString) -> Zscore {
+        Zscore {
+            key: key.to_string(),
+            member: member.to_string(),
+        }
+    }
+
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Get the member
+    pub fn member(&self) -> &str {
+        &self.member
+    }
+
+    /// Parse a `Zscore` instance from a received frame.
+    ///
+    /// The `Parse` argument provides a cursor-like API to read fields from the
+    /// `Frame`. At this point, the entire frame has already been received from
+    /// the socket.
+    ///
+    /// The `ZSCORE` string has already been consumed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Zscore` value on success. If the frame is malformed, `Err`
+    /// is returned.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing three entries.
+    ///
+    /// ```text
+    /// ZSCORE key member
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Zscore> {
+        let key = parse.next_string()?;
+        let member = parse.next_string()?;
+
+        Ok(Zscore { key, member })
+    }
+
+    /// Apply the `Zscore` command to the specified `Db` instance.
+    ///
+    /// The response is written to `dst`. This is called by the server in order
+    /// to execute a received command.
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        // Scores are sent as bulk strings, formatted like `INCRBYFLOAT` does.
+        let response = match db.zscore(&self.key, &self.member) {
+            Ok(Some(score)) => Frame::Bulk(Bytes::from(score.to_string())),
+            Ok(None) => Frame::Null,
+            Err(err) => Frame::Error(err.to_string()),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Converts the command into an equivalent `Frame`.
+    ///
+    /// This is called by the client when encoding a `Zscore` command to send
+    /// to the server.
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("zscore".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_bulk(Bytes::from(self.member.into_bytes()));
+        frame
+    }
+}
@@ -0,0 +1,97 @@
+//! RESP framing for `tokio_util::codec`.
+//!
+//! `Connection` is the simplest way to exchange frames with a peer, but it owns
+//! its socket and reads one frame at a time. `FrameCodec` exposes the same
+//! framing as a `Decoder` and `Encoder`, so frames can be read and written
+//! through `FramedRead`, `FramedWrite` or `Framed`. These implement `Stream`
+//! and `Sink`, which allows combining several connections with the usual
+//! stream utilities, such as selecting over them or forwarding one into
+//! another.
+
+use crate::connection::reserve_for_frame;
+use crate::frame::{self, Frame, FrameChecker};
+
+use bytes::BytesMut;
+use tokio_util::codec::{Decoder, Encoder, Framed};
+
+/// A stream of frames read from, and a sink of frames written to, a byte
+/// stream `T`.
+///
+/// Yields `crate::Result<Frame>` values and accepts `Frame` values.
+pub type FrameStream<T> = Framed<T, FrameCodec>;
+
+/// Decodes and encodes Redis protocol frames.
+///
+/// Decoding checks and parses frames exactly like `Connection::read_frame`,
+/// and encoding uses `Frame::encode`.
+///
+/// # Examples
+///
+/// ```
+/// use mini_redis::codec::FrameCodec;
+/// use mini_redis::Frame;
+/// use tokio_stream::StreamExt;
+/// use tokio_util::codec::FramedRead;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let input: &[u8] = b"+OK\r\n:42\r\n";
+///     let mut frames = FramedRead::new(input, FrameCodec::new());
+///
+///     assert_eq!(frames.next().await.unwrap().unwrap(), Frame::Simple("OK".into()));
+///     assert_eq!(frames.next().await.unwrap().unwrap(), Frame::Integer(42));
+///     assert!(frames.next().await.is_none());
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FrameCodec {
+    /// Remembers how much of a partially received frame has been checked, so
+    /// it is not checked again after every read.
+    checker: FrameChecker,
+}
+
+impl FrameCodec {
+    /// Create a new `FrameCodec`.
+    pub fn new() -> FrameCodec {
+        FrameCodec::default()
+    }
+}
+
+impl Decoder for FrameCodec {
+    type Item = Frame;
+    type Error = crate::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> crate::Result<Option<Frame>> {
+        // Large bulk strings share the allocation of `src`, see
+        // `Frame::split_from`.
+        match self.checker.split_from(src) {
+            Ok(frame) => Ok(Some(frame)),
+            Err(frame::Error::Incomplete) => {
+                // As in `Connection`, reserve room for the rest of the frame,
+                // without trusting the peer's declared lengths too far.
+                reserve_for_frame(src, self.checker.required());
+
+                Ok(None)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+impl Encoder<Frame> for FrameCodec {
+    type Error = crate::Error;
+
+    fn encode(&mut self, frame: Frame, dst: &mut BytesMut) -> crate::Result<()> {
+        frame.encode(dst);
+        Ok(())
+    }
+}
+
+impl Encoder<&Frame> for FrameCodec {
+    type Error = crate::Error;
+
+    fn encode(&mut self, frame: &Frame, dst: &mut BytesMut) -> crate::Result<()> {
+        frame.encode(dst);
+        Ok(())
+    }
+}
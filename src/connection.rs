@@ -1,10 +1,54 @@
-use crate::frame::{self, Frame};
+use crate::frame::{self, Frame, FrameChecker};
 
-use bytes::{Buf, BytesMut};
-use std::io::{self, Cursor};
-use tokio::io::{AsyncReadExt, AsyncWriteExt, BufWriter};
+use bytes::BytesMut;
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufWriter, ReadBuf};
 use tokio::net::TcpStream;
 
+/// The most read buffer capacity reserved ahead of the data received for a
+/// partially received frame, until more than this has been received.
+const MAX_RESERVE: usize = 64 * 1024;
+
+/// A byte stream a `Connection` can read frames from and write frames to.
+///
+/// This is usually a `TcpStream`. Other streams are used where frames do not
+/// travel over the network, such as when replaying the append-only file.
+pub(crate) trait Stream: AsyncRead + AsyncWrite + Unpin + Send + fmt::Debug {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send + fmt::Debug> Stream for T {}
+
+/// Options applied to the TCP socket of a connection.
+///
+/// By default, both options are disabled, leaving the operating system
+/// defaults in place.
+///
+/// # Examples
+///
+/// ```
+/// use mini_redis::SocketOptions;
+/// use std::time::Duration;
+///
+/// let options = SocketOptions::new()
+///     .nodelay(true)
+///     .keepalive(Duration::from_secs(300));
+/// # drop(options);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SocketOptions {
+    /// Whether `TCP_NODELAY` is set
+    nodelay: bool,
+
+    /// Idle time before keepalive probes are sent. If `None`, keepalive is
+    /// left as configured by the operating system.
+    keepalive: Option<Duration>,
+}
+
 /// Send and receive `Frame` values from a remote peer.
 ///
 /// When implementing networking protocols, a message on that protocol is
@@ -19,27 +63,127 @@ use tokio::net::TcpStream;
 /// The contents of the write buffer are then written to the socket.
 #[derive(Debug)]
 pub struct Connection {
-    // The `TcpStream`. It is decorated with a `BufWriter`, which provides write
-    // level buffering. The `BufWriter` implementation provided by Tokio is
-    // sufficient for our needs.
-    stream: BufWriter<TcpStream>,
+    // The `TcpStream`, or another `Stream`. It is decorated with a `BufWriter`,
+    // which provides write level buffering. The `BufWriter` implementation
+    // provided by Tokio is sufficient for our needs.
+    stream: BufWriter<Box<dyn Stream>>,
 
     // The buffer for reading frames.
     buffer: BytesMut,
+
+    // Checks whether the read buffer holds a whole frame, without rescanning
+    // the part of the frame already checked after every read.
+    checker: FrameChecker,
+
+    // The buffer frames are encoded into before being written.
+    write_buffer: BytesMut,
+
+    // Number of bytes read from and written to the stream.
+    bytes_read: u64,
+    bytes_written: u64,
+
+    // Server wide byte counters, also updated on every read and write.
+    net_stats: Option<Arc<NetStats>>,
+}
+
+/// Number of bytes read and written by every connection of a server, as
+/// reported by `INFO stats`.
+#[derive(Debug, Default)]
+pub(crate) struct NetStats {
+    input: AtomicU64,
+    output: AtomicU64,
 }
 
 impl Connection {
     /// Create a new `Connection`, backed by `socket`. Read and write buffers
     /// are initialized.
     pub fn new(socket: TcpStream) -> Connection {
+        Connection::from_stream(socket)
+    }
+
+    /// Create a new `Connection` backed by any `Stream`.
+    pub(crate) fn from_stream(stream: impl Stream + 'static) -> Connection {
         Connection {
-            stream: BufWriter::new(socket),
+            stream: BufWriter::new(Box::new(stream)),
             // Default to a 4KB read buffer. For the use case of mini redis,
             // this is fine. However, real applications will want to tune this
             // value to their specific use case. There is a high likelihood that
             // a larger read buffer will work better.
             buffer: BytesMut::with_capacity(4 * 1024),
+            checker: FrameChecker::new(),
+            write_buffer: BytesMut::new(),
+            bytes_read: 0,
+            bytes_written: 0,
+            net_stats: None,
+        }
+    }
+
+    /// Add the bytes read and written by the connection to `net_stats`, in
+    /// addition to its own counters.
+    pub(crate) fn with_net_stats(mut self, net_stats: Arc<NetStats>) -> Connection {
+        self.net_stats = Some(net_stats);
+        self
+    }
+
+    /// Returns the number of bytes read from the stream so far, including
+    /// data buffered but not yet parsed into a frame.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// Returns the number of bytes written to the stream so far.
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// Create a new `Connection` with nothing to read, which discards every
+    /// frame written to it.
+    ///
+    /// This is used to apply commands on behalf of a client that has no
+    /// interest in the responses, such as when replaying the append-only file.
+    pub(crate) fn discard() -> Connection {
+        Connection::from_stream(Discard)
+    }
+
+    /// Create a new `Connection` reading back the frames written to it.
+    ///
+    /// This is used to collect the responses of commands applied on behalf of
+    /// a client, such as the commands of a transaction.
+    pub(crate) fn loopback() -> Connection {
+        // The frames are already encoded into `write_buffer`, and the stream
+        // is a buffer itself, so the `BufWriter` does not buffer anything.
+        // Neither buffer is allocated until something is written.
+        Connection {
+            stream: BufWriter::with_capacity(0, Box::new(Loopback::default())),
+            buffer: BytesMut::new(),
+            checker: FrameChecker::new(),
+            write_buffer: BytesMut::new(),
+            bytes_read: 0,
+            bytes_written: 0,
+            net_stats: None,
+        }
+    }
+
+    /// Write the frames written to `loopback`, a `Connection` created with
+    /// `loopback`, to the underlying stream.
+    ///
+    /// The frames are copied as they were encoded, rather than read back and
+    /// encoded again, and flushed together.
+    pub(crate) async fn forward(&mut self, loopback: &mut Connection) -> io::Result<()> {
+        // Reading from a loopback never waits, it returns `0` once every
+        // byte written to it has been read.
+        while loopback.stream.read_buf(&mut loopback.buffer).await? != 0 {}
+
+        if loopback.buffer.is_empty() {
+            return Ok(());
         }
+
+        self.write_buffer.clear();
+        self.write_buffer.extend_from_slice(&loopback.buffer);
+        loopback.buffer.clear();
+
+        self.write_buffered().await?;
+        self.stream.flush().await
     }
 
     /// Read a single `Frame` value from the underlying stream.
@@ -61,12 +205,20 @@ impl Connection {
                 return Ok(Some(frame));
             }
 
-            // There is not enough buffered data to read a frame. Attempt to
-            // read more data from the socket.
+            // There is not enough buffered data to read a frame. If the frame
+            // is waiting on a bulk string whose length is already known, make
+            // room for the rest of it now. Otherwise, `read_buf` only grows the
+            // buffer a little at a time and a large value takes many small
+            // reads to arrive.
+            reserve_for_frame(&mut self.buffer, self.checker.required());
+
+            // Attempt to read more data from the socket.
             //
             // On success, the number of bytes is returned. `0` indicates "end
             // of stream".
-            if 0 == self.stream.read_buf(&mut self.buffer).await? {
+            let n = self.stream.read_buf(&mut self.buffer).await?;
+
+            if n == 0 {
                 // The remote closed the connection. For this to be a clean
                 // shutdown, there should be no data in the read buffer. If
                 // there is, this means that the peer closed the socket while
@@ -74,9 +226,19 @@ impl Connection {
                 if self.buffer.is_empty() {
                     return Ok(None);
                 } else {
-                    return Err("connection reset by peer".into());
+                    return Err(io::Error::new(
+                        io::ErrorKind::ConnectionReset,
+                        "connection reset by peer",
+                    )
+                    .into());
                 }
             }
+
+            self.bytes_read += n as u64;
+
+            if let Some(net_stats) = &self.net_stats {
+                net_stats.input.fetch_add(n as u64, Ordering::Relaxed);
+            }
         }
     }
 
@@ -87,49 +249,23 @@ impl Connection {
     fn parse_frame(&mut self) -> crate::Result<Option<Frame>> {
         use frame::Error::Incomplete;
 
-        // Cursor is used to track the "current" location in the
-        // buffer. Cursor also implements `Buf` from the `bytes` crate
-        // which provides a number of helpful utilities for working
-        // with bytes.
-        let mut buf = Cursor::new(&self.buffer[..]);
-
-        // The first step is to check if enough data has been buffered to parse
-        // a single frame. This step is usually much faster than doing a full
-        // parse of the frame, and allows us to skip allocating data structures
-        // to hold the frame data unless we know the full frame has been
-        // received.
-        match Frame::check(&mut buf) {
-            Ok(_) => {
-                // The `check` function will have advanced the cursor until the
-                // end of the frame. Since the cursor had position set to zero
-                // before `Frame::check` was called, we obtain the length of the
-                // frame by checking the cursor position.
-                let len = buf.position() as usize;
-
-                // Reset the position to zero before passing the cursor to
-                // `Frame::parse`.
-                buf.set_position(0);
-
-                // Parse the frame from the buffer. This allocates the necessary
-                // structures to represent the frame and returns the frame
-                // value.
-                //
-                // If the encoded frame representation is invalid, an error is
-                // returned. This should terminate the **current** connection
-                // but should not impact any other connected client.
-                let frame = Frame::parse(&mut buf)?;
-
-                // Discard the parsed data from the read buffer.
-                //
-                // When `advance` is called on the read buffer, all of the data
-                // up to `len` is discarded. The details of how this works is
-                // left to `BytesMut`. This is often done by moving an internal
-                // cursor, but it may be done by reallocating and copying data.
-                self.buffer.advance(len);
-
-                // Return the parsed frame to the caller.
-                Ok(Some(frame))
-            }
+        // The checker first checks if enough data has been buffered to parse
+        // a single frame, which is much faster than a full parse. It resumes
+        // from where the previous check stopped, so a frame received in many
+        // small reads is only checked once. Only once the full frame has been
+        // received are the data structures to hold the frame data allocated.
+        //
+        // The frame's bytes are then split off the read buffer. Large bulk
+        // strings are not copied, the `Bytes` in the frame point into the
+        // read buffer's allocation. `BytesMut` allocates a new buffer for
+        // the following reads once it cannot reuse the shared one.
+        //
+        // If the encoded frame representation is invalid, an error is
+        // returned. This should terminate the **current** connection but
+        // should not impact any other connected client.
+        match self.checker.split_from(&mut self.buffer) {
+            // Return the parsed frame to the caller.
+            Ok(frame) => Ok(Some(frame)),
             // There is not enough data present in the read buffer to parse a
             // single frame. We must wait for more data to be received from the
             // socket. Reading from the socket will be done in the statement
@@ -147,90 +283,199 @@ impl Connection {
 
     /// Write a single `Frame` value to the underlying stream.
     ///
-    /// The `Frame` value is written to the socket using the various `write_*`
-    /// functions provided by `AsyncWrite`. Calling these functions directly on
-    /// a `TcpStream` is **not** advised, as this will result in a large number of
-    /// syscalls. However, it is fine to call these functions on a *buffered*
-    /// write stream. The data will be written to the buffer. Once the buffer is
-    /// full, it is flushed to the underlying socket.
+    /// The `Frame` value is first encoded into the write buffer using
+    /// `Frame::encode`. The encoded bytes are then written to the socket with a
+    /// single `write_all` call, followed by a `flush`. Encoding up front means
+    /// the frame is handed to the socket in one piece instead of issuing a
+    /// write per field.
     pub async fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
-        // Arrays are encoded by encoding each entry. All other frame types are
-        // considered literals. For now, mini-redis is not able to encode
-        // recursive frame structures. See below for more details.
-        match frame {
-            Frame::Array(val) => {
-                // Encode the frame type prefix. For an array, it is `*`.
-                self.stream.write_u8(b'*').await?;
-
-                // Encode the length of the array.
-                self.write_decimal(val.len() as u64).await?;
-
-                // Iterate and encode each entry in the array.
-                for entry in &**val {
-                    self.write_value(entry).await?;
-                }
-            }
-            // The frame type is a literal. Encode the value directly.
-            _ => self.write_value(frame).await?,
+        // Encode the frame into the write buffer. The buffer is reused between
+        // calls so its allocation is amortized.
+        self.write_buffer.clear();
+        frame.encode(&mut self.write_buffer);
+
+        self.write_buffered().await?;
+
+        // Ensure the encoded frame is written to the socket. Calling `flush`
+        // writes the remaining contents of the buffered stream to the socket.
+        self.stream.flush().await
+    }
+
+    /// Write several `Frame` values to the underlying stream.
+    ///
+    /// This is the same as calling `write_frame` for each frame, except that
+    /// all frames are encoded up front and handed to the socket together. This
+    /// is used to pipeline requests, amortizing the cost of the writes.
+    pub async fn write_frames(&mut self, frames: &[Frame]) -> io::Result<()> {
+        self.write_buffer.clear();
+
+        for frame in frames {
+            frame.encode(&mut self.write_buffer);
         }
 
-        // Ensure the encoded frame is written to the socket. The calls above
-        // are to the buffered stream and writes. Calling `flush` writes the
-        // remaining contents of the buffer to the socket.
+        self.write_buffered().await?;
         self.stream.flush().await
     }
 
-    /// Write a frame literal to the stream
-    async fn write_value(&mut self, frame: &Frame) -> io::Result<()> {
-        match frame {
-            Frame::Simple(val) => {
-                self.stream.write_u8(b'+').await?;
-                self.stream.write_all(val.as_bytes()).await?;
-                self.stream.write_all(b"\r\n").await?;
-            }
-            Frame::Error(val) => {
-                self.stream.write_u8(b'-').await?;
-                self.stream.write_all(val.as_bytes()).await?;
-                self.stream.write_all(b"\r\n").await?;
-            }
-            Frame::Integer(val) => {
-                self.stream.write_u8(b':').await?;
-                self.write_decimal(*val).await?;
-            }
-            Frame::Null => {
-                self.stream.write_all(b"$-1\r\n").await?;
-            }
-            Frame::Bulk(val) => {
-                let len = val.len();
+    /// Flush anything written and shut down the write half of the stream, so
+    /// the peer reads to the end of the stream once it received every frame.
+    pub async fn shutdown(&mut self) -> io::Result<()> {
+        // `BufWriter` flushes its buffer before shutting down the stream.
+        self.stream.shutdown().await
+    }
 
-                self.stream.write_u8(b'$').await?;
-                self.write_decimal(len as u64).await?;
-                self.stream.write_all(val).await?;
-                self.stream.write_all(b"\r\n").await?;
-            }
-            // Encoding an `Array` from within a value cannot be done using a
-            // recursive strategy. In general, async fns do not support
-            // recursion. Mini-redis has not needed to encode nested arrays yet,
-            // so for now it is skipped.
-            Frame::Array(_val) => unreachable!(),
+    /// Writes the contents of the write buffer to the stream and counts the
+    /// bytes written.
+    async fn write_buffered(&mut self) -> io::Result<()> {
+        self.stream.write_all(&self.write_buffer).await?;
+
+        let n = self.write_buffer.len() as u64;
+        self.bytes_written += n;
+
+        if let Some(net_stats) = &self.net_stats {
+            net_stats.output.fetch_add(n, Ordering::Relaxed);
         }
 
         Ok(())
     }
+}
 
-    /// Write a decimal frame to the stream
-    async fn write_decimal(&mut self, val: u64) -> io::Result<()> {
-        use std::io::Write;
+impl NetStats {
+    /// Returns the total number of bytes read.
+    pub(crate) fn input(&self) -> u64 {
+        self.input.load(Ordering::Relaxed)
+    }
 
-        // Convert the value to a string
-        let mut buf = [0u8; 20];
-        let mut buf = Cursor::new(&mut buf[..]);
-        write!(&mut buf, "{}", val)?;
+    /// Returns the total number of bytes written.
+    pub(crate) fn output(&self) -> u64 {
+        self.output.load(Ordering::Relaxed)
+    }
+}
 
-        let pos = buf.position() as usize;
-        self.stream.write_all(&buf.get_ref()[..pos]).await?;
-        self.stream.write_all(b"\r\n").await?;
+impl SocketOptions {
+    /// Create `SocketOptions` leaving every option to the operating system
+    /// defaults.
+    pub fn new() -> SocketOptions {
+        SocketOptions::default()
+    }
+
+    /// Set `TCP_NODELAY`, which disables Nagle's algorithm.
+    ///
+    /// Every frame is flushed as soon as it is written, so Nagle's algorithm
+    /// mostly delays small requests and responses while waiting for the
+    /// previous ones to be acknowledged.
+    pub fn nodelay(mut self, nodelay: bool) -> SocketOptions {
+        self.nodelay = nodelay;
+        self
+    }
+
+    /// Enable TCP keepalive, sending probes once the connection has been idle
+    /// for `time`.
+    ///
+    /// This detects peers that went away without closing the connection, such
+    /// as a host that lost power.
+    pub fn keepalive(mut self, time: Duration) -> SocketOptions {
+        self.keepalive = Some(time);
+        self
+    }
+
+    /// Apply the options to `socket`.
+    pub(crate) fn apply(&self, socket: &TcpStream) -> io::Result<()> {
+        if self.nodelay {
+            socket.set_nodelay(true)?;
+        }
+
+        if let Some(time) = self.keepalive {
+            let keepalive = socket2::TcpKeepalive::new().with_time(time);
+            socket2::SockRef::from(socket).set_tcp_keepalive(&keepalive)?;
+        }
 
         Ok(())
     }
 }
+
+/// A `Stream` with nothing to read that discards everything written to it.
+#[derive(Debug)]
+struct Discard;
+
+impl AsyncRead for Discard {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        _buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for Discard {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// A `Stream` reading back everything written to it. Once everything written
+/// has been read, reads return end of stream.
+#[derive(Debug, Default)]
+struct Loopback {
+    buffer: BytesMut,
+}
+
+impl AsyncRead for Loopback {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let len = buf.remaining().min(self.buffer.len());
+        buf.put_slice(&self.buffer.split_to(len));
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for Loopback {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.buffer.extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Reserves capacity in `buffer` for the rest of a partially received frame,
+/// `required` bytes long as far as is known.
+///
+/// The declared length of a bulk string comes from the peer, so it is not
+/// trusted up front: no more than `MAX_RESERVE` bytes, or as many bytes as were
+/// already received if that is more, are reserved ahead of the data. A peer
+/// sending only a huge length header cannot make the buffer grow past
+/// `MAX_RESERVE`, while a large value that does arrive grows it geometrically,
+/// in a few large reads.
+pub(crate) fn reserve_for_frame(buffer: &mut BytesMut, required: usize) {
+    let additional = required.saturating_sub(buffer.len());
+    let ahead = buffer.len().max(MAX_RESERVE);
+
+    buffer.reserve(additional.min(ahead));
+}
@@ -1,11 +1,47 @@
-use tokio::sync::{broadcast, Notify};
+use crate::cmd::{SetCondition, SetOpKind};
+use crate::connection::NetStats;
+
+use tokio::runtime::Handle;
+use tokio::sync::{
+    broadcast, Mutex as AsyncMutex, Notify, OwnedMutexGuard, RwLock, RwLockReadGuard,
+    RwLockWriteGuard,
+};
 use tokio::time::{self, Duration, Instant};
 
-use bytes::Bytes;
-use std::collections::{BTreeSet, HashMap};
-use std::sync::{Arc, Mutex};
+use bytes::{BufMut, Bytes, BytesMut};
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
 use tracing::debug;
 
+/// Largest string value that can be created by modifying a string in place,
+/// e.g. with `SETRANGE`. Same as Redis' default `proto-max-bulk-len`.
+const MAX_STRING_LEN: usize = 512 * 1024 * 1024;
+
+/// Number of shards the key-value data is split into, see `Shared::shards`.
+const SHARDS: usize = 16;
+
+/// Error returned when a command is applied to a key holding a value of
+/// another type, by the `Value::as_*` methods.
+const WRONGTYPE: &str = "WRONGTYPE Operation against a key holding the wrong kind of value";
+
+/// Version of the `DUMP` payload format, see `Db::dump`. Bumped whenever the
+/// encoding changes, so payloads from another version are rejected.
+const DUMP_VERSION: u8 = 1;
+
+mod snapshot;
+pub use snapshot::DbSnapshot;
+use snapshot::SnapshotEntry;
+
+mod sorted_set;
+use sorted_set::SortedSet;
+
+mod notify;
+pub use notify::KeyspaceEvents;
+
 /// A wrapper around a `Db` instance. This exists to allow orderly cleanup
 /// of the `Db` by signalling the background purge task to shut down when
 /// this struct is dropped.
@@ -18,26 +54,45 @@ pub(crate) struct DbDropGuard {
 
 /// Server state shared across all connections.
 ///
-/// `Db` contains a `HashMap` storing the key/value data and all
-/// `broadcast::Sender` values for active pub/sub channels.
+/// `Db` contains `HashMap`s storing the key/value data, split into shards,
+/// and all `broadcast::Sender` values for active pub/sub channels.
 ///
 /// A `Db` instance is a handle to shared state. Cloning `Db` is shallow and
 /// only incurs an atomic ref count increment.
 ///
-/// When a `Db` value is created, a background task is spawned. This task is
-/// used to expire values after the requested duration has elapsed. The task
+/// A background task is used to expire values after the requested duration
+/// has elapsed. It is spawned the first time a key is given an expiration from
+/// within a Tokio runtime, so a `Db` may be created outside of one. The task
 /// runs until all instances of `Db` are dropped, at which point the task
-/// terminates.
+/// terminates. A `Db` created with `new_manual` has no such task, and expired
+/// values are removed by calling `purge_expired`.
 #[derive(Debug, Clone)]
-pub(crate) struct Db {
+pub struct Db {
     /// Handle to shared state. The background task will also have an
     /// `Arc<Shared>`.
     shared: Arc<Shared>,
+
+    /// Shuts the background task down when the last `Db` handle is dropped.
+    /// The task holds its own `Arc<Shared>`, so it cannot rely on `shared`
+    /// being released.
+    _purge_task: Arc<PurgeTask>,
+}
+
+/// Shared by all `Db` handles. Dropping it signals the background task to
+/// shut down.
+#[derive(Debug)]
+struct PurgeTask {
+    shared: Arc<Shared>,
 }
 
 #[derive(Debug)]
 struct Shared {
-    /// The shared state is guarded by a mutex. This is a `std::sync::Mutex` and
+    /// The key-value data, split into `SHARDS` shards by the hash of the key,
+    /// so commands on keys in different shards do not contend for the same
+    /// lock. Commands touching every key, such as `DBSIZE`, lock the shards in
+    /// order.
+    ///
+    /// Each shard is guarded by a mutex. This is a `std::sync::Mutex` and
     /// not a Tokio mutex. This is because there are no asynchronous operations
     /// being performed while holding the mutex. Additionally, the critical
     /// sections are very small.
@@ -49,24 +104,87 @@ struct Shared {
     /// operations), then the entire operation, including waiting for the mutex,
     /// is considered a "blocking" operation and `tokio::task::spawn_blocking`
     /// should be used.
-    state: Mutex<State>,
+    shards: Box<[Mutex<State>]>,
+
+    /// Picks the shard of a key.
+    hasher: RandomState,
+
+    /// Pub/sub state, shared by every shard to publish keyspace events.
+    ///
+    /// A shard may be locked while this is locked, never the other way
+    /// around.
+    pub_sub: Arc<Mutex<PubSub>>,
 
     /// Notifies the background task handling entry expiration. The background
     /// task waits on this to be notified, then checks for expired values or the
     /// shutdown signal.
     background_task: Notify,
+
+    /// The most memory, in bytes, the data set may use before commands that
+    /// add data are rejected. `0` means there is no limit.
+    ///
+    /// This is kept outside of the mutex so it can be read and changed without
+    /// contending with data access.
+    maxmemory: AtomicUsize,
+
+    /// The largest value, in bytes, `SET` stores. `0` means there is no
+    /// limit.
+    max_value_size: AtomicUsize,
+
+    /// Number of times in a row a subscriber may fall behind the published
+    /// messages before it is disconnected. `0` means subscribers are never
+    /// disconnected.
+    max_subscriber_lags: AtomicUsize,
+
+    /// When `false`, the background task does not purge expired keys. Expired
+    /// keys are still hidden from reads, but stay in the data set until active
+    /// expiration is enabled again. Toggled by `DEBUG SET-ACTIVE-EXPIRE`.
+    active_expire: AtomicBool,
+
+    /// True while the background task is yet to be spawned. It is spawned the
+    /// first time a key is given an expiration, or the task is notified, from
+    /// within a Tokio runtime, see `Db::spawn_purge_task`. Set again if the
+    /// runtime the task runs on shuts down, so it is spawned on the next one.
+    /// Never set for a `Db` created with `new_manual`.
+    spawn_purge_task: AtomicBool,
+
+    /// True when the Db instance is shutting down. This happens when all `Db`
+    /// values drop. Setting this to `true` signals to the background task to
+    /// exit.
+    shutdown: AtomicBool,
+
+    /// Bytes read and written by the connections of the server.
+    net_stats: Arc<NetStats>,
+
+    /// Unix time, in seconds, of the last successful save, or of when the
+    /// `Db` was created if it was never saved. Returned by `LASTSAVE`.
+    last_save: AtomicU64,
+
+    /// Held shared while a command is applied, and exclusively while a
+    /// transaction is applied, so the commands of a transaction are not
+    /// interleaved with commands from other connections.
+    apply_lock: RwLock<()>,
+
+    /// Held from applying a command that is logged to the append-only file
+    /// and forwarded to replicas until it has been logged, so commands are
+    /// logged in the order they are applied.
+    log_lock: Arc<AsyncMutex<()>>,
 }
 
+/// A shard of the key-value data.
 #[derive(Debug)]
 struct State {
     /// The key-value data. We are not trying to do anything fancy so a
     /// `std::collections::HashMap` works fine.
+    ///
+    /// A concurrent map, such as `dashmap`, would not remove any locking:
+    /// `expirations`, `used_memory` and `next_version` must change along with
+    /// `entries`, so the shard would still need its own lock around them.
+    /// The shard lock is not what limits throughput either: a `Db::get` or
+    /// `Db::set`, lock included, takes 200-300ns, while the server spends
+    /// around 10µs on each request, and runs as fast with a single shard.
     entries: HashMap<String, Entry>,
 
-    /// The pub/sub key-space. Redis uses a **separate** key space for key-value
-    /// and pub/sub. `mini-redis` handles this by using a separate `HashMap`.
-    pub_sub: HashMap<String, broadcast::Sender<Bytes>>,
-
     /// Tracks key TTLs.
     ///
     /// A `BTreeSet` is used to maintain expirations sorted by when they expire.
@@ -79,21 +197,69 @@ struct State {
     /// break these ties.
     expirations: BTreeSet<(Instant, String)>,
 
-    /// True when the Db instance is shutting down. This happens when all `Db`
-    /// values drop. Setting this to `true` signals to the background task to
-    /// exit.
-    shutdown: bool,
+    /// Approximate number of bytes used by `entries`, as computed by
+    /// `entry_size`. Kept up to date by `State::insert` and `State::remove`.
+    used_memory: usize,
+
+    /// The version assigned to the next value written, see `Entry::version`.
+    /// A key always lives in the same shard, so versions only need to be
+    /// unique within a shard.
+    next_version: u64,
+
+    /// Where keyspace events are published, see `Shared::pub_sub`.
+    pub_sub: Arc<Mutex<PubSub>>,
+}
+
+#[derive(Debug, Default)]
+struct PubSub {
+    /// The pub/sub key-space. Redis uses a **separate** key space for key-value
+    /// and pub/sub. `mini-redis` handles this by using a separate `HashMap`.
+    channels: HashMap<String, broadcast::Sender<Bytes>>,
+
+    /// Pattern subscriptions, keyed by glob-style pattern. Messages published
+    /// to any channel matching the pattern are sent along with the name of
+    /// the channel.
+    patterns: HashMap<String, broadcast::Sender<(String, Bytes)>>,
+
+    /// The keyspace events published when keys are modified. Set with
+    /// `Db::set_notify_keyspace_events`.
+    notify_keyspace_events: KeyspaceEvents,
 }
 
 /// Entry in the key-value store
 #[derive(Debug)]
 struct Entry {
     /// Stored data
-    data: Bytes,
+    data: Value,
 
     /// Instant at which the entry expires and should be removed from the
     /// database.
     expires_at: Option<Instant>,
+
+    /// Instant at which the entry was last read or written, as reported by
+    /// `OBJECT IDLETIME`.
+    last_access: Instant,
+
+    /// Changes whenever the value is written, so `WATCH` can tell whether the
+    /// key was modified. Assigned by `State::insert`.
+    version: u64,
+}
+
+/// A value stored in the key-value store.
+///
+/// Redis keys may hold values of different types. Each variant corresponds to
+/// one of those types. Commands operating on one type of value return an error
+/// when applied to a key holding another type.
+#[derive(Debug, Clone)]
+enum Value {
+    /// A binary-safe string.
+    String(Bytes),
+
+    /// A set of members ordered by score.
+    SortedSet(SortedSet),
+
+    /// An unordered set of unique, binary-safe members.
+    Set(HashSet<Bytes>),
 }
 
 impl DbDropGuard {
@@ -118,252 +284,1934 @@ impl Drop for DbDropGuard {
 }
 
 impl Db {
-    /// Create a new, empty, `Db` instance. Allocates shared state and spawns a
-    /// background task to manage key expiration.
-    pub(crate) fn new() -> Db {
+    /// Create a new, empty, `Db` instance. Allocates shared state. The
+    /// background task managing key expiration is spawned once a key is given
+    /// an expiration from within a Tokio runtime.
+    ///
+    /// This does not need a Tokio runtime, so a `Db` can be built ahead of
+    /// the runtime the server runs on.
+    pub fn new() -> Db {
+        Db::with_purge_task(true)
+    }
+
+    /// Create a new, empty, `Db` instance without a background task managing
+    /// key expiration.
+    ///
+    /// Expired keys are never returned, but they stay in memory until the
+    /// caller removes them with `purge_expired`. This is for embedders
+    /// scheduling expiration themselves.
+    pub fn new_manual() -> Db {
+        Db::with_purge_task(false)
+    }
+
+    /// Create a new, empty, `Db` instance, which spawns the background task
+    /// on first use if `spawn_purge_task` is set.
+    fn with_purge_task(spawn_purge_task: bool) -> Db {
+        let pub_sub = Arc::new(Mutex::new(PubSub::default()));
+
+        let shards = (0..SHARDS)
+            .map(|_| {
+                Mutex::new(State {
+                    entries: HashMap::new(),
+                    expirations: BTreeSet::new(),
+                    used_memory: 0,
+                    next_version: 0,
+                    pub_sub: pub_sub.clone(),
+                })
+            })
+            .collect();
+
         let shared = Arc::new(Shared {
-            state: Mutex::new(State {
-                entries: HashMap::new(),
-                pub_sub: HashMap::new(),
-                expirations: BTreeSet::new(),
-                shutdown: false,
-            }),
+            shards,
+            hasher: RandomState::new(),
+            pub_sub,
             background_task: Notify::new(),
+            maxmemory: AtomicUsize::new(0),
+            max_value_size: AtomicUsize::new(0),
+            max_subscriber_lags: AtomicUsize::new(0),
+            active_expire: AtomicBool::new(true),
+            spawn_purge_task: AtomicBool::new(spawn_purge_task),
+            shutdown: AtomicBool::new(false),
+            net_stats: Arc::new(NetStats::default()),
+            last_save: AtomicU64::new(unix_time()),
+            apply_lock: RwLock::new(()),
+            log_lock: Arc::new(AsyncMutex::new(())),
         });
 
-        // Start the background task.
-        tokio::spawn(purge_expired_tasks(shared.clone()));
+        let purge_task = Arc::new(PurgeTask {
+            shared: shared.clone(),
+        });
 
-        Db { shared }
+        Db {
+            shared,
+            _purge_task: purge_task,
+        }
     }
 
     /// Get the value associated with a key.
     ///
     /// Returns `None` if there is no value associated with the key. This may be
     /// due to never having assigned a value to the key or a previously assigned
-    /// value expired.
-    pub(crate) fn get(&self, key: &str) -> Option<Bytes> {
-        // Acquire the lock, get the entry and clone the value.
-        //
+    /// value expired. Returns an error if the key holds a value that is not a
+    /// string.
+    pub fn get(&self, key: &str) -> crate::Result<Option<Bytes>> {
         // Because data is stored using `Bytes`, a clone here is a shallow
         // clone. Data is not copied.
-        let state = self.shared.state.lock().unwrap();
-        state.entries.get(key).map(|entry| entry.data.clone())
+        self.with_value(key, |value| value.as_string().cloned())
     }
 
-    /// Set the value associated with a key along with an optional expiration
-    /// Duration.
+    /// Calls `f` with the value stored at `key`, returning its output, or
+    /// `None` if the key does not exist.
     ///
-    /// If a value is already associated with the key, it is removed.
-    pub(crate) fn set(&self, key: String, value: Bytes, expire: Option<Duration>) {
-        let mut state = self.shared.state.lock().unwrap();
+    /// Commands reading a value go through here, with `f` using one of the
+    /// `Value::as_*` methods, so they all fail with the same `WRONGTYPE` error
+    /// when the key holds a value of another type.
+    fn with_value<T>(
+        &self,
+        key: &str,
+        f: impl FnOnce(&Value) -> crate::Result<T>,
+    ) -> crate::Result<Option<T>> {
+        let mut state = self.shard(key);
+        let now = Instant::now();
 
-        // If this `set` becomes the key that expires **next**, the background
-        // task needs to be notified so it can update its state.
-        //
-        // Whether or not the task needs to be notified is computed during the
-        // `set` routine.
-        let mut notify = false;
+        // Keys that expired but have not been purged yet are treated as
+        // missing.
+        let entry = match state.entries.get_mut(key) {
+            Some(entry) if !entry.is_expired(now) => entry,
+            _ => return Ok(None),
+        };
 
-        let expires_at = expire.map(|duration| {
-            // `Instant` at which the key expires.
-            let when = Instant::now() + duration;
+        entry.last_access = now;
 
-            // Only notify the worker task if the newly inserted expiration is the
-            // **next** key to evict. In this case, the worker needs to be woken up
-            // to update its state.
-            notify = state
-                .next_expiration()
-                .map(|expiration| expiration > when)
-                .unwrap_or(true);
+        f(&entry.data).map(Some)
+    }
 
-            when
-        });
+    /// Returns the name of the type of the value stored at `key`, as reported
+    /// by the `TYPE` command.
+    ///
+    /// Returns `None` if there is no value associated with the key.
+    pub fn value_type(&self, key: &str) -> Option<&'static str> {
+        let state = self.shard(key);
+        let now = Instant::now();
 
-        // Insert the entry into the `HashMap`.
-        let prev = state.entries.insert(
-            key.clone(),
-            Entry {
-                data: value,
-                expires_at,
-            },
-        );
+        state
+            .entries
+            .get(key)
+            .filter(|entry| !entry.is_expired(now))
+            .map(|entry| entry.data.type_name())
+    }
 
-        // If there was a value previously associated with the key **and** it
-        // had an expiration time. The associated entry in the `expirations` map
-        // must also be removed. This avoids leaking data.
-        if let Some(prev) = prev {
-            if let Some(when) = prev.expires_at {
-                // clear expiration
-                state.expirations.remove(&(when, key.clone()));
-            }
-        }
+    /// Returns the name of the internal encoding of the value stored at `key`,
+    /// as reported by the `OBJECT ENCODING` command.
+    ///
+    /// Returns `None` if there is no value associated with the key.
+    pub fn encoding(&self, key: &str) -> Option<&'static str> {
+        let state = self.shard(key);
+        let now = Instant::now();
 
-        // Track the expiration. If we insert before remove that will cause bug
-        // when current `(when, key)` equals prev `(when, key)`. Remove then insert
-        // can avoid this.
-        if let Some(when) = expires_at {
-            state.expirations.insert((when, key));
-        }
+        state
+            .entries
+            .get(key)
+            .filter(|entry| !entry.is_expired(now))
+            .map(|entry| entry.data.encoding())
+    }
 
-        // Release the mutex before notifying the background task. This helps
-        // reduce contention by avoiding the background task waking up only to
-        // be unable to acquire the mutex due to this function still holding it.
-        drop(state);
+    /// Returns how long ago the value stored at `key` was last read or
+    /// written, as reported by the `OBJECT IDLETIME` command.
+    ///
+    /// Returns `None` if there is no value associated with the key.
+    pub(crate) fn idle_time(&self, key: &str) -> Option<Duration> {
+        let state = self.shard(key);
+        let now = Instant::now();
 
-        if notify {
-            // Finally, only notify the background task if it needs to update
-            // its state to reflect a new expiration.
-            self.shared.background_task.notify_one();
-        }
+        state
+            .entries
+            .get(key)
+            .filter(|entry| !entry.is_expired(now))
+            .map(|entry| now.saturating_duration_since(entry.last_access))
     }
 
-    /// Returns a `Receiver` for the requested channel.
+    /// Returns the approximate number of bytes used to store the value at
+    /// `key`, including the key itself, as reported by the `MEMORY USAGE`
+    /// command.
     ///
-    /// The returned `Receiver` is used to receive values broadcast by `PUBLISH`
-    /// commands.
-    pub(crate) fn subscribe(&self, key: String) -> broadcast::Receiver<Bytes> {
-        use std::collections::hash_map::Entry;
+    /// Returns `None` if there is no value associated with the key.
+    pub(crate) fn memory_usage(&self, key: &str) -> Option<usize> {
+        let state = self.shard(key);
+        let now = Instant::now();
 
-        // Acquire the mutex
-        let mut state = self.shared.state.lock().unwrap();
+        state
+            .entries
+            .get(key)
+            .filter(|entry| !entry.is_expired(now))
+            .map(|entry| entry_size(key.len(), &entry.data))
+    }
 
-        // If there is no entry for the requested channel, then create a new
-        // broadcast channel and associate it with the key. If one already
-        // exists, return an associated receiver.
-        match state.pub_sub.entry(key) {
-            Entry::Occupied(e) => e.get().subscribe(),
-            Entry::Vacant(e) => {
-                // No broadcast channel exists yet, so create one.
-                //
-                // The channel is created with a capacity of `1024` messages. A
-                // message is stored in the channel until **all** subscribers
-                // have seen it. This means that a slow subscriber could result
-                // in messages being held indefinitely.
-                //
-                // When the channel's capacity fills up, publishing will result
-                // in old messages being dropped. This prevents slow consumers
-                // from blocking the entire system.
-                let (tx, rx) = broadcast::channel(1024);
-                e.insert(tx);
-                rx
-            }
-        }
+    /// Returns the instant at which the value stored at `key` expires, as
+    /// reported by the `TTL` family of commands.
+    ///
+    /// Returns `None` if there is no value associated with the key, and
+    /// `Some(None)` if the value never expires.
+    pub(crate) fn expires_at(&self, key: &str) -> Option<Option<Instant>> {
+        let state = self.shard(key);
+        let now = Instant::now();
+
+        state
+            .entries
+            .get(key)
+            .filter(|entry| !entry.is_expired(now))
+            .map(|entry| entry.expires_at)
     }
 
-    /// Publish a message to the channel. Returns the number of subscribers
-    /// listening on the channel.
-    pub(crate) fn publish(&self, key: &str, value: Bytes) -> usize {
-        let state = self.shared.state.lock().unwrap();
+    /// Returns the version of the value stored at `key`, which changes
+    /// whenever the value is written, as `WATCH` needs.
+    ///
+    /// Returns `None` if there is no value associated with the key.
+    pub(crate) fn key_version(&self, key: &str) -> Option<u64> {
+        let state = self.shard(key);
+        let now = Instant::now();
 
         state
-            .pub_sub
+            .entries
             .get(key)
-            // On a successful message send on the broadcast channel, the number
-            // of subscribers is returned. An error indicates there are no
-            // receivers, in which case, `0` should be returned.
-            .map(|tx| tx.send(value).unwrap_or(0))
-            // If there is no entry for the channel key, then there are no
-            // subscribers. In this case, return `0`.
-            .unwrap_or(0)
+            .filter(|entry| !entry.is_expired(now))
+            .map(|entry| entry.version)
     }
 
-    /// Signals the purge background task to shut down. This is called by the
-    /// `DbShutdown`s `Drop` implementation.
-    fn shutdown_purge_task(&self) {
-        // The background task must be signaled to shut down. This is done by
-        // setting `State::shutdown` to `true` and signalling the task.
-        let mut state = self.shared.state.lock().unwrap();
-        state.shutdown = true;
+    /// Marks the given keys as accessed, without reading their values.
+    /// Returns the number of keys that exist.
+    pub(crate) fn touch(&self, keys: &[String]) -> u64 {
+        let now = Instant::now();
 
-        // Drop the lock before signalling the background task. This helps
-        // reduce lock contention by ensuring the background task doesn't
-        // wake up only to be unable to acquire the mutex.
-        drop(state);
-        self.shared.background_task.notify_one();
+        let mut touched = 0;
+
+        for key in keys {
+            let mut state = self.shard(key);
+
+            if let Some(entry) = state
+                .entries
+                .get_mut(key)
+                .filter(|entry| !entry.is_expired(now))
+            {
+                entry.last_access = now;
+                touched += 1;
+            }
+        }
+
+        touched
     }
-}
 
-impl Shared {
-    /// Purge all expired keys and return the `Instant` at which the **next**
-    /// key will expire. The background task will sleep until this instant.
-    fn purge_expired_keys(&self) -> Option<Instant> {
-        let mut state = self.state.lock().unwrap();
+    /// Returns a key chosen at random, or `None` if there are no keys.
+    ///
+    /// Every shard is locked while the key is chosen, so keys removed
+    /// concurrently cannot leave the chosen index out of bounds. Keys that
+    /// have expired, but have not been purged yet, are never chosen.
+    pub(crate) fn random_key(&self) -> Option<String> {
+        use std::hash::Hasher;
 
-        if state.shutdown {
-            // The database is shutting down. All handles to the shared state
-            // have dropped. The background task should exit.
+        let now = Instant::now();
+        let shards = self.lock_all();
+        let len: usize = shards
+            .iter()
+            .map(|state| {
+                state
+                    .entries
+                    .values()
+                    .filter(|entry| !entry.is_expired(now))
+                    .count()
+            })
+            .sum();
+
+        if len == 0 {
             return None;
         }
 
-        // This is needed to make the borrow checker happy. In short, `lock()`
-        // returns a `MutexGuard` and not a `&mut State`. The borrow checker is
-        // not able to see "through" the mutex guard and determine that it is
-        // safe to access both `state.expirations` and `state.entries` mutably,
-        // so we get a "real" mutable reference to `State` outside of the loop.
-        let state = &mut *state;
+        // Each `RandomState` is seeded randomly, which is enough randomness to
+        // pick a key without pulling in a random number generator.
+        let random = RandomState::new().build_hasher().finish();
+        let index = (random % len as u64) as usize;
+
+        shards
+            .iter()
+            .flat_map(|state| state.entries.iter())
+            .filter(|(_, entry)| !entry.is_expired(now))
+            .nth(index)
+            .map(|(key, _)| key.clone())
+    }
+
+    /// Removes the given keys, returning the number of keys that existed.
+    pub(crate) fn del(&self, keys: &[String]) -> usize {
+        self.remove_keys(keys).len()
+    }
 
-        // Find all keys scheduled to expire **before** now.
+    /// Removes `key` only if its version, see `key_version`, is still
+    /// `version`. Returns `true` if the key was removed.
+    ///
+    /// `MIGRATE` uses this so a value written while the key was transferred
+    /// is not lost.
+    pub(crate) fn del_if_version(&self, key: &str, version: u64) -> bool {
+        let mut state = self.shard(key);
         let now = Instant::now();
 
-        while let Some(&(when, ref key)) = state.expirations.iter().next() {
-            if when > now {
-                // Done purging, `when` is the instant at which the next key
-                // expires. The worker task will wait until this instant.
-                return Some(when);
-            }
+        let unchanged = state
+            .entries
+            .get(key)
+            .is_some_and(|entry| !entry.is_expired(now) && entry.version == version);
 
-            // The key expired, remove it
-            state.entries.remove(key);
-            state.expirations.remove(&(when, key.clone()));
+        if unchanged {
+            state.remove_keys(&[key.to_string()]);
+            state.notify(notify::GENERIC, "del", key);
         }
 
-        None
+        unchanged
     }
 
-    /// Returns `true` if the database is shutting down
+    /// Removes the given keys, returning the number of keys that existed.
     ///
-    /// The `shutdown` flag is set when all `Db` values have dropped, indicating
-    /// that the shared state can no longer be accessed.
-    fn is_shutdown(&self) -> bool {
-        self.state.lock().unwrap().shutdown
-    }
-}
+    /// Unlike `del`, the removed values are dropped on a blocking task, so
+    /// freeing large values does not hold up the caller.
+    pub(crate) fn unlink(&self, keys: &[String]) -> usize {
+        let removed = self.remove_keys(keys);
+        let count = removed.len();
 
-impl State {
-    fn next_expiration(&self) -> Option<Instant> {
-        self.expirations
-            .iter()
-            .next()
-            .map(|expiration| expiration.0)
+        if !removed.is_empty() {
+            tokio::task::spawn_blocking(move || drop(removed));
+        }
+
+        count
     }
-}
 
-/// Routine executed by the background task.
-///
-/// Wait to be notified. On notification, purge any expired keys from the shared
-/// state handle. If `shutdown` is set, terminate the task.
-async fn purge_expired_tasks(shared: Arc<Shared>) {
-    // If the shutdown flag is set, then the task should exit.
-    while !shared.is_shutdown() {
-        // Purge all keys that are expired. The function returns the instant at
-        // which the **next** key will expire. The worker should wait until the
-        // instant has passed then purge again.
-        if let Some(when) = shared.purge_expired_keys() {
-            // Wait until the next key expires **or** until the background task
-            // is notified. If the task is notified, then it must reload its
-            // state as new keys have been set to expire early. This is done by
-            // looping.
-            tokio::select! {
-                _ = time::sleep_until(when) => {}
-                _ = shared.background_task.notified() => {}
+    /// Removes the given keys, publishing a `del` event for each, and returns
+    /// the keys that existed along with their entries.
+    ///
+    /// Keys that have expired, but have not been purged yet, did not exist:
+    /// they are expired instead, without being counted.
+    fn remove_keys(&self, keys: &[String]) -> Vec<(String, Entry)> {
+        let now = Instant::now();
+        let mut removed = vec![];
+
+        for key in keys {
+            let mut state = self.shard(key);
+            state.remove_if_expired(key, now);
+
+            if let Some((key, entry)) = state.remove_keys(std::slice::from_ref(key)).pop() {
+                state.notify(notify::GENERIC, "del", &key);
+                removed.push((key, entry));
             }
-        } else {
-            // There are no keys expiring in the future. Wait until the task is
-            // notified.
-            shared.background_task.notified().await;
         }
+
+        removed
     }
 
-    debug!("Purge background task shut down")
+    /// Removes all keys whose expiration has passed, returning the number of
+    /// keys removed.
+    ///
+    /// This is the same pass the background task runs whenever a key expires.
+    /// Calling it directly lets tests process expirations deterministically,
+    /// without waiting for the background task to be scheduled.
+    pub fn process_expirations(&self) -> usize {
+        self.shared.remove_expired_keys(Instant::now()).0
+    }
+
+    /// Removes all keys whose expiration has passed, returning the `Instant`
+    /// at which the **next** key expires, or `None` if no key has an
+    /// expiration.
+    ///
+    /// This drives expiration for a `Db` created with `new_manual`: call it
+    /// again once the returned instant has passed. Keys set to expire earlier
+    /// in the meantime are not purged until the next call. Unlike the
+    /// background task, it runs while active expiration is disabled.
+    pub fn purge_expired(&self) -> Option<Instant> {
+        self.shared.remove_expired_keys(Instant::now()).1
+    }
+
+    /// Returns the approximate number of bytes used by the stored keys and
+    /// values.
+    pub fn used_memory(&self) -> usize {
+        self.shared
+            .shards
+            .iter()
+            .map(|state| state.lock().unwrap().used_memory)
+            .sum()
+    }
+
+    /// Returns the memory limit, in bytes. `0` means there is no limit.
+    pub fn maxmemory(&self) -> usize {
+        self.shared.maxmemory.load(Ordering::Relaxed)
+    }
+
+    /// Sets the memory limit, in bytes. `0` removes the limit.
+    ///
+    /// Existing keys are never evicted. Once the limit is exceeded, commands
+    /// that may add data are rejected until enough keys are removed.
+    pub fn set_maxmemory(&self, bytes: usize) {
+        self.shared.maxmemory.store(bytes, Ordering::Relaxed);
+    }
+
+    /// Returns the largest value, in bytes, `SET` stores. `0` means there is
+    /// no limit.
+    pub(crate) fn max_value_size(&self) -> usize {
+        self.shared.max_value_size.load(Ordering::Relaxed)
+    }
+
+    /// Sets the largest value, in bytes, `SET` stores. `0` removes the limit.
+    pub(crate) fn set_max_value_size(&self, bytes: usize) {
+        self.shared.max_value_size.store(bytes, Ordering::Relaxed);
+    }
+
+    /// Returns the number of times in a row a subscriber may fall behind the
+    /// published messages before it is disconnected. `0` means there is no
+    /// limit.
+    pub(crate) fn max_subscriber_lags(&self) -> usize {
+        self.shared.max_subscriber_lags.load(Ordering::Relaxed)
+    }
+
+    /// Sets the number of times in a row a subscriber may fall behind the
+    /// published messages before it is disconnected. `0` disables the limit.
+    pub(crate) fn set_max_subscriber_lags(&self, lags: usize) {
+        self.shared
+            .max_subscriber_lags
+            .store(lags, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if a memory limit is set and the data set exceeds it.
+    pub(crate) fn is_over_maxmemory(&self) -> bool {
+        let maxmemory = self.maxmemory();
+        maxmemory != 0 && self.used_memory() > maxmemory
+    }
+
+    /// Returns the number of keys, including expired keys not purged yet.
+    pub(crate) fn dbsize(&self) -> usize {
+        self.shared
+            .shards
+            .iter()
+            .map(|state| state.lock().unwrap().entries.len())
+            .sum()
+    }
+
+    /// Enables or disables purging expired keys in the background.
+    ///
+    /// While disabled, expired keys are no longer returned by reads, but are
+    /// kept in the data set, which lets tests observe lazy and active
+    /// expiration separately.
+    pub(crate) fn set_active_expire(&self, enabled: bool) {
+        self.shared.active_expire.store(enabled, Ordering::Relaxed);
+
+        // Wake up the background task, so keys that expired while active
+        // expiration was disabled are purged.
+        self.notify_purge_task();
+    }
+
+    /// Publishes `events` whenever keys are modified, see `KeyspaceEvents`.
+    pub(crate) fn set_notify_keyspace_events(&self, events: KeyspaceEvents) {
+        self.shared.pub_sub.lock().unwrap().notify_keyspace_events = events;
+    }
+
+    /// Waits until no transaction is being applied, then returns a guard
+    /// preventing transactions from being applied until it is dropped.
+    pub(crate) async fn lock_shared(&self) -> RwLockReadGuard<'_, ()> {
+        self.shared.apply_lock.read().await
+    }
+
+    /// Waits until no other command is being applied, then returns a guard
+    /// preventing other commands from being applied until it is dropped.
+    pub(crate) async fn lock_exclusive(&self) -> RwLockWriteGuard<'_, ()> {
+        self.shared.apply_lock.write().await
+    }
+
+    /// Waits until no other command is being applied and logged, then returns
+    /// a guard preventing other commands from being logged until it is
+    /// dropped.
+    ///
+    /// The guard is taken before the apply lock, and held until the command
+    /// has been logged.
+    pub(crate) async fn lock_log(&self) -> OwnedMutexGuard<()> {
+        self.shared.log_lock.clone().lock_owned().await
+    }
+
+    /// Returns the byte counters shared by the connections of the server.
+    pub(crate) fn net_stats(&self) -> &Arc<NetStats> {
+        &self.shared.net_stats
+    }
+
+    /// Returns the Unix time, in seconds, of the last successful save, see
+    /// `record_save`. Before the first save, this is when the `Db` was
+    /// created.
+    pub(crate) fn last_save(&self) -> u64 {
+        self.shared.last_save.load(Ordering::Relaxed)
+    }
+
+    /// Records that a snapshot of the data set was just saved.
+    pub(crate) fn record_save(&self) {
+        self.shared.last_save.store(unix_time(), Ordering::Relaxed);
+    }
+
+    /// Returns the number of keys along with the number of keys that have an
+    /// expiration set.
+    pub(crate) fn keyspace(&self) -> (usize, usize) {
+        self.lock_all()
+            .iter()
+            .map(|state| (state.entries.len(), state.expirations.len()))
+            .fold((0, 0), |(keys, expires), (k, e)| (keys + k, expires + e))
+    }
+
+    /// Returns a copy of every key that has not expired, along with its value
+    /// and remaining time to live.
+    ///
+    /// Pub/sub state is not included.
+    pub fn snapshot(&self) -> DbSnapshot {
+        // Every shard is locked, so the snapshot is consistent across shards.
+        let shards = self.lock_all();
+        let now = Instant::now();
+
+        // Values are `Bytes`, so copying them is shallow.
+        let entries = shards
+            .iter()
+            .flat_map(|state| state.entries.iter())
+            .filter(|(_, entry)| !entry.is_expired(now))
+            .map(|(key, entry)| SnapshotEntry {
+                key: key.clone(),
+                value: entry.data.clone(),
+                ttl: entry.expires_at.map(|when| when - now),
+            })
+            .collect();
+
+        DbSnapshot { entries }
+    }
+
+    /// Replaces every key with the keys captured in `snapshot`.
+    ///
+    /// Expirations restart from now: a key that had 10 seconds left when the
+    /// snapshot was taken expires 10 seconds after being restored. Pub/sub
+    /// state is left untouched.
+    pub fn restore(&self, snapshot: DbSnapshot) {
+        let mut shards = self.lock_all();
+        let now = Instant::now();
+
+        for state in shards.iter_mut() {
+            let keys: Vec<String> = state.entries.keys().cloned().collect();
+            state.remove_keys(&keys);
+        }
+
+        for SnapshotEntry { key, value, ttl } in snapshot.entries {
+            let state = &mut shards[self.shared.shard_index(&key)];
+            let expires_at = ttl.map(|ttl| now + ttl);
+
+            if let Some(when) = expires_at {
+                state.expirations.insert((when, key.clone()));
+            }
+
+            state.insert(
+                key,
+                Entry {
+                    data: value,
+                    expires_at,
+                    last_access: now,
+                    version: 0,
+                },
+            );
+        }
+
+        // The next expiration most likely changed, so the background task
+        // must reload its state.
+        drop(shards);
+        self.notify_purge_task();
+    }
+
+    /// Serializes the value stored at `key`, as returned by the `DUMP`
+    /// command. Returns `None` if there is no value associated with the key.
+    ///
+    /// The payload is self-describing, so `restore_dump` recreates the value,
+    /// of any type, in another `Db`. The key and its expiration are not
+    /// included.
+    ///
+    /// # Format
+    ///
+    /// The value is encoded as in a `DbSnapshot`, between its type tag and the
+    /// version of the format, `DUMP_VERSION`. All integers are big-endian.
+    ///
+    /// ```text
+    /// type:u8 value version:u8
+    ///
+    /// value (string):     len:u32 data
+    /// value (sorted set): count:u32 (member_len:u32 member score:f64)*
+    /// value (set):        count:u32 (member_len:u32 member)*
+    /// ```
+    pub fn dump(&self, key: &str) -> Option<Bytes> {
+        let state = self.shard(key);
+
+        let entry = state
+            .entries
+            .get(key)
+            .filter(|entry| !entry.is_expired(Instant::now()))?;
+
+        let mut buf = BytesMut::new();
+        buf.put_u8(snapshot::type_tag(&entry.data));
+        snapshot::put_value(&mut buf, &entry.data);
+        buf.put_u8(DUMP_VERSION);
+
+        Some(buf.freeze())
+    }
+
+    /// Stores the value serialized in `payload` by `dump` at `key`, expiring
+    /// after `ttl` if given, as the `RESTORE` command does.
+    ///
+    /// Returns an error if `payload` is not a valid payload of the current
+    /// version, or if the key already exists and `replace` is not set.
+    pub fn restore_dump(
+        &self,
+        key: String,
+        payload: &[u8],
+        ttl: Option<Duration>,
+        replace: bool,
+    ) -> crate::Result<()> {
+        let value = decode_dump(payload)?;
+
+        let mut state = self.shard(&key);
+        let now = Instant::now();
+
+        state.remove_if_expired(&key, now);
+
+        if state.entries.contains_key(&key) {
+            if !replace {
+                return Err("BUSYKEY Target key name already exists.".into());
+            }
+
+            state.remove_keys(std::slice::from_ref(&key));
+        }
+
+        let expires_at = ttl.map(|ttl| now + ttl);
+
+        if let Some(when) = expires_at {
+            state.expirations.insert((when, key.clone()));
+        }
+
+        state.insert(
+            key.clone(),
+            Entry {
+                data: value,
+                expires_at,
+                last_access: now,
+                version: 0,
+            },
+        );
+
+        state.notify(notify::GENERIC, "restore", &key);
+
+        // Release the mutex before notifying the background task.
+        drop(state);
+
+        if expires_at.is_some() {
+            self.notify_purge_task();
+        }
+
+        Ok(())
+    }
+
+    /// Set the value associated with a key along with an optional expiration
+    /// Duration.
+    ///
+    /// If a value is already associated with the key, it is removed.
+    ///
+    /// Returns the value previously associated with the key, if any. Values
+    /// that are not strings are replaced as well, but are not returned.
+    pub fn set(&self, key: String, value: Bytes, expire: Option<Duration>) -> Option<Bytes> {
+        let state = self.shard(&key);
+        self.set_locked(state, key, value, expire)
+    }
+
+    /// Set the value associated with a key, as `set` does, but only if
+    /// `condition` holds, as with the `NX` and `XX` options of `SET`.
+    ///
+    /// Returns whether the value was set. With `get`, the string previously
+    /// associated with the key is returned as well, even if the value was not
+    /// set. In that case, if the key holds a value that is not a string, an
+    /// error is returned and nothing is set, like Redis does.
+    pub(crate) fn set_with(
+        &self,
+        key: String,
+        value: Bytes,
+        expire: Option<Duration>,
+        condition: SetCondition,
+        get: bool,
+    ) -> crate::Result<(bool, Option<Bytes>)> {
+        let state = self.shard(&key);
+        let now = Instant::now();
+
+        // Checked under the shard's lock, so no other command changes the key
+        // between the check and the write.
+        let entry = state
+            .entries
+            .get(&key)
+            .filter(|entry| !entry.is_expired(now));
+
+        let prev = match entry {
+            Some(entry) if get => Some(entry.data.as_string()?.clone()),
+            _ => None,
+        };
+
+        if !condition.holds(entry.is_some()) {
+            return Ok((false, prev));
+        }
+
+        self.set_locked(state, key, value, expire);
+
+        Ok((true, prev))
+    }
+
+    /// Set the value associated with a key, holding the lock of its shard.
+    /// See `set`.
+    fn set_locked(
+        &self,
+        mut state: MutexGuard<'_, State>,
+        key: String,
+        value: Bytes,
+        expire: Option<Duration>,
+    ) -> Option<Bytes> {
+        let now = Instant::now();
+
+        // If this `set` becomes the key that expires **next**, the background
+        // task needs to be notified so it can update its state.
+        //
+        // Whether or not the task needs to be notified is computed during the
+        // `set` routine.
+        let mut notify = false;
+
+        let expires_at = expire.map(|duration| {
+            // `Instant` at which the key expires.
+            let when = now + duration;
+
+            // Only notify the worker task if the newly inserted expiration is the
+            // **next** key to evict. In this case, the worker needs to be woken up
+            // to update its state. Only this shard's expirations are checked, so
+            // the worker may be woken up when a key in another shard expires
+            // sooner, which is harmless.
+            notify = state
+                .next_expiration()
+                .map(|expiration| expiration > when)
+                .unwrap_or(true);
+
+            when
+        });
+
+        // Insert the entry into the `HashMap`.
+        let prev = state.insert(
+            key.clone(),
+            Entry {
+                data: Value::String(value),
+                expires_at,
+                last_access: now,
+                version: 0,
+            },
+        );
+
+        // If there was a value previously associated with the key **and** it
+        // had an expiration time. The associated entry in the `expirations` map
+        // must also be removed. This avoids leaking data.
+        if let Some(prev) = &prev {
+            if let Some(when) = prev.expires_at {
+                // clear expiration
+                state.expirations.remove(&(when, key.clone()));
+            }
+        }
+
+        state.notify(notify::STRING, "set", &key);
+
+        // Track the expiration. If we insert before remove that will cause bug
+        // when current `(when, key)` equals prev `(when, key)`. Remove then insert
+        // can avoid this.
+        if let Some(when) = expires_at {
+            state.notify(notify::GENERIC, "expire", &key);
+            state.expirations.insert((when, key));
+        }
+
+        // Release the mutex before notifying the background task. This helps
+        // reduce contention by avoiding the background task waking up only to
+        // be unable to acquire the mutex due to this function still holding it.
+        drop(state);
+
+        if notify {
+            // Finally, only notify the background task if it needs to update
+            // its state to reflect a new expiration.
+            self.notify_purge_task();
+        } else if expires_at.is_some() {
+            // The task does not need to reload its state, but may not be
+            // spawned yet.
+            self.spawn_purge_task();
+        }
+
+        // A value that has expired, but has not been purged yet, is not
+        // returned.
+        prev.filter(|prev| !prev.is_expired(now))
+            .and_then(|prev| match prev.data {
+                Value::String(data) => Some(data),
+                _ => None,
+            })
+    }
+
+    /// Adds `delta` to the floating point number stored at `key`, returning
+    /// the new value.
+    ///
+    /// A missing key is treated as `0`. The key's expiration, if any, is kept.
+    /// Returns an error if the stored value is not a valid float, or if the
+    /// result would be NaN or infinite.
+    pub(crate) fn incr_by_float(&self, key: &str, delta: f64) -> crate::Result<f64> {
+        let mut state = self.shard(key);
+        let now = Instant::now();
+
+        state.remove_if_expired(key, now);
+
+        let (current, expires_at) = match state.entries.get(key) {
+            Some(entry) => {
+                let current = std::str::from_utf8(entry.data.as_string()?)
+                    .ok()
+                    .and_then(|data| data.parse::<f64>().ok())
+                    // Like Redis, stored values must be finite numbers.
+                    .filter(|current| current.is_finite())
+                    .ok_or("ERR value is not a valid float")?;
+
+                (current, entry.expires_at)
+            }
+            None => (0.0, None),
+        };
+
+        let value = current + delta;
+
+        if !value.is_finite() {
+            return Err("ERR increment would produce NaN or Infinity".into());
+        }
+
+        // Rust's `Display` implementation for `f64` matches how Redis formats
+        // the result: plain decimal notation, never an exponent, and no
+        // trailing zeros. For example, `3.0e3` is stored as `3000`.
+        //
+        // The expiration is unchanged, so the `expirations` set and the
+        // background task do not need updating.
+        state.insert(
+            key.to_string(),
+            Entry {
+                data: Value::String(Bytes::from(value.to_string())),
+                expires_at,
+                last_access: now,
+                version: 0,
+            },
+        );
+
+        state.notify(notify::STRING, "incrbyfloat", key);
+
+        Ok(value)
+    }
+
+    /// Overwrites part of the string stored at `key`, starting at `offset`,
+    /// with `value`. Returns the length of the string after the change.
+    ///
+    /// If the string is shorter than `offset`, it is padded with zero bytes.
+    /// A missing key is treated as an empty string, but is only created if
+    /// `value` is not empty. The key's expiration, if any, is kept.
+    ///
+    /// Returns an error if the resulting string would exceed
+    /// `MAX_STRING_LEN`.
+    pub(crate) fn setrange(&self, key: &str, offset: usize, value: &[u8]) -> crate::Result<usize> {
+        let end = offset
+            .checked_add(value.len())
+            .filter(|end| *end <= MAX_STRING_LEN)
+            .ok_or("ERR string exceeds maximum allowed size (proto-max-bulk-len)")?;
+
+        if value.is_empty() {
+            return Ok(self.get(key)?.map_or(0, |data| data.len()));
+        }
+
+        let len = self.update_string(key, "setrange", |data| {
+            if data.len() < end {
+                data.resize(end, 0);
+            }
+
+            data[offset..end].copy_from_slice(value);
+            data.len()
+        })?;
+
+        Ok(len)
+    }
+
+    /// Sets the string stored at `key` to `value` if it currently equals
+    /// `expected`, returning whether it was set.
+    ///
+    /// The comparison and the write happen under the shard's lock, so no other
+    /// command changes the value in between. A missing key never matches. The
+    /// key's expiration, if any, is kept.
+    pub(crate) fn cas(&self, key: &str, expected: &[u8], value: Bytes) -> crate::Result<bool> {
+        let mut state = self.shard(key);
+        let now = Instant::now();
+
+        state.remove_if_expired(key, now);
+
+        let expires_at = match state.entries.get(key) {
+            Some(entry) if entry.data.as_string()?[..] == *expected => entry.expires_at,
+            _ => return Ok(false),
+        };
+
+        // The expiration is unchanged, so the `expirations` set and the
+        // background task do not need updating.
+        state.insert(
+            key.to_string(),
+            Entry {
+                data: Value::String(value),
+                expires_at,
+                last_access: now,
+                version: 0,
+            },
+        );
+
+        state.notify(notify::STRING, "set", key);
+
+        Ok(true)
+    }
+
+    /// Sets the bit at `offset` in the string stored at `key` to `value`,
+    /// returning the previous value of the bit.
+    ///
+    /// Bits are numbered from the most significant bit of the first byte. If
+    /// the string is too short, it is grown with zero bytes. A missing key is
+    /// treated as an empty string. The key's expiration, if any, is kept.
+    pub(crate) fn setbit(&self, key: &str, offset: u32, value: bool) -> crate::Result<bool> {
+        let (byte, mask) = bit_position(offset);
+
+        self.update_string(key, "setbit", |data| {
+            if data.len() <= byte {
+                data.resize(byte + 1, 0);
+            }
+
+            let prev = data[byte] & mask != 0;
+
+            if value {
+                data[byte] |= mask;
+            } else {
+                data[byte] &= !mask;
+            }
+
+            prev
+        })
+    }
+
+    /// Returns the bit at `offset` in the string stored at `key`.
+    ///
+    /// Bits past the end of the string, or of a missing key, are `false`.
+    pub(crate) fn getbit(&self, key: &str, offset: u32) -> crate::Result<bool> {
+        let (byte, mask) = bit_position(offset);
+
+        Ok(self
+            .get(key)?
+            .and_then(|data| data.get(byte).copied())
+            .is_some_and(|data| data & mask != 0))
+    }
+
+    /// Returns the number of bits set in the string stored at `key`.
+    ///
+    /// When `range` is given, only the bytes between its `start` and `end`
+    /// offsets are counted, interpreted the same way as by `getrange`.
+    pub(crate) fn bitcount(&self, key: &str, range: Option<(i64, i64)>) -> crate::Result<u64> {
+        let data = match range {
+            Some((start, end)) => self.getrange(key, start, end)?,
+            None => self.get(key)?.unwrap_or_default(),
+        };
+
+        Ok(data.iter().map(|byte| byte.count_ones() as u64).sum())
+    }
+
+    /// Replaces the string stored at `key` with the result of `f`, which is
+    /// given a copy of the string to modify, then publishes `event`. Returns
+    /// the output of `f`.
+    ///
+    /// A missing key is treated as an empty string. The key's expiration, if
+    /// any, is kept. Returns an error, without calling `f`, if the key holds
+    /// a value that is not a string.
+    fn update_string<T>(
+        &self,
+        key: &str,
+        event: &str,
+        f: impl FnOnce(&mut BytesMut) -> T,
+    ) -> crate::Result<T> {
+        let mut state = self.shard(key);
+        let now = Instant::now();
+
+        state.remove_if_expired(key, now);
+
+        let (current, expires_at) = match state.entries.get(key) {
+            Some(entry) => (entry.data.as_string()?.clone(), entry.expires_at),
+            None => (Bytes::new(), None),
+        };
+
+        // `Bytes` values are immutable and may be shared with readers, so the
+        // string is copied before being modified.
+        let mut data = BytesMut::from(&current[..]);
+        let ret = f(&mut data);
+
+        // The expiration is unchanged, so the `expirations` set and the
+        // background task do not need updating.
+        state.insert(
+            key.to_string(),
+            Entry {
+                data: Value::String(data.freeze()),
+                expires_at,
+                last_access: now,
+                version: 0,
+            },
+        );
+
+        state.notify(notify::STRING, event, key);
+
+        Ok(ret)
+    }
+
+    /// Returns the part of the string stored at `key` between `start` and
+    /// `end`, both inclusive.
+    ///
+    /// Negative offsets count from the end of the string, so `-1` is the last
+    /// byte. The range is clamped to the string. A missing key is treated as
+    /// an empty string.
+    pub(crate) fn getrange(&self, key: &str, start: i64, end: i64) -> crate::Result<Bytes> {
+        let data = match self.get(key)? {
+            Some(data) => data,
+            None => return Ok(Bytes::new()),
+        };
+
+        // Like Redis, a range entirely counted from the end that is reversed
+        // is empty, even if both offsets are clamped to the first byte.
+        if start < 0 && end < 0 && start > end {
+            return Ok(Bytes::new());
+        }
+
+        let len = data.len() as i64;
+
+        let start = if start < 0 { start + len } else { start }.max(0);
+        let end = if end < 0 { end + len } else { end }.max(0).min(len - 1);
+
+        if start > end {
+            return Ok(Bytes::new());
+        }
+
+        // Slicing `Bytes` does not copy the data.
+        Ok(data.slice(start as usize..=end as usize))
+    }
+
+    /// Adds the given members, with their scores, to the sorted set stored at
+    /// `key`. Returns the number of members that were added, not counting
+    /// members whose score was updated.
+    ///
+    /// A missing key is treated as an empty sorted set. Returns an error if
+    /// the key holds a value of another type.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a score is NaN.
+    pub(crate) fn zadd(&self, key: &str, members: Vec<(f64, String)>) -> crate::Result<usize> {
+        let mut state = self.shard(key);
+        let now = Instant::now();
+
+        state.remove_if_expired(key, now);
+
+        // Like Redis, updating the score of a member is a change, even though
+        // it is not counted as added. If nothing changes, the entry keeps its
+        // version, so a `WATCH` on the key is not aborted.
+        if let Some(entry) = state.entries.get_mut(key) {
+            let set = entry.data.as_sorted_set()?;
+            let changed = members
+                .iter()
+                .any(|(score, member)| set.score(member) != Some(*score));
+
+            if !changed {
+                entry.last_access = now;
+                return Ok(0);
+            }
+        }
+
+        // The entry is taken out of the map while it is modified and then
+        // inserted back, so `used_memory` accounts for the new members. Its
+        // expiration does not change, so the `expirations` set is left as is.
+        let mut entry = match state.remove(key) {
+            Some(entry) => entry,
+            None => Entry {
+                data: Value::SortedSet(SortedSet::default()),
+                expires_at: None,
+                last_access: now,
+                version: 0,
+            },
+        };
+
+        let set = entry.data.as_sorted_set_mut()?;
+        let mut added = 0;
+
+        for (score, member) in members {
+            if set.insert(member, score) {
+                added += 1;
+            }
+        }
+
+        entry.last_access = now;
+        state.insert(key.to_string(), entry);
+        state.notify(notify::ZSET, "zadd", key);
+
+        Ok(added)
+    }
+
+    /// Returns the score of `member` in the sorted set stored at `key`.
+    ///
+    /// Returns `None` if the key or the member does not exist, and an error if
+    /// the key holds a value of another type.
+    pub(crate) fn zscore(&self, key: &str, member: &str) -> crate::Result<Option<f64>> {
+        self.with_sorted_set(key, |set| set.score(member))
+            .map(Option::flatten)
+    }
+
+    /// Returns the members ranked between `start` and `stop` in the sorted set
+    /// stored at `key`, with their scores, ordered by ascending score.
+    ///
+    /// Negative ranks count from the end of the set. A missing key is treated
+    /// as an empty sorted set. Returns an error if the key holds a value of
+    /// another type.
+    pub(crate) fn zrange(
+        &self,
+        key: &str,
+        start: i64,
+        stop: i64,
+    ) -> crate::Result<Vec<(String, f64)>> {
+        self.with_sorted_set(key, |set| set.range(start, stop))
+            .map(Option::unwrap_or_default)
+    }
+
+    /// Calls `f` with the sorted set stored at `key`, returning its output, or
+    /// `None` if the key does not exist.
+    ///
+    /// Returns an error if the key holds a value of another type.
+    fn with_sorted_set<T>(
+        &self,
+        key: &str,
+        f: impl FnOnce(&SortedSet) -> T,
+    ) -> crate::Result<Option<T>> {
+        self.with_value(key, |value| value.as_sorted_set().map(f))
+    }
+
+    /// Adds `members` to the set stored at `key`, returning the number of
+    /// members that were not already in the set.
+    ///
+    /// A missing key is treated as an empty set. Returns an error if the key
+    /// holds a value of another type.
+    pub(crate) fn sadd(&self, key: &str, members: Vec<Bytes>) -> crate::Result<usize> {
+        let mut state = self.shard(key);
+        let now = Instant::now();
+
+        state.remove_if_expired(key, now);
+
+        if !state.entries.contains_key(key) {
+            state.insert(
+                key.to_string(),
+                Entry {
+                    data: Value::Set(HashSet::new()),
+                    expires_at: None,
+                    last_access: now,
+                    version: 0,
+                },
+            );
+        }
+
+        // Reborrow the guard, so the entry and `used_memory` can be borrowed
+        // at the same time.
+        let state = &mut *state;
+        let entry = state.entries.get_mut(key).unwrap();
+
+        let set = entry.data.as_set_mut()?;
+
+        entry.last_access = now;
+
+        // The set is modified in place, rather than being taken out of the map
+        // and inserted back, so `used_memory` is updated one member at a time.
+        let mut added = 0;
+
+        for member in members {
+            let size = set_member_size(&member);
+
+            if set.insert(member) {
+                state.used_memory += size;
+                added += 1;
+            }
+        }
+
+        // The entry is borrowed, so the version is not taken through
+        // `State::next_version`.
+        if added > 0 {
+            state.next_version += 1;
+            entry.version = state.next_version;
+            state.notify(notify::SET, "sadd", key);
+        }
+
+        Ok(added)
+    }
+
+    /// Removes `members` from the set stored at `key`, returning the number of
+    /// members that were in the set.
+    ///
+    /// The key is removed once the set is empty. Returns an error if the key
+    /// holds a value of another type.
+    pub(crate) fn srem(&self, key: &str, members: &[Bytes]) -> crate::Result<usize> {
+        let mut state = self.shard(key);
+        let now = Instant::now();
+
+        state.remove_if_expired(key, now);
+
+        let state = &mut *state;
+
+        let entry = match state.entries.get_mut(key) {
+            Some(entry) => entry,
+            None => return Ok(0),
+        };
+
+        let set = entry.data.as_set_mut()?;
+
+        entry.last_access = now;
+
+        let mut removed = 0;
+
+        for member in members {
+            if set.remove(member) {
+                state.used_memory -= set_member_size(member);
+                removed += 1;
+            }
+        }
+
+        if removed == 0 {
+            return Ok(0);
+        }
+
+        state.next_version += 1;
+        entry.version = state.next_version;
+
+        let empty = set.is_empty();
+
+        state.notify(notify::SET, "srem", key);
+
+        // Like Redis, empty sets are not kept around.
+        if empty {
+            state.remove_keys(&[key.to_string()]);
+        }
+
+        Ok(removed)
+    }
+
+    /// Returns the members of the set stored at `key`, in no particular
+    /// order.
+    ///
+    /// A missing key is treated as an empty set. Returns an error if the key
+    /// holds a value of another type.
+    pub(crate) fn smembers(&self, key: &str) -> crate::Result<Vec<Bytes>> {
+        // Members are `Bytes`, so copying them is shallow.
+        self.with_set(key, |set| set.iter().cloned().collect())
+            .map(Option::unwrap_or_default)
+    }
+
+    /// Returns `true` if `member` is in the set stored at `key`.
+    ///
+    /// Returns an error if the key holds a value of another type.
+    pub(crate) fn sismember(&self, key: &str, member: &[u8]) -> crate::Result<bool> {
+        self.with_set(key, |set| set.contains(member))
+            .map(|found| found.unwrap_or(false))
+    }
+
+    /// Returns the number of members of the set stored at `key`.
+    ///
+    /// Returns an error if the key holds a value of another type.
+    pub(crate) fn scard(&self, key: &str) -> crate::Result<usize> {
+        self.with_set(key, |set| set.len())
+            .map(|len| len.unwrap_or(0))
+    }
+
+    /// Combines the sets stored at `keys` as described by `kind`, returning
+    /// the members of the result, in no particular order. If `destination` is
+    /// given, the result is also stored there, replacing any value it holds.
+    ///
+    /// Missing keys are treated as empty sets. Returns an error if a key holds
+    /// a value of another type, in which case nothing is stored.
+    pub(crate) fn set_op(
+        &self,
+        kind: SetOpKind,
+        keys: &[String],
+        destination: Option<&str>,
+    ) -> crate::Result<Vec<Bytes>> {
+        // Every shard is locked, so the keys are read, and the destination
+        // written, atomically.
+        let mut shards = self.lock_all();
+        let now = Instant::now();
+
+        let empty = HashSet::new();
+        let mut sets = Vec::with_capacity(keys.len());
+
+        for key in keys {
+            let entry = shards[self.shared.shard_index(key)]
+                .entries
+                .get(key)
+                .filter(|entry| !entry.is_expired(now));
+
+            match entry {
+                Some(entry) => sets.push(entry.data.as_set()?),
+                None => sets.push(&empty),
+            }
+        }
+
+        let result = kind.combine(&sets);
+        drop(sets);
+
+        let destination = match destination {
+            Some(destination) => destination,
+            None => return Ok(result.into_iter().collect()),
+        };
+
+        let state = &mut shards[self.shared.shard_index(destination)];
+        let members = result.iter().cloned().collect();
+
+        // A destination that has expired, but has not been purged yet, did
+        // not exist, so no `del` event is published for it.
+        state.remove_if_expired(destination, now);
+        let existed = !state.remove_keys(&[destination.to_string()]).is_empty();
+
+        // Like Redis, empty sets are not stored.
+        if !result.is_empty() {
+            state.insert(
+                destination.to_string(),
+                Entry {
+                    data: Value::Set(result),
+                    expires_at: None,
+                    last_access: now,
+                    version: 0,
+                },
+            );
+            state.notify(notify::SET, kind.name(true), destination);
+        } else if existed {
+            state.notify(notify::GENERIC, "del", destination);
+        }
+
+        Ok(members)
+    }
+
+    /// Calls `f` with the set stored at `key`, returning its output, or `None`
+    /// if the key does not exist.
+    ///
+    /// Returns an error if the key holds a value of another type.
+    fn with_set<T>(
+        &self,
+        key: &str,
+        f: impl FnOnce(&HashSet<Bytes>) -> T,
+    ) -> crate::Result<Option<T>> {
+        self.with_value(key, |value| value.as_set().map(f))
+    }
+
+    /// Returns a `Receiver` for the requested channel.
+    ///
+    /// The returned `Receiver` is used to receive values broadcast by `PUBLISH`
+    /// commands.
+    pub(crate) fn subscribe(&self, key: String) -> broadcast::Receiver<Bytes> {
+        use std::collections::hash_map::Entry;
+
+        // Acquire the mutex
+        let mut pub_sub = self.shared.pub_sub.lock().unwrap();
+
+        // If there is no entry for the requested channel, then create a new
+        // broadcast channel and associate it with the key. If one already
+        // exists, return an associated receiver.
+        match pub_sub.channels.entry(key) {
+            Entry::Occupied(e) => e.get().subscribe(),
+            Entry::Vacant(e) => {
+                // No broadcast channel exists yet, so create one.
+                //
+                // The channel is created with a capacity of `1024` messages. A
+                // message is stored in the channel until **all** subscribers
+                // have seen it. This means that a slow subscriber could result
+                // in messages being held indefinitely.
+                //
+                // When the channel's capacity fills up, publishing will result
+                // in old messages being dropped. This prevents slow consumers
+                // from blocking the entire system.
+                let (tx, rx) = broadcast::channel(1024);
+                e.insert(tx);
+                rx
+            }
+        }
+    }
+
+    /// Returns a `Receiver` for the requested pattern.
+    ///
+    /// The returned `Receiver` is used to receive values broadcast by `PUBLISH`
+    /// commands to any channel matching the pattern, along with the name of
+    /// that channel.
+    pub(crate) fn psubscribe(&self, pattern: String) -> broadcast::Receiver<(String, Bytes)> {
+        let mut pub_sub = self.shared.pub_sub.lock().unwrap();
+
+        // Pattern subscriptions share broadcast channels the same way channel
+        // subscriptions do. See `subscribe` for details.
+        pub_sub
+            .patterns
+            .entry(pattern)
+            .or_insert_with(|| broadcast::channel(1024).0)
+            .subscribe()
+    }
+
+    /// Publish a message to the channel. Returns the number of subscribers
+    /// listening on the channel, including those subscribed to a matching
+    /// pattern.
+    pub(crate) fn publish(&self, key: &str, value: Bytes) -> usize {
+        self.shared.pub_sub.lock().unwrap().publish(key, value)
+    }
+
+    /// Locks the shard holding `key`.
+    fn shard(&self, key: &str) -> MutexGuard<'_, State> {
+        self.shared.shards[self.shared.shard_index(key)]
+            .lock()
+            .unwrap()
+    }
+
+    /// Locks every shard, in order, so that concurrent calls cannot deadlock.
+    fn lock_all(&self) -> Vec<MutexGuard<'_, State>> {
+        self.shared
+            .shards
+            .iter()
+            .map(|state| state.lock().unwrap())
+            .collect()
+    }
+
+    /// Notifies the background task, so it reloads the next expiration,
+    /// spawning it first if needed.
+    fn notify_purge_task(&self) {
+        self.spawn_purge_task();
+        self.shared.background_task.notify_one();
+    }
+
+    /// Spawns the background task on the runtime of the caller, if it is yet
+    /// to be spawned, or its runtime has shut down. Outside of a runtime, it
+    /// stays unspawned: expired keys are hidden from reads, but are not purged
+    /// until a later call spawns it.
+    fn spawn_purge_task(&self) {
+        if self.shared.spawn_purge_task.load(Ordering::Relaxed) {
+            if let Ok(handle) = Handle::try_current() {
+                // Only the caller flipping the flag spawns the task.
+                if self.shared.spawn_purge_task.swap(false, Ordering::AcqRel) {
+                    handle.spawn(purge_expired_tasks(self.shared.clone()));
+                }
+            }
+        }
+    }
+
+    /// Signals the purge background task to shut down. This is called by the
+    /// `DbDropGuard`s `Drop` implementation.
+    fn shutdown_purge_task(&self) {
+        self.shared.shutdown_purge_task();
+    }
+}
+
+impl Default for Db {
+    fn default() -> Db {
+        Db::new()
+    }
+}
+
+impl Drop for PurgeTask {
+    fn drop(&mut self) {
+        // The last `Db` handle is gone, nothing can read the keys anymore.
+        self.shared.shutdown_purge_task();
+    }
+}
+
+impl Shared {
+    /// Signals the purge background task to shut down.
+    fn shutdown_purge_task(&self) {
+        // The background task must be signaled to shut down. This is done by
+        // setting `Shared::shutdown` to `true` and signalling the task.
+        self.shutdown.store(true, Ordering::Relaxed);
+        self.background_task.notify_one();
+    }
+
+    /// Returns the index of the shard holding `key`.
+    fn shard_index(&self, key: &str) -> usize {
+        (self.hasher.hash_one(key) % SHARDS as u64) as usize
+    }
+
+    /// Purge all expired keys and return the `Instant` at which the **next**
+    /// key will expire. The background task will sleep until this instant.
+    fn purge_expired_keys(&self) -> Option<Instant> {
+        if self.is_shutdown() {
+            // The database is shutting down. All handles to the shared state
+            // have dropped. The background task should exit.
+            return None;
+        }
+
+        if !self.active_expire.load(Ordering::Relaxed) {
+            // Active expiration is disabled. The background task waits until
+            // it is notified that it was enabled again.
+            return None;
+        }
+
+        self.remove_expired_keys(Instant::now()).1
+    }
+
+    /// Removes all keys that expired at or before `now` from every shard.
+    /// Returns the number of keys removed along with the `Instant` at which
+    /// the **next** key will expire, if any.
+    fn remove_expired_keys(&self, now: Instant) -> (usize, Option<Instant>) {
+        // Shards are locked one at a time, so the purge does not hold up
+        // commands on the other shards.
+        self.shards
+            .iter()
+            .map(|state| state.lock().unwrap().remove_expired_keys(now))
+            .fold((0, None), |(removed, next), (r, n)| {
+                (removed + r, next.into_iter().chain(n).min())
+            })
+    }
+
+    /// Returns `true` if the database is shutting down
+    ///
+    /// The `shutdown` flag is set when all `Db` values have dropped, indicating
+    /// that the shared state can no longer be accessed.
+    fn is_shutdown(&self) -> bool {
+        self.shutdown.load(Ordering::Relaxed)
+    }
+}
+
+impl Entry {
+    /// Returns `true` if the entry expired at or before `now`.
+    fn is_expired(&self, now: Instant) -> bool {
+        self.expires_at.map(|when| when <= now).unwrap_or(false)
+    }
+}
+
+impl Value {
+    /// Returns the string, or a `WRONGTYPE` error if the value is of another
+    /// type.
+    fn as_string(&self) -> crate::Result<&Bytes> {
+        match self {
+            Value::String(data) => Ok(data),
+            _ => Err(WRONGTYPE.into()),
+        }
+    }
+
+    /// Returns the sorted set, or a `WRONGTYPE` error if the value is of
+    /// another type.
+    fn as_sorted_set(&self) -> crate::Result<&SortedSet> {
+        match self {
+            Value::SortedSet(set) => Ok(set),
+            _ => Err(WRONGTYPE.into()),
+        }
+    }
+
+    /// Mutable variant of `as_sorted_set`.
+    fn as_sorted_set_mut(&mut self) -> crate::Result<&mut SortedSet> {
+        match self {
+            Value::SortedSet(set) => Ok(set),
+            _ => Err(WRONGTYPE.into()),
+        }
+    }
+
+    /// Returns the set, or a `WRONGTYPE` error if the value is of another
+    /// type.
+    fn as_set(&self) -> crate::Result<&HashSet<Bytes>> {
+        match self {
+            Value::Set(set) => Ok(set),
+            _ => Err(WRONGTYPE.into()),
+        }
+    }
+
+    /// Mutable variant of `as_set`.
+    fn as_set_mut(&mut self) -> crate::Result<&mut HashSet<Bytes>> {
+        match self {
+            Value::Set(set) => Ok(set),
+            _ => Err(WRONGTYPE.into()),
+        }
+    }
+
+    /// Returns the type name reported by `TYPE`.
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::SortedSet(_) => "zset",
+            Value::Set(_) => "set",
+        }
+    }
+
+    /// Returns the encoding name reported by `OBJECT ENCODING`.
+    ///
+    /// mini-redis does not actually use different representations, but the
+    /// names Redis would report are used so tools relying on them keep
+    /// working.
+    fn encoding(&self) -> &'static str {
+        match self {
+            // Redis stores strings that look like 64 bit integers as integers,
+            // short strings inline with the object header and everything else
+            // in a separate allocation.
+            Value::String(data) if data.len() <= 20 && parse_i64(data).is_some() => "int",
+            Value::String(data) if data.len() <= 44 => "embstr",
+            Value::String(_) => "raw",
+            // Small sorted sets are stored compactly, larger ones in a skip
+            // list, using Redis' default thresholds.
+            Value::SortedSet(set) if set.len() <= 128 && set.iter().all(|(m, _)| m.len() <= 64) => {
+                "listpack"
+            }
+            Value::SortedSet(_) => "skiplist",
+            Value::Set(set) if set.len() <= 512 && set.iter().all(|m| parse_i64(m).is_some()) => {
+                "intset"
+            }
+            Value::Set(set) if set.len() <= 128 && set.iter().all(|m| m.len() <= 64) => "listpack",
+            Value::Set(_) => "hashtable",
+        }
+    }
+}
+
+impl State {
+    /// Inserts an entry, returning the entry previously stored at `key`.
+    ///
+    /// All insertions go through here so `used_memory` stays accurate.
+    fn insert(&mut self, key: String, mut entry: Entry) -> Option<Entry> {
+        entry.version = self.next_version();
+        self.used_memory += entry_size(key.len(), &entry.data);
+
+        let key_len = key.len();
+        let prev = self.entries.insert(key, entry);
+
+        if let Some(prev) = &prev {
+            self.used_memory -= entry_size(key_len, &prev.data);
+        }
+
+        prev
+    }
+
+    /// Returns a version no value has had yet, see `Entry::version`.
+    fn next_version(&mut self) -> u64 {
+        self.next_version += 1;
+        self.next_version
+    }
+
+    /// Removes the entry stored at `key`, if any.
+    ///
+    /// All removals go through here so `used_memory` stays accurate.
+    fn remove(&mut self, key: &str) -> Option<Entry> {
+        let prev = self.entries.remove(key)?;
+        self.used_memory -= entry_size(key.len(), &prev.data);
+        Some(prev)
+    }
+
+    /// Removes `key` if it expired but has not been purged yet.
+    ///
+    /// Called before modifying a value in place, so the stale expiration does
+    /// not apply to the new value.
+    fn remove_if_expired(&mut self, key: &str, now: Instant) {
+        if self
+            .entries
+            .get(key)
+            .is_some_and(|entry| entry.is_expired(now))
+        {
+            self.remove_keys(&[key.to_string()]);
+            self.notify(notify::EXPIRED, "expired", key);
+        }
+    }
+
+    /// Removes the given keys, returning the keys that existed along with
+    /// their entries.
+    fn remove_keys(&mut self, keys: &[String]) -> Vec<(String, Entry)> {
+        let mut removed = vec![];
+
+        for key in keys {
+            if let Some(prev) = self.remove(key) {
+                // Also stop tracking the key's expiration, if it had one.
+                if let Some(when) = prev.expires_at {
+                    self.expirations.remove(&(when, key.clone()));
+                }
+
+                removed.push((key.clone(), prev));
+            }
+        }
+
+        removed
+    }
+
+    /// Publishes the keyspace event `event` of `class` for `key`, if enabled.
+    fn notify(&self, class: u32, event: &str, key: &str) {
+        self.pub_sub.lock().unwrap().notify(class, event, key);
+    }
+
+    fn next_expiration(&self) -> Option<Instant> {
+        self.expirations
+            .iter()
+            .next()
+            .map(|expiration| expiration.0)
+    }
+
+    /// Removes all keys that expired at or before `now`. Returns the number of
+    /// keys removed along with the `Instant` at which the **next** key will
+    /// expire, if any.
+    fn remove_expired_keys(&mut self, now: Instant) -> (usize, Option<Instant>) {
+        let mut removed = 0;
+
+        while let Some(&(when, ref key)) = self.expirations.iter().next() {
+            if when > now {
+                // Done purging, `when` is the instant at which the next key
+                // expires. The worker task will wait until this instant.
+                return (removed, Some(when));
+            }
+
+            // The key expired, remove it.
+            let key = key.clone();
+            self.remove(&key);
+            self.notify(notify::EXPIRED, "expired", &key);
+            self.expirations.remove(&(when, key));
+            removed += 1;
+        }
+
+        (removed, None)
+    }
+}
+
+impl PubSub {
+    /// Publish a message to the channel. See `Db::publish`.
+    fn publish(&self, key: &str, value: Bytes) -> usize {
+        let num_subscribers = self
+            .channels
+            .get(key)
+            // On a successful message send on the broadcast channel, the number
+            // of subscribers is returned. An error indicates there are no
+            // receivers, in which case, `0` should be returned.
+            .map(|tx| tx.send(value.clone()).unwrap_or(0))
+            // If there is no entry for the channel key, then there are no
+            // subscribers. In this case, return `0`.
+            .unwrap_or(0);
+
+        // Every pattern is checked against the channel. A client subscribed
+        // to several matching patterns receives the message once per pattern,
+        // as with Redis.
+        let num_pattern_subscribers: usize = self
+            .patterns
+            .iter()
+            .filter(|(pattern, _)| glob_match(pattern.as_bytes(), key.as_bytes()))
+            .map(|(_, tx)| tx.send((key.to_string(), value.clone())).unwrap_or(0))
+            .sum();
+
+        num_subscribers + num_pattern_subscribers
+    }
+
+    /// Publishes the keyspace event `event` of `class` for `key`, if enabled.
+    ///
+    /// The event name is published to the key's keyspace channel, and the key
+    /// to the event's keyevent channel, as Redis does.
+    fn notify(&self, class: u32, event: &str, key: &str) {
+        let events = self.notify_keyspace_events;
+
+        if !events.includes(class) {
+            return;
+        }
+
+        if events.keyspace() {
+            let channel = format!("__keyspace@0__:{}", key);
+            self.publish(&channel, Bytes::copy_from_slice(event.as_bytes()));
+        }
+
+        if events.keyevent() {
+            let channel = format!("__keyevent@0__:{}", event);
+            self.publish(&channel, Bytes::copy_from_slice(key.as_bytes()));
+        }
+    }
+}
+
+/// Returns the index of the byte holding the bit at `offset`, and the mask
+/// selecting that bit within the byte. Bits are numbered from the most
+/// significant bit of the first byte, like Redis does.
+fn bit_position(offset: u32) -> (usize, u8) {
+    (offset as usize / 8, 0x80 >> (offset % 8))
+}
+
+/// Approximate overhead, in bytes, of storing an entry on top of its key and
+/// value: the `Entry` itself plus the hash table bookkeeping.
+const ENTRY_OVERHEAD: usize = 64;
+
+/// Approximate number of bytes used to store `value` under a key of `key_len`
+/// bytes.
+fn entry_size(key_len: usize, value: &Value) -> usize {
+    let value_len = match value {
+        Value::String(data) => data.len(),
+        Value::SortedSet(set) => set.size(),
+        Value::Set(set) => set.iter().map(|member| set_member_size(member)).sum(),
+    };
+
+    key_len + value_len + ENTRY_OVERHEAD
+}
+
+/// Approximate overhead, in bytes, of storing a set member on top of its
+/// data: the `Bytes` handle and the hash table bookkeeping.
+const SET_MEMBER_OVERHEAD: usize = 40;
+
+/// Approximate number of bytes used to store `member` in a set.
+fn set_member_size(member: &[u8]) -> usize {
+    member.len() + SET_MEMBER_OVERHEAD
+}
+
+/// Returns the current Unix time, in seconds. A clock set before 1970 is
+/// reported as the epoch.
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or(0)
+}
+
+/// Decodes a value serialized by `Db::dump`.
+fn decode_dump(payload: &[u8]) -> crate::Result<Value> {
+    let (value_type, mut src, version) = match payload {
+        [value_type, src @ .., version] => (*value_type, src, *version),
+        _ => return Err("ERR DUMP payload version or checksum are wrong".into()),
+    };
+
+    if version != DUMP_VERSION {
+        return Err("ERR DUMP payload version or checksum are wrong".into());
+    }
+
+    match snapshot::get_value(&mut src, value_type) {
+        Ok(value) if src.is_empty() => Ok(value),
+        _ => Err("ERR Bad data format".into()),
+    }
+}
+
+/// Parses `data` as a base 10, signed 64 bit integer. The entire input must be
+/// consumed for the parse to succeed.
+fn parse_i64(data: &[u8]) -> Option<i64> {
+    std::str::from_utf8(data).ok()?.parse().ok()
+}
+
+/// Routine executed by the background task.
+///
+/// Wait to be notified. On notification, purge any expired keys from the shared
+/// state handle. If `shutdown` is set, terminate the task.
+async fn purge_expired_tasks(shared: Arc<Shared>) {
+    // The task is dropped without completing when its runtime shuts down. The
+    // `Db` may outlive the runtime, so the task is then spawned again by the
+    // next caller within a runtime.
+    let _respawn = RespawnPurgeTask(&shared);
+
+    // If the shutdown flag is set, then the task should exit.
+    while !shared.is_shutdown() {
+        // Purge all keys that are expired. The function returns the instant at
+        // which the **next** key will expire. The worker should wait until the
+        // instant has passed then purge again.
+        if let Some(when) = shared.purge_expired_keys() {
+            // Wait until the next key expires **or** until the background task
+            // is notified. If the task is notified, then it must reload its
+            // state as new keys have been set to expire early. This is done by
+            // looping.
+            tokio::select! {
+                _ = time::sleep_until(when) => {}
+                _ = shared.background_task.notified() => {}
+            }
+        } else {
+            // There are no keys expiring in the future. Wait until the task is
+            // notified.
+            shared.background_task.notified().await;
+        }
+    }
+
+    debug!("Purge background task shut down")
+}
+
+/// Marks the background task as yet to be spawned when dropped, unless the
+/// `Db` is shutting down.
+struct RespawnPurgeTask<'a>(&'a Shared);
+
+impl Drop for RespawnPurgeTask<'_> {
+    fn drop(&mut self) {
+        if !self.0.is_shutdown() {
+            self.0.spawn_purge_task.store(true, Ordering::Release);
+        }
+    }
+}
+
+/// Returns `true` if `name` matches the glob-style `pattern`.
+///
+/// The pattern syntax is the one Redis uses for `PSUBSCRIBE`:
+///
+/// * `?` matches any single byte.
+/// * `*` matches any sequence of bytes, including an empty one.
+/// * `[abc]` matches any of the listed bytes, `[^abc]` any byte not listed and
+///   `[a-z]` any byte in the range.
+/// * `\` escapes the following byte, so it is matched literally.
+pub(crate) fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    let (mut p, mut n) = (0, 0);
+
+    // The positions just after the most recent `*` and the byte of `name` it
+    // is currently assumed to stop at. On a mismatch, the `*` is extended by
+    // one byte and matching resumes from there.
+    let mut backtrack = None;
+
+    while n < name.len() {
+        let matched = match pattern.get(p) {
+            Some(b'*') => {
+                p += 1;
+                backtrack = Some((p, n));
+                continue;
+            }
+            Some(b'?') => {
+                p += 1;
+                true
+            }
+            Some(b'[') => {
+                let (matched, len) = match_class(&pattern[p..], name[n]);
+                p += len;
+                matched
+            }
+            Some(b'\\') if p + 1 < pattern.len() => {
+                p += 2;
+                pattern[p - 1] == name[n]
+            }
+            Some(&c) => {
+                p += 1;
+                c == name[n]
+            }
+            None => false,
+        };
+
+        if matched {
+            n += 1;
+        } else if let Some((star_p, star_n)) = backtrack {
+            p = star_p;
+            n = star_n + 1;
+            backtrack = Some((star_p, n));
+        } else {
+            return false;
+        }
+    }
+
+    // All of `name` has been consumed. Only trailing `*`s may remain.
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// Matches `c` against the character class at the start of `pattern`, which
+/// must begin with `[`.
+///
+/// Returns whether `c` matched along with the length of the class, including
+/// the closing `]`. An unterminated class extends to the end of the pattern.
+fn match_class(pattern: &[u8], c: u8) -> (bool, usize) {
+    let mut i = 1;
+
+    let negate = pattern.get(i) == Some(&b'^');
+    if negate {
+        i += 1;
+    }
+
+    let mut matched = false;
+
+    while i < pattern.len() && pattern[i] != b']' {
+        if pattern[i] == b'\\' && i + 1 < pattern.len() {
+            matched |= pattern[i + 1] == c;
+            i += 2;
+        } else if i + 2 < pattern.len() && pattern[i + 1] == b'-' && pattern[i + 2] != b']' {
+            let (start, end) = (pattern[i], pattern[i + 2]);
+            matched |= start.min(end) <= c && c <= start.max(end);
+            i += 3;
+        } else {
+            matched |= pattern[i] == c;
+            i += 1;
+        }
+    }
+
+    (matched != negate, (i + 1).min(pattern.len()))
 }
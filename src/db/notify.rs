@@ -0,0 +1,152 @@
+//! Keyspace notifications, see `ServerBuilder::notify_keyspace_events`.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Publish events to `__keyspace@0__:<key>` channels, with the event name as
+/// the message.
+const KEYSPACE: u32 = 1 << 0;
+
+/// Publish events to `__keyevent@0__:<event>` channels, with the key as the
+/// message.
+const KEYEVENT: u32 = 1 << 1;
+
+/// Generic commands, such as `DEL`, and setting an expiration.
+pub(crate) const GENERIC: u32 = 1 << 2;
+
+/// String commands, such as `SET`.
+pub(crate) const STRING: u32 = 1 << 3;
+
+/// List commands.
+const LIST: u32 = 1 << 4;
+
+/// Set commands.
+pub(crate) const SET: u32 = 1 << 5;
+
+/// Hash commands.
+const HASH: u32 = 1 << 6;
+
+/// Sorted set commands.
+pub(crate) const ZSET: u32 = 1 << 7;
+
+/// Keys removed because they expired.
+pub(crate) const EXPIRED: u32 = 1 << 8;
+
+/// Keys evicted because of `maxmemory`.
+const EVICTED: u32 = 1 << 9;
+
+/// Every event class, what the `A` flag stands for.
+const ALL: u32 = GENERIC | STRING | LIST | SET | HASH | ZSET | EXPIRED | EVICTED;
+
+/// The flag character of each event class, in the order `Display` writes
+/// them.
+const FLAGS: &[(char, u32)] = &[
+    ('g', GENERIC),
+    ('$', STRING),
+    ('l', LIST),
+    ('s', SET),
+    ('h', HASH),
+    ('z', ZSET),
+    ('x', EXPIRED),
+    ('e', EVICTED),
+];
+
+/// The keyspace events a server publishes, configured with the same flags as
+/// Redis' `notify-keyspace-events`.
+///
+/// `K` and `E` select the channels events are published to, keyspace and
+/// keyevent channels respectively. The other flags select the classes of
+/// events published:
+///
+/// * `g` -- Generic events: `del` and `expire`.
+/// * `$` -- String events: `set`, `setrange`, `setbit` and `incrbyfloat`.
+/// * `s` -- Set events: `sadd`, `srem`, `sinterstore`, `sunionstore` and
+///   `sdiffstore`.
+/// * `z` -- Sorted set events: `zadd`.
+/// * `x` -- `expired` events, when a key is removed because it expired,
+///   either by the background task or when a command modifies it.
+/// * `A` -- Every class, an alias for `g$lshzxe`.
+///
+/// Commands that leave a value unchanged, such as `SADD` of an existing
+/// member, publish nothing. `l`, `h` and `e` are accepted for compatibility,
+/// but no events are published for those classes yet. Nothing is published
+/// unless `K` or `E` is given along with at least one class.
+///
+/// # Examples
+///
+/// ```
+/// use mini_redis::server::KeyspaceEvents;
+///
+/// let events: KeyspaceEvents = "KEA".parse().unwrap();
+/// assert_eq!("AKE", events.to_string());
+///
+/// assert!("Kq".parse::<KeyspaceEvents>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeyspaceEvents {
+    flags: u32,
+}
+
+impl KeyspaceEvents {
+    /// Returns `true` if events of `class` are published to any channel.
+    pub(crate) fn includes(&self, class: u32) -> bool {
+        self.flags & class != 0 && self.flags & (KEYSPACE | KEYEVENT) != 0
+    }
+
+    /// Returns `true` if events are published to keyspace channels.
+    pub(crate) fn keyspace(&self) -> bool {
+        self.flags & KEYSPACE != 0
+    }
+
+    /// Returns `true` if events are published to keyevent channels.
+    pub(crate) fn keyevent(&self) -> bool {
+        self.flags & KEYEVENT != 0
+    }
+}
+
+impl FromStr for KeyspaceEvents {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> crate::Result<KeyspaceEvents> {
+        let mut flags = 0;
+
+        for c in s.chars() {
+            flags |= match c {
+                'K' => KEYSPACE,
+                'E' => KEYEVENT,
+                'A' => ALL,
+                c => match FLAGS.iter().find(|(flag, _)| *flag == c) {
+                    Some((_, class)) => *class,
+                    None => return Err(format!("invalid keyspace event flag '{}'", c).into()),
+                },
+            };
+        }
+
+        Ok(KeyspaceEvents { flags })
+    }
+}
+
+impl fmt::Display for KeyspaceEvents {
+    /// Writes the flags, with `A` standing for every class, like Redis does.
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        if self.flags & ALL == ALL {
+            write!(fmt, "A")?;
+        } else {
+            for (flag, class) in FLAGS {
+                if self.flags & class != 0 {
+                    write!(fmt, "{}", flag)?;
+                }
+            }
+        }
+
+        if self.keyspace() {
+            write!(fmt, "K")?;
+        }
+
+        if self.keyevent() {
+            write!(fmt, "E")?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,315 @@
+//! Point-in-time copies of the key space, see `Db::snapshot`.
+
+use super::{SortedSet, Value};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+/// Identifies the snapshot format, followed by a version byte.
+const MAGIC: &[u8] = b"MINIREDIS";
+
+/// Version of the snapshot format. Bumped whenever the encoding changes.
+const VERSION: u8 = 1;
+
+/// Type tag of string values.
+const TYPE_STRING: u8 = 0;
+
+/// Type tag of sorted set values.
+const TYPE_SORTED_SET: u8 = 1;
+
+/// Type tag of set values.
+const TYPE_SET: u8 = 2;
+
+/// Distinguishes the temporary files of concurrent `write_to_file` calls.
+static NEXT_TEMP_FILE: AtomicUsize = AtomicUsize::new(0);
+
+/// A copy of the key space, taken by `Db::snapshot` and loaded back with
+/// `Db::restore`.
+///
+/// Only keys and their values are captured. Each key's expiration is stored
+/// as the time to live remaining when the snapshot was taken, so restored keys
+/// expire that long after being restored. Pub/sub subscriptions are not part
+/// of the snapshot.
+///
+/// A snapshot can be converted to bytes with `encode` and back with `decode`,
+/// or stored in a file with `write_to_file` and `read_from_file`.
+///
+/// # Format
+///
+/// The encoding is a simple length-prefixed binary format. All integers are
+/// big-endian.
+///
+/// ```text
+/// "MINIREDIS" version:u8 count:u64 entry*
+///
+/// entry: type:u8 key_len:u32 key value has_ttl:u8 [ttl_ms:u64]
+///
+/// value (string):     len:u32 data
+/// value (sorted set): count:u32 (member_len:u32 member score:f64)*
+/// value (set):        count:u32 (member_len:u32 member)*
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DbSnapshot {
+    pub(super) entries: Vec<SnapshotEntry>,
+}
+
+/// A key captured in a `DbSnapshot`.
+#[derive(Debug, Clone)]
+pub(super) struct SnapshotEntry {
+    pub(super) key: String,
+    pub(super) value: Value,
+
+    /// Time to live remaining when the snapshot was taken, `None` if the key
+    /// does not expire.
+    pub(super) ttl: Option<Duration>,
+}
+
+impl DbSnapshot {
+    /// Returns the number of keys in the snapshot.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the snapshot contains no keys.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Encodes the snapshot into bytes, which `decode` turns back into an
+    /// equivalent snapshot.
+    pub fn encode(&self) -> Bytes {
+        let mut buf = BytesMut::new();
+
+        buf.put_slice(MAGIC);
+        buf.put_u8(VERSION);
+        buf.put_u64(self.entries.len() as u64);
+
+        for entry in &self.entries {
+            buf.put_u8(type_tag(&entry.value));
+            put_bytes(&mut buf, entry.key.as_bytes());
+            put_value(&mut buf, &entry.value);
+
+            match entry.ttl {
+                Some(ttl) => {
+                    buf.put_u8(1);
+                    buf.put_u64(ttl.as_millis() as u64);
+                }
+                None => buf.put_u8(0),
+            }
+        }
+
+        buf.freeze()
+    }
+
+    /// Decodes a snapshot previously encoded with `encode`.
+    ///
+    /// Returns an error if `src` is not a valid snapshot.
+    pub fn decode(mut src: &[u8]) -> crate::Result<DbSnapshot> {
+        if src.len() < MAGIC.len() || &src[..MAGIC.len()] != MAGIC {
+            return Err("invalid snapshot; missing header".into());
+        }
+        src.advance(MAGIC.len());
+
+        let version = get_u8(&mut src)?;
+        if version != VERSION {
+            return Err(format!("invalid snapshot; unsupported version {}", version).into());
+        }
+
+        let count = get_u64(&mut src)?;
+
+        // The count comes from the input, so it is not trusted to size the
+        // allocation up front.
+        let mut entries = vec![];
+
+        for _ in 0..count {
+            let value_type = get_u8(&mut src)?;
+
+            let key = String::from_utf8(get_bytes(&mut src)?.to_vec())
+                .map_err(|_| "invalid snapshot; key is not valid UTF-8")?;
+
+            let value = get_value(&mut src, value_type)?;
+
+            let ttl = match get_u8(&mut src)? {
+                0 => None,
+                1 => Some(Duration::from_millis(get_u64(&mut src)?)),
+                _ => return Err("invalid snapshot; invalid expiration".into()),
+            };
+
+            entries.push(SnapshotEntry { key, value, ttl });
+        }
+
+        if src.has_remaining() {
+            return Err("invalid snapshot; trailing data".into());
+        }
+
+        Ok(DbSnapshot { entries })
+    }
+
+    /// Writes the encoded snapshot to the file at `path`, replacing it.
+    ///
+    /// The snapshot is written to a temporary file in the same directory,
+    /// which is then renamed to `path`. This way, `path` never holds a
+    /// partially written snapshot, even if writing fails midway.
+    pub async fn write_to_file(&self, path: &Path) -> crate::Result<()> {
+        let temp = temp_path(path);
+
+        let result = async {
+            let mut file = fs::File::create(&temp).await?;
+            file.write_all(&self.encode()).await?;
+            file.sync_all().await?;
+            fs::rename(&temp, path).await
+        }
+        .await;
+
+        if result.is_err() {
+            let _ = fs::remove_file(&temp).await;
+        }
+
+        Ok(result?)
+    }
+
+    /// Reads a snapshot previously written with `write_to_file`.
+    pub async fn read_from_file(path: &Path) -> crate::Result<DbSnapshot> {
+        let data = fs::read(path).await?;
+        DbSnapshot::decode(&data)
+    }
+}
+
+/// Returns the path of a temporary file, next to `path`, to write a snapshot
+/// to before renaming it.
+fn temp_path(path: &Path) -> PathBuf {
+    let n = NEXT_TEMP_FILE.fetch_add(1, Ordering::Relaxed);
+
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}.{}.tmp", std::process::id(), n));
+
+    path.with_file_name(name)
+}
+
+/// Returns the tag identifying the type of `value` in the encoding.
+pub(super) fn type_tag(value: &Value) -> u8 {
+    match value {
+        Value::String(_) => TYPE_STRING,
+        Value::SortedSet(_) => TYPE_SORTED_SET,
+        Value::Set(_) => TYPE_SET,
+    }
+}
+
+/// Writes `value`, without its type tag.
+pub(super) fn put_value(buf: &mut BytesMut, value: &Value) {
+    match value {
+        Value::String(data) => put_bytes(buf, data),
+        Value::SortedSet(set) => {
+            buf.put_u32(set.len() as u32);
+
+            for (member, score) in set.iter() {
+                put_bytes(buf, member.as_bytes());
+                buf.put_f64(score);
+            }
+        }
+        Value::Set(set) => {
+            buf.put_u32(set.len() as u32);
+
+            for member in set {
+                put_bytes(buf, member);
+            }
+        }
+    }
+}
+
+/// Reads a value written by `put_value`, of the type identified by
+/// `value_type`.
+pub(super) fn get_value(src: &mut &[u8], value_type: u8) -> crate::Result<Value> {
+    let value = match value_type {
+        TYPE_STRING => Value::String(get_bytes(src)?),
+        TYPE_SORTED_SET => Value::SortedSet(get_sorted_set(src)?),
+        TYPE_SET => {
+            let count = get_u32(src)?;
+            let mut set = HashSet::new();
+
+            for _ in 0..count {
+                set.insert(get_bytes(src)?);
+            }
+
+            Value::Set(set)
+        }
+        _ => return Err(format!("invalid snapshot; unknown type {}", value_type).into()),
+    };
+
+    Ok(value)
+}
+
+/// Writes `data` prefixed by its length.
+fn put_bytes(buf: &mut BytesMut, data: &[u8]) {
+    buf.put_u32(data.len() as u32);
+    buf.put_slice(data);
+}
+
+fn get_bytes(src: &mut &[u8]) -> crate::Result<Bytes> {
+    let len = get_u32(src)? as usize;
+
+    if src.remaining() < len {
+        return Err(truncated());
+    }
+
+    let data = Bytes::copy_from_slice(&src[..len]);
+    src.advance(len);
+    Ok(data)
+}
+
+fn get_sorted_set(src: &mut &[u8]) -> crate::Result<SortedSet> {
+    let count = get_u32(src)?;
+    let mut set = SortedSet::default();
+
+    for _ in 0..count {
+        let member = String::from_utf8(get_bytes(src)?.to_vec())
+            .map_err(|_| "invalid snapshot; member is not valid UTF-8")?;
+
+        if src.remaining() < 8 {
+            return Err(truncated());
+        }
+
+        let score = src.get_f64();
+
+        if score.is_nan() {
+            return Err("invalid snapshot; invalid score".into());
+        }
+
+        set.insert(member, score);
+    }
+
+    Ok(set)
+}
+
+fn get_u8(src: &mut &[u8]) -> crate::Result<u8> {
+    if !src.has_remaining() {
+        return Err(truncated());
+    }
+
+    Ok(src.get_u8())
+}
+
+fn get_u32(src: &mut &[u8]) -> crate::Result<u32> {
+    if src.remaining() < 4 {
+        return Err(truncated());
+    }
+
+    Ok(src.get_u32())
+}
+
+fn get_u64(src: &mut &[u8]) -> crate::Result<u64> {
+    if src.remaining() < 8 {
+        return Err(truncated());
+    }
+
+    Ok(src.get_u64())
+}
+
+fn truncated() -> crate::Error {
+    "invalid snapshot; unexpected end of data".into()
+}
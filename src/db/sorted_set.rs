@@ -0,0 +1,141 @@
+//! The sorted set value type, see `Value::SortedSet`.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Approximate overhead, in bytes, of storing a member on top of its name:
+/// its score, stored twice, and the bookkeeping of both maps.
+const MEMBER_OVERHEAD: usize = 48;
+
+/// A set of unique members, each associated with a score, ordered by score.
+///
+/// Members are stored twice: grouped by score in a `BTreeMap`, which keeps
+/// them sorted, and in a `HashMap` from member to score, which finds the score
+/// of a member without scanning the whole set. Members sharing a score are
+/// kept in a `BTreeSet`, so ties are ordered lexicographically, like Redis
+/// does.
+#[derive(Debug, Clone, Default)]
+pub(super) struct SortedSet {
+    /// Members grouped by score, in ascending order.
+    by_score: BTreeMap<Score, BTreeSet<String>>,
+
+    /// The score of each member.
+    scores: HashMap<String, f64>,
+
+    /// Approximate number of bytes used by the members, kept up to date as
+    /// members are added so it does not need to be computed on each change.
+    size: usize,
+}
+
+/// A score, ordered with `f64::total_cmp` so it can be used as a `BTreeMap`
+/// key.
+///
+/// Scores are never NaN, and `-0.0` is stored as `0.0`, so this order matches
+/// the numeric one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Score(f64);
+
+impl SortedSet {
+    /// Returns the number of members.
+    pub(super) fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    /// Returns the approximate number of bytes used by the members.
+    pub(super) fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Adds `member` with `score`, or updates the score of an existing member.
+    /// Returns `true` if the member was added.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `score` is NaN.
+    pub(super) fn insert(&mut self, member: String, score: f64) -> bool {
+        assert!(!score.is_nan(), "sorted set scores cannot be NaN");
+
+        // Adding `0.0` turns `-0.0` into `0.0`, so both are the same score.
+        let score = score + 0.0;
+
+        let added = match self.scores.insert(member.clone(), score) {
+            // The score did not change, so neither does the order.
+            Some(prev) if prev == score => return false,
+            Some(prev) => {
+                self.remove_from_score(&member, prev);
+                false
+            }
+            None => {
+                self.size += member.len() + MEMBER_OVERHEAD;
+                true
+            }
+        };
+
+        self.by_score
+            .entry(Score(score))
+            .or_default()
+            .insert(member);
+
+        added
+    }
+
+    /// Returns the score of `member`, if it is in the set.
+    pub(super) fn score(&self, member: &str) -> Option<f64> {
+        self.scores.get(member).copied()
+    }
+
+    /// Returns the members, with their scores, in ascending order.
+    pub(super) fn iter(&self) -> impl Iterator<Item = (&str, f64)> {
+        self.by_score
+            .iter()
+            .flat_map(|(score, members)| members.iter().map(move |m| (m.as_str(), score.0)))
+    }
+
+    /// Returns the members ranked between `start` and `stop`, both inclusive,
+    /// with their scores.
+    ///
+    /// Negative ranks count from the end of the set, so `-1` is the member
+    /// with the highest score. The range is clamped to the set.
+    pub(super) fn range(&self, start: i64, stop: i64) -> Vec<(String, f64)> {
+        let len = self.len() as i64;
+
+        let start = if start < 0 { start + len } else { start }.max(0);
+        let stop = if stop < 0 { stop + len } else { stop }.min(len - 1);
+
+        if start > stop {
+            return vec![];
+        }
+
+        self.iter()
+            .skip(start as usize)
+            .take((stop - start + 1) as usize)
+            .map(|(member, score)| (member.to_string(), score))
+            .collect()
+    }
+
+    /// Removes `member` from the group of members with `score`, dropping the
+    /// group once it is empty.
+    fn remove_from_score(&mut self, member: &str, score: f64) {
+        if let Some(members) = self.by_score.get_mut(&Score(score)) {
+            members.remove(member);
+
+            if members.is_empty() {
+                self.by_score.remove(&Score(score));
+            }
+        }
+    }
+}
+
+impl Eq for Score {}
+
+impl PartialOrd for Score {
+    fn partial_cmp(&self, other: &Score) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Score {
+    fn cmp(&self, other: &Score) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
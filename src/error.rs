@@ -0,0 +1,98 @@
+use std::io;
+
+/// Error returned by `Client` when a request fails.
+///
+/// Errors replied by the server are mapped to a variant by their prefix, e.g.
+/// `WRONGTYPE`, and hold the message as sent.
+///
+/// Functions still return the boxed [`crate::Error`], so a `MiniRedisError` is
+/// matched on after downcasting it:
+///
+/// ```no_run
+/// use mini_redis::{clients::Client, MiniRedisError};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut client = Client::connect("localhost:6379").await.unwrap();
+///
+///     if let Err(err) = client.get("foo").await {
+///         match err.downcast_ref::<MiniRedisError>() {
+///             Some(MiniRedisError::ServerError(msg)) => println!("rejected: {}", msg),
+///             Some(MiniRedisError::ConnectionReset) => println!("server went away"),
+///             _ => println!("failed: {}", err),
+///         }
+///     }
+/// }
+/// ```
+#[derive(Debug, thiserror::Error)]
+pub enum MiniRedisError {
+    /// Reading from or writing to the socket failed.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    /// The server sent bytes that are not a valid frame, or a frame the
+    /// request does not expect.
+    #[error("{0}")]
+    Protocol(String),
+
+    /// The server closed the connection before sending a response.
+    #[error("connection reset by server")]
+    ConnectionReset,
+
+    /// The key holds a value of another type than the command operates on.
+    #[error("{0}")]
+    WrongType(String),
+
+    /// The server requires the connection to authenticate first.
+    #[error("{0}")]
+    NoAuth(String),
+
+    /// The password passed to `AUTH` is wrong.
+    #[error("{0}")]
+    WrongPass(String),
+
+    /// The server rejects writes, as it is a replica or runs read-only.
+    #[error("{0}")]
+    ReadOnly(String),
+
+    /// The write would take the server over its `maxmemory` limit.
+    #[error("{0}")]
+    Oom(String),
+
+    /// `EXEC` discarded the transaction, as a queued command was invalid.
+    #[error("{0}")]
+    ExecAbort(String),
+
+    /// The server replied with any other error frame.
+    #[error("{0}")]
+    ServerError(String),
+}
+
+impl MiniRedisError {
+    /// Returns `true` if `err` means the connection it was returned by can no
+    /// longer be used: the socket failed, the server closed it, or the bytes
+    /// received can no longer be split into frames.
+    pub(crate) fn is_connection_error(err: &crate::Error) -> bool {
+        if err.is::<io::Error>() {
+            return true;
+        }
+
+        matches!(
+            err.downcast_ref::<MiniRedisError>(),
+            Some(
+                MiniRedisError::Io(_)
+                    | MiniRedisError::ConnectionReset
+                    | MiniRedisError::Protocol(_)
+            )
+        )
+    }
+
+    /// Converts an error returned while reading a frame. I/O errors are kept
+    /// as is, anything else means the frame could not be decoded.
+    pub(crate) fn from_read(err: crate::Error) -> MiniRedisError {
+        match err.downcast::<io::Error>() {
+            Ok(err) => MiniRedisError::Io(*err),
+            Err(err) => MiniRedisError::Protocol(err.to_string()),
+        }
+    }
+}
@@ -1,7 +1,9 @@
 //! Provides a type representing a Redis protocol frame as well as utilities for
 //! parsing frames from a byte array.
 
-use bytes::{Buf, Bytes};
+use crate::MiniRedisError;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 use std::convert::TryInto;
 use std::fmt;
 use std::io::Cursor;
@@ -9,16 +11,21 @@ use std::num::TryFromIntError;
 use std::string::FromUtf8Error;
 
 /// A frame in the Redis protocol.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Frame {
     Simple(String),
     Error(String),
-    Integer(u64),
+    Integer(i64),
     Bulk(Bytes),
     Null,
     Array(Vec<Frame>),
 }
 
+/// Bulk strings at least this long are not copied out of the read buffer by
+/// `Frame::split_from`. Smaller ones are copied, so a small value kept around,
+/// e.g. in the database, does not keep a whole read buffer alive.
+const SHARE_MIN_LEN: usize = 16 * 1024;
+
 #[derive(Debug)]
 pub enum Error {
     /// Not enough data is available to parse a message
@@ -30,7 +37,7 @@ pub enum Error {
 
 impl Frame {
     /// Returns an empty array
-    pub(crate) fn array() -> Frame {
+    pub fn array() -> Frame {
         Frame::Array(vec![])
     }
 
@@ -39,7 +46,7 @@ impl Frame {
     /// # Panics
     ///
     /// panics if `self` is not an array
-    pub(crate) fn push_bulk(&mut self, bytes: Bytes) {
+    pub fn push_bulk(&mut self, bytes: Bytes) {
         match self {
             Frame::Array(vec) => {
                 vec.push(Frame::Bulk(bytes));
@@ -53,7 +60,7 @@ impl Frame {
     /// # Panics
     ///
     /// panics if `self` is not an array
-    pub(crate) fn push_int(&mut self, value: u64) {
+    pub fn push_int(&mut self, value: i64) {
         match self {
             Frame::Array(vec) => {
                 vec.push(Frame::Integer(value));
@@ -62,114 +69,205 @@ impl Frame {
         }
     }
 
-    /// Checks if an entire message can be decoded from `src`
-    pub fn check(src: &mut Cursor<&[u8]>) -> Result<(), Error> {
-        match get_u8(src)? {
-            b'+' => {
-                get_line(src)?;
-                Ok(())
+    /// Encodes the frame, appending the bytes to `dst`.
+    ///
+    /// This does not perform any I/O. Arrays are encoded by encoding each
+    /// entry, so nested arrays are supported.
+    pub fn encode(&self, dst: &mut BytesMut) {
+        match self {
+            Frame::Simple(val) => {
+                dst.put_u8(b'+');
+                dst.put_slice(val.as_bytes());
+                dst.put_slice(b"\r\n");
             }
-            b'-' => {
-                get_line(src)?;
-                Ok(())
+            Frame::Error(val) => {
+                dst.put_u8(b'-');
+                dst.put_slice(val.as_bytes());
+                dst.put_slice(b"\r\n");
             }
-            b':' => {
-                let _ = get_decimal(src)?;
-                Ok(())
+            Frame::Integer(val) => {
+                dst.put_u8(b':');
+                put_decimal(dst, *val);
             }
-            b'$' => {
-                if b'-' == peek_u8(src)? {
-                    // Skip '-1\r\n'
-                    skip(src, 4)
-                } else {
-                    // Read the bulk string
-                    let len: usize = get_decimal(src)?.try_into()?;
-
-                    // skip that number of bytes + 2 (\r\n).
-                    skip(src, len + 2)
-                }
+            Frame::Null => {
+                dst.put_slice(b"$-1\r\n");
+            }
+            Frame::Bulk(val) => {
+                dst.put_u8(b'$');
+                put_decimal(dst, val.len() as i64);
+                dst.put_slice(val);
+                dst.put_slice(b"\r\n");
             }
-            b'*' => {
-                let len = get_decimal(src)?;
+            Frame::Array(val) => {
+                dst.put_u8(b'*');
+                put_decimal(dst, val.len() as i64);
 
-                for _ in 0..len {
-                    Frame::check(src)?;
+                for entry in val {
+                    entry.encode(dst);
                 }
-
-                Ok(())
             }
-            actual => Err(format!("protocol error; invalid frame type byte `{}`", actual).into()),
         }
     }
 
-    /// The message has already been validated with `check`.
+    /// Checks if an entire message can be decoded from `src`
+    pub fn check(src: &mut Cursor<&[u8]>) -> Result<(), Error> {
+        let start = src.position() as usize;
+        let len = FrameChecker::new().check(&src.get_ref()[start..])?;
+
+        src.set_position((start + len) as u64);
+        Ok(())
+    }
+
+    /// Parses the message at the position of `src`.
+    ///
+    /// The message is expected to have been validated with `check`. Input
+    /// that was not is still rejected with an error rather than a panic, but
+    /// a message that is incomplete may be partially parsed before
+    /// `Error::Incomplete` is returned. `parse_from` and `split_from` check
+    /// the message first.
     pub fn parse(src: &mut Cursor<&[u8]>) -> Result<Frame, Error> {
-        match get_u8(src)? {
-            b'+' => {
-                // Read the line and convert it to `Vec<u8>`
-                let line = get_line(src)?.to_vec();
+        parse_frame(src, None)
+    }
+
+    /// Parses a single frame from the start of `buf`.
+    ///
+    /// On success, the frame is removed from `buf` and returned. If `buf` does
+    /// not yet hold an entire frame, `Error::Incomplete` is returned, `buf` is
+    /// left untouched, and more data should be read before trying again.
+    ///
+    /// Unlike `parse_from`, large bulk strings are not copied: the frame's
+    /// bytes are split off `buf`, and the returned `Bytes` share its
+    /// allocation.
+    pub fn split_from(buf: &mut BytesMut) -> Result<Frame, Error> {
+        // As in `parse_from`, the frame is checked first, which gives its
+        // length without allocating anything.
+        FrameChecker::new().split_from(buf)
+    }
 
-                // Convert the line to a String
-                let string = String::from_utf8(line)?;
+    /// Parses a single frame from the start of `buf`.
+    ///
+    /// On success, the frame is returned along with the number of bytes of
+    /// `buf` that it occupied. Any bytes after those belong to the next frame.
+    /// If `buf` does not yet hold an entire frame, `Error::Incomplete` is
+    /// returned and more data should be read before trying again.
+    pub fn parse_from(buf: &[u8]) -> Result<(Frame, usize), Error> {
+        let mut src = Cursor::new(buf);
 
-                Ok(Frame::Simple(string))
-            }
-            b'-' => {
-                // Read the line and convert it to `Vec<u8>`
-                let line = get_line(src)?.to_vec();
+        // Checking is much faster than a full parse and avoids allocating the
+        // frame's data structures until the whole frame has been received.
+        Frame::check(&mut src)?;
 
-                // Convert the line to a String
-                let string = String::from_utf8(line)?;
+        // `check` advanced the cursor to the end of the frame, which gives us
+        // the frame length. Reset the position before parsing.
+        let len = src.position() as usize;
+        src.set_position(0);
 
-                Ok(Frame::Error(string))
-            }
-            b':' => {
-                let len = get_decimal(src)?;
-                Ok(Frame::Integer(len))
+        let frame = Frame::parse(&mut src)?;
+
+        Ok((frame, len))
+    }
+
+    /// Converts the frame to an error.
+    ///
+    /// An `Error` frame is converted to the `MiniRedisError` variant matching
+    /// the prefix of its message, e.g. `WRONGTYPE`. Any other frame is
+    /// converted to an "unexpected frame" `MiniRedisError::Protocol` error.
+    pub fn to_error(&self) -> crate::Error {
+        let msg = match self {
+            Frame::Error(msg) => msg.clone(),
+            frame => {
+                return MiniRedisError::Protocol(format!("unexpected frame: {}", frame)).into()
             }
-            b'$' => {
-                if b'-' == peek_u8(src)? {
-                    let line = get_line(src)?;
+        };
+
+        let err = match msg.split(' ').next().unwrap_or_default() {
+            "WRONGTYPE" => MiniRedisError::WrongType(msg),
+            "NOAUTH" => MiniRedisError::NoAuth(msg),
+            "WRONGPASS" => MiniRedisError::WrongPass(msg),
+            "READONLY" => MiniRedisError::ReadOnly(msg),
+            "OOM" => MiniRedisError::Oom(msg),
+            "EXECABORT" => MiniRedisError::ExecAbort(msg),
+            _ => MiniRedisError::ServerError(msg),
+        };
+
+        err.into()
+    }
+}
 
-                    if line != b"-1" {
-                        return Err("protocol error; invalid frame format".into());
-                    }
+/// Implementation of `Frame::parse`. If `shared` is set, it holds the bytes
+/// `src` reads from, and large bulk strings are sliced out of it rather than
+/// copied.
+fn parse_frame(src: &mut Cursor<&[u8]>, shared: Option<&Bytes>) -> Result<Frame, Error> {
+    match get_u8(src)? {
+        b'+' => {
+            // Read the line and convert it to `Vec<u8>`
+            let line = get_line(src)?.to_vec();
 
-                    Ok(Frame::Null)
-                } else {
-                    // Read the bulk string
-                    let len = get_decimal(src)?.try_into()?;
-                    let n = len + 2;
+            // Convert the line to a String
+            let string = String::from_utf8(line)?;
 
-                    if src.remaining() < n {
-                        return Err(Error::Incomplete);
-                    }
+            Ok(Frame::Simple(string))
+        }
+        b'-' => {
+            // Read the line and convert it to `Vec<u8>`
+            let line = get_line(src)?.to_vec();
 
-                    let data = Bytes::copy_from_slice(&src.chunk()[..len]);
+            // Convert the line to a String
+            let string = String::from_utf8(line)?;
 
-                    // skip that number of bytes + 2 (\r\n).
-                    skip(src, n)?;
+            Ok(Frame::Error(string))
+        }
+        b':' => {
+            let val = get_integer(src)?;
+            Ok(Frame::Integer(val))
+        }
+        b'$' => {
+            if b'-' == peek_u8(src)? {
+                let line = get_line(src)?;
 
-                    Ok(Frame::Bulk(data))
+                if line != b"-1" {
+                    return Err("protocol error; invalid frame format".into());
                 }
-            }
-            b'*' => {
-                let len = get_decimal(src)?.try_into()?;
-                let mut out = Vec::with_capacity(len);
 
-                for _ in 0..len {
-                    out.push(Frame::parse(src)?);
+                Ok(Frame::Null)
+            } else {
+                // Read the bulk string
+                let len: usize = get_decimal(src)?.try_into()?;
+                let n = len
+                    .checked_add(2)
+                    .ok_or("protocol error; invalid frame format")?;
+
+                if src.remaining() < n {
+                    return Err(Error::Incomplete);
                 }
 
-                Ok(Frame::Array(out))
+                let start = src.position() as usize;
+                let data = match shared {
+                    Some(bytes) if len >= SHARE_MIN_LEN => bytes.slice(start..start + len),
+                    _ => Bytes::copy_from_slice(&src.chunk()[..len]),
+                };
+
+                // skip that number of bytes + 2 (\r\n).
+                skip(src, n)?;
+
+                Ok(Frame::Bulk(data))
             }
-            _ => unimplemented!(),
         }
-    }
+        b'*' => {
+            let len = get_decimal(src)?.try_into()?;
+
+            // Every element takes at least 3 bytes, so an unchecked length
+            // larger than the input does not allocate more than the input
+            // can fill.
+            let mut out = Vec::with_capacity(std::cmp::min(len, src.remaining() / 3));
+
+            for _ in 0..len {
+                out.push(parse_frame(src, shared)?);
+            }
 
-    /// Converts the frame to an "unexpected frame" error
-    pub(crate) fn to_error(&self) -> crate::Error {
-        format!("unexpected frame: {}", self).into()
+            Ok(Frame::Array(out))
+        }
+        actual => Err(format!("protocol error; invalid frame type byte `{}`", actual).into()),
     }
 }
 
@@ -212,6 +310,155 @@ impl fmt::Display for Frame {
     }
 }
 
+/// Checks whether a buffer holds an entire frame, resuming where the previous
+/// check stopped.
+///
+/// A frame may arrive over many reads. Checking it from the start after each
+/// read rescans everything received so far, which is quadratic in the size of
+/// a frame arriving in small pieces, e.g. an array with many elements over a
+/// slow link. `FrameChecker` instead remembers the elements it has already
+/// checked and the arrays they belong to.
+///
+/// Each call must be given the same buffer as the previous one, with any
+/// newly received bytes appended. Once a frame is complete, or found to be
+/// invalid, the checker starts over for the next frame.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FrameChecker {
+    /// Offset of the first element not checked yet.
+    pos: usize,
+
+    /// Number of elements left in each array being checked, innermost last.
+    open_arrays: Vec<u64>,
+
+    /// Number of bytes the buffer must hold before the frame can be complete,
+    /// as far as can be told from the data received so far.
+    required: usize,
+}
+
+impl FrameChecker {
+    /// Create a checker for a new frame.
+    pub(crate) fn new() -> FrameChecker {
+        FrameChecker::default()
+    }
+
+    /// Checks if an entire frame can be decoded from the start of `buf`,
+    /// returning its length.
+    pub(crate) fn check(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        // A bulk string whose length is known has not been received in full,
+        // so there is nothing new to check yet.
+        if buf.len() < self.required {
+            return Err(Error::Incomplete);
+        }
+
+        let mut src = Cursor::new(buf);
+        src.set_position(self.pos as u64);
+
+        loop {
+            match check_element(&mut src, &mut self.required) {
+                // The element is an array. Its elements are checked next.
+                Ok(Some(len)) => self.open_arrays.push(len),
+                Ok(None) => self.complete_element(),
+                // The element is checked again, from its start, once more
+                // data has been received.
+                Err(Error::Incomplete) => return Err(Error::Incomplete),
+                Err(err) => {
+                    *self = FrameChecker::new();
+                    return Err(err);
+                }
+            }
+
+            self.pos = src.position() as usize;
+
+            if self.open_arrays.is_empty() {
+                let len = self.pos;
+                *self = FrameChecker::new();
+                return Ok(len);
+            }
+        }
+    }
+
+    /// Returns the number of bytes the buffer must hold before the frame can
+    /// be complete, as far as can be told from the data checked so far.
+    ///
+    /// Only bulk strings declare their length ahead of their content, so this
+    /// is the end of the last bulk string whose length prefix has been read.
+    /// When the frame is stalled elsewhere, e.g. on a line that has not been
+    /// terminated yet, the returned value is no larger than the buffer.
+    pub(crate) fn required(&self) -> usize {
+        self.required
+    }
+
+    /// Parses a single frame from the start of `buf`, removing it from `buf`.
+    /// See `Frame::split_from`.
+    pub(crate) fn split_from(&mut self, buf: &mut BytesMut) -> Result<Frame, Error> {
+        let len = self.check(&buf[..])?;
+
+        let bytes = buf.split_to(len).freeze();
+        parse_frame(&mut Cursor::new(&bytes[..]), Some(&bytes))
+    }
+
+    /// Records that an element has been checked, closing every array it
+    /// completes.
+    fn complete_element(&mut self) {
+        while let Some(left) = self.open_arrays.last_mut() {
+            *left -= 1;
+
+            if *left > 0 {
+                return;
+            }
+
+            self.open_arrays.pop();
+        }
+    }
+}
+
+/// Checks a single element. Returns the number of elements of an array, which
+/// follow and are checked separately, or `None` if the element is complete.
+///
+/// `required` is set to the end offset of a bulk string as its length prefix
+/// is read.
+fn check_element(src: &mut Cursor<&[u8]>, required: &mut usize) -> Result<Option<u64>, Error> {
+    match get_u8(src)? {
+        b'+' => {
+            get_line(src)?;
+            Ok(None)
+        }
+        b'-' => {
+            get_line(src)?;
+            Ok(None)
+        }
+        b':' => {
+            let _ = get_integer(src)?;
+            Ok(None)
+        }
+        b'$' => {
+            if b'-' == peek_u8(src)? {
+                // Skip '-1\r\n'
+                skip(src, 4)?;
+            } else {
+                // Read the bulk string
+                let len: usize = get_decimal(src)?.try_into()?;
+
+                // Record how much data the bulk string needs before
+                // skipping over it, so an incomplete read can be sized.
+                *required = (src.position() as usize)
+                    .saturating_add(len)
+                    .saturating_add(2);
+
+                // skip that number of bytes + 2 (\r\n).
+                skip(src, len + 2)?;
+            }
+
+            Ok(None)
+        }
+        b'*' => match get_decimal(src)? {
+            0 => Ok(None),
+            len => Ok(Some(len)),
+        },
+        actual => Err(format!("protocol error; invalid frame type byte `{}`", actual).into()),
+    }
+}
+
 fn peek_u8(src: &mut Cursor<&[u8]>) -> Result<u8, Error> {
     if !src.has_remaining() {
         return Err(Error::Incomplete);
@@ -237,6 +484,16 @@ fn skip(src: &mut Cursor<&[u8]>, n: usize) -> Result<(), Error> {
     Ok(())
 }
 
+/// Write a new-line terminated decimal
+fn put_decimal(dst: &mut BytesMut, val: i64) {
+    // Every integer, and the length of every bulk string and array, goes
+    // through here. `itoa` formats the value into a stack buffer without the
+    // overhead of the `fmt` machinery.
+    let mut buf = itoa::Buffer::new();
+    dst.put_slice(buf.format(val).as_bytes());
+    dst.put_slice(b"\r\n");
+}
+
 /// Read a new-line terminated decimal
 fn get_decimal(src: &mut Cursor<&[u8]>) -> Result<u64, Error> {
     use atoi::atoi;
@@ -246,6 +503,15 @@ fn get_decimal(src: &mut Cursor<&[u8]>) -> Result<u64, Error> {
     atoi::<u64>(line).ok_or_else(|| "protocol error; invalid frame format".into())
 }
 
+/// Read a new-line terminated, possibly negative, decimal
+fn get_integer(src: &mut Cursor<&[u8]>) -> Result<i64, Error> {
+    use atoi::atoi;
+
+    let line = get_line(src)?;
+
+    atoi::<i64>(line).ok_or_else(|| "protocol error; invalid frame format".into())
+}
+
 /// Find a line
 fn get_line<'a>(src: &mut Cursor<&'a [u8]>) -> Result<&'a [u8], Error> {
     // Scan the bytes directly
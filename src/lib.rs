@@ -21,18 +21,34 @@
 //!
 //! * `cmd`: implementations of the supported Redis commands.
 //!
+//! * `codec`: the `frame` encoding as a `tokio_util` codec, for reading and
+//!   writing frames through `Stream` and `Sink`.
+//!
 //! * `frame`: represents a single Redis protocol frame. A frame is used as an
 //!   intermediate representation between a "command" and the byte
 //!   representation.
+//!
+//! * `store`: the `Store` trait, the storage operations `GET`, `SET`, `DEL`
+//!   and `PUBLISH` are applied to, for plugging in another backend.
+//!
+//! * `proto`: the protocol types for building other Redis tooling, with a
+//!   documented stability surface.
+//!
+//! * `testing`: helpers for running a server in-process from tests. Only
+//!   available with the `test-util` feature.
 
 pub mod clients;
-pub use clients::{BlockingClient, BufferedClient, Client};
+pub use clients::{BlockingClient, BufferedClient, Client, Pool};
 
 pub mod cmd;
 pub use cmd::Command;
 
+mod aof;
+
+pub mod codec;
+
 mod connection;
-pub use connection::Connection;
+pub use connection::{Connection, SocketOptions};
 
 pub mod frame;
 pub use frame::Frame;
@@ -41,14 +57,30 @@ mod db;
 use db::Db;
 use db::DbDropGuard;
 
+mod error;
+pub use error::MiniRedisError;
+
 mod parse;
+
+pub mod proto;
+
+mod replication;
 use parse::{Parse, ParseError};
 
 pub mod server;
 
+mod session;
+use session::Session;
+
 mod shutdown;
 use shutdown::Shutdown;
 
+pub mod store;
+pub use store::Store;
+
+#[cfg(feature = "test-util")]
+pub mod testing;
+
 /// Default port that a redis server listens on.
 ///
 /// Used if no port is specified.
@@ -56,9 +88,9 @@ pub const DEFAULT_PORT: u16 = 6379;
 
 /// Error returned by most functions.
 ///
-/// When writing a real application, one might want to consider a specialized
-/// error handling crate or defining an error type as an `enum` of causes.
-/// However, for our example, using a boxed `std::error::Error` is sufficient.
+/// A boxed `std::error::Error` keeps the signatures simple. Where callers need
+/// to tell failures apart, e.g. errors replied by the server, a
+/// [`MiniRedisError`] is boxed instead, and can be recovered with `downcast`.
 ///
 /// For performance reasons, boxing is avoided in any hot path. For example, in
 /// `parse`, a custom error `enum` is defined. This is because the error is hit
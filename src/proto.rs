@@ -0,0 +1,59 @@
+//! The Redis protocol (RESP) implementation, for building other Redis tooling
+//! on top of mini-redis.
+//!
+//! This module gathers the types needed to speak the protocol without running
+//! the mini-redis client or server:
+//!
+//! * `Frame`, the intermediate representation of a message, along with its
+//!   constructors (`Frame::array`, `Frame::push_bulk`, `Frame::push_int`).
+//! * `Frame::encode` to turn a frame into bytes and `Frame::parse_from` to
+//!   turn bytes back into a frame. Parsing reports `FrameError::Incomplete`
+//!   when more data is needed. `Frame::split_from` parses out of a `BytesMut`
+//!   instead, without copying large bulk strings.
+//! * `FrameCodec`, the same encoding as a `tokio_util` codec.
+//! * The command types, such as `Get` and `Set`, whose `into_frame` method
+//!   builds the frame a client sends, and `Command::from_frame` which parses
+//!   one.
+//!
+//! # Stability
+//!
+//! The items re-exported here follow the crate's semantic versioning: they
+//! only change in a backwards incompatible way along with the crate's minor
+//! version while it is below 1.0. How commands are applied on the server, and
+//! the key-value store they operate on, are implementation details and are
+//! not exposed.
+//!
+//! # Examples
+//!
+//! Building, encoding and decoding a command that mini-redis does not
+//! implement:
+//!
+//! ```
+//! use mini_redis::proto::{Frame, Get};
+//! use bytes::{Bytes, BytesMut};
+//!
+//! let mut frame = Frame::array();
+//! frame.push_bulk(Bytes::from("incrby"));
+//! frame.push_bulk(Bytes::from("counter"));
+//! frame.push_int(10);
+//!
+//! let mut buf = BytesMut::new();
+//! frame.encode(&mut buf);
+//! assert_eq!(&buf[..], b"*3\r\n$6\r\nincrby\r\n$7\r\ncounter\r\n:10\r\n");
+//!
+//! let (decoded, len) = Frame::parse_from(&buf).unwrap();
+//! assert_eq!(decoded, frame);
+//! assert_eq!(len, buf.len());
+//!
+//! // Commands mini-redis implements can build their own frames.
+//! let get = Get::new("counter").into_frame();
+//! assert_eq!(get.to_string(), "get counter");
+//! ```
+
+pub use crate::cmd::{
+    Auth, Bitcount, Command, Get, Getbit, Getrange, Incrbyfloat, Ping, Psubscribe, Psync, Publish,
+    Sadd, Scard, Set, Setbit, Setrange, Sismember, Smembers, Srem, Subscribe, Ttl, TtlKind,
+    Unsubscribe, Zadd, Zrange, Zscore,
+};
+pub use crate::codec::{FrameCodec, FrameStream};
+pub use crate::frame::{Error as FrameError, Frame};
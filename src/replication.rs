@@ -0,0 +1,87 @@
+//! Following a primary server, see `ServerBuilder::replicaof`.
+//!
+//! A replica connects to its primary and issues `PSYNC`. The primary replies
+//! with a snapshot of its data set, which replaces the replica's, followed by
+//! every command that modifies the data set. The replica applies those
+//! commands, exactly as if a client had sent them.
+
+use crate::cmd::Psync;
+use crate::db::DbSnapshot;
+use crate::{Command, Connection, Db, Frame, Session, Shutdown};
+
+use tokio::net::TcpStream;
+use tokio::time::{self, Duration};
+use tracing::{error, info};
+
+/// How long to wait before connecting to the primary again, after losing the
+/// connection.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Routine executed by the replication task.
+///
+/// Follows the primary at `addr`, applying its commands to `db`. When the
+/// connection is lost, the replica connects again and resynchronizes. Runs
+/// until `shutdown` is signalled.
+pub(crate) async fn run(addr: String, db: Db, mut shutdown: Shutdown) {
+    loop {
+        match follow(&addr, &db, &mut shutdown).await {
+            Ok(()) if shutdown.is_shutdown() => return,
+            Ok(()) => info!(%addr, "primary closed the replication stream"),
+            Err(err) => error!(cause = %err, %addr, "replication failed"),
+        }
+
+        tokio::select! {
+            _ = time::sleep(RECONNECT_DELAY) => {}
+            _ = shutdown.recv() => return,
+        }
+    }
+}
+
+/// Synchronizes `db` with the primary at `addr`, then applies the commands
+/// it forwards until the connection closes or `shutdown` is signalled.
+async fn follow(addr: &str, db: &Db, shutdown: &mut Shutdown) -> crate::Result<()> {
+    let mut connection = Connection::new(TcpStream::connect(addr).await?);
+
+    let snapshot = tokio::select! {
+        res = sync(&mut connection) => res?,
+        _ = shutdown.recv() => return Ok(()),
+    };
+
+    info!(keys = snapshot.len(), %addr, "synchronized with primary");
+    db.restore(snapshot);
+
+    // Commands are applied on behalf of the primary, which does not expect
+    // responses.
+    let mut dst = Connection::discard();
+    let mut session = Session::new(false);
+
+    loop {
+        let frame = tokio::select! {
+            res = connection.read_frame() => match res? {
+                Some(frame) => frame,
+                None => return Ok(()),
+            },
+            _ = shutdown.recv() => return Ok(()),
+        };
+
+        let cmd = Command::from_frame(frame)?;
+        cmd.apply(db, &mut dst, shutdown, &mut session).await?;
+    }
+}
+
+/// Issues `PSYNC` and reads the snapshot the primary replies with.
+async fn sync(connection: &mut Connection) -> crate::Result<DbSnapshot> {
+    connection.write_frame(&Psync::new().into_frame()).await?;
+
+    match connection.read_frame().await? {
+        Some(Frame::Simple(response)) if response.starts_with("FULLRESYNC") => {}
+        Some(frame) => return Err(frame.to_error()),
+        None => return Err("primary closed the connection".into()),
+    }
+
+    match connection.read_frame().await? {
+        Some(Frame::Bulk(data)) => DbSnapshot::decode(&data),
+        Some(frame) => Err(frame.to_error()),
+        None => Err("primary closed the connection".into()),
+    }
+}
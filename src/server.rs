@@ -1,16 +1,31 @@
 //! Minimal Redis server implementation
 //!
 //! Provides an async `run` function that listens for inbound connections,
-//! spawning a task per connection.
+//! spawning a task per connection. `ServerBuilder` runs a server with
+//! non-default configuration.
 
-use crate::{Command, Connection, Db, DbDropGuard, Shutdown};
+use crate::aof::{self, AofWriter};
+use crate::cmd::{Function, Functions, MonitoredCommand, SlowCommands};
+use crate::db::DbSnapshot;
+use crate::{
+    replication, Command, Connection, Db, DbDropGuard, Frame, Session, Shutdown, SocketOptions,
+    Store,
+};
 
+pub use crate::db::KeyspaceEvents;
+
+use bytes::Bytes;
 use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::{broadcast, mpsc, Semaphore};
+use tokio::sync::{broadcast, mpsc, oneshot, Semaphore};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
 use tokio::time::{self, Duration};
-use tracing::{debug, error, info, instrument};
+use tracing::{debug, error, info, instrument, warn};
 
 /// Server listener state. Created in the `run` call. It includes a `run` method
 /// which performs the TCP listening and initialization of per-connection state.
@@ -35,8 +50,9 @@ struct Listener {
     /// semaphore. If none are available, the listener waits for one.
     ///
     /// When handlers complete processing a connection, the permit is returned
-    /// to the semaphore.
-    limit_connections: Arc<Semaphore>,
+    /// to the semaphore. `CONFIG SET maxclients` changes the number of
+    /// permits.
+    limit_connections: Arc<ConnectionLimit>,
 
     /// Broadcasts a shutdown signal to all active connections.
     ///
@@ -61,6 +77,115 @@ struct Listener {
     /// `shutdown_complete_rx.recv()` completing with `None`. At this point, it
     /// is safe to exit the server process.
     shutdown_complete_tx: mpsc::Sender<()>,
+
+    /// Server configuration, shared with every connection handler.
+    config: Arc<Config>,
+
+    /// Appends commands to the append-only file, if enabled.
+    aof: Option<AofWriter>,
+
+    /// Forwards the commands that modify the data set to connected replicas.
+    replicas: broadcast::Sender<Frame>,
+
+    /// Reports every command received to connections running `MONITOR`.
+    monitors: broadcast::Sender<MonitoredCommand>,
+
+    /// The most recent slow commands, as returned by `SLOWLOG GET`.
+    slow_commands: SlowCommands,
+}
+
+/// Server configuration, set using `ServerBuilder`.
+#[derive(Debug, Default)]
+struct Config {
+    /// Password clients must authenticate with before issuing commands. If
+    /// `None`, no authentication is required.
+    requirepass: Option<String>,
+
+    /// Memory limit, in bytes, of the data set. `0` means there is no limit.
+    maxmemory: usize,
+
+    /// Largest value, in bytes, `SET` stores. `0` means there is no limit.
+    max_value_size: usize,
+
+    /// Times in a row a subscriber may fall behind before it is disconnected.
+    /// `0` means there is no limit.
+    max_subscriber_lags: usize,
+
+    /// Keyspace events published when keys are modified. None by default.
+    notify_keyspace_events: KeyspaceEvents,
+
+    /// Whether the `DEBUG` command is available.
+    enable_debug: bool,
+
+    /// Whether commands that modify the data set are rejected.
+    read_only: bool,
+
+    /// Path of the append-only file. If `None`, commands are not logged.
+    appendonly: Option<PathBuf>,
+
+    /// Path of the snapshot file written by `SAVE` and `BGSAVE`. If `None`,
+    /// snapshots cannot be saved.
+    dbfilename: Option<PathBuf>,
+
+    /// Address of the primary to replicate, as `host:port`. If `None`, the
+    /// server is a primary.
+    replicaof: Option<String>,
+
+    /// Commands taking longer than this to apply are logged. If `None`,
+    /// commands are not timed.
+    slowlog_threshold: Option<Duration>,
+
+    /// Maximum number of slow commands kept for `SLOWLOG GET`. If `None`,
+    /// `SLOWLOG_MAX_LEN` is used.
+    slowlog_max_len: Option<usize>,
+
+    /// Options applied to the socket of every accepted connection.
+    socket_options: SocketOptions,
+
+    /// Functions clients may invoke with `FCALL`.
+    functions: Functions,
+}
+
+/// Configures and runs a mini-redis server.
+///
+/// `run` starts a server with the default configuration. `ServerBuilder` is
+/// used when the configuration needs to be changed.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mini_redis::server::ServerBuilder;
+/// use tokio::net::TcpListener;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let listener = TcpListener::bind("127.0.0.1:6379").await.unwrap();
+///
+///     ServerBuilder::new()
+///         .requirepass("secret")
+///         .run(listener, tokio::signal::ctrl_c())
+///         .await;
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct ServerBuilder {
+    config: Config,
+}
+
+/// Handle to a server started on a background task with `spawn`.
+///
+/// Dropping the handle also shuts the server down, but without waiting for
+/// it to complete.
+#[derive(Debug)]
+pub struct ServerHandle {
+    /// Address the server is listening on.
+    addr: SocketAddr,
+
+    /// The server task waits on the paired receiver as its shutdown signal.
+    shutdown: oneshot::Sender<()>,
+
+    /// The task running the server, completing once it has shut down.
+    task: JoinHandle<()>,
 }
 
 /// Per-connection handler. Reads requests from `connection` and applies the
@@ -95,6 +220,46 @@ struct Handler {
 
     /// Not used directly. Instead, when `Handler` is dropped...?
     _shutdown_complete: mpsc::Sender<()>,
+
+    /// Server configuration.
+    config: Arc<Config>,
+
+    /// Per-connection state, such as whether the connection has
+    /// authenticated.
+    session: Session,
+
+    /// Appends commands that modify the data set to the append-only file, if
+    /// enabled.
+    aof: Option<AofWriter>,
+
+    /// Forwards the commands that modify the data set to connected replicas.
+    replicas: broadcast::Sender<Frame>,
+
+    /// Reports every command received to connections running `MONITOR`.
+    monitors: broadcast::Sender<MonitoredCommand>,
+
+    /// Records the commands taking longer than the slow log threshold.
+    slow_commands: SlowCommands,
+
+    /// Address of the connected client, as reported by `MONITOR`.
+    addr: SocketAddr,
+
+    /// The connection limit, read and changed by `CONFIG`.
+    limit_connections: Arc<ConnectionLimit>,
+}
+
+/// The maximum number of concurrent connections, the `maxclients` parameter.
+///
+/// The listener acquires a permit from `semaphore` for each connection.
+/// Raising the limit adds permits. Lowering it takes away the permits
+/// available, and those held by connections once they close, so connections
+/// are never closed because of the new limit.
+#[derive(Debug)]
+pub(crate) struct ConnectionLimit {
+    semaphore: Arc<Semaphore>,
+
+    /// The number of permits, once every permit taken away is reclaimed.
+    max: std::sync::Mutex<usize>,
 }
 
 /// Maximum number of concurrent connections the redis server will accept.
@@ -102,8 +267,8 @@ struct Handler {
 /// When this limit is reached, the server will stop accepting connections until
 /// an active connection terminates.
 ///
-/// A real application will want to make this value configurable, but for this
-/// example, it is hard coded.
+/// This is the limit the server starts with, `CONFIG SET maxclients` changes
+/// it at runtime.
 ///
 /// This is also set to a pretty low value to discourage using this in
 /// production (you'd think that all the disclaimers would make it obvious that
@@ -111,6 +276,26 @@ struct Handler {
 /// well).
 const MAX_CONNECTIONS: usize = 250;
 
+/// Number of commands buffered for each replica. A replica that falls further
+/// behind is disconnected and has to resynchronize.
+const REPLICATION_BUFFER: usize = 1024;
+
+/// Number of commands buffered for each monitoring connection. Commands are
+/// skipped for a connection that falls further behind.
+const MONITOR_BUFFER: usize = 1024;
+
+/// Number of slow commands kept for `SLOWLOG GET`, unless configured
+/// otherwise.
+const SLOWLOG_MAX_LEN: usize = 128;
+
+/// Name of the histogram recording how long commands take to apply, in
+/// seconds. Each command is labeled with its name, as `command`.
+///
+/// Only recorded with the `metrics` feature, through the recorder installed
+/// with the `metrics` crate.
+#[cfg(feature = "metrics")]
+pub const COMMAND_DURATION: &str = "mini_redis_command_duration_seconds";
+
 /// Run the mini-redis server.
 ///
 /// Accepts connections from the supplied listener. For each inbound connection,
@@ -121,6 +306,301 @@ const MAX_CONNECTIONS: usize = 250;
 /// `tokio::signal::ctrl_c()` can be used as the `shutdown` argument. This will
 /// listen for a SIGINT signal.
 pub async fn run(listener: TcpListener, shutdown: impl Future) {
+    ServerBuilder::new().run(listener, shutdown).await
+}
+
+/// Start the mini-redis server on a background task.
+///
+/// Accepts connections from the supplied listener, like `run`, but returns
+/// right away with a handle to shut the server down and the address it
+/// listens on. This lets tests and applications embedding mini-redis start and
+/// stop a server inline.
+///
+/// Must be called from within a Tokio runtime.
+///
+/// # Panics
+///
+/// Panics if the address of `listener` cannot be read.
+///
+/// # Examples
+///
+/// ```
+/// use mini_redis::{clients::Client, server};
+/// use tokio::net::TcpListener;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+///     let (server, addr) = server::spawn(listener);
+///
+///     let mut client = Client::connect(addr).await.unwrap();
+///     client.set("hello", "world".into()).await.unwrap();
+///
+///     server.shutdown().await;
+/// }
+/// ```
+pub fn spawn(listener: TcpListener) -> (ServerHandle, SocketAddr) {
+    ServerBuilder::new().spawn(listener)
+}
+
+impl ServerBuilder {
+    /// Create a new `ServerBuilder` with the default configuration.
+    pub fn new() -> ServerBuilder {
+        ServerBuilder::default()
+    }
+
+    /// Require clients to authenticate with `password`, using `AUTH`, before
+    /// issuing any other command.
+    pub fn requirepass(mut self, password: impl Into<String>) -> ServerBuilder {
+        self.config.requirepass = Some(password.into());
+        self
+    }
+
+    /// Limit the memory used by the data set to `bytes`.
+    ///
+    /// Keys are never evicted. Once the limit is exceeded, commands that may
+    /// add data, such as `SET`, are rejected until enough keys are removed.
+    /// A limit of `0`, the default, means there is no limit.
+    pub fn maxmemory(mut self, bytes: usize) -> ServerBuilder {
+        self.config.maxmemory = bytes;
+        self
+    }
+
+    /// Reject `SET` commands whose value is larger than `bytes`.
+    ///
+    /// Unlike `maxmemory`, which applies to the whole data set, this limits
+    /// each value stored. Other commands growing a string, such as
+    /// `SETRANGE`, are not limited. A limit of `0`, the default, means there
+    /// is no limit.
+    pub fn max_value_size(mut self, bytes: usize) -> ServerBuilder {
+        self.config.max_value_size = bytes;
+        self
+    }
+
+    /// Disconnect subscribers that fall behind the published messages `lags`
+    /// times in a row.
+    ///
+    /// Each channel buffers a limited number of messages. A subscriber too
+    /// slow to keep up misses the oldest ones. By default, it silently skips
+    /// them and keeps receiving newer messages. With a limit, a subscriber
+    /// that misses messages `lags` times without catching up in between is
+    /// sent an error and disconnected instead, so it can tell data was lost.
+    /// A limit of `0`, the default, means there is no limit.
+    pub fn max_subscriber_lags(mut self, lags: usize) -> ServerBuilder {
+        self.config.max_subscriber_lags = lags;
+        self
+    }
+
+    /// Publish `events` on pub/sub channels when keys are modified, so
+    /// clients can subscribe to changes.
+    ///
+    /// Events are published to `__keyspace@0__:<key>` channels with the event
+    /// name as the message, and to `__keyevent@0__:<event>` channels with the
+    /// key as the message. See `KeyspaceEvents` for the events available.
+    pub fn notify_keyspace_events(mut self, events: KeyspaceEvents) -> ServerBuilder {
+        self.config.notify_keyspace_events = events;
+        self
+    }
+
+    /// Make the `DEBUG` command available to clients.
+    ///
+    /// `DEBUG` lets clients interfere with the server, for example by making
+    /// it sleep, so it is only meant to be enabled for testing. It is
+    /// disabled by default.
+    pub fn enable_debug(mut self, enable: bool) -> ServerBuilder {
+        self.config.enable_debug = enable;
+        self
+    }
+
+    /// Reject every command that modifies the data set, such as `SET` or
+    /// `DEL`, with a `READONLY` error. Commands that only read, such as `GET`,
+    /// and pub/sub commands are still applied.
+    ///
+    /// Replicas, see `replicaof`, are always read-only.
+    pub fn read_only(mut self, read_only: bool) -> ServerBuilder {
+        self.config.read_only = read_only;
+        self
+    }
+
+    /// Log every command that modifies the data set to the append-only file
+    /// at `path`.
+    ///
+    /// On startup, the commands already in the file are replayed to rebuild
+    /// the data set. Writes to the file are synced to disk every second, so a
+    /// crash loses at most about a second of commands. Expirations set with a
+    /// relative time, such as `SET key value EX 10`, restart from the time the
+    /// file is replayed.
+    pub fn appendonly(mut self, path: impl Into<PathBuf>) -> ServerBuilder {
+        self.config.appendonly = Some(path.into());
+        self
+    }
+
+    /// Save snapshots of the data set, using `SAVE` and `BGSAVE`, to the file
+    /// at `path`.
+    ///
+    /// If the file exists on startup, the data set is loaded from it, unless
+    /// the append-only file is enabled, in which case that is replayed
+    /// instead.
+    pub fn dbfilename(mut self, path: impl Into<PathBuf>) -> ServerBuilder {
+        self.config.dbfilename = Some(path.into());
+        self
+    }
+
+    /// Replicate the primary server listening on `host` and `port`.
+    ///
+    /// The server copies the primary's data set, then applies every command
+    /// that modifies it on the primary. Clients may read from the replica,
+    /// but commands that modify the data set are rejected. If the connection
+    /// to the primary is lost, the replica reconnects and copies the data set
+    /// again.
+    ///
+    /// `host` may be a host name, an IPv4 address or an IPv6 address, with or
+    /// without brackets.
+    pub fn replicaof(mut self, host: impl Into<String>, port: u16) -> ServerBuilder {
+        let host = host.into();
+
+        // IPv6 addresses contain colons, so they must be wrapped in brackets
+        // to be told apart from the port.
+        let addr = if host.contains(':') && !host.starts_with('[') {
+            format!("[{}]:{}", host, port)
+        } else {
+            format!("{}:{}", host, port)
+        };
+
+        self.config.replicaof = Some(addr);
+        self
+    }
+
+    /// Log a warning for every command taking longer than `threshold` to
+    /// apply.
+    ///
+    /// The warning includes the command, its arguments, the client address
+    /// and the time the command took. The most recent slow commands are also
+    /// kept in memory, and returned by `SLOWLOG GET`.
+    pub fn slowlog_threshold(mut self, threshold: Duration) -> ServerBuilder {
+        self.config.slowlog_threshold = Some(threshold);
+        self
+    }
+
+    /// Keep at most `max_len` slow commands for `SLOWLOG GET`. Defaults to
+    /// 128. Once full, recording a slow command drops the oldest one.
+    pub fn slowlog_max_len(mut self, max_len: usize) -> ServerBuilder {
+        self.config.slowlog_max_len = Some(max_len);
+        self
+    }
+
+    /// Set `TCP_NODELAY` on every accepted connection, see
+    /// `SocketOptions::nodelay`.
+    pub fn tcp_nodelay(mut self, nodelay: bool) -> ServerBuilder {
+        self.config.socket_options = self.config.socket_options.nodelay(nodelay);
+        self
+    }
+
+    /// Enable TCP keepalive on every accepted connection, sending probes once
+    /// the connection has been idle for `time`, see
+    /// `SocketOptions::keepalive`.
+    pub fn tcp_keepalive(mut self, time: Duration) -> ServerBuilder {
+        self.config.socket_options = self.config.socket_options.keepalive(time);
+        self
+    }
+
+    /// Register `function` under `name`, so clients can invoke it with
+    /// `FCALL name numkeys [key ...] [arg ...]`.
+    ///
+    /// The function is passed the store, the keys and the other arguments,
+    /// and returns the response sent to the client. An error is sent as an
+    /// error frame. No other command is applied while the function runs, so
+    /// it can read and write several keys atomically. Its writes, rather than
+    /// the `FCALL` itself, are logged to the append-only file and forwarded
+    /// to replicas.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use mini_redis::server::ServerBuilder;
+    /// use mini_redis::Frame;
+    /// use tokio::net::TcpListener;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let listener = TcpListener::bind("127.0.0.1:6379").await.unwrap();
+    ///
+    ///     // Sets the key to the argument, unless it is already set.
+    ///     ServerBuilder::new()
+    ///         .register_function("set_once", |store, keys, args| {
+    ///             if store.get(&keys[0])?.is_some() {
+    ///                 return Ok(Frame::Integer(0));
+    ///             }
+    ///
+    ///             store.set(keys[0].clone(), args[0].clone(), None);
+    ///             Ok(Frame::Integer(1))
+    ///         })
+    ///         .run(listener, tokio::signal::ctrl_c())
+    ///         .await;
+    /// }
+    /// ```
+    pub fn register_function<F>(mut self, name: impl Into<String>, function: F) -> ServerBuilder
+    where
+        F: Fn(&dyn Store, &[String], &[Bytes]) -> crate::Result<Frame> + Send + Sync + 'static,
+    {
+        let function: Function = Arc::new(function);
+        self.config.functions.register(name.into(), function);
+        self
+    }
+
+    /// Run the mini-redis server with this configuration.
+    ///
+    /// See `run` for details.
+    pub async fn run(self, listener: TcpListener, shutdown: impl Future) {
+        run_with_config(listener, shutdown, self.config).await
+    }
+
+    /// Start the mini-redis server with this configuration on a background
+    /// task.
+    ///
+    /// See `spawn` for details.
+    pub fn spawn(self, listener: TcpListener) -> (ServerHandle, SocketAddr) {
+        let addr = listener
+            .local_addr()
+            .expect("failed to read the listener address");
+
+        // The server shuts down once the sender is used or dropped.
+        let (shutdown, rx) = oneshot::channel::<()>();
+        let task = tokio::spawn(self.run(listener, rx));
+
+        let handle = ServerHandle {
+            addr,
+            shutdown,
+            task,
+        };
+
+        (handle, addr)
+    }
+}
+
+impl ServerHandle {
+    /// Returns the address the server is listening on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Shut the server down, waiting until every connection has been closed.
+    pub async fn shutdown(self) {
+        // The server may already be gone if it panicked, which is reported
+        // below.
+        let _ = self.shutdown.send(());
+
+        if let Err(err) = self.task.await {
+            if err.is_panic() {
+                std::panic::resume_unwind(err.into_panic());
+            }
+        }
+    }
+}
+
+/// Implementation of `run`, with the configuration provided by
+/// `ServerBuilder`.
+async fn run_with_config(listener: TcpListener, shutdown: impl Future, config: Config) {
     // When the provided `shutdown` future completes, we must send a shutdown
     // message to all active connections. We use a broadcast channel for this
     // purpose. The call below ignores the receiver of the broadcast pair, and when
@@ -129,13 +609,64 @@ pub async fn run(listener: TcpListener, shutdown: impl Future) {
     let (notify_shutdown, _) = broadcast::channel(1);
     let (shutdown_complete_tx, mut shutdown_complete_rx) = mpsc::channel(1);
 
+    let db_holder = DbDropGuard::new();
+    db_holder.db().set_maxmemory(config.maxmemory);
+    db_holder.db().set_max_value_size(config.max_value_size);
+    db_holder
+        .db()
+        .set_max_subscriber_lags(config.max_subscriber_lags);
+    db_holder
+        .db()
+        .set_notify_keyspace_events(config.notify_keyspace_events);
+
+    // Load the last snapshot before accepting connections. The append-only
+    // file, when enabled, holds every write, so it takes precedence.
+    if let (None, Some(path)) = (&config.appendonly, &config.dbfilename) {
+        if let Err(err) = load_snapshot(path, &db_holder.db()).await {
+            error!(cause = %err, path = %path.display(), "failed to load snapshot");
+            return;
+        }
+    }
+
+    // Rebuild the data set from the append-only file before accepting
+    // connections, then keep logging to it.
+    let (aof, aof_task) = match &config.appendonly {
+        Some(path) => match open_aof(path, &db_holder.db()).await {
+            Ok((aof, task)) => (Some(aof), Some(task)),
+            Err(err) => {
+                error!(cause = %err, path = %path.display(), "failed to load append-only file");
+                return;
+            }
+        },
+        None => (None, None),
+    };
+
+    // Follow the primary, if this server is a replica.
+    if let Some(addr) = &config.replicaof {
+        let shutdown = Shutdown::new(notify_shutdown.subscribe());
+        let shutdown_complete = shutdown_complete_tx.clone();
+        let task = replication::run(addr.clone(), db_holder.db(), shutdown);
+
+        tokio::spawn(async move {
+            task.await;
+            drop(shutdown_complete);
+        });
+    }
+
+    let slow_commands = SlowCommands::new(config.slowlog_max_len.unwrap_or(SLOWLOG_MAX_LEN));
+
     // Initialize the listener state
     let mut server = Listener {
         listener,
-        db_holder: DbDropGuard::new(),
-        limit_connections: Arc::new(Semaphore::new(MAX_CONNECTIONS)),
+        db_holder,
+        limit_connections: Arc::new(ConnectionLimit::new(MAX_CONNECTIONS)),
         notify_shutdown,
         shutdown_complete_tx,
+        config: Arc::new(config),
+        aof,
+        replicas: broadcast::channel(REPLICATION_BUFFER).0,
+        monitors: broadcast::channel(MONITOR_BUFFER).0,
+        slow_commands,
     };
 
     // Concurrently run the server and listen for the `shutdown` signal. The
@@ -181,6 +712,7 @@ pub async fn run(listener: TcpListener, shutdown: impl Future) {
     let Listener {
         shutdown_complete_tx,
         notify_shutdown,
+        aof,
         ..
     } = server;
 
@@ -195,6 +727,36 @@ pub async fn run(listener: TcpListener, shutdown: impl Future) {
     // `Sender` instances are held by connection handler tasks. When those drop,
     // the `mpsc` channel will close and `recv()` will return `None`.
     let _ = shutdown_complete_rx.recv().await;
+
+    // Every handler has dropped its `AofWriter`. Once the listener's is
+    // dropped too, the writer task writes out the remaining commands and
+    // exits.
+    drop(aof);
+    if let Some(task) = aof_task {
+        let _ = task.await;
+    }
+}
+
+/// Restores `db` from the snapshot file at `path`, if it exists.
+async fn load_snapshot(path: &Path, db: &Db) -> crate::Result<()> {
+    if !tokio::fs::try_exists(path).await? {
+        return Ok(());
+    }
+
+    let snapshot = DbSnapshot::read_from_file(path).await?;
+    info!(keys = snapshot.len(), "loaded snapshot");
+
+    db.restore(snapshot);
+    Ok(())
+}
+
+/// Replays the append-only file at `path` into `db`, then opens it to log
+/// further commands.
+async fn open_aof(path: &Path, db: &Db) -> crate::Result<(AofWriter, JoinHandle<()>)> {
+    let applied = aof::replay(path, db).await?;
+    info!(applied, "loaded append-only file");
+
+    AofWriter::open(path).await
 }
 
 impl Listener {
@@ -217,7 +779,15 @@ impl Listener {
         info!("accepting inbound connections");
 
         loop {
-            // Wait for a permit to become available
+            // Accept a new socket. This will attempt to perform error handling.
+            // The `accept` method internally attempts to recover errors, so an
+            // error here is non-recoverable.
+            let (socket, addr) = self.accept().await?;
+
+            // Wait for a permit to become available before serving the
+            // connection. The permit is only acquired once the connection is
+            // accepted, so a connection accepted after `maxclients` is lowered
+            // is subject to the new limit.
             //
             // `acquire_owned` returns a permit that is bound to the semaphore.
             // When the permit value is dropped, it is automatically returned
@@ -227,24 +797,30 @@ impl Listener {
             // closed. We don't ever close the semaphore, so `unwrap()` is safe.
             let permit = self
                 .limit_connections
+                .semaphore
                 .clone()
                 .acquire_owned()
                 .await
                 .unwrap();
 
-            // Accept a new socket. This will attempt to perform error handling.
-            // The `accept` method internally attempts to recover errors, so an
-            // error here is non-recoverable.
-            let socket = self.accept().await?;
+            // Failing to set an option only makes the connection behave as it
+            // would by default, so it is still served.
+            if let Err(err) = self.config.socket_options.apply(&socket) {
+                warn!(cause = %err, %addr, "failed to set socket options");
+            }
+
+            let db = self.db_holder.db();
+
+            // Initialize the connection state. This allocates read/write
+            // buffers to perform redis protocol frame parsing.
+            let connection = Connection::new(socket).with_net_stats(db.net_stats().clone());
 
             // Create the necessary per-connection handler state.
             let mut handler = Handler {
                 // Get a handle to the shared database.
-                db: self.db_holder.db(),
+                db,
 
-                // Initialize the connection state. This allocates read/write
-                // buffers to perform redis protocol frame parsing.
-                connection: Connection::new(socket),
+                connection,
 
                 // Receive shutdown notifications.
                 shutdown: Shutdown::new(self.notify_shutdown.subscribe()),
@@ -252,14 +828,37 @@ impl Listener {
                 // Notifies the receiver half once all clones are
                 // dropped.
                 _shutdown_complete: self.shutdown_complete_tx.clone(),
+
+                config: self.config.clone(),
+
+                // Without a password, there is nothing to authenticate.
+                session: Session::new(self.config.requirepass.is_some()),
+
+                aof: self.aof.clone(),
+
+                replicas: self.replicas.clone(),
+
+                monitors: self.monitors.clone(),
+
+                slow_commands: self.slow_commands.clone(),
+
+                addr,
+
+                limit_connections: self.limit_connections.clone(),
             };
 
             // Spawn a new task to process the connections. Tokio tasks are like
             // asynchronous green threads and are executed concurrently.
             tokio::spawn(async move {
                 // Process the connection. If an error is encountered, log it.
+                // A peer going away abruptly is common enough not to be
+                // logged as an error.
                 if let Err(err) = handler.run().await {
-                    error!(cause = ?err, "connection error");
+                    if is_connection_reset(&err) {
+                        info!(cause = %err, %addr, "connection reset by peer");
+                    } else {
+                        error!(cause = ?err, "connection error");
+                    }
                 }
                 // Move the permit into the task and drop it after completion.
                 // This returns the permit back to the semaphore.
@@ -268,14 +867,15 @@ impl Listener {
         }
     }
 
-    /// Accept an inbound connection.
+    /// Accept an inbound connection, returning the socket along with the
+    /// address of the peer.
     ///
     /// Errors are handled by backing off and retrying. An exponential backoff
     /// strategy is used. After the first failure, the task waits for 1 second.
     /// After the second failure, the task waits for 2 seconds. Each subsequent
     /// failure doubles the wait time. If accepting fails on the 6th try after
     /// waiting for 64 seconds, then this function returns with an error.
-    async fn accept(&mut self) -> crate::Result<TcpStream> {
+    async fn accept(&mut self) -> crate::Result<(TcpStream, SocketAddr)> {
         let mut backoff = 1;
 
         // Try to accept a few times
@@ -283,7 +883,7 @@ impl Listener {
             // Perform the accept operation. If a socket is successfully
             // accepted, return it. Otherwise, save the error.
             match self.listener.accept().await {
-                Ok((socket, _)) => return Ok(socket),
+                Ok(accepted) => return Ok(accepted),
                 Err(err) => {
                     if backoff > 64 {
                         // Accept has failed too many times. Return the error.
@@ -331,17 +931,57 @@ impl Handler {
             };
 
             // If `None` is returned from `read_frame()` then the peer closed
-            // the socket. There is no further work to do and the task can be
-            // terminated.
+            // the socket, or only its write half, between two frames. Every
+            // command it sent has been replied to, so the write half is shut
+            // down, letting the peer read every reply up to the end of the
+            // stream, and the task can be terminated.
+            //
+            // Shutting down fails if the peer closed the socket entirely,
+            // which is not an error either.
             let frame = match maybe_frame {
                 Some(frame) => frame,
-                None => return Ok(()),
+                None => {
+                    debug!(addr = %self.addr, "peer closed the connection");
+                    let _ = self.connection.shutdown().await;
+                    return Ok(());
+                }
             };
 
             // Convert the redis frame into a command struct. This returns an
             // error if the frame is not a valid redis command or it is an
             // unsupported command.
-            let cmd = Command::from_frame(frame)?;
+            //
+            // The frame is kept so commands that modify the data set can be
+            // logged and forwarded as they were received. Whether there is
+            // anywhere to log them to is only checked once they are applied,
+            // as a replica may connect in the meantime.
+            let logged = frame.clone();
+
+            // The command is only recorded for `MONITOR` if a connection is
+            // monitoring.
+            let monitored = (self.monitors.receiver_count() > 0)
+                .then(|| MonitoredCommand::new(self.addr, &frame));
+
+            // With the slow log enabled, the frame is kept so slow commands
+            // can be logged with their arguments.
+            let slowlog = self
+                .config
+                .slowlog_threshold
+                .map(|threshold| (threshold, frame.clone()));
+
+            // A malformed command, e.g. one with missing arguments or an
+            // unsupported option, is answered with an error and the connection
+            // stays open, like Redis does. Only errors reading frames off the
+            // socket terminate the connection.
+            let cmd = match Command::from_frame(frame) {
+                Ok(cmd) => cmd,
+                Err(err) => {
+                    debug!(cause = %err, "malformed command");
+
+                    self.reject(error_reply(&err)).await?;
+                    continue;
+                }
+            };
 
             // Logs the `cmd` object. The syntax here is a shorthand provided by
             // the `tracing` crate. It can be thought of as similar to:
@@ -354,6 +994,190 @@ impl Handler {
             // as key-value pairs.
             debug!(?cmd);
 
+            // `AUTH` is applied here, rather than by `Command::apply`, as it
+            // updates the state of the connection.
+            if let Command::Auth(cmd) = cmd {
+                let requirepass = self.config.requirepass.as_deref();
+                if cmd.apply(requirepass, &mut self.connection).await? {
+                    self.session.authenticate();
+                }
+                continue;
+            }
+
+            // Until the connection has authenticated, every other command is
+            // rejected, except the few flagged `no_auth`, such as `QUIT`.
+            if !self.session.is_authenticated() && !cmd.is_no_auth() {
+                let response = Frame::Error("NOAUTH Authentication required.".to_string());
+                self.connection.write_frame(&response).await?;
+                continue;
+            }
+
+            // Commands are reported once the connection has authenticated, so
+            // `MONITOR` never shows passwords sent with `AUTH`.
+            if let Some(monitored) = monitored {
+                // Sending fails when no connection is monitoring anymore.
+                let _ = self.monitors.send(monitored);
+            }
+
+            // `DEBUG` is only available when it has been enabled.
+            if matches!(cmd, Command::Debug(_)) && !self.config.enable_debug {
+                self.reject("ERR DEBUG command not allowed".to_string())
+                    .await?;
+                continue;
+            }
+
+            // Replicas only apply the commands their primary forwards.
+            let read_only = self.config.read_only || self.config.replicaof.is_some();
+            if read_only && cmd.is_write() {
+                self.reject("READONLY You can't write against a read only replica.".to_string())
+                    .await?;
+                continue;
+            }
+
+            // While the data set is over the memory limit, commands that may
+            // add data are rejected. Keys are never evicted to make room.
+            if cmd.is_denyoom() && self.db.is_over_maxmemory() {
+                self.reject("OOM command not allowed when used memory > 'maxmemory'.".to_string())
+                    .await?;
+                continue;
+            }
+
+            // Between `MULTI` and `EXEC`, commands are queued rather than
+            // applied.
+            if let (true, Some(transaction)) = (cmd.is_queued(), self.session.transaction()) {
+                let response = if cmd.is_allowed_in_transaction() {
+                    transaction.queue(cmd, logged);
+                    Frame::Simple("QUEUED".to_string())
+                } else {
+                    transaction.abort();
+                    Frame::Error("ERR Command not allowed inside a transaction".to_string())
+                };
+
+                self.connection.write_frame(&response).await?;
+                continue;
+            }
+
+            // `SAVE` and `BGSAVE` are applied here, rather than by
+            // `Command::apply`, as they write to the configured snapshot file.
+            let dbfilename = self.config.dbfilename.as_deref();
+            let cmd = match cmd {
+                Command::Save(cmd) => {
+                    cmd.apply(&self.db, dbfilename, &mut self.connection)
+                        .await?;
+                    continue;
+                }
+                Command::Bgsave(cmd) => {
+                    cmd.apply(&self.db, dbfilename, &mut self.connection)
+                        .await?;
+                    continue;
+                }
+                // The connection is a replication stream from now on, until
+                // the replica disconnects.
+                Command::Psync(cmd) => {
+                    return cmd
+                        .apply(
+                            &self.db,
+                            &self.replicas,
+                            &mut self.connection,
+                            &mut self.shutdown,
+                        )
+                        .await;
+                }
+                Command::Slowlog(cmd) => {
+                    cmd.apply(&self.slow_commands, &mut self.connection).await?;
+                    continue;
+                }
+                Command::Config(cmd) => {
+                    let appendonly = self.config.appendonly.is_some();
+                    let limit = &self.limit_connections;
+                    cmd.apply(&self.db, limit, appendonly, &mut self.connection)
+                        .await?;
+                    continue;
+                }
+                Command::Role(cmd) => {
+                    let replicaof = self.config.replicaof.as_deref();
+                    cmd.apply(replicaof, &mut self.connection).await?;
+                    continue;
+                }
+                Command::Failover(cmd) => {
+                    let is_replica = self.config.replicaof.is_some();
+                    let replicas = self.replicas.receiver_count();
+                    cmd.apply(is_replica, replicas, &mut self.connection)
+                        .await?;
+                    continue;
+                }
+                // The commands of the transaction that modified the data set
+                // are logged and forwarded like any other, before the reply is
+                // sent.
+                Command::Exec(cmd) => {
+                    let mut replies = Connection::loopback();
+
+                    {
+                        let _log = self.db.lock_log().await;
+
+                        let applied = cmd
+                            .apply(
+                                &self.db,
+                                &mut replies,
+                                &mut self.shutdown,
+                                &mut self.session,
+                            )
+                            .await?;
+
+                        for frame in applied {
+                            self.log(frame).await;
+                        }
+                    }
+
+                    self.connection.forward(&mut replies).await?;
+                    continue;
+                }
+                // Functions run while no other command is applied, so they
+                // are atomic. The reply is only sent once the lock is
+                // released. Their writes are logged in place of `FCALL`.
+                Command::Fcall(cmd) => {
+                    let response = {
+                        let _log = self.db.lock_log().await;
+
+                        let (response, writes) = {
+                            let _lock = self.db.lock_exclusive().await;
+                            cmd.apply(&self.db, &self.config.functions)
+                        };
+
+                        for frame in writes {
+                            self.log(frame).await;
+                        }
+
+                        response
+                    };
+                    self.connection.write_frame(&response).await?;
+                    continue;
+                }
+                // Replicas and the append-only file must not contact the
+                // target again, so the `DEL` of the migrated key is logged
+                // rather than `MIGRATE` itself. `Migrate` takes the lock itself,
+                // only while reading and deleting the key, and returns the log
+                // lock along with the `DEL`, so it is logged in order.
+                Command::Migrate(cmd) => {
+                    let mut replies = Connection::loopback();
+
+                    if let Some((frame, _log)) = cmd.apply(&self.db, &mut replies).await? {
+                        self.log(frame).await;
+                    }
+
+                    self.connection.forward(&mut replies).await?;
+                    continue;
+                }
+                // The connection streams the commands received from now on,
+                // until it is closed.
+                Command::Monitor(cmd) => {
+                    return cmd
+                        .apply(&self.monitors, &mut self.connection, &mut self.shutdown)
+                        .await;
+                }
+                cmd => cmd,
+            };
+
             // Perform the work needed to apply the command. This may mutate the
             // database state as a result.
             //
@@ -361,10 +1185,239 @@ impl Handler {
             // command to write response frames directly to the connection. In
             // the case of pub/sub, multiple frames may be send back to the
             // peer.
-            cmd.apply(&self.db, &mut self.connection, &mut self.shutdown)
-                .await?;
+            //
+            // Whether the command is terminal is checked up front, as applying
+            // the command consumes it.
+            let is_terminal = cmd.is_terminal();
+            let is_write = cmd.is_write();
+
+            // The time taken to apply the command is recorded, except for
+            // subscriptions, which last for as long as the client wants.
+            #[cfg(feature = "metrics")]
+            let label = command_label(&cmd);
+
+            // Transactions are applied while no other command is, see `Exec`.
+            // Subscriptions last for as long as the client wants, so they do
+            // not hold up transactions, and write to the connection directly.
+            //
+            // Other commands write their replies to `replies` while the lock
+            // is held, and the replies are only sent once it is released. A
+            // client that does not read its replies must not hold up
+            // transactions, and every command queued behind them.
+            //
+            // Commands that modify the data set are logged and forwarded once
+            // applied. The log lock is held from before the command is applied
+            // until it is logged, so commands racing on the same key from
+            // different connections are logged in the order they were
+            // applied.
+
+            let elapsed = match cmd {
+                Command::Subscribe(_) | Command::Psubscribe(_) => {
+                    let start = Instant::now();
+
+                    cmd.apply(
+                        &self.db,
+                        &mut self.connection,
+                        &mut self.shutdown,
+                        &mut self.session,
+                    )
+                    .await?;
+
+                    start.elapsed()
+                }
+                cmd => {
+                    let mut replies = Connection::loopback();
+
+                    let elapsed = {
+                        let _log = if is_write {
+                            Some(self.db.lock_log().await)
+                        } else {
+                            None
+                        };
+
+                        let elapsed = {
+                            let _lock = self.db.lock_shared().await;
+                            let start = Instant::now();
+
+                            cmd.apply(
+                                &self.db,
+                                &mut replies,
+                                &mut self.shutdown,
+                                &mut self.session,
+                            )
+                            .await?;
+
+                            start.elapsed()
+                        };
+
+                        if is_write {
+                            self.log(logged).await;
+                        }
+
+                        elapsed
+                    };
+
+                    self.connection.forward(&mut replies).await?;
+
+                    elapsed
+                }
+            };
+
+            #[cfg(feature = "metrics")]
+            if let Some(name) = label {
+                metrics::histogram!(COMMAND_DURATION, "command" => name).record(elapsed);
+            }
+
+            if let Some((threshold, frame)) = slowlog {
+                if elapsed > threshold {
+                    let entry = self.slow_commands.record(self.addr, &frame, elapsed);
+                    warn!(
+                        command = %entry.name(),
+                        args = %entry.args_summary(),
+                        peer = %self.addr,
+                        ?elapsed,
+                        "slow command"
+                    );
+                }
+            }
+
+            // The response to a terminal command, such as `QUIT`, has been
+            // written. Returning drops the connection, closing the socket.
+            if is_terminal {
+                return Ok(());
+            }
         }
 
         Ok(())
     }
+
+    /// Appends `frame`, a command that modified the data set, to the
+    /// append-only file and forwards it to the replicas, with its expiration
+    /// made absolute.
+    ///
+    /// This is called while holding the log lock, see `Db::lock_log`. A
+    /// replica subscribes while holding it too, so whether any replica is
+    /// connected does not change in the meantime.
+    async fn log(&mut self, frame: Frame) {
+        if self.aof.is_none() && self.replicas.receiver_count() == 0 {
+            return;
+        }
+
+        let frame = Command::absolute_expiration(frame);
+
+        if let Some(aof) = &self.aof {
+            aof.append(frame.clone()).await;
+        }
+
+        // Sending fails when no replica is connected, which is fine.
+        let _ = self.replicas.send(frame);
+    }
+
+    /// Replies to the current command with the error `msg`, without applying
+    /// it.
+    ///
+    /// Inside a transaction, the transaction is aborted, so `EXEC` does not
+    /// apply the commands that were queued.
+    async fn reject(&mut self, msg: String) -> crate::Result<()> {
+        if let Some(transaction) = self.session.transaction() {
+            transaction.abort();
+        }
+
+        let response = Frame::Error(msg);
+        self.connection.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
+
+impl ConnectionLimit {
+    fn new(max: usize) -> ConnectionLimit {
+        ConnectionLimit {
+            semaphore: Arc::new(Semaphore::new(max)),
+            max: std::sync::Mutex::new(max),
+        }
+    }
+
+    /// Returns the maximum number of connections.
+    pub(crate) fn max(&self) -> usize {
+        *self.max.lock().unwrap()
+    }
+
+    /// Sets the maximum number of connections.
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub(crate) fn set_max(&self, max: usize) {
+        let mut current = self.max.lock().unwrap();
+
+        if max > *current {
+            self.semaphore.add_permits(max - *current);
+        } else {
+            let excess = *current - max;
+            let held = excess - self.semaphore.forget_permits(excess);
+
+            // The permits held by connections are taken away as they are
+            // returned. The semaphore is fair, so no connection is accepted
+            // before they are. If the limit is raised in the meantime, the
+            // permits added go to this task first, which is the same as not
+            // taking away as many.
+            if held > 0 {
+                let semaphore = self.semaphore.clone();
+
+                tokio::spawn(async move {
+                    // The semaphore is never closed.
+                    if let Ok(permits) = semaphore.acquire_many_owned(held as u32).await {
+                        permits.forget();
+                    }
+                });
+            }
+        }
+
+        *current = max;
+    }
+}
+
+/// Returns the label the latency of `cmd` is recorded under, or `None` if it is
+/// not recorded.
+///
+/// Unknown commands share a label, so clients cannot create an unbounded
+/// number of histograms.
+#[cfg(feature = "metrics")]
+fn command_label(cmd: &Command) -> Option<String> {
+    match cmd {
+        Command::Subscribe(_) | Command::Psubscribe(_) => None,
+        Command::Unknown(_) => Some("unknown".to_string()),
+        cmd => Some(cmd.get_name().to_string()),
+    }
+}
+
+/// Formats `err` as the message of an error reply.
+///
+/// Messages that already start with an error code, such as `ERR` or
+/// `WRONGTYPE`, are sent as is. Others are prefixed with `ERR`.
+fn error_reply(err: &crate::Error) -> String {
+    let msg = err.to_string();
+
+    let has_code = msg
+        .split(' ')
+        .next()
+        .map(|code| !code.is_empty() && code.bytes().all(|b| b.is_ascii_uppercase()))
+        .unwrap_or(false);
+
+    if has_code {
+        msg
+    } else {
+        format!("ERR {}", msg)
+    }
+}
+
+/// Returns `true` if `err` means the peer went away without closing the
+/// connection cleanly, e.g. it reset the connection or closed it in the middle
+/// of a frame.
+fn is_connection_reset(err: &crate::Error) -> bool {
+    err.downcast_ref::<io::Error>().is_some_and(|err| {
+        matches!(
+            err.kind(),
+            io::ErrorKind::ConnectionReset | io::ErrorKind::BrokenPipe
+        )
+    })
 }
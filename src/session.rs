@@ -0,0 +1,89 @@
+use crate::cmd::Transaction;
+
+/// State of a single client connection.
+///
+/// The connection handler holds the `Session` for its connection. Commands
+/// that change how the connection behaves, such as `AUTH`, `MULTI` and
+/// `RESET`, update it.
+#[derive(Debug)]
+pub(crate) struct Session {
+    /// `true` if the client must authenticate before issuing commands.
+    requires_auth: bool,
+
+    /// `true` once the connection has authenticated. Connections start out
+    /// authenticated unless the server requires a password.
+    authenticated: bool,
+
+    /// Commands queued since `MULTI`, or `None` outside of a transaction.
+    transaction: Option<Transaction>,
+
+    /// Keys watched with `WATCH`, along with the version of their value at the
+    /// time, or `None` if they did not exist. `EXEC` fails if any changed.
+    watched: Vec<(String, Option<u64>)>,
+}
+
+impl Session {
+    /// Create the state of a new connection. `requires_auth` is `true` when
+    /// the server is configured with a password.
+    pub(crate) fn new(requires_auth: bool) -> Session {
+        Session {
+            requires_auth,
+            authenticated: !requires_auth,
+            transaction: None,
+            watched: vec![],
+        }
+    }
+
+    /// Returns `true` if the connection may issue commands.
+    pub(crate) fn is_authenticated(&self) -> bool {
+        self.authenticated
+    }
+
+    /// Marks the connection as authenticated.
+    pub(crate) fn authenticate(&mut self) {
+        self.authenticated = true;
+    }
+
+    /// Starts a transaction. Returns `false` if one was already started.
+    pub(crate) fn begin_transaction(&mut self) -> bool {
+        if self.transaction.is_some() {
+            return false;
+        }
+
+        self.transaction = Some(Transaction::default());
+        true
+    }
+
+    /// Returns the transaction started with `MULTI`, if any.
+    pub(crate) fn transaction(&mut self) -> Option<&mut Transaction> {
+        self.transaction.as_mut()
+    }
+
+    /// Ends the transaction started with `MULTI`, returning it.
+    pub(crate) fn take_transaction(&mut self) -> Option<Transaction> {
+        self.transaction.take()
+    }
+
+    /// Watches `key`, whose value currently has `version`.
+    ///
+    /// A key watched more than once keeps the version it had when it was
+    /// first watched, so changes in between are not missed.
+    pub(crate) fn watch(&mut self, key: String, version: Option<u64>) {
+        if !self.watched.iter().any(|(watched, _)| *watched == key) {
+            self.watched.push((key, version));
+        }
+    }
+
+    /// Stops watching every key, returning the watched keys along with their
+    /// versions at the time they were watched.
+    pub(crate) fn unwatch(&mut self) -> Vec<(String, Option<u64>)> {
+        std::mem::take(&mut self.watched)
+    }
+
+    /// Returns the connection to the state it was in when it was established.
+    pub(crate) fn reset(&mut self) {
+        self.authenticated = !self.requires_auth;
+        self.transaction = None;
+        self.watched.clear();
+    }
+}
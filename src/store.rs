@@ -0,0 +1,151 @@
+//! The storage operations commands are applied to.
+//!
+//! The server stores its data in `Db`, an in-memory store. The commands that
+//! only need the operations of the `Store` trait can be applied to any other
+//! implementation, such as a sharded or persistent store, without changing
+//! the command layer.
+
+use crate::cmd::SetCondition;
+use crate::db::Db;
+
+use bytes::Bytes;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// A key-value store with pub/sub channels.
+///
+/// Values are binary-safe strings. Implementations holding values of other
+/// types must return an error from `get` when the key holds one of those,
+/// which is sent to the client as is, e.g. a `WRONGTYPE` error.
+///
+/// # Examples
+///
+/// A store keeping nothing, which could back a server that only relays
+/// messages:
+///
+/// ```
+/// use mini_redis::Store;
+///
+/// use bytes::Bytes;
+/// use std::time::Duration;
+/// use tokio::sync::broadcast;
+///
+/// struct Nothing;
+///
+/// impl Store for Nothing {
+///     fn get(&self, _key: &str) -> mini_redis::Result<Option<Bytes>> {
+///         Ok(None)
+///     }
+///
+///     fn set(&self, _key: String, _value: Bytes, _expire: Option<Duration>) -> Option<Bytes> {
+///         None
+///     }
+///
+///     fn del(&self, _keys: &[String]) -> usize {
+///         0
+///     }
+///
+///     fn subscribe(&self, _channel: String) -> broadcast::Receiver<Bytes> {
+///         broadcast::channel(1).1
+///     }
+///
+///     fn publish(&self, _channel: &str, _message: Bytes) -> usize {
+///         0
+///     }
+/// }
+/// ```
+pub trait Store {
+    /// Returns the value stored at `key`, or `None` if there is none.
+    fn get(&self, key: &str) -> crate::Result<Option<Bytes>>;
+
+    /// Stores `value` at `key`, expiring after `expire` if given, and returns
+    /// the value previously stored there, if any.
+    fn set(&self, key: String, value: Bytes, expire: Option<Duration>) -> Option<Bytes>;
+
+    /// Stores `value` at `key`, as `set` does, but only if `condition` holds,
+    /// and returns whether it was stored.
+    ///
+    /// With `get`, the string previously stored at `key` is returned as well,
+    /// even if `value` was not stored. A key holding a value of another type is
+    /// then an error, and is left untouched.
+    ///
+    /// The default implementation checks the key with `get` before storing the
+    /// value with `set`, so another client may write the key in between.
+    /// `Db` does both atomically.
+    fn set_with(
+        &self,
+        key: String,
+        value: Bytes,
+        expire: Option<Duration>,
+        condition: SetCondition,
+        get: bool,
+    ) -> crate::Result<(bool, Option<Bytes>)> {
+        let (exists, prev) = match self.get(&key) {
+            Ok(prev) => (prev.is_some(), prev),
+            Err(err) if get => return Err(err),
+            // The key holds a value of another type.
+            Err(_) => (true, None),
+        };
+
+        if !condition.holds(exists) {
+            return Ok((false, prev));
+        }
+
+        self.set(key, value, expire);
+
+        Ok((true, prev))
+    }
+
+    /// Removes the given keys, returning the number of keys that existed.
+    fn del(&self, keys: &[String]) -> usize;
+
+    /// Returns a `Receiver` for the messages published to `channel`.
+    fn subscribe(&self, channel: String) -> broadcast::Receiver<Bytes>;
+
+    /// Publishes `message` to `channel`, returning the number of subscribers
+    /// it was sent to.
+    fn publish(&self, channel: &str, message: Bytes) -> usize;
+
+    /// Returns the largest value, in bytes, `SET` stores. `0`, the default,
+    /// means there is no limit.
+    fn max_value_size(&self) -> usize {
+        0
+    }
+}
+
+impl Store for Db {
+    fn get(&self, key: &str) -> crate::Result<Option<Bytes>> {
+        Db::get(self, key)
+    }
+
+    fn set(&self, key: String, value: Bytes, expire: Option<Duration>) -> Option<Bytes> {
+        Db::set(self, key, value, expire)
+    }
+
+    fn set_with(
+        &self,
+        key: String,
+        value: Bytes,
+        expire: Option<Duration>,
+        condition: SetCondition,
+        get: bool,
+    ) -> crate::Result<(bool, Option<Bytes>)> {
+        Db::set_with(self, key, value, expire, condition, get)
+    }
+
+    fn del(&self, keys: &[String]) -> usize {
+        Db::del(self, keys)
+    }
+
+    fn subscribe(&self, channel: String) -> broadcast::Receiver<Bytes> {
+        Db::subscribe(self, channel)
+    }
+
+    fn publish(&self, channel: &str, message: Bytes) -> usize {
+        Db::publish(self, channel, message)
+    }
+
+    fn max_value_size(&self) -> usize {
+        Db::max_value_size(self)
+    }
+}
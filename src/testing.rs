@@ -0,0 +1,96 @@
+//! Utilities for testing code against a real mini-redis server.
+//!
+//! This module is only available when the `test-util` feature is enabled. It
+//! packages up the "bind a listener, spawn the server, connect a client"
+//! boilerplate that every integration test otherwise has to repeat.
+
+use crate::clients::Client;
+use crate::server::{self, ServerHandle};
+use crate::Connection;
+
+/// The server's key-value store and its snapshots, for tests that exercise
+/// them directly without going through a connection.
+pub use crate::db::{Db, DbSnapshot};
+
+use std::fmt;
+use std::net::SocketAddr;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+
+/// A mini-redis server running on a background task.
+///
+/// The server listens on a random port on `127.0.0.1`. It is shut down when
+/// the `TestServer` value is dropped.
+///
+/// # Examples
+///
+/// ```
+/// use mini_redis::testing::TestServer;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let server = TestServer::start().await;
+///     let mut client = server.client().await;
+///
+///     client.set("hello", "world".into()).await.unwrap();
+///
+///     let value = client.get("hello").await.unwrap().unwrap();
+///     assert_eq!(b"world", &value[..]);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct TestServer {
+    /// Address the server is listening on.
+    addr: SocketAddr,
+
+    /// Not used directly. Dropping the handle shuts the server down.
+    _server: ServerHandle,
+}
+
+impl TestServer {
+    /// Start a new server on a random local port.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a local port cannot be bound. This is intended for use in
+    /// tests, where failing loudly is the desired behavior.
+    pub async fn start() -> TestServer {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("failed to bind test server");
+        let (server, addr) = server::spawn(listener);
+
+        TestServer {
+            addr,
+            _server: server,
+        }
+    }
+
+    /// Returns the address the server is listening on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Returns a new `Client` connected to the server.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the connection cannot be established.
+    pub async fn client(&self) -> Client {
+        Client::connect(self.addr)
+            .await
+            .expect("failed to connect to test server")
+    }
+}
+
+/// Returns a `Connection` that reads frames from, and writes frames to,
+/// `stream` instead of a TCP socket.
+///
+/// This lets tests control exactly how the bytes of a frame arrive, for
+/// example in chunks of a given size, and observe how they are read.
+pub fn connection<S>(stream: S) -> Connection
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + fmt::Debug + 'static,
+{
+    Connection::from_stream(stream)
+}
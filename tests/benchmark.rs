@@ -0,0 +1,32 @@
+use mini_redis::testing::TestServer;
+
+use tokio::process::Command;
+
+/// A tiny benchmark runs to completion against an in-process server and
+/// reports its results.
+#[tokio::test(flavor = "multi_thread")]
+async fn benchmark_smoke_test() {
+    let server = TestServer::start().await;
+
+    for command in ["set", "get"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_mini-redis-benchmark"))
+            .args(["--port", &server.addr().port().to_string()])
+            .args(["--clients", "2", "--requests", "25", "--pipeline", "4"])
+            .args(["--payload-size", "16", "--command", command])
+            .output()
+            .await
+            .unwrap();
+
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(output.status.success(), "{}", stderr);
+
+        assert!(stdout.contains("25 requests completed"), "{}", stdout);
+        assert!(stdout.contains("requests per second"), "{}", stdout);
+    }
+
+    // The benchmark wrote the key it reads.
+    let mut client = server.client().await;
+    let value = client.get("key:__rand_int__").await.unwrap().unwrap();
+    assert_eq!(16, value.len());
+}
@@ -0,0 +1,101 @@
+use mini_redis::clients::{BlockingClient, Message};
+use mini_redis::testing::TestServer;
+use mini_redis::MiniRedisError;
+use std::thread;
+use std::time::{Duration, SystemTime};
+use tokio::runtime::Runtime;
+
+/// Commands are issued from plain, non-async code, against a server running
+/// on a runtime of its own.
+#[test]
+fn key_value_commands() {
+    let (_rt, server) = start_server();
+
+    let mut client = BlockingClient::connect(server.addr()).unwrap();
+
+    assert_eq!(b"PONG", &client.ping(None).unwrap()[..]);
+
+    client.set("hello", "world".into()).unwrap();
+    let value = client.get("hello").unwrap().unwrap();
+    assert_eq!(b"world", &value[..]);
+
+    let previous = client.set_get("hello", "there".into()).unwrap().unwrap();
+    assert_eq!(b"world", &previous[..]);
+
+    client
+        .set_expires("expiring", "1".into(), Duration::from_secs(60))
+        .unwrap();
+    let ttl = client.ttl("expiring").unwrap();
+    assert!(ttl > 0 && ttl <= 60, "{}", ttl);
+    assert_eq!(1.5, client.incrbyfloat("expiring", 0.5).unwrap());
+
+    assert_eq!(
+        2,
+        client.sadd("tags", vec!["a".into(), "b".into()]).unwrap()
+    );
+    assert!(client.sismember("tags", "a".into()).unwrap());
+
+    client
+        .zadd("scores", vec![(2.0, "b".into()), (1.0, "a".into())])
+        .unwrap();
+    assert_eq!(vec!["a", "b"], client.zrange("scores", 0, -1).unwrap());
+
+    let now = SystemTime::now();
+    let time = client.time().unwrap();
+    let skew = now
+        .duration_since(time)
+        .or_else(|_| time.duration_since(now))
+        .unwrap();
+    assert!(skew < Duration::from_secs(5), "{:?}", skew);
+
+    // Errors are the same as the asynchronous client's
+    let err = client.get("tags").unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<MiniRedisError>(),
+        Some(MiniRedisError::WrongType(_))
+    ));
+}
+
+/// A subscriber is turned into an iterator blocking on the next message.
+#[test]
+fn subscriber_iterates_messages() {
+    let (_rt, server) = start_server();
+
+    let subscriber = BlockingClient::connect(server.addr())
+        .unwrap()
+        .subscribe(vec!["hello".into()])
+        .unwrap();
+    assert_eq!(&["hello".to_string()], subscriber.get_subscribed());
+
+    // The subscription is confirmed, so the messages are received.
+    let addr = server.addr();
+    let publisher = thread::spawn(move || {
+        let mut client = BlockingClient::connect(addr).unwrap();
+        assert_eq!(1, client.publish("hello", "one".into()).unwrap());
+        assert_eq!(1, client.publish("hello", "two".into()).unwrap());
+    });
+
+    let contents: Vec<_> = subscriber
+        .into_iter()
+        .take(2)
+        .map(|message| match message.unwrap() {
+            Message::Channel { channel, content } => {
+                assert_eq!("hello", channel);
+                content
+            }
+            message => panic!("unexpected message {:?}", message),
+        })
+        .collect();
+
+    assert_eq!(vec!["one", "two"], contents);
+    publisher.join().unwrap();
+}
+
+/// Starts a server on a runtime of its own. Its worker threads keep running
+/// the server while the test blocks.
+fn start_server() -> (Runtime, TestServer) {
+    let rt = Runtime::new().unwrap();
+    let server = rt.block_on(TestServer::start());
+
+    (rt, server)
+}
@@ -1,10 +1,13 @@
 use mini_redis::{
-    clients::{BufferedClient, Client},
-    server,
+    clients::{BufferedClient, Client, FlushPolicy},
+    server, Connection, Frame,
 };
+use std::io::ErrorKind;
 use std::net::SocketAddr;
+use std::time::Duration;
 use tokio::net::TcpListener;
 use tokio::task::JoinHandle;
+use tokio::time;
 
 /// A basic "hello world" style test. A server instance is started in a
 /// background task. A client instance is then established and used to initialize
@@ -23,6 +26,125 @@ async fn pool_key_value_get_set() {
     assert_eq!(b"world", &value[..])
 }
 
+/// When the connection backing the buffer stops responding, requests fail
+/// once the configured timeout elapses instead of hanging forever.
+#[tokio::test]
+async fn request_times_out_when_connection_stalls() {
+    // A "server" that accepts the connection but never responds to anything.
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let (_socket, _) = listener.accept().await.unwrap();
+        std::future::pending::<()>().await;
+    });
+
+    let client = Client::connect(addr).await.unwrap();
+    let mut client = BufferedClient::buffer(client).timeout(Duration::from_millis(100));
+
+    let err = client.get("hello").await.unwrap_err();
+    let err = err.downcast_ref::<std::io::Error>().unwrap();
+    assert_eq!(ErrorKind::TimedOut, err.kind());
+}
+
+/// Buffered `SET`s are not visible to other connections until `max_writes`
+/// of them are pending, at which point they are written together.
+#[tokio::test]
+async fn buffered_writes_flush_on_count() {
+    let (addr, _) = start_server().await;
+
+    let client = Client::connect(addr).await.unwrap();
+    let mut client = BufferedClient::with_policy(client, FlushPolicy::immediate().max_writes(3));
+    let mut reader = Client::connect(addr).await.unwrap();
+
+    client.set("a", "1".into()).await.unwrap();
+    client.set("b", "2".into()).await.unwrap();
+    assert_eq!(None, reader.get("a").await.unwrap());
+    assert_eq!(None, reader.get("b").await.unwrap());
+
+    client.set("c", "3".into()).await.unwrap();
+    for key in ["a", "b", "c"] {
+        assert!(reader.get(key).await.unwrap().is_some(), "{} missing", key);
+    }
+}
+
+/// Buffered `SET`s are written once the oldest has been pending for the
+/// configured interval.
+#[tokio::test]
+async fn buffered_writes_flush_on_interval() {
+    let (addr, _) = start_server().await;
+
+    let policy = FlushPolicy::immediate().interval(Duration::from_millis(500));
+    let client = Client::connect(addr).await.unwrap();
+    let mut client = BufferedClient::with_policy(client, policy);
+    let mut reader = Client::connect(addr).await.unwrap();
+
+    client.set("hello", "world".into()).await.unwrap();
+    assert_eq!(None, reader.get("hello").await.unwrap());
+
+    time::sleep(Duration::from_millis(1000)).await;
+    assert_eq!(Some("world".into()), reader.get("hello").await.unwrap());
+}
+
+/// Other requests, and explicit flushes, write pending `SET`s first, so the
+/// buffering client observes its own writes.
+#[tokio::test]
+async fn buffered_writes_flush_before_other_requests() {
+    let (addr, _) = start_server().await;
+
+    let client = Client::connect(addr).await.unwrap();
+    let mut client = BufferedClient::with_policy(client, FlushPolicy::immediate().max_writes(100));
+    let mut reader = Client::connect(addr).await.unwrap();
+
+    client.set("hello", "world".into()).await.unwrap();
+    assert_eq!(Some("world".into()), client.get("hello").await.unwrap());
+
+    client.set("other", "value".into()).await.unwrap();
+    assert_eq!(None, reader.get("other").await.unwrap());
+
+    client.flush().await.unwrap();
+    assert_eq!(Some("value".into()), reader.get("other").await.unwrap());
+}
+
+/// A failure to write buffered `SET`s is returned by the next `set` or
+/// `flush`, and not by other requests.
+#[tokio::test]
+async fn buffered_write_failure_is_returned_to_writers() {
+    // A server that rejects every `SET`.
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut connection = Connection::new(socket);
+
+        while let Some(frame) = connection.read_frame().await.unwrap() {
+            let response = match frame {
+                Frame::Array(args) if args[0] == "set" => Frame::Error("ERR rejected".into()),
+                _ => Frame::Null,
+            };
+
+            connection.write_frame(&response).await.unwrap();
+        }
+    });
+
+    let policy = FlushPolicy::immediate().interval(Duration::from_millis(10));
+    let client = Client::connect(addr).await.unwrap();
+    let mut client = BufferedClient::with_policy(client, policy);
+
+    // Buffered, so the rejection is not known yet.
+    client.set("hello", "world".into()).await.unwrap();
+    time::sleep(Duration::from_millis(100)).await;
+
+    assert_eq!(None, client.get("hello").await.unwrap());
+
+    let err = client.flush().await.unwrap_err();
+    assert_eq!("ERR rejected", err.to_string());
+
+    // The failure is only reported once.
+    client.flush().await.unwrap();
+}
+
 async fn start_server() -> (SocketAddr, JoinHandle<()>) {
     let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
     let addr = listener.local_addr().unwrap();
@@ -1,6 +1,12 @@
-use mini_redis::{clients::Client, server};
+use mini_redis::{
+    clients::{Client, Message},
+    server,
+    server::ServerBuilder,
+    Connection, MiniRedisError, SocketOptions,
+};
 use std::net::SocketAddr;
-use tokio::net::TcpListener;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::{TcpListener, TcpSocket};
 use tokio::task::JoinHandle;
 
 /// A PING PONG test without message provided.
@@ -25,6 +31,22 @@ async fn ping_pong_with_message() {
     assert_eq!("你好世界".as_bytes(), &pong[..]);
 }
 
+/// `TIME` returns the server's clock, which is the test's clock here.
+#[tokio::test]
+async fn time_matches_local_clock() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let before = SystemTime::now();
+    let time = client.time().await.unwrap();
+
+    let diff = match time.duration_since(before) {
+        Ok(diff) => diff,
+        Err(err) => err.duration(),
+    };
+    assert!(diff < Duration::from_secs(5), "{:?}", diff);
+}
+
 /// A basic "hello world" style test. A server instance is started in a
 /// background task. A client instance is then established and set and get
 /// commands are sent to the server. The response is then evaluated
@@ -39,6 +61,648 @@ async fn key_value_get_set() {
     assert_eq!(b"world", &value[..])
 }
 
+/// `SET` with the `GET` option replaces the value and returns the one it
+/// replaced, or `None` if the key was not set.
+#[tokio::test]
+async fn set_get_returns_previous_value() {
+    let (addr, _) = start_server().await;
+
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let prev = client.set_get("hello", "world".into()).await.unwrap();
+    assert!(prev.is_none());
+
+    let prev = client.set_get("hello", "again".into()).await.unwrap();
+    assert_eq!(b"world", &prev.unwrap()[..]);
+
+    let value = client.get("hello").await.unwrap().unwrap();
+    assert_eq!(b"again", &value[..])
+}
+
+/// The `TTL` family of commands report the expiration of a key relative to
+/// now or as a unix timestamp.
+#[tokio::test]
+async fn ttl_and_expiretime() {
+    let (addr, _) = start_server().await;
+
+    let mut client = Client::connect(addr).await.unwrap();
+
+    client
+        .set_expires("hello", "world".into(), Duration::from_millis(5000))
+        .await
+        .unwrap();
+
+    let pttl = client.pttl("hello").await.unwrap();
+    assert!((4000..=5000).contains(&pttl), "pttl = {}", pttl);
+
+    let ttl = client.ttl("hello").await.unwrap();
+    assert!((4..=5).contains(&ttl), "ttl = {}", ttl);
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as i64;
+
+    let pexpiretime = client.pexpiretime("hello").await.unwrap();
+    assert!(
+        (pexpiretime - (now + 5000)).abs() < 1000,
+        "pexpiretime = {}, now = {}",
+        pexpiretime,
+        now
+    );
+
+    let expiretime = client.expiretime("hello").await.unwrap();
+    assert!((expiretime - (now / 1000 + 5)).abs() <= 1);
+
+    // A key without an expiration
+    client.set("forever", "value".into()).await.unwrap();
+    assert_eq!(-1, client.ttl("forever").await.unwrap());
+    assert_eq!(-1, client.pexpiretime("forever").await.unwrap());
+
+    // A key that does not exist
+    assert_eq!(-2, client.pttl("missing").await.unwrap());
+    assert_eq!(-2, client.expiretime("missing").await.unwrap());
+}
+
+/// `INCRBYFLOAT` adds to the stored number, creating missing keys, and stores
+/// the result without trailing zeros or exponent.
+#[tokio::test]
+async fn incrbyfloat() {
+    let (addr, _) = start_server().await;
+
+    let mut client = Client::connect(addr).await.unwrap();
+
+    // A missing key starts at zero
+    assert_eq!(2.5, client.incrbyfloat("float", 2.5).await.unwrap());
+    assert_eq!(1.25, client.incrbyfloat("float", -1.25).await.unwrap());
+
+    // Exponents are accepted, but the result is stored in plain notation
+    client.set("float", "3.0e3".into()).await.unwrap();
+    assert_eq!(3000.5, client.incrbyfloat("float", 0.5).await.unwrap());
+    assert_eq!(3000.0, client.incrbyfloat("float", -0.5).await.unwrap());
+
+    let value = client.get("float").await.unwrap().unwrap();
+    assert_eq!(b"3000", &value[..]);
+
+    // The expiration is kept
+    client
+        .set_expires("expiring", "1".into(), Duration::from_secs(60))
+        .await
+        .unwrap();
+    assert_eq!(1.5, client.incrbyfloat("expiring", 0.5).await.unwrap());
+    assert!(client.ttl("expiring").await.unwrap() > 0);
+
+    // Values that are not numbers are rejected and left unchanged
+    client.set("text", "hello".into()).await.unwrap();
+    let err = client.incrbyfloat("text", 1.0).await.unwrap_err();
+    assert_eq!("ERR value is not a valid float", err.to_string());
+
+    let value = client.get("text").await.unwrap().unwrap();
+    assert_eq!(b"hello", &value[..]);
+}
+
+/// Of two clients racing to `CAS` the same value, exactly one succeeds.
+#[tokio::test]
+async fn concurrent_cas_has_one_winner() {
+    let (addr, _) = start_server().await;
+
+    let mut client = Client::connect(addr).await.unwrap();
+
+    // A missing key never matches
+    assert!(!client.cas("counter", "".into(), "1".into()).await.unwrap());
+    assert!(client.get("counter").await.unwrap().is_none());
+
+    for round in 0..50 {
+        let current = round.to_string();
+        client.set("counter", current.clone().into()).await.unwrap();
+
+        let mut a = Client::connect(addr).await.unwrap();
+        let mut b = Client::connect(addr).await.unwrap();
+
+        let (won_a, won_b) = tokio::join!(
+            a.cas("counter", current.clone().into(), "a".into()),
+            b.cas("counter", current.clone().into(), "b".into()),
+        );
+        let (won_a, won_b) = (won_a.unwrap(), won_b.unwrap());
+        assert!(won_a != won_b, "round {}: {} {}", round, won_a, won_b);
+
+        let winner = if won_a { "a" } else { "b" };
+        let value = client.get("counter").await.unwrap().unwrap();
+        assert_eq!(winner.as_bytes(), &value[..]);
+    }
+
+    // Values of another type are rejected
+    client.sadd("tags", vec!["a".into()]).await.unwrap();
+    let err = client
+        .cas("tags", "a".into(), "b".into())
+        .await
+        .unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"), "{}", err);
+}
+
+/// Values of every type are dumped from one server and restored into another.
+#[tokio::test]
+async fn dump_and_restore_between_servers() {
+    let (source_addr, _) = start_server().await;
+    let (target_addr, _) = start_server().await;
+
+    let mut source = Client::connect(source_addr).await.unwrap();
+    let mut target = Client::connect(target_addr).await.unwrap();
+
+    source.set("text", "hello".into()).await.unwrap();
+    source
+        .sadd("tags", vec!["a".into(), "b".into()])
+        .await
+        .unwrap();
+    source
+        .zadd("scores", vec![(1.5, "a".into()), (2.5, "b".into())])
+        .await
+        .unwrap();
+
+    assert!(source.dump("missing").await.unwrap().is_none());
+
+    for key in ["text", "tags", "scores"] {
+        let payload = source.dump(key).await.unwrap().unwrap();
+        target.restore(key, None, payload, false).await.unwrap();
+    }
+
+    assert_eq!(b"hello", &target.get("text").await.unwrap().unwrap()[..]);
+    let mut tags = target.smembers("tags").await.unwrap();
+    tags.sort();
+    assert_eq!(vec!["a", "b"], tags);
+    assert_eq!(
+        vec![("a".to_string(), 1.5), ("b".to_string(), 2.5)],
+        target.zrange_withscores("scores", 0, -1).await.unwrap()
+    );
+
+    // Restoring over an existing key needs `replace`, and sets the TTL
+    let payload = source.dump("text").await.unwrap().unwrap();
+    let err = target
+        .restore("text", None, payload.clone(), false)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().starts_with("BUSYKEY"), "{}", err);
+
+    target
+        .restore("text", Some(Duration::from_secs(60)), payload, true)
+        .await
+        .unwrap();
+    let ttl = target.ttl("text").await.unwrap();
+    assert!(ttl > 0 && ttl <= 60, "{}", ttl);
+}
+
+/// `SETRANGE` overwrites part of a string, padding it with zero bytes, and
+/// `GETRANGE` reads part of it back.
+#[tokio::test]
+async fn setrange_and_getrange() {
+    let (addr, _) = start_server().await;
+
+    let mut client = Client::connect(addr).await.unwrap();
+
+    client.set("greeting", "Hello World".into()).await.unwrap();
+    assert_eq!(
+        11,
+        client
+            .setrange("greeting", 6, "Redis".into())
+            .await
+            .unwrap()
+    );
+
+    let value = client.get("greeting").await.unwrap().unwrap();
+    assert_eq!(b"Hello Redis", &value[..]);
+
+    // Missing keys are padded with zero bytes
+    assert_eq!(5, client.setrange("padded", 3, "ab".into()).await.unwrap());
+    let value = client.get("padded").await.unwrap().unwrap();
+    assert_eq!(b"\0\0\0ab", &value[..]);
+
+    // An empty value does not create the key
+    assert_eq!(0, client.setrange("empty", 10, "".into()).await.unwrap());
+    assert!(client.get("empty").await.unwrap().is_none());
+
+    // Binary data is written and read as is
+    let binary = vec![0xff, 0x00, 0xfe];
+    client
+        .setrange("greeting", 0, binary.clone().into())
+        .await
+        .unwrap();
+    let value = client.getrange("greeting", 0, 2).await.unwrap();
+    assert_eq!(binary, &value[..]);
+
+    // Negative offsets count from the end, and ranges are clamped
+    client.set("greeting", "Hello World".into()).await.unwrap();
+    assert_eq!(
+        b"World",
+        &client.getrange("greeting", -5, -1).await.unwrap()[..]
+    );
+    assert_eq!(
+        b"Hello",
+        &client.getrange("greeting", 0, 4).await.unwrap()[..]
+    );
+    assert_eq!(
+        b"Hello World",
+        &client.getrange("greeting", -100, 100).await.unwrap()[..]
+    );
+    assert!(client.getrange("greeting", 5, 2).await.unwrap().is_empty());
+    assert!(client
+        .getrange("greeting", -1, -5)
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(client.getrange("missing", 0, -1).await.unwrap().is_empty());
+}
+
+/// `SETBIT` and `GETBIT` address single bits of a string, growing it as
+/// needed, and `BITCOUNT` counts the bits set.
+#[tokio::test]
+async fn setbit_getbit_and_bitcount() {
+    let (addr, _) = start_server().await;
+
+    let mut client = Client::connect(addr).await.unwrap();
+
+    // Bits are numbered from the most significant bit of the first byte
+    assert!(!client.setbit("bits", 1, true).await.unwrap());
+    assert!(!client.setbit("bits", 7, true).await.unwrap());
+    assert!(client.setbit("bits", 7, true).await.unwrap());
+    let value = client.get("bits").await.unwrap().unwrap();
+    assert_eq!(&[0b0100_0001], &value[..]);
+
+    assert!(client.getbit("bits", 1).await.unwrap());
+    assert!(!client.getbit("bits", 2).await.unwrap());
+
+    // Bits past the end are zero
+    assert!(!client.getbit("bits", 1000).await.unwrap());
+    assert!(!client.getbit("missing", 0).await.unwrap());
+
+    // Setting a bit far beyond the end grows the string with zero bytes
+    assert!(!client.setbit("bits", 8 * 100 + 2, true).await.unwrap());
+    let value = client.get("bits").await.unwrap().unwrap();
+    assert_eq!(101, value.len());
+    assert_eq!(0b0010_0000, value[100]);
+    assert!(value[1..100].iter().all(|byte| *byte == 0));
+
+    // Clearing a bit returns its previous value
+    assert!(client.setbit("bits", 1, false).await.unwrap());
+    assert!(!client.getbit("bits", 1).await.unwrap());
+
+    client.set("count", "foobar".into()).await.unwrap();
+    assert_eq!(26, client.bitcount("count", None).await.unwrap());
+    assert_eq!(4, client.bitcount("count", Some((0, 0))).await.unwrap());
+    assert_eq!(6, client.bitcount("count", Some((1, 1))).await.unwrap());
+    assert_eq!(7, client.bitcount("count", Some((-2, -1))).await.unwrap());
+    assert_eq!(0, client.bitcount("missing", None).await.unwrap());
+}
+
+/// Sorted sets order their members by score, breaking ties lexicographically,
+/// and updating a member's score moves it.
+#[tokio::test]
+async fn sorted_set_zadd_zrange_zscore() {
+    let (addr, _) = start_server().await;
+
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let members = vec![
+        (3.0, "carol".to_string()),
+        (1.0, "bob".to_string()),
+        (1.0, "alice".to_string()),
+        (2.5, "dave".to_string()),
+    ];
+    assert_eq!(4, client.zadd("scores", members).await.unwrap());
+
+    assert_eq!(
+        vec!["alice", "bob", "dave", "carol"],
+        client.zrange("scores", 0, -1).await.unwrap()
+    );
+    assert_eq!(
+        vec![("dave".to_string(), 2.5), ("carol".to_string(), 3.0)],
+        client.zrange_withscores("scores", -2, -1).await.unwrap()
+    );
+    assert!(client.zrange("scores", 5, 10).await.unwrap().is_empty());
+
+    // Updating a score is not counted as an addition, but moves the member
+    let members = vec![(0.5, "carol".to_string()), (4.0, "erin".to_string())];
+    assert_eq!(1, client.zadd("scores", members).await.unwrap());
+    assert_eq!(
+        vec!["carol", "alice", "bob", "dave", "erin"],
+        client.zrange("scores", 0, -1).await.unwrap()
+    );
+
+    assert_eq!(Some(0.5), client.zscore("scores", "carol").await.unwrap());
+    assert_eq!(None, client.zscore("scores", "nobody").await.unwrap());
+    assert_eq!(None, client.zscore("missing", "carol").await.unwrap());
+    assert!(client.zrange("missing", 0, -1).await.unwrap().is_empty());
+
+    // Sorted sets and strings cannot be used in place of each other
+    let err = client.get("scores").await.unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"), "{}", err);
+
+    client.set("text", "hello".into()).await.unwrap();
+    let err = client
+        .zadd("text", vec![(1.0, "a".to_string())])
+        .await
+        .unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"), "{}", err);
+    let err = client.zscore("text", "a").await.unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"), "{}", err);
+}
+
+/// Sets hold unique members: adding a duplicate is not counted, and removing
+/// the last member removes the key.
+#[tokio::test]
+async fn set_sadd_srem_smembers() {
+    let (addr, _) = start_server().await;
+
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let members = vec!["a".into(), "b".into(), "a".into()];
+    assert_eq!(2, client.sadd("tags", members).await.unwrap());
+    assert_eq!(
+        1,
+        client
+            .sadd("tags", vec!["b".into(), "c".into()])
+            .await
+            .unwrap()
+    );
+    assert_eq!(3, client.scard("tags").await.unwrap());
+
+    let mut members = client.smembers("tags").await.unwrap();
+    members.sort();
+    assert_eq!(vec!["a", "b", "c"], members);
+
+    assert!(client.sismember("tags", "a".into()).await.unwrap());
+    assert!(!client.sismember("tags", "z".into()).await.unwrap());
+
+    // Binary members are supported
+    assert_eq!(
+        1,
+        client
+            .sadd("tags", vec![vec![0, 255].into()])
+            .await
+            .unwrap()
+    );
+    assert!(client.sismember("tags", vec![0, 255].into()).await.unwrap());
+
+    let members = vec!["a".into(), "z".into(), vec![0, 255].into()];
+    assert_eq!(2, client.srem("tags", members).await.unwrap());
+    assert_eq!(2, client.scard("tags").await.unwrap());
+
+    // Removing the last members removes the key
+    assert_eq!(
+        2,
+        client
+            .srem("tags", vec!["b".into(), "c".into()])
+            .await
+            .unwrap()
+    );
+    assert!(client.get("tags").await.unwrap().is_none());
+
+    // Missing keys are empty sets
+    assert_eq!(0, client.scard("missing").await.unwrap());
+    assert!(client.smembers("missing").await.unwrap().is_empty());
+    assert!(!client.sismember("missing", "a".into()).await.unwrap());
+    assert_eq!(0, client.srem("missing", vec!["a".into()]).await.unwrap());
+
+    // Sets and other types cannot be used in place of each other
+    client.set("text", "hello".into()).await.unwrap();
+    let err = client.sadd("text", vec!["a".into()]).await.unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"), "{}", err);
+
+    client.sadd("tags", vec!["a".into()]).await.unwrap();
+    let err = client.get("tags").await.unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"), "{}", err);
+}
+
+/// Sets are intersected, united and subtracted, with the result either
+/// returned or stored.
+#[tokio::test]
+async fn set_algebra() {
+    let (addr, _) = start_server().await;
+
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let keys = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+
+    client
+        .sadd("a", vec!["1".into(), "2".into(), "3".into()])
+        .await
+        .unwrap();
+    client
+        .sadd("b", vec!["2".into(), "3".into(), "4".into()])
+        .await
+        .unwrap();
+    client.sadd("c", vec!["5".into()]).await.unwrap();
+
+    // Overlapping sets
+    let mut members = client.sinter(keys(&["a", "b"])).await.unwrap();
+    members.sort();
+    assert_eq!(vec!["2", "3"], members);
+
+    let mut members = client.sunion(keys(&["a", "b"])).await.unwrap();
+    members.sort();
+    assert_eq!(vec!["1", "2", "3", "4"], members);
+
+    let members = client.sdiff(keys(&["a", "b"])).await.unwrap();
+    assert_eq!(vec!["1"], members);
+
+    // Disjoint sets
+    assert!(client.sinter(keys(&["a", "c"])).await.unwrap().is_empty());
+
+    let mut members = client.sunion(keys(&["a", "c"])).await.unwrap();
+    members.sort();
+    assert_eq!(vec!["1", "2", "3", "5"], members);
+
+    let mut members = client.sdiff(keys(&["a", "c"])).await.unwrap();
+    members.sort();
+    assert_eq!(vec!["1", "2", "3"], members);
+
+    // Missing keys are empty sets
+    assert!(client
+        .sinter(keys(&["a", "missing"]))
+        .await
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        3,
+        client.sunion(keys(&["a", "missing"])).await.unwrap().len()
+    );
+    assert!(client
+        .sdiff(keys(&["missing", "a"]))
+        .await
+        .unwrap()
+        .is_empty());
+
+    // The result is stored, replacing the destination
+    client.set("dest", "text".into()).await.unwrap();
+    assert_eq!(
+        2,
+        client.sinterstore("dest", keys(&["a", "b"])).await.unwrap()
+    );
+    let mut members = client.smembers("dest").await.unwrap();
+    members.sort();
+    assert_eq!(vec!["2", "3"], members);
+
+    assert_eq!(
+        4,
+        client.sunionstore("dest", keys(&["b", "c"])).await.unwrap()
+    );
+    assert_eq!(4, client.scard("dest").await.unwrap());
+
+    // The destination may be one of the keys
+    assert_eq!(1, client.sdiffstore("a", keys(&["a", "b"])).await.unwrap());
+    assert_eq!(vec!["1"], client.smembers("a").await.unwrap());
+
+    // An empty result removes the destination
+    assert_eq!(
+        0,
+        client.sinterstore("dest", keys(&["a", "c"])).await.unwrap()
+    );
+    assert!(client.get("dest").await.unwrap().is_none());
+
+    // Every key must hold a set
+    client.set("text", "hello".into()).await.unwrap();
+    let err = client.sunion(keys(&["a", "text"])).await.unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<MiniRedisError>(),
+        Some(MiniRedisError::WrongType(_))
+    ));
+
+    // Nothing is stored when the operation fails
+    let err = client
+        .sunionstore("dest", keys(&["a", "text"]))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"), "{}", err);
+    assert!(client.get("dest").await.unwrap().is_none());
+}
+
+/// A client connects to a server listening on an IPv6 address.
+#[tokio::test]
+async fn connect_over_ipv6() {
+    let listener = TcpListener::bind("[::1]:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    tokio::spawn(async move { server::run(listener, tokio::signal::ctrl_c()).await });
+
+    let mut client = Client::connect(("::1", port)).await.unwrap();
+
+    client.set("hello", "world".into()).await.unwrap();
+    let value = client.get("hello").await.unwrap().unwrap();
+    assert_eq!(b"world", &value[..]);
+}
+
+/// Connecting to a server that never answers gives up once the timeout
+/// elapses, instead of waiting for the operating system to give up.
+#[tokio::test]
+async fn connect_timeout_to_unresponsive_server() {
+    // The listener never accepts connections. Once its backlog is full, the
+    // handshake of further connections does not complete.
+    let socket = TcpSocket::new_v4().unwrap();
+    socket.bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    let listener = socket.listen(1).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let mut clients = vec![];
+
+    let err = loop {
+        assert!(clients.len() < 16, "every connection was established");
+
+        let start = std::time::Instant::now();
+
+        match Client::connect_timeout(addr, Duration::from_millis(100)).await {
+            Ok(client) => clients.push(client),
+            Err(err) => {
+                assert!(start.elapsed() < Duration::from_secs(5));
+                break err;
+            }
+        }
+    };
+
+    let err = err.downcast::<std::io::Error>().unwrap();
+    assert_eq!(std::io::ErrorKind::TimedOut, err.kind());
+}
+
+/// A reachable server is connected to as with `connect`.
+#[tokio::test]
+async fn connect_timeout_to_running_server() {
+    let (addr, _) = start_server().await;
+
+    let mut client = Client::connect_timeout(addr, Duration::from_secs(5))
+        .await
+        .unwrap();
+
+    client.set("hello", "world".into()).await.unwrap();
+    let value = client.get("hello").await.unwrap().unwrap();
+    assert_eq!(b"world", &value[..]);
+}
+
+/// Socket options are applied on both ends of the connection without
+/// getting in the way of commands.
+#[tokio::test]
+async fn socket_options_round_trip() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        ServerBuilder::new()
+            .tcp_nodelay(true)
+            .tcp_keepalive(Duration::from_secs(60))
+            .run(listener, tokio::signal::ctrl_c())
+            .await
+    });
+
+    let options = SocketOptions::new()
+        .nodelay(true)
+        .keepalive(Duration::from_secs(60));
+    let mut client = Client::connect_with_options(addr, &options).await.unwrap();
+
+    client.set("hello", "world".into()).await.unwrap();
+    let value = client.get("hello").await.unwrap().unwrap();
+    assert_eq!(b"world", &value[..]);
+}
+
+/// With a password configured, commands are rejected until the client
+/// authenticates, and a wrong password is refused.
+#[tokio::test]
+async fn auth_required_before_commands() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        ServerBuilder::new()
+            .requirepass("secret")
+            .run(listener, tokio::signal::ctrl_c())
+            .await
+    });
+
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let err = client.get("hello").await.unwrap_err();
+    assert!(err.to_string().starts_with("NOAUTH"), "{}", err);
+
+    let err = client.auth("wrong").await.unwrap_err();
+    assert!(err.to_string().starts_with("WRONGPASS"), "{}", err);
+
+    let err = client.set("hello", "world".into()).await.unwrap_err();
+    assert!(err.to_string().starts_with("NOAUTH"), "{}", err);
+
+    client.auth("secret").await.unwrap();
+
+    client.set("hello", "world".into()).await.unwrap();
+    let value = client.get("hello").await.unwrap().unwrap();
+    assert_eq!(b"world", &value[..]);
+}
+
+/// Without a password configured, `AUTH` is an error but commands work.
+#[tokio::test]
+async fn auth_without_password_configured() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    assert!(client.auth("secret").await.is_err());
+
+    client.set("hello", "world".into()).await.unwrap();
+}
+
 /// similar to the "hello world" style test, But this time
 /// a single channel subscription will be tested instead
 #[tokio::test]
@@ -54,8 +718,8 @@ async fn receive_message_subscribed_channel() {
     });
 
     let message = subscriber.next_message().await.unwrap().unwrap();
-    assert_eq!("hello", &message.channel);
-    assert_eq!(b"world", &message.content[..])
+    assert_eq!("hello", message.channel());
+    assert_eq!(b"world", &message.content()[..])
 }
 
 /// test that a client gets messages from multiple subscribed channels
@@ -75,8 +739,8 @@ async fn receive_message_multiple_subscribed_channels() {
     });
 
     let message1 = subscriber.next_message().await.unwrap().unwrap();
-    assert_eq!("hello", &message1.channel);
-    assert_eq!(b"world", &message1.content[..]);
+    assert_eq!("hello", message1.channel());
+    assert_eq!(b"world", &message1.content()[..]);
 
     tokio::spawn(async move {
         let mut client = Client::connect(addr).await.unwrap();
@@ -84,8 +748,8 @@ async fn receive_message_multiple_subscribed_channels() {
     });
 
     let message2 = subscriber.next_message().await.unwrap().unwrap();
-    assert_eq!("world", &message2.channel);
-    assert_eq!(b"howdy?", &message2.content[..])
+    assert_eq!("world", message2.channel());
+    assert_eq!(b"howdy?", &message2.content()[..])
 }
 
 /// test that a client accurately removes its own subscribed channel list
@@ -104,6 +768,119 @@ async fn unsubscribes_from_channels() {
     assert_eq!(subscriber.get_subscribed().len(), 0);
 }
 
+/// Unsubscribing from all channels while only subscribed to patterns reads
+/// the single reply the server sends, leaving the connection usable.
+#[tokio::test]
+async fn unsubscribe_all_without_channels() {
+    let (addr, _) = start_server().await;
+
+    let client = Client::connect(addr).await.unwrap();
+    let mut subscriber = client.psubscribe(vec!["h*".into()]).await.unwrap();
+
+    subscriber.unsubscribe(&[]).await.unwrap();
+    assert_eq!(1, subscriber.subscription_count());
+
+    subscriber.subscribe(&["hello".into()]).await.unwrap();
+    assert_eq!(2, subscriber.subscription_count());
+}
+
+/// The subscription count reported by the server is exposed, and tracks
+/// subscribing and unsubscribing.
+#[tokio::test]
+async fn subscription_count_reported_by_server() {
+    let (addr, _) = start_server().await;
+
+    let client = Client::connect(addr).await.unwrap();
+    let mut subscriber = client.subscribe(vec!["hello".into()]).await.unwrap();
+    assert_eq!(1, subscriber.subscription_count());
+
+    subscriber.subscribe(&["world".into()]).await.unwrap();
+    assert_eq!(2, subscriber.subscription_count());
+
+    subscriber.unsubscribe(&["hello".into()]).await.unwrap();
+    assert_eq!(1, subscriber.subscription_count());
+}
+
+/// Messages received through a channel subscription and through a pattern
+/// subscription are yielded as the matching `Message` variant.
+#[tokio::test]
+async fn receive_channel_and_pattern_messages() {
+    let (addr, _) = start_server().await;
+
+    let client = Client::connect(addr).await.unwrap();
+    let mut subscriber = client.psubscribe(vec!["news.*".into()]).await.unwrap();
+    subscriber.subscribe(&["hello".into()]).await.unwrap();
+    assert_eq!(2, subscriber.subscription_count());
+    assert_eq!(
+        &["news.*".to_string()],
+        subscriber.get_subscribed_patterns()
+    );
+
+    let mut client = Client::connect(addr).await.unwrap();
+    client.publish("news.tech", "rust".into()).await.unwrap();
+
+    match subscriber.next_message().await.unwrap().unwrap() {
+        Message::Pattern {
+            pattern,
+            channel,
+            content,
+        } => {
+            assert_eq!("news.*", pattern);
+            assert_eq!("news.tech", channel);
+            assert_eq!(b"rust", &content[..]);
+        }
+        message => panic!("unexpected message {:?}", message),
+    }
+
+    client.publish("hello", "world".into()).await.unwrap();
+
+    match subscriber.next_message().await.unwrap().unwrap() {
+        Message::Channel { channel, content } => {
+            assert_eq!("hello", channel);
+            assert_eq!(b"world", &content[..]);
+        }
+        message => panic!("unexpected message {:?}", message),
+    }
+}
+
+/// Errors replied by the server, and a server closing the connection, are
+/// reported as distinct `MiniRedisError` variants.
+#[tokio::test]
+async fn errors_are_structured() {
+    let (addr, _) = start_server().await;
+
+    let mut client = Client::connect(addr).await.unwrap();
+
+    client.set("text", "hello".into()).await.unwrap();
+    let err = client.incrbyfloat("text", 1.0).await.unwrap_err();
+
+    match err.downcast_ref::<MiniRedisError>() {
+        Some(MiniRedisError::ServerError(msg)) => {
+            assert_eq!("ERR value is not a valid float", msg);
+        }
+        err => panic!("unexpected error {:?}", err),
+    }
+
+    // A "server" that reads the request, then closes the connection without
+    // replying.
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut connection = Connection::new(socket);
+        connection.read_frame().await.unwrap();
+    });
+
+    let mut client = Client::connect(addr).await.unwrap();
+    let err = client.get("hello").await.unwrap_err();
+
+    assert!(matches!(
+        err.downcast_ref::<MiniRedisError>(),
+        Some(MiniRedisError::ConnectionReset)
+    ));
+}
+
 async fn start_server() -> (SocketAddr, JoinHandle<()>) {
     let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
     let addr = listener.local_addr().unwrap();
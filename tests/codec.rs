@@ -0,0 +1,135 @@
+use mini_redis::codec::FrameCodec;
+use mini_redis::Frame;
+
+use bytes::{Bytes, BytesMut};
+use futures_sink::Sink;
+use std::future::poll_fn;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWriteExt, ReadBuf};
+use tokio_stream::StreamExt;
+use tokio_util::codec::{Decoder, FramedRead, FramedWrite};
+
+/// Frames written through `FramedWrite` are read back unchanged through
+/// `FramedRead`.
+#[tokio::test]
+async fn framed_round_trip() {
+    let (client, server) = tokio::io::duplex(64);
+    let mut writer = FramedWrite::new(client, FrameCodec::new());
+    let mut reader = FramedRead::new(server, FrameCodec::new());
+
+    let frames = vec![
+        Frame::Simple("OK".into()),
+        Frame::Error("ERR oops".into()),
+        Frame::Integer(-42),
+        Frame::Null,
+        // Larger than the duplex buffer, so it is received in several reads.
+        Frame::Bulk(vec![b'x'; 1024].into()),
+        Frame::Array(vec![
+            Frame::Bulk("SET".into()),
+            Frame::Array(vec![Frame::Integer(1), Frame::Null]),
+        ]),
+    ];
+
+    let expected = frames.clone();
+    let write = tokio::spawn(async move {
+        for frame in frames {
+            send(&mut writer, frame).await;
+        }
+    });
+
+    for frame in expected {
+        assert_eq!(frame, reader.next().await.unwrap().unwrap());
+    }
+
+    write.await.unwrap();
+
+    // The writer has been dropped, closing the stream.
+    assert!(reader.next().await.is_none());
+}
+
+/// Invalid input is reported as an error from the stream.
+#[tokio::test]
+async fn framed_read_invalid_frame() {
+    let (mut client, server) = tokio::io::duplex(64);
+    let mut reader = FramedRead::new(server, FrameCodec::new());
+
+    client.write_all(b"$abc\r\n").await.unwrap();
+
+    assert!(reader.next().await.unwrap().is_err());
+}
+
+/// Frames received one byte at a time are decoded without checking the whole
+/// frame again after every byte, which would take quadratic time.
+#[tokio::test]
+async fn framed_read_one_byte_at_a_time() {
+    let frames = vec![
+        Frame::Bulk(vec![b'x'; 2 * 1024 * 1024].into()),
+        Frame::Array(
+            (0..20_000)
+                .map(|i| Frame::Bulk(i.to_string().into()))
+                .collect(),
+        ),
+    ];
+
+    let mut buf = BytesMut::new();
+    for frame in &frames {
+        frame.encode(&mut buf);
+    }
+
+    let mut reader = FramedRead::new(OneByteAtATime(buf.freeze()), FrameCodec::new());
+
+    for frame in frames {
+        assert_eq!(frame, reader.next().await.unwrap().unwrap());
+    }
+    assert!(reader.next().await.is_none());
+}
+
+/// A bulk string header declaring a huge length does not make the decoder
+/// reserve that much up front. The buffer only grows as the data arrives.
+#[test]
+fn huge_bulk_header_is_not_reserved_up_front() {
+    let mut codec = FrameCodec::new();
+    let mut buf = BytesMut::from(&b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$1000000000\r\n"[..]);
+
+    assert!(codec.decode(&mut buf).unwrap().is_none());
+    assert!(buf.capacity() < 1024 * 1024, "{}", buf.capacity());
+
+    // The reservation grows with the data received, not with the header.
+    buf.extend_from_slice(&vec![b'x'; 4 * 1024 * 1024]);
+    assert!(codec.decode(&mut buf).unwrap().is_none());
+    assert!(buf.capacity() < 64 * 1024 * 1024, "{}", buf.capacity());
+}
+
+/// A reader returning its data one byte per read.
+struct OneByteAtATime(Bytes);
+
+impl AsyncRead for OneByteAtATime {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if !self.0.is_empty() && buf.remaining() > 0 {
+            let byte = self.0.split_to(1);
+            buf.put_slice(&byte);
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Writes `frame` and flushes it, like `SinkExt::send`.
+async fn send<S>(sink: &mut S, frame: Frame)
+where
+    S: Sink<Frame, Error = mini_redis::Error> + Unpin,
+{
+    poll_fn(|cx| Pin::new(&mut *sink).poll_ready(cx))
+        .await
+        .unwrap();
+    Pin::new(&mut *sink).start_send(frame).unwrap();
+    poll_fn(|cx| Pin::new(&mut *sink).poll_flush(cx))
+        .await
+        .unwrap();
+}
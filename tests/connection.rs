@@ -0,0 +1,172 @@
+use mini_redis::{testing, Connection, Frame};
+
+use bytes::BytesMut;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Frames containing nested arrays are written by one end of a connection and
+/// read back, unchanged, by the other end.
+#[tokio::test]
+async fn nested_array_round_trip() {
+    let (mut tx, mut rx) = connection_pair().await;
+
+    let frame = Frame::Array(vec![
+        Frame::Bulk("outer".into()),
+        Frame::Array(vec![
+            Frame::Integer(1),
+            Frame::Array(vec![Frame::Simple("inner".into()), Frame::Null]),
+        ]),
+        Frame::Array(vec![]),
+        Frame::Integer(2),
+    ]);
+
+    tx.write_frame(&frame).await.unwrap();
+
+    let received = rx.read_frame().await.unwrap().unwrap();
+    assert_eq!(frame, received);
+}
+
+/// A bulk value many times larger than the initial read buffer is received
+/// intact.
+#[tokio::test]
+async fn large_bulk_round_trip() {
+    let (mut tx, mut rx) = connection_pair().await;
+
+    let value: Vec<u8> = (0..10 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+    let frame = Frame::Array(vec![
+        Frame::Bulk("SET".into()),
+        Frame::Bulk("big".into()),
+        Frame::Bulk(value.into()),
+    ]);
+
+    // The value does not fit in the socket buffers, so the write only
+    // completes as the other end reads.
+    let (written, received) = tokio::join!(tx.write_frame(&frame), rx.read_frame());
+    written.unwrap();
+
+    assert_eq!(frame, received.unwrap().unwrap());
+}
+
+/// A 10MB bulk value is read in close to the fewest reads the stream allows,
+/// as the connection reserves room for the rest of the value once its length
+/// is known instead of letting `read_buf` grow the buffer a little at a time.
+///
+/// Each read returns at most 256KB, about what a socket has ready at a time.
+/// An earlier value is still held, so the buffer cannot be grown in place.
+/// Without the reservation, the value takes 47 reads rather than 43.
+#[tokio::test]
+async fn large_bulk_read_count() {
+    const CHUNK: usize = 256 * 1024;
+
+    let mut data = BytesMut::new();
+    Frame::Bulk("small".into()).encode(&mut data);
+    Frame::Bulk(vec![0; 10 * 1024 * 1024].into()).encode(&mut data);
+    let fewest = data.len().div_ceil(CHUNK);
+
+    let (stream, reads) = ChunkedStream::new(data.to_vec(), CHUNK);
+    let mut connection = testing::connection(stream);
+
+    let _held = connection.read_frame().await.unwrap().unwrap();
+    connection.read_frame().await.unwrap().unwrap();
+
+    // A couple of extra reads while the reservation catches up with the
+    // value, starting from a 4KB buffer.
+    let reads = reads.load(Ordering::SeqCst);
+    assert!(reads <= fewest + 2, "{} reads, {} at best", reads, fewest);
+}
+
+/// An array reply with many elements, such as `SMEMBERS` on a large set, is
+/// received intact. The whole array is encoded into one buffer and handed to
+/// the socket at once, rather than written element by element.
+#[tokio::test]
+async fn large_array_round_trip() {
+    let (mut tx, mut rx) = connection_pair().await;
+
+    let frame = Frame::Array(
+        (0..10_000)
+            .map(|i| Frame::Bulk(format!("member:{}", i).into()))
+            .collect(),
+    );
+
+    let (written, received) = tokio::join!(tx.write_frame(&frame), rx.read_frame());
+    written.unwrap();
+
+    assert_eq!(frame, received.unwrap().unwrap());
+}
+
+/// Returns two `Connection` values connected to each other over TCP.
+async fn connection_pair() -> (Connection, Connection) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let (client, server) = tokio::join!(TcpStream::connect(addr), listener.accept());
+
+    (
+        Connection::new(client.unwrap()),
+        Connection::new(server.unwrap().0),
+    )
+}
+
+/// An in-memory stream that returns at most `chunk` bytes per read and counts
+/// the reads. Writes are discarded.
+#[derive(Debug)]
+struct ChunkedStream {
+    data: Vec<u8>,
+    pos: usize,
+    chunk: usize,
+    reads: Arc<AtomicUsize>,
+}
+
+impl ChunkedStream {
+    fn new(data: Vec<u8>, chunk: usize) -> (ChunkedStream, Arc<AtomicUsize>) {
+        let reads = Arc::new(AtomicUsize::new(0));
+        let stream = ChunkedStream {
+            data,
+            pos: 0,
+            chunk,
+            reads: reads.clone(),
+        };
+
+        (stream, reads)
+    }
+}
+
+impl AsyncRead for ChunkedStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        self.reads.fetch_add(1, Ordering::SeqCst);
+
+        let start = self.pos;
+        let end = self.data.len().min(start + self.chunk.min(buf.remaining()));
+        buf.put_slice(&self.data[start..end]);
+        self.pos = end;
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for ChunkedStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
@@ -0,0 +1,362 @@
+use mini_redis::testing::{Db, DbSnapshot};
+
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Expired keys are removed by an explicit purge pass, without waiting for the
+/// background task.
+#[tokio::test]
+async fn process_expirations_removes_expired_keys() {
+    let db = Db::new();
+
+    db.set(
+        "short".into(),
+        "value".into(),
+        Some(Duration::from_millis(1)),
+    );
+    db.set("long".into(), "value".into(), Some(Duration::from_secs(60)));
+    db.set("forever".into(), "value".into(), None);
+
+    // Let the short expiration pass. The test blocks instead of awaiting, so
+    // the background purge task does not get a chance to run in between.
+    std::thread::sleep(Duration::from_millis(10));
+
+    assert_eq!(1, db.process_expirations());
+    assert!(db.get("short").unwrap().is_none());
+    assert!(db.get("long").unwrap().is_some());
+    assert!(db.get("forever").unwrap().is_some());
+
+    // Nothing else has expired
+    assert_eq!(0, db.process_expirations());
+}
+
+/// A `Db` without a background task is created and purged outside of a Tokio
+/// runtime, the caller driving expiration.
+#[test]
+fn manual_purge_outside_runtime() {
+    let db = Db::new_manual();
+
+    // Nothing expires yet
+    db.set("forever".into(), "value".into(), None);
+    assert_eq!(None, db.purge_expired());
+
+    db.set(
+        "short".into(),
+        "value".into(),
+        Some(Duration::from_millis(1)),
+    );
+    db.set("long".into(), "value".into(), Some(Duration::from_secs(60)));
+
+    std::thread::sleep(Duration::from_millis(10));
+
+    // The expired key is removed, and the next purge is due when the other
+    // one expires.
+    let next = db.purge_expired().unwrap();
+    let remaining = next - Instant::now();
+    assert!(remaining > Duration::from_secs(50), "{:?}", remaining);
+
+    assert_eq!(0, db.process_expirations());
+    assert!(db.get("short").unwrap().is_none());
+    assert!(db.get("long").unwrap().is_some());
+    assert!(db.get("forever").unwrap().is_some());
+}
+
+/// A key that has expired, but has not been purged yet, has neither a type nor
+/// an encoding.
+#[tokio::test]
+async fn expired_key_has_no_type() {
+    tokio::time::pause();
+
+    let db = Db::new_manual();
+    db.set("short".into(), "1".into(), Some(Duration::from_secs(1)));
+
+    assert_eq!(Some("string"), db.value_type("short"));
+    assert_eq!(Some("int"), db.encoding("short"));
+
+    tokio::time::advance(Duration::from_secs(2)).await;
+
+    assert_eq!(None, db.value_type("short"));
+    assert_eq!(None, db.encoding("short"));
+}
+
+/// A value dumped from one `Db` is restored into another, with a time to live
+/// of its own.
+#[test]
+fn dump_restore_round_trip() {
+    let source = Db::new_manual();
+    let target = Db::new_manual();
+
+    source.set("hello".into(), "world".into(), None);
+    assert!(source.dump("missing").is_none());
+    let payload = source.dump("hello").unwrap();
+
+    target
+        .restore_dump(
+            "short".into(),
+            &payload,
+            Some(Duration::from_millis(1)),
+            false,
+        )
+        .unwrap();
+    target
+        .restore_dump(
+            "long".into(),
+            &payload,
+            Some(Duration::from_secs(60)),
+            false,
+        )
+        .unwrap();
+
+    let next = target.purge_expired().unwrap();
+    assert!(next - Instant::now() < Duration::from_millis(2));
+    assert_eq!(b"world", &target.get("long").unwrap().unwrap()[..]);
+
+    std::thread::sleep(Duration::from_millis(10));
+    assert!(target.get("short").unwrap().is_none());
+    assert!(target.get("long").unwrap().is_some());
+
+    // Existing keys are only overwritten when asked to
+    target.set("taken".into(), "before".into(), None);
+    let err = target
+        .restore_dump("taken".into(), &payload, None, false)
+        .unwrap_err();
+    assert_eq!("BUSYKEY Target key name already exists.", err.to_string());
+
+    target
+        .restore_dump("taken".into(), &payload, None, true)
+        .unwrap();
+    assert_eq!(b"world", &target.get("taken").unwrap().unwrap()[..]);
+
+    // Corrupted and truncated payloads are rejected
+    let mut corrupted = payload.to_vec();
+    *corrupted.last_mut().unwrap() += 1;
+    let err = target
+        .restore_dump("bad".into(), &corrupted, None, false)
+        .unwrap_err();
+    assert_eq!(
+        "ERR DUMP payload version or checksum are wrong",
+        err.to_string()
+    );
+
+    let mut truncated = payload[..payload.len() - 2].to_vec();
+    truncated.push(*payload.last().unwrap());
+    let err = target
+        .restore_dump("bad".into(), &truncated, None, false)
+        .unwrap_err();
+    assert_eq!("ERR Bad data format", err.to_string());
+    assert!(target.get("bad").unwrap().is_none());
+}
+
+/// `Db::new` does not need a runtime. The background task is spawned once a
+/// key is given an expiration from within one, and then purges every expired
+/// key.
+#[test]
+fn new_outside_runtime_spawns_purge_task_lazily() {
+    let db = Db::new();
+
+    // Enough keys that every shard holds one expiring before the key set
+    // from within the runtime.
+    for i in 0..500 {
+        db.set(
+            format!("outside{}", i),
+            "value".into(),
+            Some(Duration::from_millis(1)),
+        );
+    }
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    rt.block_on(async {
+        let metrics = tokio::runtime::Handle::current().metrics();
+        assert_eq!(0, metrics.num_alive_tasks());
+
+        db.set(
+            "inside".into(),
+            "value".into(),
+            Some(Duration::from_millis(1)),
+        );
+        assert_eq!(1, metrics.num_alive_tasks());
+
+        let all_purged = async {
+            while db.used_memory() > 0 {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        };
+
+        tokio::time::timeout(Duration::from_secs(1), all_purged)
+            .await
+            .expect("expired keys were not purged");
+    });
+}
+
+/// A `Db` outliving the runtime its background task ran on spawns the task
+/// again on the next runtime, so expired keys are still purged.
+#[test]
+fn purge_task_respawned_after_runtime_shutdown() {
+    let db = Db::new();
+
+    for _ in 0..2 {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        rt.block_on(async {
+            db.set(
+                "short".into(),
+                "value".into(),
+                Some(Duration::from_millis(1)),
+            );
+
+            let purged = async {
+                while db.used_memory() > 0 {
+                    tokio::time::sleep(Duration::from_millis(1)).await;
+                }
+            };
+
+            tokio::time::timeout(Duration::from_secs(1), purged)
+                .await
+                .expect("expired key was not purged");
+        });
+    }
+}
+
+/// Dropping the last handle to a `Db` shuts down its background purge task,
+/// so creating and dropping databases does not leak tasks.
+#[tokio::test]
+async fn dropping_db_stops_purge_task() {
+    let metrics = tokio::runtime::Handle::current().metrics();
+    let before = metrics.num_alive_tasks();
+
+    for _ in 0..100 {
+        let db = Db::new();
+        db.set("key".into(), "value".into(), Some(Duration::from_secs(60)));
+
+        // The task keeps running while any handle is alive.
+        let clone = db.clone();
+        drop(db);
+        drop(clone);
+    }
+
+    // Give the purge tasks a chance to observe the shutdown signal.
+    let all_stopped = async {
+        while metrics.num_alive_tasks() > before {
+            tokio::task::yield_now().await;
+        }
+    };
+
+    tokio::time::timeout(Duration::from_secs(1), all_stopped)
+        .await
+        .expect("purge tasks are still running");
+}
+
+/// A snapshot survives encoding, and restoring it into a fresh `Db` brings
+/// back the values along with their remaining time to live.
+#[tokio::test]
+async fn snapshot_round_trip() {
+    tokio::time::pause();
+
+    let db = Db::new();
+    db.set("forever".into(), "value".into(), None);
+    db.set(
+        "expiring".into(),
+        "soon".into(),
+        Some(Duration::from_secs(10)),
+    );
+    db.set(
+        "expired".into(),
+        "gone".into(),
+        Some(Duration::from_secs(1)),
+    );
+
+    tokio::time::advance(Duration::from_secs(5)).await;
+
+    // Expired keys are left out
+    let snapshot = db.snapshot();
+    assert_eq!(2, snapshot.len());
+
+    let snapshot = DbSnapshot::decode(&snapshot.encode()).unwrap();
+
+    let restored = Db::new();
+    restored.set("stale".into(), "value".into(), None);
+    restored.restore(snapshot);
+
+    // Restoring replaces existing keys
+    assert!(restored.get("stale").unwrap().is_none());
+    assert!(restored.get("expired").unwrap().is_none());
+    assert_eq!(b"value", &restored.get("forever").unwrap().unwrap()[..]);
+    assert_eq!(b"soon", &restored.get("expiring").unwrap().unwrap()[..]);
+
+    // "expiring" had 5 seconds left when the snapshot was taken.
+    tokio::time::advance(Duration::from_secs(4)).await;
+    assert!(restored.get("expiring").unwrap().is_some());
+
+    tokio::time::advance(Duration::from_secs(2)).await;
+    assert!(restored.get("expiring").unwrap().is_none());
+    assert!(restored.get("forever").unwrap().is_some());
+}
+
+/// Malformed input is rejected rather than producing a partial snapshot.
+#[test]
+fn snapshot_decode_rejects_invalid_input() {
+    assert!(DbSnapshot::decode(b"").is_err());
+    assert!(DbSnapshot::decode(b"not a snapshot").is_err());
+
+    let mut snapshot = DbSnapshot::default().encode().to_vec();
+    assert!(DbSnapshot::decode(&snapshot).unwrap().is_empty());
+
+    // Claim one more entry than there is.
+    let count = snapshot.len() - 1;
+    snapshot[count] = 1;
+    assert!(DbSnapshot::decode(&snapshot).is_err());
+}
+
+/// Many tasks reading and writing disjoint keys concurrently each see their
+/// own writes, and the memory and key accounting adds up once they are done.
+#[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+async fn concurrent_access_to_disjoint_keys() {
+    const TASKS: usize = 32;
+    const KEYS: usize = 500;
+
+    let db = Db::new();
+
+    let tasks: Vec<_> = (0..TASKS)
+        .map(|task| {
+            let db = db.clone();
+
+            tokio::spawn(async move {
+                for i in 0..KEYS {
+                    let key = format!("{}:{}", task, i);
+                    let value = format!("{}", i);
+
+                    db.set(key.clone(), value.clone().into(), None);
+                    assert_eq!(value.as_bytes(), &db.get(&key).unwrap().unwrap()[..]);
+                }
+            })
+        })
+        .collect();
+
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    assert_eq!(TASKS * KEYS, db.snapshot().len());
+
+    // Every key expires, and removing them all brings the accounting back
+    // to zero.
+    for task in 0..TASKS {
+        for i in 0..KEYS {
+            let key = format!("{}:{}", task, i);
+            db.set(key, "value".into(), Some(Duration::from_millis(1)));
+        }
+    }
+
+    std::thread::sleep(Duration::from_millis(10));
+    db.process_expirations();
+
+    assert_eq!(0, db.snapshot().len());
+    assert_eq!(0, db.used_memory());
+}
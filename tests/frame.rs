@@ -0,0 +1,261 @@
+use mini_redis::frame::{Error, Frame};
+use mini_redis::MiniRedisError;
+
+use bytes::BytesMut;
+use std::io::Cursor;
+
+/// Each frame type encodes to the exact bytes defined by the RESP protocol.
+#[test]
+fn encode_matches_resp_fixtures() {
+    let fixtures: &[(Frame, &[u8])] = &[
+        (Frame::Simple("OK".into()), b"+OK\r\n"),
+        (Frame::Simple("".into()), b"+\r\n"),
+        (
+            Frame::Error("ERR unknown command 'foo'".into()),
+            b"-ERR unknown command 'foo'\r\n",
+        ),
+        (Frame::Integer(0), b":0\r\n"),
+        (Frame::Integer(1000), b":1000\r\n"),
+        (Frame::Integer(-1), b":-1\r\n"),
+        (Frame::Integer(i64::MAX), b":9223372036854775807\r\n"),
+        (Frame::Integer(i64::MIN), b":-9223372036854775808\r\n"),
+        (Frame::Null, b"$-1\r\n"),
+        (Frame::Bulk("hello".into()), b"$5\r\nhello\r\n"),
+        (Frame::Bulk("".into()), b"$0\r\n\r\n"),
+        (Frame::Bulk("a\r\nb".into()), b"$4\r\na\r\nb\r\n"),
+        (Frame::Array(vec![]), b"*0\r\n"),
+        (
+            Frame::Array(vec![Frame::Bulk("GET".into()), Frame::Bulk("key".into())]),
+            b"*2\r\n$3\r\nGET\r\n$3\r\nkey\r\n",
+        ),
+        (
+            Frame::Array(vec![
+                Frame::Integer(1),
+                Frame::Array(vec![Frame::Simple("a".into()), Frame::Null]),
+            ]),
+            b"*2\r\n:1\r\n*2\r\n+a\r\n$-1\r\n",
+        ),
+    ];
+
+    for (frame, expected) in fixtures {
+        let mut dst = BytesMut::new();
+        frame.encode(&mut dst);
+        assert_eq!(&dst[..], *expected, "{:?}", frame);
+    }
+}
+
+/// Encoding appends to the destination buffer rather than overwriting it.
+/// Integers encode to their decimal representation, across the whole `i64`
+/// range.
+#[test]
+fn encode_integers() {
+    let mut values = vec![i64::MIN, i64::MIN + 1, i64::MAX, i64::MAX - 1];
+
+    // Every power of ten, the values around it, and their negations, cover
+    // every number of digits.
+    let mut power = 1i64;
+    loop {
+        for value in [power - 1, power, power + 1] {
+            values.push(value);
+            values.push(-value);
+        }
+
+        match power.checked_mul(10) {
+            Some(next) => power = next,
+            None => break,
+        }
+    }
+
+    values.extend(-1000..=1000);
+
+    for value in values {
+        let mut buf = BytesMut::new();
+        Frame::Integer(value).encode(&mut buf);
+        assert_eq!(format!(":{}\r\n", value).as_bytes(), &buf[..]);
+    }
+}
+
+#[test]
+fn encode_appends() {
+    let mut dst = BytesMut::new();
+    Frame::Simple("OK".into()).encode(&mut dst);
+    Frame::Integer(1).encode(&mut dst);
+
+    assert_eq!(&dst[..], b"+OK\r\n:1\r\n");
+}
+
+/// Decoding reports the frame along with the number of bytes it occupied,
+/// leaving any following bytes untouched.
+#[test]
+fn parse_from_reports_consumed_length() {
+    let buf = b"$5\r\nhello\r\n:1\r\n";
+
+    let (frame, len) = Frame::parse_from(buf).unwrap();
+    assert_eq!(Frame::Bulk("hello".into()), frame);
+    assert_eq!(11, len);
+
+    let (frame, len) = Frame::parse_from(&buf[len..]).unwrap();
+    assert_eq!(Frame::Integer(1), frame);
+    assert_eq!(4, len);
+}
+
+/// `split_from` removes the frame from the buffer, and returns large bulk
+/// strings without copying them out of it.
+#[test]
+fn split_from_shares_large_bulk_strings() {
+    let value: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+    let frame = Frame::Array(vec![
+        Frame::Bulk("SET".into()),
+        Frame::Bulk("big".into()),
+        Frame::Bulk(value.into()),
+    ]);
+
+    let mut buf = BytesMut::new();
+    frame.encode(&mut buf);
+    Frame::Integer(1).encode(&mut buf);
+
+    let start = buf.as_ptr() as usize;
+    let end = start + buf.len();
+
+    let parsed = Frame::split_from(&mut buf).unwrap();
+    assert_eq!(frame, parsed);
+    assert_eq!(&buf[..], b":1\r\n");
+
+    let parts = match parsed {
+        Frame::Array(parts) => parts,
+        frame => panic!("unexpected frame {:?}", frame),
+    };
+
+    // The large value points into the buffer it was parsed from, while small
+    // values are copied so they do not keep the buffer alive.
+    let in_buf = |part: &Frame| match part {
+        Frame::Bulk(data) => (start..end).contains(&(data.as_ptr() as usize)),
+        frame => panic!("unexpected frame {:?}", frame),
+    };
+    assert!(in_buf(&parts[2]));
+    assert!(!in_buf(&parts[1]));
+}
+
+/// `split_from` leaves the buffer untouched until a whole frame is buffered.
+#[test]
+fn split_from_incomplete_leaves_buffer() {
+    let mut buf = BytesMut::from(&b"$5\r\nhel"[..]);
+
+    assert!(matches!(
+        Frame::split_from(&mut buf),
+        Err(Error::Incomplete)
+    ));
+    assert_eq!(&buf[..], b"$5\r\nhel");
+
+    buf.extend_from_slice(b"lo\r\n");
+    assert_eq!(
+        Frame::Bulk("hello".into()),
+        Frame::split_from(&mut buf).unwrap()
+    );
+    assert!(buf.is_empty());
+}
+
+/// Every strict prefix of a valid encoding is reported as `Incomplete`, and
+/// never as a frame or a protocol error.
+#[test]
+fn parse_from_truncated_input_is_incomplete() {
+    let frames = [
+        Frame::Simple("OK".into()),
+        Frame::Error("ERR oops".into()),
+        Frame::Integer(12345),
+        Frame::Integer(-12345),
+        Frame::Null,
+        Frame::Bulk("hello world".into()),
+        Frame::Array(vec![]),
+        Frame::Array(vec![
+            Frame::Bulk("SET".into()),
+            Frame::Bulk("key".into()),
+            Frame::Array(vec![Frame::Integer(1), Frame::Null]),
+        ]),
+    ];
+
+    for frame in &frames {
+        let mut encoded = BytesMut::new();
+        frame.encode(&mut encoded);
+
+        for end in 0..encoded.len() {
+            match Frame::parse_from(&encoded[..end]) {
+                Err(Error::Incomplete) => {}
+                res => panic!("{:?} truncated to {} bytes: {:?}", frame, end, res),
+            }
+        }
+
+        let (decoded, len) = Frame::parse_from(&encoded).unwrap();
+        assert_eq!(*frame, decoded);
+        assert_eq!(encoded.len(), len);
+    }
+}
+
+/// `Frame::parse` rejects input that was not checked first with an error,
+/// rather than panicking or allocating for lengths the input cannot hold.
+#[test]
+fn parse_unchecked_input_is_an_error() {
+    let inputs: &[&[u8]] = &[
+        b"",
+        b"?\r\n",
+        b"*1\r\n?\r\n",
+        b"*9223372036854775807\r\n",
+        b"$18446744073709551615\r\n",
+        b"$3\r\nab",
+    ];
+
+    for input in inputs {
+        let mut src = Cursor::new(*input);
+
+        if let Ok(frame) = Frame::parse(&mut src) {
+            panic!("{:?} parsed as {:?}", input, frame);
+        }
+    }
+}
+
+/// Error frames are converted to the `MiniRedisError` variant matching their
+/// prefix, keeping the message as sent.
+#[test]
+fn to_error_maps_prefixes() {
+    let fixtures = [
+        (
+            "WRONGTYPE Operation against a key holding the wrong kind of value",
+            "WrongType",
+        ),
+        ("NOAUTH Authentication required.", "NoAuth"),
+        (
+            "WRONGPASS invalid username-password pair or user is disabled.",
+            "WrongPass",
+        ),
+        (
+            "READONLY You can't write against a read only replica.",
+            "ReadOnly",
+        ),
+        (
+            "OOM command not allowed when used memory > 'maxmemory'.",
+            "Oom",
+        ),
+        (
+            "EXECABORT Transaction discarded because of previous errors.",
+            "ExecAbort",
+        ),
+        ("ERR unknown command 'foo'", "ServerError"),
+        // Only the whole first word is a prefix
+        ("OOMPH", "ServerError"),
+        ("", "ServerError"),
+    ];
+
+    for (msg, variant) in fixtures {
+        let err = Frame::Error(msg.to_string()).to_error();
+        let err = err.downcast_ref::<MiniRedisError>().unwrap();
+
+        assert_eq!(format!("{}({:?})", variant, msg), format!("{:?}", err));
+        assert_eq!(msg, err.to_string());
+    }
+
+    // Any other frame is unexpected where an error is asked for
+    let err = Frame::Integer(1).to_error();
+    let err = err.downcast_ref::<MiniRedisError>().unwrap();
+    assert!(matches!(err, MiniRedisError::Protocol(_)), "{:?}", err);
+    assert_eq!("unexpected frame: 1", err.to_string());
+}
@@ -0,0 +1,61 @@
+use mini_redis::{clients::Client, server};
+
+use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+use metrics_util::MetricKind;
+use tokio::net::TcpListener;
+
+/// The time taken by each command is recorded in a histogram labeled with the
+/// command name.
+#[tokio::test]
+async fn command_duration_is_recorded() {
+    // The recorder is global, and this is the only test in this file, so no
+    // other test records into it.
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    recorder.install().unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { server::run(listener, tokio::signal::ctrl_c()).await });
+
+    let mut client = Client::connect(addr).await.unwrap();
+
+    client.set("hello", "world".into()).await.unwrap();
+    for _ in 0..3 {
+        client.get("hello").await.unwrap();
+    }
+
+    // Latencies are recorded once the response has been written, so wait for
+    // one more response to be sure the last `GET` has been recorded.
+    client.ping(None).await.unwrap();
+
+    let histograms: Vec<_> = snapshotter
+        .snapshot()
+        .into_vec()
+        .into_iter()
+        .filter(|(key, _, _, _)| {
+            key.kind() == MetricKind::Histogram && key.key().name() == server::COMMAND_DURATION
+        })
+        .map(|(key, _, _, value)| {
+            let label = key.key().labels().next().unwrap();
+            assert_eq!("command", label.key());
+
+            let count = match value {
+                DebugValue::Histogram(samples) => samples.len(),
+                value => panic!("unexpected value {:?}", value),
+            };
+            (label.value().to_string(), count)
+        })
+        .collect();
+
+    assert!(
+        histograms.contains(&("set".to_string(), 1)),
+        "{:?}",
+        histograms
+    );
+    assert!(
+        histograms.contains(&("get".to_string(), 3)),
+        "{:?}",
+        histograms
+    );
+}
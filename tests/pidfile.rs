@@ -0,0 +1,90 @@
+#![cfg(unix)]
+
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::process::Command;
+use tokio::time::{self, Duration};
+
+/// The server writes its PID to the pidfile once it runs, and removes the
+/// file when shut down with SIGINT.
+#[tokio::test]
+async fn pidfile_written_and_removed() {
+    let path = pidfile_path("clean");
+    let _ = std::fs::remove_file(&path);
+
+    let mut server = server_command(&path).spawn().unwrap();
+    let pid = server.id().unwrap();
+
+    wait_for_contents(&path, &format!("{}\n", pid)).await;
+
+    let status = Command::new("kill")
+        .args(["-INT", &pid.to_string()])
+        .status()
+        .await
+        .unwrap();
+    assert!(status.success());
+
+    let status = time::timeout(Duration::from_secs(5), server.wait())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(status.success(), "{}", status);
+    assert!(!path.exists());
+}
+
+/// An existing pidfile stops the server from starting, unless it may be
+/// overwritten.
+#[tokio::test]
+async fn existing_pidfile() {
+    let path = pidfile_path("stale");
+    std::fs::write(&path, "1\n").unwrap();
+
+    let output = server_command(&path).output().await.unwrap();
+    assert!(!output.status.success());
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("already exists"), "{}", stderr);
+    assert_eq!("1\n", std::fs::read_to_string(&path).unwrap());
+
+    let mut server = server_command(&path)
+        .arg("--overwrite-pidfile")
+        .spawn()
+        .unwrap();
+    let pid = server.id().unwrap();
+
+    // The stale contents are replaced.
+    wait_for_contents(&path, &format!("{}\n", pid)).await;
+
+    server.kill().await.unwrap();
+    let _ = std::fs::remove_file(&path);
+}
+
+/// Returns a path for a pidfile unique to this process and `name`.
+fn pidfile_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("mini-redis-{}.{}.pid", std::process::id(), name))
+}
+
+/// Returns the command running the server on a free port, with `path` as its
+/// pidfile.
+fn server_command(path: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_mini-redis-server"));
+    command
+        .args(["--port", "0", "--pidfile"])
+        .arg(path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    command
+}
+
+/// Waits for the file at `path` to hold `expected`, failing after a few
+/// seconds.
+async fn wait_for_contents(path: &Path, expected: &str) {
+    let read = async {
+        while std::fs::read_to_string(path).ok().as_deref() != Some(expected) {
+            time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+
+    time::timeout(Duration::from_secs(5), read).await.unwrap();
+}
@@ -0,0 +1,226 @@
+use mini_redis::{
+    clients::{Client, Pool},
+    server, Connection, Frame, MiniRedisError,
+};
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::Barrier;
+use tokio::time;
+
+/// Values written through one pooled connection are visible through the
+/// others, as they all talk to the same server.
+#[tokio::test]
+async fn key_value_get_set_across_connections() {
+    let addr = start_server().await;
+
+    let pool = Pool::connect(addr, 3).await.unwrap();
+    assert_eq!(3, pool.size());
+
+    pool.get_connection()
+        .set("hello", "world".into())
+        .await
+        .unwrap();
+
+    for _ in 0..pool.size() {
+        let value = pool.get_connection().get("hello").await.unwrap().unwrap();
+        assert_eq!(b"world", &value[..]);
+    }
+}
+
+/// `PING` through the pool returns `PONG`, or echoes the message.
+#[tokio::test]
+async fn ping() {
+    let addr = start_server().await;
+    let pool = Pool::connect(addr, 2).await.unwrap();
+
+    let pong = pool.get_connection().ping(None).await.unwrap();
+    assert_eq!(b"PONG", &pong[..]);
+
+    let pong = pool.get_connection().ping(Some("hi".into())).await.unwrap();
+    assert_eq!(b"hi", &pong[..]);
+}
+
+/// `PUBLISH` through the pool returns the number of subscribers the message
+/// was delivered to.
+#[tokio::test]
+async fn publish() {
+    let addr = start_server().await;
+    let pool = Pool::connect(addr, 2).await.unwrap();
+
+    let count = pool.get_connection().publish("news", "hi".into()).await;
+    assert_eq!(0, count.unwrap());
+
+    let subscriber = Client::connect(addr).await.unwrap();
+    let mut subscriber = subscriber.subscribe(vec!["news".into()]).await.unwrap();
+
+    let count = pool.get_connection().publish("news", "hi".into()).await;
+    assert_eq!(1, count.unwrap());
+
+    let message = subscriber.next_message().await.unwrap().unwrap();
+    assert_eq!("news", message.channel());
+    assert_eq!(b"hi", &message.content()[..]);
+}
+
+/// A `GET` for a missing key returns `None` rather than an error.
+#[tokio::test]
+async fn get_missing_key() {
+    let addr = start_server().await;
+    let pool = Pool::connect(addr, 1).await.unwrap();
+
+    let value = pool.get_connection().get("missing").await.unwrap();
+    assert_eq!(None, value);
+}
+
+/// Requests issued through different pooled connections are in flight at the
+/// same time.
+///
+/// The server used here only responds to a `GET` once a `GET` has been
+/// received on every connection. If the pool sent requests one at a time, the
+/// first request would never complete.
+#[tokio::test]
+async fn concurrent_requests_run_in_parallel() {
+    const SIZE: usize = 4;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let barrier = Arc::new(Barrier::new(SIZE));
+
+    tokio::spawn(async move {
+        loop {
+            let (socket, _) = listener.accept().await.unwrap();
+            let barrier = barrier.clone();
+
+            tokio::spawn(async move {
+                let mut connection = Connection::new(socket);
+
+                while let Some(frame) = connection.read_frame().await.unwrap() {
+                    let response = match frame {
+                        Frame::Array(args) if args[0] == "ping" => Frame::Simple("PONG".into()),
+                        _ => {
+                            barrier.wait().await;
+                            Frame::Bulk("value".into())
+                        }
+                    };
+
+                    connection.write_frame(&response).await.unwrap();
+                }
+            });
+        }
+    });
+
+    let pool = Pool::connect(addr, SIZE).await.unwrap();
+
+    let requests: Vec<_> = (0..SIZE)
+        .map(|_| {
+            let mut connection = pool.get_connection();
+            tokio::spawn(async move { connection.get("key").await.unwrap() })
+        })
+        .collect();
+
+    for request in requests {
+        let value = time::timeout(Duration::from_secs(5), request)
+            .await
+            .expect("requests were not sent in parallel")
+            .unwrap();
+        assert_eq!(Some("value".into()), value);
+    }
+}
+
+/// When the server stops responding, pool requests fail once the configured
+/// timeout elapses instead of hanging forever.
+#[tokio::test]
+async fn request_times_out_when_server_stalls() {
+    // A server that passes the health check, then never responds again.
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut connection = Connection::new(socket);
+
+        connection.read_frame().await.unwrap();
+        let pong = Frame::Simple("PONG".into());
+        connection.write_frame(&pong).await.unwrap();
+
+        std::future::pending::<()>().await;
+    });
+
+    let pool = Pool::connect(addr, 1)
+        .await
+        .unwrap()
+        .timeout(Duration::from_millis(100));
+
+    let err = pool.get_connection().get("hello").await.unwrap_err();
+    let err = err.downcast_ref::<std::io::Error>().unwrap();
+    assert_eq!(ErrorKind::TimedOut, err.kind());
+}
+
+/// A pooled connection closed by the server fails the request sent through
+/// it, and is re-established before the next one.
+#[tokio::test]
+async fn failed_connection_is_reestablished() {
+    // A server that closes the first connection right after the health
+    // check, and serves every later one.
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let accepted = Arc::new(AtomicUsize::new(0));
+
+    let count = accepted.clone();
+    tokio::spawn(async move {
+        loop {
+            let (socket, _) = listener.accept().await.unwrap();
+            let first = count.fetch_add(1, Ordering::SeqCst) == 0;
+
+            tokio::spawn(async move {
+                let mut connection = Connection::new(socket);
+
+                while let Some(frame) = connection.read_frame().await.unwrap() {
+                    let response = match frame {
+                        Frame::Array(args) if args[0] == "ping" => Frame::Simple("PONG".into()),
+                        _ => Frame::Bulk("value".into()),
+                    };
+
+                    connection.write_frame(&response).await.unwrap();
+
+                    if first {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+
+    let pool = Pool::connect(addr, 2).await.unwrap();
+    assert_eq!(2, accepted.load(Ordering::SeqCst));
+
+    // The first connection is closed by the server.
+    let err = pool.get_connection().get("key").await.unwrap_err();
+    assert!(
+        matches!(
+            err.downcast_ref::<MiniRedisError>(),
+            Some(MiniRedisError::ConnectionReset | MiniRedisError::Io(_))
+        ),
+        "{:?}",
+        err
+    );
+
+    for _ in 0..pool.size() {
+        let value = pool.get_connection().get("key").await.unwrap();
+        assert_eq!(Some("value".into()), value);
+    }
+
+    assert_eq!(3, accepted.load(Ordering::SeqCst));
+}
+
+async fn start_server() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move { server::run(listener, tokio::signal::ctrl_c()).await });
+
+    addr
+}
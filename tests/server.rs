@@ -1,8 +1,14 @@
-use mini_redis::server;
+use mini_redis::testing::{Db, DbSnapshot};
+use mini_redis::{server, server::ServerBuilder, Command, Connection, Frame};
 
+use bytes::Bytes;
 use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::{TcpListener, TcpStream};
+use tokio::net::{TcpListener, TcpSocket, TcpStream};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
 use tokio::time::{self, Duration};
 
 /// A basic "hello world" style test. A server instance is started in a
@@ -331,70 +337,2686 @@ async fn manage_subscription() {
     );
 }
 
+/// `SELECT 0` succeeds so clients that select a database on connect work, and
+/// other database indices are rejected.
+#[tokio::test]
+async fn select_only_accepts_database_zero() {
+    let addr = start_server().await;
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let response = command(&mut connection, &["SELECT", "0"]).await;
+    assert_eq!(Frame::Simple("OK".to_string()), response);
+
+    let response = command(&mut connection, &["SELECT", "1"]).await;
+    assert_eq!(
+        Frame::Error("ERR DB index is out of range".to_string()),
+        response
+    );
+
+    // The connection is still usable
+    let response = command(&mut connection, &["SET", "hello", "world"]).await;
+    assert_eq!(Frame::Simple("OK".to_string()), response);
+}
+
+/// `RANDOMKEY` replies with an existing key, or `Null` once the database is
+/// empty, while another connection is concurrently removing keys.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn randomkey_while_keys_are_deleted() {
+    let addr = start_server().await;
+
+    let mut writer = Connection::new(TcpStream::connect(addr).await.unwrap());
+    let mut reader = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let keys: Vec<String> = (0..100).map(|i| format!("key{}", i)).collect();
+
+    for key in &keys {
+        command(&mut writer, &["SET", key, "value"]).await;
+    }
+
+    let deleter = tokio::spawn(async move {
+        for key in &keys {
+            let response = command(&mut writer, &["DEL", key, "missing"]).await;
+            assert_eq!(Frame::Integer(1), response);
+        }
+    });
+
+    loop {
+        match command(&mut reader, &["RANDOMKEY"]).await {
+            Frame::Bulk(key) => assert!(key.starts_with(b"key"), "{:?}", key),
+            Frame::Null => break,
+            frame => panic!("unexpected response {:?}", frame),
+        }
+    }
+
+    deleter.await.unwrap();
+
+    // The database stays empty
+    assert_eq!(Frame::Null, command(&mut reader, &["RANDOMKEY"]).await);
+}
+
+/// `DEL` and `UNLINK` do not count keys that have expired, but have not been
+/// purged yet, and publish `expired` rather than `del` for them.
+#[tokio::test]
+async fn del_skips_expired_keys() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        ServerBuilder::new()
+            .enable_debug(true)
+            .notify_keyspace_events("KEA".parse().unwrap())
+            .run(listener, tokio::signal::ctrl_c())
+            .await
+    });
+
+    let mut keyspace = Connection::new(TcpStream::connect(addr).await.unwrap());
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    time::pause();
+
+    command(&mut connection, &["DEBUG", "SET-ACTIVE-EXPIRE", "0"]).await;
+    command(&mut connection, &["SET", "short1", "value", "PX", "100"]).await;
+    command(&mut connection, &["SET", "short2", "value", "PX", "100"]).await;
+    command(&mut connection, &["SET", "forever", "value"]).await;
+
+    time::advance(Duration::from_millis(200)).await;
+
+    let response = command(&mut keyspace, &["PSUBSCRIBE", "__keyspace@0__:*"]).await;
+    assert_eq!(confirmation("psubscribe", "__keyspace@0__:*", 1), response);
+
+    let response = command(&mut connection, &["DEL", "short1", "forever"]).await;
+    assert_eq!(Frame::Integer(1), response);
+
+    let response = command(&mut connection, &["UNLINK", "short2"]).await;
+    assert_eq!(Frame::Integer(0), response);
+
+    for (key, event) in [
+        ("short1", "expired"),
+        ("forever", "del"),
+        ("short2", "expired"),
+    ] {
+        let channel = format!("__keyspace@0__:{}", key);
+        let response = keyspace.read_frame().await.unwrap().unwrap();
+        assert_eq!(
+            message(&["pmessage", "__keyspace@0__:*", &channel, event]),
+            response
+        );
+    }
+
+    let response = command(&mut connection, &["DBSIZE"]).await;
+    assert_eq!(Frame::Integer(0), response);
+}
+
+/// Storing the result of a set operation at a key that has expired, but has
+/// not been purged yet, does not report the key as deleted.
+#[tokio::test]
+async fn setop_store_skips_expired_destination() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        ServerBuilder::new()
+            .enable_debug(true)
+            .notify_keyspace_events("KEA".parse().unwrap())
+            .run(listener, tokio::signal::ctrl_c())
+            .await
+    });
+
+    let mut keyspace = Connection::new(TcpStream::connect(addr).await.unwrap());
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    time::pause();
+
+    command(&mut connection, &["DEBUG", "SET-ACTIVE-EXPIRE", "0"]).await;
+    command(&mut connection, &["SET", "short", "value", "PX", "100"]).await;
+
+    time::advance(Duration::from_millis(200)).await;
+
+    let response = command(&mut keyspace, &["PSUBSCRIBE", "__keyspace@0__:*"]).await;
+    assert_eq!(confirmation("psubscribe", "__keyspace@0__:*", 1), response);
+
+    let response = command(&mut connection, &["SINTERSTORE", "short", "missing"]).await;
+    assert_eq!(Frame::Integer(0), response);
+
+    command(&mut connection, &["SADD", "short", "a"]).await;
+
+    for event in ["expired", "sadd"] {
+        let response = keyspace.read_frame().await.unwrap().unwrap();
+        let expected = message(&[
+            "pmessage",
+            "__keyspace@0__:*",
+            "__keyspace@0__:short",
+            event,
+        ]);
+        assert_eq!(expected, response);
+    }
+}
+
+/// `RANDOMKEY` never replies with a key that has expired, but has not been
+/// purged yet.
+#[tokio::test]
+async fn randomkey_skips_expired_keys() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        ServerBuilder::new()
+            .enable_debug(true)
+            .run(listener, tokio::signal::ctrl_c())
+            .await
+    });
+
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    time::pause();
+
+    command(&mut connection, &["DEBUG", "SET-ACTIVE-EXPIRE", "0"]).await;
+    for i in 0..20 {
+        let key = format!("short{}", i);
+        command(&mut connection, &["SET", &key, "value", "PX", "100"]).await;
+    }
+    command(&mut connection, &["SET", "forever", "value"]).await;
+
+    time::advance(Duration::from_millis(200)).await;
+
+    for _ in 0..20 {
+        let response = command(&mut connection, &["RANDOMKEY"]).await;
+        assert_eq!(Frame::Bulk("forever".into()), response);
+    }
+
+    command(&mut connection, &["DEL", "forever"]).await;
+
+    let response = command(&mut connection, &["RANDOMKEY"]).await;
+    assert_eq!(Frame::Null, response);
+}
+
+/// `UNLINK` removes keys immediately, replying with the number of keys that
+/// existed, even though their memory is freed in the background.
+#[tokio::test]
+async fn unlink_removes_keys() {
+    let addr = start_server().await;
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    command(&mut connection, &["SET", "a", "1"]).await;
+    command(&mut connection, &["SET", "b", "2"]).await;
+
+    let response = command(&mut connection, &["UNLINK", "a", "b", "missing"]).await;
+    assert_eq!(Frame::Integer(2), response);
+
+    assert_eq!(Frame::Null, command(&mut connection, &["GET", "a"]).await);
+    assert_eq!(Frame::Null, command(&mut connection, &["GET", "b"]).await);
+
+    let response = command(&mut connection, &["UNLINK", "a"]).await;
+    assert_eq!(Frame::Integer(0), response);
+}
+
+/// `TOUCH` counts the keys that exist and resets their idle time, as reported
+/// by `OBJECT IDLETIME`.
+#[tokio::test]
+async fn touch_resets_idle_time() {
+    let addr = start_server().await;
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    command(&mut connection, &["SET", "hot", "1"]).await;
+    command(&mut connection, &["SET", "cold", "2"]).await;
+
+    // Pausing time after the keys are written makes the idle times exact.
+    time::pause();
+    time::advance(Duration::from_secs(10)).await;
+
+    let response = command(&mut connection, &["OBJECT", "IDLETIME", "hot"]).await;
+    assert_eq!(Frame::Integer(10), response);
+
+    let response = command(&mut connection, &["TOUCH", "hot", "missing"]).await;
+    assert_eq!(Frame::Integer(1), response);
+
+    let response = command(&mut connection, &["OBJECT", "IDLETIME", "hot"]).await;
+    assert_eq!(Frame::Integer(0), response);
+
+    let response = command(&mut connection, &["OBJECT", "IDLETIME", "cold"]).await;
+    assert_eq!(Frame::Integer(10), response);
+
+    let response = command(&mut connection, &["OBJECT", "IDLETIME", "missing"]).await;
+    assert_eq!(Frame::Null, response);
+}
+
+/// A key that has expired, but has not been purged yet, is neither touched nor
+/// reported by `OBJECT IDLETIME`.
+#[tokio::test]
+async fn touch_skips_expired_keys() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        ServerBuilder::new()
+            .enable_debug(true)
+            .run(listener, tokio::signal::ctrl_c())
+            .await
+    });
+
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    time::pause();
+
+    command(&mut connection, &["DEBUG", "SET-ACTIVE-EXPIRE", "0"]).await;
+    command(&mut connection, &["SET", "short", "1", "PX", "100"]).await;
+
+    time::advance(Duration::from_millis(200)).await;
+
+    // The key has not been purged.
+    let response = command(&mut connection, &["DBSIZE"]).await;
+    assert_eq!(Frame::Integer(1), response);
+
+    let response = command(&mut connection, &["TOUCH", "short"]).await;
+    assert_eq!(Frame::Integer(0), response);
+
+    let response = command(&mut connection, &["OBJECT", "IDLETIME", "short"]).await;
+    assert_eq!(Frame::Null, response);
+}
+
+/// `MEMORY USAGE` grows with the size of the value, and `OBJECT REFCOUNT` is
+/// `1` for every existing key.
+#[tokio::test]
+async fn memory_usage_and_refcount() {
+    let addr = start_server().await;
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let large = "x".repeat(10_000);
+    command(&mut connection, &["SET", "small", "x"]).await;
+    command(&mut connection, &["SET", "large", &large]).await;
+
+    let small = match command(&mut connection, &["MEMORY", "USAGE", "small"]).await {
+        Frame::Integer(bytes) => bytes,
+        frame => panic!("unexpected frame: {:?}", frame),
+    };
+    let large = match command(
+        &mut connection,
+        &["MEMORY", "USAGE", "large", "SAMPLES", "5"],
+    )
+    .await
+    {
+        Frame::Integer(bytes) => bytes,
+        frame => panic!("unexpected frame: {:?}", frame),
+    };
+
+    // Both include the key and a fixed overhead.
+    assert!(small > "small".len() as i64 + 1, "{}", small);
+    assert!(large > "large".len() as i64 + 10_000, "{}", large);
+    assert_eq!(large - small, 10_000 - 1);
+
+    let response = command(&mut connection, &["MEMORY", "USAGE", "missing"]).await;
+    assert_eq!(Frame::Null, response);
+
+    let response = command(&mut connection, &["OBJECT", "REFCOUNT", "large"]).await;
+    assert_eq!(Frame::Integer(1), response);
+
+    let response = command(&mut connection, &["OBJECT", "REFCOUNT", "missing"]).await;
+    assert_eq!(Frame::Null, response);
+}
+
+/// `RESET` leaves the subscribed state, after which regular commands can be
+/// issued on the same connection.
+#[tokio::test]
+async fn reset_leaves_subscribed_state() {
+    let addr = start_server().await;
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let response = command(&mut connection, &["SUBSCRIBE", "hello"]).await;
+    assert_eq!(confirmation("subscribe", "hello", 1), response);
+
+    let response = command(&mut connection, &["RESET"]).await;
+    assert_eq!(Frame::Simple("RESET".into()), response);
+
+    let response = command(&mut connection, &["SET", "foo", "bar"]).await;
+    assert_eq!(Frame::Simple("OK".into()), response);
+
+    let response = command(&mut connection, &["GET", "foo"]).await;
+    assert_eq!(Frame::Bulk("bar".into()), response);
+
+    // The subscription was dropped, so publishing reaches no one.
+    let response = command(&mut connection, &["PUBLISH", "hello", "world"]).await;
+    assert_eq!(Frame::Integer(0), response);
+}
+
+/// A server listening on all interfaces accepts connections made through the
+/// loopback interface.
+#[tokio::test]
+async fn server_bound_to_all_interfaces() {
+    let listener = TcpListener::bind("0.0.0.0:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    tokio::spawn(async move { server::run(listener, tokio::signal::ctrl_c()).await });
+
+    let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    let mut connection = Connection::new(stream);
+
+    let response = command(&mut connection, &["PING"]).await;
+    assert_eq!(Frame::Simple("PONG".into()), response);
+}
+
+/// `RESET` requires the connection to authenticate again.
+#[tokio::test]
+async fn reset_clears_authentication() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        ServerBuilder::new()
+            .requirepass("secret")
+            .run(listener, tokio::signal::ctrl_c())
+            .await
+    });
+
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let response = command(&mut connection, &["AUTH", "secret"]).await;
+    assert_eq!(Frame::Simple("OK".into()), response);
+
+    let response = command(&mut connection, &["RESET"]).await;
+    assert_eq!(Frame::Simple("RESET".into()), response);
+
+    let response = command(&mut connection, &["GET", "foo"]).await;
+    assert_eq!(
+        Frame::Error("NOAUTH Authentication required.".into()),
+        response
+    );
+}
+
+/// `QUIT` is accepted before the connection has authenticated.
+#[tokio::test]
+async fn quit_before_auth() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        ServerBuilder::new()
+            .requirepass("secret")
+            .run(listener, tokio::signal::ctrl_c())
+            .await
+    });
+
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let response = command(&mut connection, &["QUIT"]).await;
+    assert_eq!(Frame::Simple("OK".to_string()), response);
+
+    assert!(connection.read_frame().await.unwrap().is_none());
+}
+
+/// `DEBUG SLEEP` delays the response by the requested duration.
+#[tokio::test]
+async fn debug_sleep_delays_response() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        ServerBuilder::new()
+            .enable_debug(true)
+            .run(listener, tokio::signal::ctrl_c())
+            .await
+    });
+
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    // With time paused, the sleep completes as soon as the runtime is idle,
+    // while still advancing the clock by the requested duration.
+    time::pause();
+    let start = time::Instant::now();
+
+    let response = command(&mut connection, &["DEBUG", "SLEEP", "10"]).await;
+    assert_eq!(Frame::Simple("OK".into()), response);
+    assert!(start.elapsed() >= Duration::from_secs(10));
+}
+
+/// Commands taking longer than the slow log threshold are logged as warnings,
+/// with their arguments and the client address.
+#[tokio::test]
+async fn slow_command_is_logged() {
+    // The server tasks run on the test's thread, so a thread-local subscriber
+    // captures their logs.
+    let logs = Logs::default();
+    let writer = logs.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::WARN)
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        ServerBuilder::new()
+            .enable_debug(true)
+            .slowlog_threshold(Duration::from_secs(1))
+            .run(listener, tokio::signal::ctrl_c())
+            .await
+    });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let client_addr = stream.local_addr().unwrap();
+    let mut connection = Connection::new(stream);
+
+    // Fast commands are not logged.
+    command(&mut connection, &["SET", "hello", "world"]).await;
+    assert!(logs.contents().is_empty(), "{}", logs.contents());
+
+    time::pause();
+    command(&mut connection, &["DEBUG", "SLEEP", "2"]).await;
+
+    let contents = logs.contents();
+    assert!(contents.contains("WARN"), "{}", contents);
+    assert!(contents.contains("slow command"), "{}", contents);
+    assert!(contents.contains("command=debug"), "{}", contents);
+    assert!(contents.contains("args=SLEEP 2"), "{}", contents);
+    assert!(
+        contents.contains(&format!("peer={}", client_addr)),
+        "{}",
+        contents
+    );
+}
+
+/// `SLOWLOG GET` returns the slow commands, newest first, and `SLOWLOG RESET`
+/// clears them.
+#[tokio::test]
+async fn slowlog_records_slow_commands() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        ServerBuilder::new()
+            .enable_debug(true)
+            .slowlog_threshold(Duration::from_secs(1))
+            .slowlog_max_len(2)
+            .run(listener, tokio::signal::ctrl_c())
+            .await
+    });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let client_addr = stream.local_addr().unwrap().to_string();
+    let mut connection = Connection::new(stream);
+
+    // Fast commands are not recorded.
+    command(&mut connection, &["SET", "hello", "world"]).await;
+    let response = command(&mut connection, &["SLOWLOG", "LEN"]).await;
+    assert_eq!(Frame::Integer(0), response);
+
+    time::pause();
+    for seconds in ["2", "3", "4"] {
+        command(&mut connection, &["DEBUG", "SLEEP", seconds]).await;
+    }
+
+    // Only the two most recent entries are kept.
+    let response = command(&mut connection, &["SLOWLOG", "LEN"]).await;
+    assert_eq!(Frame::Integer(2), response);
+
+    let entries = match command(&mut connection, &["SLOWLOG", "GET"]).await {
+        Frame::Array(entries) => entries,
+        frame => panic!("unexpected frame {:?}", frame),
+    };
+    assert_eq!(2, entries.len());
+
+    let entry = match &entries[0] {
+        Frame::Array(entry) => entry,
+        frame => panic!("unexpected frame {:?}", frame),
+    };
+    assert_eq!(5, entry.len());
+    assert_eq!(Frame::Integer(2), entry[0]);
+    assert!(matches!(entry[1], Frame::Integer(time) if time > 0));
+    assert!(matches!(entry[2], Frame::Integer(micros) if micros >= 4_000_000));
+    assert_eq!(
+        Frame::Array(vec![
+            Frame::Bulk("DEBUG".into()),
+            Frame::Bulk("SLEEP".into()),
+            Frame::Bulk("4".into()),
+        ]),
+        entry[3]
+    );
+    assert_eq!(Frame::Bulk(client_addr.into()), entry[4]);
+
+    let response = command(&mut connection, &["SLOWLOG", "GET", "1"]).await;
+    assert!(matches!(response, Frame::Array(entries) if entries.len() == 1));
+
+    let response = command(&mut connection, &["SLOWLOG", "RESET"]).await;
+    assert_eq!(Frame::Simple("OK".into()), response);
+
+    let response = command(&mut connection, &["SLOWLOG", "GET", "-1"]).await;
+    assert_eq!(Frame::Array(vec![]), response);
+}
+
+/// With active expiration disabled, expired keys are hidden from reads but
+/// stay in the data set until it is enabled again.
+#[tokio::test]
+async fn debug_set_active_expire() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        ServerBuilder::new()
+            .enable_debug(true)
+            .run(listener, tokio::signal::ctrl_c())
+            .await
+    });
+
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    time::pause();
+
+    let response = command(&mut connection, &["DEBUG", "SET-ACTIVE-EXPIRE", "0"]).await;
+    assert_eq!(Frame::Simple("OK".into()), response);
+
+    let response = command(&mut connection, &["SET", "hello", "world", "PX", "100"]).await;
+    assert_eq!(Frame::Simple("OK".into()), response);
+
+    time::advance(Duration::from_millis(200)).await;
+
+    // The key is logically expired...
+    let response = command(&mut connection, &["GET", "hello"]).await;
+    assert_eq!(Frame::Null, response);
+
+    // ...but has not been purged.
+    let response = command(&mut connection, &["DBSIZE"]).await;
+    assert_eq!(Frame::Integer(1), response);
+
+    let response = command(&mut connection, &["DEBUG", "SET-ACTIVE-EXPIRE", "1"]).await;
+    assert_eq!(Frame::Simple("OK".into()), response);
+
+    // Give the background task a chance to run.
+    time::sleep(Duration::from_millis(1)).await;
+
+    let response = command(&mut connection, &["DBSIZE"]).await;
+    assert_eq!(Frame::Integer(0), response);
+}
+
+/// `DEBUG` is rejected unless it has been enabled.
+#[tokio::test]
+async fn debug_disabled_by_default() {
+    let addr = start_server().await;
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let response = command(&mut connection, &["DEBUG", "SLEEP", "0"]).await;
+    assert_eq!(
+        Frame::Error("ERR DEBUG command not allowed".into()),
+        response
+    );
+}
+
+/// Writes logged to the append-only file are replayed when the server
+/// restarts.
+#[tokio::test]
+async fn appendonly_file_survives_restart() {
+    let path = std::env::temp_dir().join(format!("mini-redis-{}.aof", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let (addr, shutdown, server) = start_appendonly_server(&path).await;
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    for args in [
+        &["SET", "hello", "world"][..],
+        &["SET", "goodbye", "world"],
+        &["DEL", "goodbye"],
+        &["SET", "expiring", "value", "EX", "60"],
+    ] {
+        command(&mut connection, args).await;
+    }
+
+    // Shut the server down, which flushes the file.
+    shutdown.send(()).unwrap();
+    server.await.unwrap();
+
+    let (addr, _shutdown, _server) = start_appendonly_server(&path).await;
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let response = command(&mut connection, &["GET", "hello"]).await;
+    assert_eq!(Frame::Bulk("world".into()), response);
+
+    let response = command(&mut connection, &["GET", "goodbye"]).await;
+    assert_eq!(Frame::Null, response);
+
+    let response = command(&mut connection, &["GET", "expiring"]).await;
+    assert_eq!(Frame::Bulk("value".into()), response);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+/// Commands racing on the same key from different connections are logged in
+/// the order they were applied, so the restarted server has the same value.
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn appendonly_file_logs_racing_commands_in_order() {
+    let path = std::env::temp_dir().join(format!("mini-redis-{}.race.aof", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let (addr, shutdown, server) = start_appendonly_server(&path).await;
+
+    let writers = ["a", "b"].map(|value| {
+        tokio::spawn(async move {
+            let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+            for _ in 0..200 {
+                command(&mut connection, &["SET", "raced", value]).await;
+            }
+        })
+    });
+
+    for writer in writers {
+        writer.await.unwrap();
+    }
+
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+    let expected = command(&mut connection, &["GET", "raced"]).await;
+
+    shutdown.send(()).unwrap();
+    server.await.unwrap();
+
+    let (addr, _shutdown, _server) = start_appendonly_server(&path).await;
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let response = command(&mut connection, &["GET", "raced"]).await;
+    assert_eq!(expected, response);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+/// Expirations are logged to the append-only file as unix timestamps, so a
+/// restart neither brings back keys that have expired in the meantime nor
+/// gives the others a new lease.
+#[tokio::test]
+async fn appendonly_file_keeps_absolute_expirations() {
+    let path = std::env::temp_dir().join(format!("mini-redis-{}.ttl.aof", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let (addr, shutdown, server) = start_appendonly_server(&path).await;
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    command(&mut connection, &["SET", "short", "value", "PX", "100"]).await;
+    command(&mut connection, &["SET", "long", "value", "EX", "60"]).await;
+
+    shutdown.send(()).unwrap();
+    server.await.unwrap();
+
+    time::sleep(Duration::from_millis(300)).await;
+
+    let (addr, _shutdown, _server) = start_appendonly_server(&path).await;
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let response = command(&mut connection, &["GET", "short"]).await;
+    assert_eq!(Frame::Null, response);
+
+    match command(&mut connection, &["PTTL", "long"]).await {
+        Frame::Integer(ms) => assert!(ms > 50_000 && ms <= 59_700, "{}", ms),
+        frame => panic!("unexpected frame {:?}", frame),
+    }
+
+    let _ = std::fs::remove_file(&path);
+}
+
+/// `SAVE` writes a snapshot, which can be loaded into a fresh `Db`, and
+/// `BGSAVE` writes one in the background.
+#[tokio::test]
+async fn save_and_bgsave_write_snapshot() {
+    let path = std::env::temp_dir().join(format!("mini-redis-{}.snapshot", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = ServerBuilder::new().dbfilename(&path);
+    tokio::spawn(async move { server.run(listener, tokio::signal::ctrl_c()).await });
+
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    command(&mut connection, &["SET", "hello", "world"]).await;
+
+    let response = command(&mut connection, &["SAVE"]).await;
+    assert_eq!(Frame::Simple("OK".into()), response);
+
+    let db = Db::new();
+    db.restore(DbSnapshot::read_from_file(&path).await.unwrap());
+    assert_eq!(b"world", &db.get("hello").unwrap().unwrap()[..]);
+
+    command(&mut connection, &["SET", "hello", "again"]).await;
+
+    let response = command(&mut connection, &["BGSAVE"]).await;
+    assert_eq!(Frame::Simple("Background saving started".into()), response);
+
+    // Wait for the background save to replace the snapshot.
+    let saved = async {
+        loop {
+            if let Ok(snapshot) = DbSnapshot::read_from_file(&path).await {
+                db.restore(snapshot);
+
+                if db.get("hello").unwrap().as_deref() == Some(&b"again"[..]) {
+                    break;
+                }
+            }
+
+            time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+    time::timeout(Duration::from_secs(5), saved).await.unwrap();
+
+    let _ = std::fs::remove_file(&path);
+}
+
+/// `LASTSAVE` returns when the server started until `SAVE` succeeds.
+#[tokio::test]
+async fn lastsave_advances_on_save() {
+    let path = std::env::temp_dir().join(format!(
+        "mini-redis-{}.lastsave.snapshot",
+        std::process::id()
+    ));
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = ServerBuilder::new().dbfilename(&path);
+    tokio::spawn(async move { server.run(listener, tokio::signal::ctrl_c()).await });
+
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let started = match command(&mut connection, &["LASTSAVE"]).await {
+        Frame::Integer(time) => time,
+        frame => panic!("unexpected frame: {:?}", frame),
+    };
+
+    // `LASTSAVE` has a resolution of one second.
+    time::sleep(Duration::from_millis(1100)).await;
+
+    let response = command(&mut connection, &["SAVE"]).await;
+    assert_eq!(Frame::Simple("OK".into()), response);
+
+    let saved = match command(&mut connection, &["LASTSAVE"]).await {
+        Frame::Integer(time) => time,
+        frame => panic!("unexpected frame: {:?}", frame),
+    };
+    assert!(saved > started, "{} <= {}", saved, started);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+/// Sorted sets are saved in snapshots and loaded back on startup, keeping
+/// their order and scores.
+#[tokio::test]
+async fn sorted_set_survives_snapshot() {
+    let path =
+        std::env::temp_dir().join(format!("mini-redis-{}.zset.snapshot", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = ServerBuilder::new().dbfilename(&path);
+    tokio::spawn(async move { server.run(listener, tokio::signal::ctrl_c()).await });
+
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    command(
+        &mut connection,
+        &["ZADD", "scores", "2", "b", "1.5", "a", "-inf", "c"],
+    )
+    .await;
+
+    let response = command(&mut connection, &["SAVE"]).await;
+    assert_eq!(Frame::Simple("OK".into()), response);
+
+    // A second server loads the snapshot on startup.
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = ServerBuilder::new().dbfilename(&path);
+    tokio::spawn(async move { server.run(listener, tokio::signal::ctrl_c()).await });
+
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let response = command(
+        &mut connection,
+        &["ZRANGE", "scores", "0", "-1", "WITHSCORES"],
+    )
+    .await;
+    assert_eq!(
+        Frame::Array(
+            ["c", "-inf", "a", "1.5", "b", "2"]
+                .iter()
+                .map(|s| Frame::Bulk(Bytes::from(*s)))
+                .collect()
+        ),
+        response
+    );
+
+    let _ = std::fs::remove_file(&path);
+}
+
+/// `SAVE` is rejected when no snapshot file is configured.
+#[tokio::test]
+async fn save_without_dbfilename() {
+    let addr = start_server().await;
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let response = command(&mut connection, &["SAVE"]).await;
+    assert_eq!(
+        Frame::Error("ERR no snapshot file configured".into()),
+        response
+    );
+}
+
+/// Without replicas, `WAIT` replies right away that no replica acknowledged
+/// the writes.
+#[tokio::test]
+async fn wait_returns_zero_replicas() {
+    let addr = start_server().await;
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    command(&mut connection, &["SET", "hello", "world"]).await;
+
+    let start = time::Instant::now();
+    let response = command(&mut connection, &["WAIT", "0", "100"]).await;
+    assert_eq!(Frame::Integer(0), response);
+    assert!(start.elapsed() < Duration::from_millis(100));
+}
+
+/// A replica copies the data set of its primary, then applies the writes the
+/// primary receives, while rejecting writes from its own clients.
+#[tokio::test]
+async fn replica_follows_primary() {
+    let primary_addr = start_server().await;
+    let mut primary = Connection::new(TcpStream::connect(primary_addr).await.unwrap());
+
+    // Written before the replica connects, so it is part of the snapshot.
+    command(&mut primary, &["SET", "before", "value"]).await;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let replica_addr = listener.local_addr().unwrap();
+
+    let server = ServerBuilder::new().replicaof("127.0.0.1", primary_addr.port());
+    tokio::spawn(async move { server.run(listener, tokio::signal::ctrl_c()).await });
+
+    let mut replica = Connection::new(TcpStream::connect(replica_addr).await.unwrap());
+
+    wait_for_value(&mut replica, "before", Frame::Bulk("value".into())).await;
+
+    // Written once the replica follows the primary.
+    command(&mut primary, &["SET", "after", "value"]).await;
+    wait_for_value(&mut replica, "after", Frame::Bulk("value".into())).await;
+
+    command(&mut primary, &["DEL", "before"]).await;
+    wait_for_value(&mut replica, "before", Frame::Null).await;
+
+    let response = command(&mut replica, &["SET", "hello", "world"]).await;
+    assert_eq!(
+        Frame::Error("READONLY You can't write against a read only replica.".into()),
+        response
+    );
+}
+
+/// Writes applied while a replica connects are either in its snapshot or
+/// forwarded to it, never both, so `INCRBYFLOAT` is not applied twice.
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn replica_connecting_during_writes_does_not_diverge() {
+    let primary_addr = start_server().await;
+
+    let writer = tokio::spawn(async move {
+        let mut primary = Connection::new(TcpStream::connect(primary_addr).await.unwrap());
+
+        for _ in 0..500 {
+            command(&mut primary, &["INCRBYFLOAT", "counter", "1"]).await;
+        }
+    });
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let replica_addr = listener.local_addr().unwrap();
+
+    let server = ServerBuilder::new().replicaof("127.0.0.1", primary_addr.port());
+    tokio::spawn(async move { server.run(listener, tokio::signal::ctrl_c()).await });
+
+    writer.await.unwrap();
+
+    let mut primary = Connection::new(TcpStream::connect(primary_addr).await.unwrap());
+    let mut replica = Connection::new(TcpStream::connect(replica_addr).await.unwrap());
+
+    // Written last, so the replica has applied every write once it has it.
+    command(&mut primary, &["SET", "done", "1"]).await;
+    wait_for_value(&mut replica, "done", Frame::Bulk("1".into())).await;
+
+    let response = command(&mut replica, &["GET", "counter"]).await;
+    assert_eq!(Frame::Bulk("500".into()), response);
+}
+
+/// A replica follows a primary listening on an IPv6 address.
+#[tokio::test]
+async fn replica_follows_ipv6_primary() {
+    let listener = TcpListener::bind("[::1]:0").await.unwrap();
+    let primary_port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move { server::run(listener, tokio::signal::ctrl_c()).await });
+
+    let stream = TcpStream::connect(("::1", primary_port)).await.unwrap();
+    let mut primary = Connection::new(stream);
+    command(&mut primary, &["SET", "hello", "world"]).await;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let replica_addr = listener.local_addr().unwrap();
+
+    let server = ServerBuilder::new().replicaof("::1", primary_port);
+    tokio::spawn(async move { server.run(listener, tokio::signal::ctrl_c()).await });
+
+    let mut replica = Connection::new(TcpStream::connect(replica_addr).await.unwrap());
+    wait_for_value(&mut replica, "hello", Frame::Bulk("world".into())).await;
+
+    let response = command(&mut replica, &["ROLE"]).await;
+    assert_eq!(
+        Frame::Array(vec![
+            Frame::Bulk("slave".into()),
+            Frame::Bulk("::1".into()),
+            Frame::Integer(primary_port as i64),
+            Frame::Bulk("connected".into()),
+            Frame::Integer(0),
+        ]),
+        response
+    );
+}
+
+/// A server that is not a replica reports itself as a primary, without
+/// replicas, and cannot fail over.
+#[tokio::test]
+async fn role_of_standalone_server() {
+    let addr = start_server().await;
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let response = command(&mut connection, &["ROLE"]).await;
+    assert_eq!(
+        Frame::Array(vec![
+            Frame::Bulk("master".into()),
+            Frame::Integer(0),
+            Frame::Array(vec![]),
+        ]),
+        response
+    );
+
+    let response = command(&mut connection, &["FAILOVER"]).await;
+    assert_eq!(
+        Frame::Error("ERR FAILOVER requires connected replicas.".into()),
+        response
+    );
+}
+
+/// A connection running `MONITOR` receives the commands other connections
+/// send.
+#[tokio::test]
+async fn monitor_streams_commands() {
+    let addr = start_server().await;
+    let mut monitor = Connection::new(TcpStream::connect(addr).await.unwrap());
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let response = command(&mut monitor, &["MONITOR"]).await;
+    assert_eq!(Frame::Simple("OK".into()), response);
+
+    command(&mut connection, &["SET", "hello", "wor\"ld"]).await;
+
+    let line = match monitor.read_frame().await.unwrap().unwrap() {
+        Frame::Bulk(line) => String::from_utf8(line.to_vec()).unwrap(),
+        frame => panic!("unexpected frame {:?}", frame),
+    };
+
+    // The line starts with a timestamp, followed by the database and the
+    // address of the client.
+    let (timestamp, rest) = line.split_once(' ').unwrap();
+    assert!(timestamp.parse::<f64>().is_ok(), "{}", line);
+    assert!(rest.starts_with("[0 127.0.0.1:"), "{}", line);
+    assert!(rest.ends_with(r#"] "SET" "hello" "wor\"ld""#), "{}", line);
+}
+
+/// `QUIT` is acknowledged, after which the server closes the connection.
+#[tokio::test]
+async fn quit_closes_connection() {
+    let addr = start_server().await;
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let response = command(&mut connection, &["QUIT"]).await;
+    assert_eq!(Frame::Simple("OK".to_string()), response);
+
+    assert!(connection.read_frame().await.unwrap().is_none());
+}
+
+/// Channel and pattern subscriptions are counted together in every
+/// confirmation, and a message is delivered once per matching subscription.
+#[tokio::test]
+async fn subscribe_and_psubscribe_share_count() {
+    let addr = start_server().await;
+
+    let mut sub = Connection::new(TcpStream::connect(addr).await.unwrap());
+    let mut publisher = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let response = command(&mut sub, &["SUBSCRIBE", "foo"]).await;
+    assert_eq!(confirmation("subscribe", "foo", 1), response);
+
+    let response = command(&mut sub, &["PSUBSCRIBE", "f*", "b?"]).await;
+    assert_eq!(confirmation("psubscribe", "f*", 2), response);
+    let response = sub.read_frame().await.unwrap().unwrap();
+    assert_eq!(confirmation("psubscribe", "b?", 3), response);
+
+    // A pattern named like a subscribed channel is a separate subscription
+    let response = command(&mut sub, &["PSUBSCRIBE", "foo"]).await;
+    assert_eq!(confirmation("psubscribe", "foo", 4), response);
+
+    // Subscribing again to the same channel does not change the count
+    let response = command(&mut sub, &["SUBSCRIBE", "foo"]).await;
+    assert_eq!(confirmation("subscribe", "foo", 4), response);
+
+    // `foo` matches the channel and the `f*` and `foo` patterns
+    let response = command(&mut publisher, &["PUBLISH", "foo", "hello"]).await;
+    assert_eq!(Frame::Integer(3), response);
+
+    let mut received = vec![];
+    for _ in 0..3 {
+        received.push(sub.read_frame().await.unwrap().unwrap());
+    }
+    for expected in [
+        message(&["message", "foo", "hello"]),
+        message(&["pmessage", "f*", "foo", "hello"]),
+        message(&["pmessage", "foo", "foo", "hello"]),
+    ] {
+        assert!(received.contains(&expected), "{:?}", expected);
+    }
+
+    let response = command(&mut publisher, &["PUBLISH", "bar", "hello"]).await;
+    assert_eq!(Frame::Integer(0), response);
+
+    // Unsubscribing from all patterns leaves the channel subscription
+    command(&mut sub, &["PUNSUBSCRIBE"]).await;
+    sub.read_frame().await.unwrap().unwrap();
+    let response = sub.read_frame().await.unwrap().unwrap();
+    assert_eq!(Frame::Integer(1), last_entry(response));
+
+    let response = command(&mut sub, &["UNSUBSCRIBE"]).await;
+    assert_eq!(confirmation("unsubscribe", "foo", 0), response);
+}
+
+/// A message published while a client subscribes is never delivered before
+/// the confirmation of the subscription.
+#[tokio::test]
+async fn subscribe_confirmation_precedes_messages() {
+    let addr = start_server().await;
+
+    let mut sub = Connection::new(TcpStream::connect(addr).await.unwrap());
+    let mut publisher = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    // Send the subscription without waiting for its confirmation, and publish
+    // until the message is delivered, so publishing races the subscription.
+    let subscribe = Frame::Array(vec![
+        Frame::Bulk(Bytes::from("SUBSCRIBE")),
+        Frame::Bulk(Bytes::from("foo")),
+    ]);
+    sub.write_frame(&subscribe).await.unwrap();
+
+    while command(&mut publisher, &["PUBLISH", "foo", "hello"]).await != Frame::Integer(1) {}
+
+    let response = sub.read_frame().await.unwrap().unwrap();
+    assert_eq!(confirmation("subscribe", "foo", 1), response);
+
+    let response = sub.read_frame().await.unwrap().unwrap();
+    assert_eq!(message(&["message", "foo", "hello"]), response);
+
+    // The same holds for a subscription added once in the subscribed state.
+    let subscribe = Frame::Array(vec![
+        Frame::Bulk(Bytes::from("SUBSCRIBE")),
+        Frame::Bulk(Bytes::from("bar")),
+    ]);
+    sub.write_frame(&subscribe).await.unwrap();
+
+    while command(&mut publisher, &["PUBLISH", "bar", "hello"]).await != Frame::Integer(1) {}
+
+    let response = sub.read_frame().await.unwrap().unwrap();
+    assert_eq!(confirmation("subscribe", "bar", 2), response);
+
+    let response = sub.read_frame().await.unwrap().unwrap();
+    assert_eq!(message(&["message", "bar", "hello"]), response);
+}
+
+/// With `max_subscriber_lags`, a subscriber too slow to keep up with the
+/// published messages is sent an error and disconnected, rather than silently
+/// missing messages.
+#[tokio::test]
+async fn slow_subscriber_is_disconnected() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        ServerBuilder::new()
+            .max_subscriber_lags(1)
+            .run(listener, tokio::signal::ctrl_c())
+            .await
+    });
+
+    // A fixed receive buffer keeps the kernel from buffering most of the
+    // messages on the subscriber's behalf.
+    let socket = TcpSocket::new_v4().unwrap();
+    socket.set_recv_buffer_size(64 * 1024).unwrap();
+    let mut sub = Connection::new(socket.connect(addr).await.unwrap());
+
+    let response = command(&mut sub, &["SUBSCRIBE", "news"]).await;
+    assert_eq!(confirmation("subscribe", "news", 1), response);
+
+    // The subscriber stalls while far more messages than the channel buffers
+    // are published. The publisher pipelines its commands, reading the
+    // replies as they arrive.
+    const MESSAGES: usize = 16 * 1024;
+    let message = "x".repeat(1024);
+    let publish = format!(
+        "*3\r\n$7\r\nPUBLISH\r\n$4\r\nnews\r\n${}\r\n{}\r\n",
+        message.len(),
+        message
+    );
+
+    let (mut rd, mut wr) = TcpStream::connect(addr).await.unwrap().into_split();
+    let replies = tokio::spawn(async move {
+        // Every reply is an integer, ending with a single newline.
+        let mut buf = [0; 4096];
+        let mut remaining = MESSAGES;
+        while remaining > 0 {
+            let n = rd.read(&mut buf).await.unwrap();
+            assert_ne!(0, n);
+            remaining -= buf[..n].iter().filter(|&&b| b == b'\n').count();
+        }
+    });
+    wr.write_all(publish.repeat(MESSAGES).as_bytes())
+        .await
+        .unwrap();
+    replies.await.unwrap();
+
+    // Once it reads again, the subscriber receives the messages sent before
+    // it fell behind, then the error.
+    loop {
+        match sub.read_frame().await.unwrap() {
+            Some(Frame::Array(_)) => {}
+            Some(frame) => {
+                assert_eq!(
+                    Frame::Error("ERR client fell behind the published messages".into()),
+                    frame
+                );
+                break;
+            }
+            None => panic!("connection closed without an error"),
+        }
+    }
+
+    assert!(matches!(sub.read_frame().await, Ok(None) | Err(_)));
+}
+
+/// A subscriber closing its write half ends its subscriptions cleanly, and
+/// the server keeps serving other clients.
+#[tokio::test]
+async fn subscriber_closing_write_half() {
+    let addr = start_server().await;
+
+    let mut sub = TcpStream::connect(addr).await.unwrap();
+    sub.write_all(b"*2\r\n$9\r\nSUBSCRIBE\r\n$5\r\nhello\r\n")
+        .await
+        .unwrap();
+
+    let mut response = [0; 34];
+    sub.read_exact(&mut response).await.unwrap();
+    assert_eq!(
+        &b"*3\r\n$9\r\nsubscribe\r\n$5\r\nhello\r\n:1\r\n"[..],
+        &response[..]
+    );
+
+    // The server closes the connection once it sees the end of the stream.
+    sub.shutdown().await.unwrap();
+    let mut response = vec![];
+    sub.read_to_end(&mut response).await.unwrap();
+    assert!(response.is_empty(), "{:?}", response);
+
+    // The subscription is gone, and the server still answers.
+    let mut publisher = Connection::new(TcpStream::connect(addr).await.unwrap());
+    let response = command(&mut publisher, &["PUBLISH", "hello", "world"]).await;
+    assert_eq!(Frame::Integer(0), response);
+}
+
+/// A client closing its write half right after sending its commands still
+/// receives every reply, followed by the end of the stream.
+#[tokio::test]
+async fn half_closed_connection_receives_replies() {
+    let addr = start_server().await;
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(
+            b"*3\r\n$3\r\nSET\r\n$5\r\nhello\r\n$5\r\nworld\r\n\
+              *2\r\n$3\r\nGET\r\n$5\r\nhello\r\n",
+        )
+        .await
+        .unwrap();
+    stream.shutdown().await.unwrap();
+
+    let mut response = vec![];
+    time::timeout(Duration::from_secs(5), stream.read_to_end(&mut response))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(&b"+OK\r\n$5\r\nworld\r\n"[..], &response[..]);
+}
+
+/// Unsubscribing from all channels or patterns without holding any is
+/// confirmed with a single reply naming no channel.
+#[tokio::test]
+async fn unsubscribe_without_subscriptions() {
+    let addr = start_server().await;
+
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    // Outside of the subscribed state
+    let response = command(&mut connection, &["UNSUBSCRIBE"]).await;
+    assert_eq!(unsubscribe_none("unsubscribe", 0), response);
+
+    let response = command(&mut connection, &["PUNSUBSCRIBE"]).await;
+    assert_eq!(unsubscribe_none("punsubscribe", 0), response);
+
+    // Exactly one reply was sent for each, so the next one answers `PING`
+    let response = command(&mut connection, &["PING"]).await;
+    assert_eq!(Frame::Simple("PONG".to_string()), response);
+
+    // Named channels are each confirmed, with nothing left subscribed
+    let response = command(&mut connection, &["UNSUBSCRIBE", "foo", "bar"]).await;
+    assert_eq!(confirmation("unsubscribe", "foo", 0), response);
+    let response = connection.read_frame().await.unwrap().unwrap();
+    assert_eq!(confirmation("unsubscribe", "bar", 0), response);
+
+    // In the subscribed state, holding only a pattern subscription
+    let response = command(&mut connection, &["PSUBSCRIBE", "f*"]).await;
+    assert_eq!(confirmation("psubscribe", "f*", 1), response);
+
+    let response = command(&mut connection, &["UNSUBSCRIBE"]).await;
+    assert_eq!(unsubscribe_none("unsubscribe", 1), response);
+
+    let response = command(&mut connection, &["PUNSUBSCRIBE"]).await;
+    assert_eq!(confirmation("punsubscribe", "f*", 0), response);
+
+    let response = command(&mut connection, &["PUNSUBSCRIBE"]).await;
+    assert_eq!(unsubscribe_none("punsubscribe", 0), response);
+}
+
+/// With keyspace notifications enabled, modifying keys publishes events to
+/// the keyspace and keyevent channels.
+#[tokio::test]
+async fn keyspace_notifications() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        ServerBuilder::new()
+            .notify_keyspace_events("KEA".parse().unwrap())
+            .run(listener, tokio::signal::ctrl_c())
+            .await
+    });
+
+    // Messages from different subscriptions of a connection may arrive in
+    // any order, so each channel gets its own connection.
+    let mut keyevent = Connection::new(TcpStream::connect(addr).await.unwrap());
+    let mut keyspace = Connection::new(TcpStream::connect(addr).await.unwrap());
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let response = command(&mut keyevent, &["SUBSCRIBE", "__keyevent@0__:set"]).await;
+    assert_eq!(confirmation("subscribe", "__keyevent@0__:set", 1), response);
+
+    let response = command(&mut keyspace, &["PSUBSCRIBE", "__keyspace@0__:*"]).await;
+    assert_eq!(confirmation("psubscribe", "__keyspace@0__:*", 1), response);
+
+    command(&mut connection, &["SET", "foo", "bar", "EX", "10"]).await;
+
+    let response = keyevent.read_frame().await.unwrap().unwrap();
+    assert_eq!(message(&["message", "__keyevent@0__:set", "foo"]), response);
+
+    for event in ["set", "expire"] {
+        let response = keyspace.read_frame().await.unwrap().unwrap();
+        let expected = message(&["pmessage", "__keyspace@0__:*", "__keyspace@0__:foo", event]);
+        assert_eq!(expected, response);
+    }
+
+    // Only keys that existed are reported as deleted.
+    command(&mut connection, &["DEL", "foo", "missing"]).await;
+
+    let response = keyspace.read_frame().await.unwrap().unwrap();
+    let expected = message(&["pmessage", "__keyspace@0__:*", "__keyspace@0__:foo", "del"]);
+    assert_eq!(expected, response);
+
+    // Commands updating a string in place publish their own event.
+    command(&mut connection, &["INCRBYFLOAT", "foo", "1.5"]).await;
+    command(&mut connection, &["SETRANGE", "foo", "0", "2"]).await;
+    command(&mut connection, &["SETBIT", "foo", "0", "1"]).await;
+
+    for event in ["incrbyfloat", "setrange", "setbit"] {
+        let response = keyspace.read_frame().await.unwrap().unwrap();
+        let expected = message(&["pmessage", "__keyspace@0__:*", "__keyspace@0__:foo", event]);
+        assert_eq!(expected, response);
+    }
+
+    // Set and sorted set commands only publish when they change the value.
+    command(&mut connection, &["SADD", "tags", "a", "b"]).await;
+    command(&mut connection, &["SADD", "tags", "a"]).await;
+    command(&mut connection, &["SREM", "tags", "a", "missing"]).await;
+    command(&mut connection, &["ZADD", "scores", "1", "a"]).await;
+    command(&mut connection, &["ZADD", "scores", "1", "a"]).await;
+
+    // Storing the result of a set operation publishes the command name, or
+    // `del` if an empty result removes the destination.
+    command(&mut connection, &["SUNIONSTORE", "copy", "tags"]).await;
+    command(&mut connection, &["SINTERSTORE", "copy", "tags", "missing"]).await;
+    command(&mut connection, &["SDIFFSTORE", "copy", "missing"]).await;
+    command(&mut connection, &["SDIFFSTORE", "copy", "tags", "missing"]).await;
+
+    let events = [
+        ("tags", "sadd"),
+        ("tags", "srem"),
+        ("scores", "zadd"),
+        ("copy", "sunionstore"),
+        ("copy", "del"),
+        ("copy", "sdiffstore"),
+    ];
+
+    for (key, event) in events {
+        let response = keyspace.read_frame().await.unwrap().unwrap();
+        let channel = format!("__keyspace@0__:{}", key);
+        let expected = message(&["pmessage", "__keyspace@0__:*", &channel, event]);
+        assert_eq!(expected, response);
+    }
+
+    time::timeout(Duration::from_millis(100), keyspace.read_frame())
+        .await
+        .unwrap_err();
+}
+
+/// Keys removed by the background task because they expired are reported
+/// with an `expired` event.
+#[tokio::test]
+async fn keyspace_notification_on_expiration() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        ServerBuilder::new()
+            .notify_keyspace_events("Ex".parse().unwrap())
+            .run(listener, tokio::signal::ctrl_c())
+            .await
+    });
+
+    let mut sub = Connection::new(TcpStream::connect(addr).await.unwrap());
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let response = command(&mut sub, &["SUBSCRIBE", "__keyevent@0__:expired"]).await;
+    assert_eq!(
+        confirmation("subscribe", "__keyevent@0__:expired", 1),
+        response
+    );
+
+    command(&mut connection, &["SET", "foo", "bar", "PX", "100"]).await;
+
+    // Nothing is published until the key expires.
+    time::pause();
+
+    time::timeout(Duration::from_millis(50), sub.read_frame())
+        .await
+        .unwrap_err();
+
+    time::advance(Duration::from_millis(100)).await;
+
+    let response = sub.read_frame().await.unwrap().unwrap();
+    assert_eq!(
+        message(&["message", "__keyevent@0__:expired", "foo"]),
+        response
+    );
+}
+
+/// Keyspace events are not published unless enabled.
+#[tokio::test]
+async fn keyspace_notifications_disabled_by_default() {
+    let addr = start_server().await;
+
+    let mut sub = Connection::new(TcpStream::connect(addr).await.unwrap());
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    command(&mut sub, &["SUBSCRIBE", "__keyevent@0__:set"]).await;
+    command(&mut connection, &["SET", "foo", "bar"]).await;
+
+    time::timeout(Duration::from_millis(100), sub.read_frame())
+        .await
+        .unwrap_err();
+}
+
+/// Commands sent after `MULTI` are queued, then applied by `EXEC`, which
+/// replies with an array of their replies.
+#[tokio::test]
+async fn multi_exec_applies_queued_commands() {
+    let addr = start_server().await;
+
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+    let mut other = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let response = command(&mut connection, &["MULTI"]).await;
+    assert_eq!(Frame::Simple("OK".to_string()), response);
+
+    for args in [&["SET", "a", "1"][..], &["SET", "b", "2"], &["GET", "a"]] {
+        let response = command(&mut connection, args).await;
+        assert_eq!(Frame::Simple("QUEUED".to_string()), response);
+    }
+
+    // Nothing is applied until `EXEC`.
+    let response = command(&mut other, &["GET", "a"]).await;
+    assert_eq!(Frame::Null, response);
+
+    let response = command(&mut connection, &["EXEC"]).await;
+    assert_eq!(
+        Frame::Array(vec![
+            Frame::Simple("OK".to_string()),
+            Frame::Simple("OK".to_string()),
+            Frame::Bulk("1".into()),
+        ]),
+        response
+    );
+
+    let response = command(&mut other, &["GET", "b"]).await;
+    assert_eq!(Frame::Bulk("2".into()), response);
+
+    // The transaction is over, commands are applied right away again.
+    let response = command(&mut connection, &["GET", "b"]).await;
+    assert_eq!(Frame::Bulk("2".into()), response);
+}
+
+/// `DISCARD` drops the queued commands, and a command rejected while queuing
+/// makes `EXEC` discard the whole transaction.
+#[tokio::test]
+async fn multi_discard_and_abort() {
+    let addr = start_server().await;
+
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let response = command(&mut connection, &["EXEC"]).await;
+    assert_eq!(Frame::Error("ERR EXEC without MULTI".to_string()), response);
+
+    let response = command(&mut connection, &["DISCARD"]).await;
+    assert_eq!(
+        Frame::Error("ERR DISCARD without MULTI".to_string()),
+        response
+    );
+
+    command(&mut connection, &["MULTI"]).await;
+    command(&mut connection, &["SET", "a", "1"]).await;
+
+    let response = command(&mut connection, &["MULTI"]).await;
+    let nested = Frame::Error("ERR MULTI calls can not be nested".to_string());
+    assert_eq!(nested, response);
+
+    let response = command(&mut connection, &["DISCARD"]).await;
+    assert_eq!(Frame::Simple("OK".to_string()), response);
+
+    let response = command(&mut connection, &["GET", "a"]).await;
+    assert_eq!(Frame::Null, response);
+
+    // Subscribing is not allowed inside a transaction.
+    command(&mut connection, &["MULTI"]).await;
+    command(&mut connection, &["SET", "a", "1"]).await;
+
+    let response = command(&mut connection, &["SUBSCRIBE", "foo"]).await;
+    let not_allowed = Frame::Error("ERR Command not allowed inside a transaction".to_string());
+    assert_eq!(not_allowed, response);
+
+    let response = command(&mut connection, &["EXEC"]).await;
+    let aborted = "EXECABORT Transaction discarded because of previous errors.";
+    assert_eq!(Frame::Error(aborted.to_string()), response);
+
+    // Neither is a malformed command.
+    command(&mut connection, &["MULTI"]).await;
+    command(&mut connection, &["SET", "a", "1"]).await;
+
+    let response = command(&mut connection, &["GET"]).await;
+    assert!(matches!(response, Frame::Error(_)), "{:?}", response);
+
+    let response = command(&mut connection, &["EXEC"]).await;
+    assert_eq!(Frame::Error(aborted.to_string()), response);
+
+    let response = command(&mut connection, &["GET", "a"]).await;
+    assert_eq!(Frame::Null, response);
+}
+
+/// A transaction is not applied if a key watched with `WATCH` is modified
+/// before `EXEC`, and `EXEC` returns `nil`.
+#[tokio::test]
+async fn watch_aborts_exec_when_key_modified() {
+    let addr = start_server().await;
+
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+    let mut other = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    command(&mut connection, &["SET", "a", "1"]).await;
+
+    let response = command(&mut connection, &["WATCH", "a", "b"]).await;
+    assert_eq!(Frame::Simple("OK".to_string()), response);
+
+    command(&mut connection, &["MULTI"]).await;
+    command(&mut connection, &["SET", "c", "1"]).await;
+
+    let response = command(&mut connection, &["WATCH", "a"]).await;
+    let inside_multi = Frame::Error("ERR WATCH inside MULTI is not allowed".to_string());
+    assert_eq!(inside_multi, response);
+
+    // Another connection modifies a watched key.
+    command(&mut other, &["SET", "a", "2"]).await;
+
+    let response = command(&mut connection, &["EXEC"]).await;
+    assert_eq!(Frame::Null, response);
+
+    let response = command(&mut connection, &["GET", "c"]).await;
+    assert_eq!(Frame::Null, response);
+
+    // `EXEC` stopped watching the keys, so the next transaction is applied.
+    command(&mut other, &["SET", "a", "3"]).await;
+    command(&mut connection, &["MULTI"]).await;
+    command(&mut connection, &["SET", "c", "1"]).await;
+
+    let response = command(&mut connection, &["EXEC"]).await;
+    assert_eq!(
+        Frame::Array(vec![Frame::Simple("OK".to_string())]),
+        response
+    );
+
+    // Creating a watched key counts as modifying it.
+    command(&mut connection, &["WATCH", "b"]).await;
+    command(&mut other, &["SADD", "b", "x"]).await;
+    command(&mut connection, &["MULTI"]).await;
+
+    let response = command(&mut connection, &["EXEC"]).await;
+    assert_eq!(Frame::Null, response);
+
+    // `UNWATCH` stops watching the keys.
+    command(&mut connection, &["WATCH", "b"]).await;
+    let response = command(&mut connection, &["UNWATCH"]).await;
+    assert_eq!(Frame::Simple("OK".to_string()), response);
+
+    command(&mut other, &["SADD", "b", "y"]).await;
+    command(&mut connection, &["MULTI"]).await;
+
+    let response = command(&mut connection, &["EXEC"]).await;
+    assert_eq!(Frame::Array(vec![]), response);
+
+    // Commands that leave the value unchanged do not modify the key.
+    command(&mut other, &["ZADD", "scores", "1", "a"]).await;
+    command(&mut connection, &["WATCH", "b", "scores"]).await;
+    command(&mut other, &["SADD", "b", "x"]).await;
+    command(&mut other, &["ZADD", "scores", "1", "a"]).await;
+    command(&mut connection, &["MULTI"]).await;
+
+    let response = command(&mut connection, &["EXEC"]).await;
+    assert_eq!(Frame::Array(vec![]), response);
+}
+
+/// Every command operating on one type of value returns the same `WRONGTYPE`
+/// error when applied to a key holding a value of any other type, and leaves
+/// the value untouched.
+#[tokio::test]
+async fn wrongtype_for_every_type_pairing() {
+    let addr = start_server().await;
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    command(&mut connection, &["SET", "string", "hello"]).await;
+    command(&mut connection, &["ZADD", "zset", "1", "a"]).await;
+    command(&mut connection, &["SADD", "set", "a"]).await;
+
+    // The commands of each type, along with their arguments after the key.
+    let commands: &[(&str, &[&[&str]])] = &[
+        (
+            "string",
+            &[
+                &["GET"],
+                &["INCRBYFLOAT", "1"],
+                &["SETRANGE", "0", "x"],
+                &["GETRANGE", "0", "-1"],
+                &["SETBIT", "0", "1"],
+                &["GETBIT", "0"],
+                &["BITCOUNT"],
+            ],
+        ),
+        (
+            "zset",
+            &[
+                &["ZADD", "1", "b"],
+                &["ZSCORE", "a"],
+                &["ZRANGE", "0", "-1"],
+            ],
+        ),
+        (
+            "set",
+            &[
+                &["SADD", "b"],
+                &["SREM", "a"],
+                &["SMEMBERS"],
+                &["SISMEMBER", "a"],
+                &["SCARD"],
+            ],
+        ),
+    ];
+
+    let wrongtype =
+        Frame::Error("WRONGTYPE Operation against a key holding the wrong kind of value".into());
+
+    for (key, _) in commands {
+        for (kind, cmds) in commands.iter().filter(|(kind, _)| kind != key) {
+            for cmd in cmds.iter() {
+                let mut args = vec![cmd[0], key];
+                args.extend_from_slice(&cmd[1..]);
+
+                let response = command(&mut connection, &args).await;
+                assert_eq!(wrongtype, response, "{} command {:?}", kind, args);
+            }
+        }
+
+        let response = command(&mut connection, &["TYPE", key]).await;
+        assert_eq!(Frame::Simple(key.to_string()), response);
+    }
+
+    let response = command(&mut connection, &["GET", "string"]).await;
+    assert_eq!(Frame::Bulk("hello".into()), response);
+}
+
 // In this case we test that server Responds with an Error message if a client
 // sends an unknown command
 #[tokio::test]
-async fn send_error_unknown_command() {
+async fn send_error_unknown_command() {
+    let addr = start_server().await;
+
+    // Establish a connection to the server
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    // Get a key, data is missing
+    stream
+        .write_all(b"*2\r\n$3\r\nFOO\r\n$5\r\nhello\r\n")
+        .await
+        .unwrap();
+
+    let mut response = [0; 28];
+
+    stream.read_exact(&mut response).await.unwrap();
+
+    assert_eq!(b"-ERR unknown command \'foo\'\r\n", &response);
+}
+
+// In this case we test that server Responds with an Error message if a client
+// sends an GET or SET command after a SUBSCRIBE
+#[tokio::test]
+async fn send_error_get_set_after_subscribe() {
+    let addr = start_server().await;
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    // send SUBSCRIBE command
+    stream
+        .write_all(b"*2\r\n$9\r\nsubscribe\r\n$5\r\nhello\r\n")
+        .await
+        .unwrap();
+
+    let mut response = [0; 34];
+
+    stream.read_exact(&mut response).await.unwrap();
+
+    assert_eq!(
+        &b"*3\r\n$9\r\nsubscribe\r\n$5\r\nhello\r\n:1\r\n"[..],
+        &response[..]
+    );
+
+    stream
+        .write_all(b"*3\r\n$3\r\nSET\r\n$5\r\nhello\r\n$5\r\nworld\r\n")
+        .await
+        .unwrap();
+
+    let mut response = [0; 28];
+
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"-ERR unknown command \'set\'\r\n", &response);
+
+    stream
+        .write_all(b"*2\r\n$3\r\nGET\r\n$5\r\nhello\r\n")
+        .await
+        .unwrap();
+
+    let mut response = [0; 28];
+
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"-ERR unknown command \'get\'\r\n", &response);
+}
+
+/// Every kind of value reports a `TYPE` and `OBJECT ENCODING` pair that
+/// belong together. When a new kind of value is added, it must be added to the
+/// matrix below.
+#[tokio::test]
+async fn type_and_encoding_are_consistent() {
+    let addr = start_server().await;
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    // (setup command, key, expected type, allowed encodings)
+    let matrix: &[(&[&str], &str, &str, &[&str])] = &[
+        (&["SET", "int", "12345"], "int", "string", &["int"]),
+        (&["SET", "short", "hello"], "short", "string", &["embstr"]),
+        (
+            &["SET", "long", &"x".repeat(100)],
+            "long",
+            "string",
+            &["raw"],
+        ),
+    ];
+
+    for (setup, key, expected_type, encodings) in matrix {
+        command(&mut connection, setup).await;
+
+        let response = command(&mut connection, &["TYPE", key]).await;
+        assert_eq!(
+            Frame::Simple(expected_type.to_string()),
+            response,
+            "{}",
+            key
+        );
+
+        let response = command(&mut connection, &["OBJECT", "ENCODING", key]).await;
+        assert!(
+            encodings.iter().any(|encoding| response == *encoding),
+            "{}: unexpected encoding {:?}",
+            key,
+            response
+        );
+    }
+
+    // Missing keys have no type and no encoding
+    let response = command(&mut connection, &["TYPE", "missing"]).await;
+    assert_eq!(Frame::Simple("none".to_string()), response);
+
+    let response = command(&mut connection, &["OBJECT", "ENCODING", "missing"]).await;
+    assert_eq!(Frame::Null, response);
+}
+
+/// The `GET` option of `SET` may be given before or after an expiration.
+#[tokio::test]
+async fn set_get_option_with_expiration() {
+    let addr = start_server().await;
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let response = command(
+        &mut connection,
+        &["SET", "hello", "world", "GET", "EX", "60"],
+    )
+    .await;
+    assert_eq!(Frame::Null, response);
+
+    let response = command(
+        &mut connection,
+        &["set", "hello", "again", "px", "60000", "get"],
+    )
+    .await;
+    assert_eq!(Frame::Bulk("world".into()), response);
+
+    let response = command(&mut connection, &["GET", "hello"]).await;
+    assert_eq!(Frame::Bulk("again".into()), response);
+}
+
+/// `SET ... GET` on a key holding another type is an error, and leaves the
+/// key untouched. A value that has expired, but has not been purged yet, is
+/// not returned.
+#[tokio::test]
+async fn set_get_option_checks_type_and_expiration() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        ServerBuilder::new()
+            .enable_debug(true)
+            .run(listener, tokio::signal::ctrl_c())
+            .await
+    });
+
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    command(&mut connection, &["SADD", "members", "a"]).await;
+
+    let response = command(&mut connection, &["SET", "members", "value", "GET"]).await;
+    assert_eq!(
+        Frame::Error("WRONGTYPE Operation against a key holding the wrong kind of value".into()),
+        response
+    );
+
+    let response = command(&mut connection, &["TYPE", "members"]).await;
+    assert_eq!(Frame::Simple("set".into()), response);
+
+    time::pause();
+
+    command(&mut connection, &["DEBUG", "SET-ACTIVE-EXPIRE", "0"]).await;
+    command(&mut connection, &["SET", "short", "old", "PX", "100"]).await;
+
+    time::advance(Duration::from_millis(200)).await;
+
+    let response = command(&mut connection, &["SET", "short", "new", "GET"]).await;
+    assert_eq!(Frame::Null, response);
+}
+
+/// `NX` and `XX` only set the key if it is missing or present, respectively.
+/// With `GET`, the previous value is returned even if the key was not set.
+#[tokio::test]
+async fn set_nx_and_xx_options() {
     let addr = start_server().await;
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
 
-    // Establish a connection to the server
-    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let response = command(&mut connection, &["SET", "hello", "world", "XX"]).await;
+    assert_eq!(Frame::Null, response);
 
-    // Get a key, data is missing
-    stream
-        .write_all(b"*2\r\n$3\r\nFOO\r\n$5\r\nhello\r\n")
-        .await
-        .unwrap();
+    let response = command(&mut connection, &["SET", "hello", "world", "NX"]).await;
+    assert_eq!(Frame::Simple("OK".into()), response);
 
-    let mut response = [0; 28];
+    let response = command(&mut connection, &["SET", "hello", "again", "NX", "GET"]).await;
+    assert_eq!(Frame::Bulk("world".into()), response);
 
-    stream.read_exact(&mut response).await.unwrap();
+    let response = command(&mut connection, &["SET", "hello", "again", "XX", "GET"]).await;
+    assert_eq!(Frame::Bulk("world".into()), response);
 
-    assert_eq!(b"-ERR unknown command \'foo\'\r\n", &response);
+    let response = command(&mut connection, &["GET", "hello"]).await;
+    assert_eq!(Frame::Bulk("again".into()), response);
+
+    let response = command(&mut connection, &["SET", "hello", "value", "NX", "XX"]).await;
+    assert_eq!(Frame::Error("ERR syntax error".into()), response);
 }
 
-// In this case we test that server Responds with an Error message if a client
-// sends an GET or SET command after a SUBSCRIBE
+/// `SET` options are matched regardless of case, as sent by `redis-cli`.
 #[tokio::test]
-async fn send_error_get_set_after_subscribe() {
+async fn set_options_are_case_insensitive() {
     let addr = start_server().await;
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
 
-    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let response = command(&mut connection, &["set", "hello", "world", "ex", "60"]).await;
+    assert_eq!(Frame::Simple("OK".into()), response);
 
-    // send SUBSCRIBE command
-    stream
-        .write_all(b"*2\r\n$9\r\nsubscribe\r\n$5\r\nhello\r\n")
+    let response = command(&mut connection, &["TTL", "hello"]).await;
+    assert_eq!(Frame::Integer(60), response);
+
+    let response = command(&mut connection, &["SET", "hello", "again", "Px", "5000"]).await;
+    assert_eq!(Frame::Simple("OK".into()), response);
+
+    let response = command(&mut connection, &["TTL", "hello"]).await;
+    assert_eq!(Frame::Integer(5), response);
+}
+
+/// An unsupported `SET` option is rejected with a syntax error, without
+/// setting the key, and the connection stays usable.
+#[tokio::test]
+async fn set_unknown_option_is_rejected() {
+    let addr = start_server().await;
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let response = command(&mut connection, &["SET", "k", "v", "FOO"]).await;
+    assert_eq!(Frame::Error("ERR syntax error".into()), response);
+
+    let response = command(&mut connection, &["GET", "k"]).await;
+    assert_eq!(Frame::Null, response);
+}
+
+/// Commands called with too few or too many arguments are rejected with the
+/// same error as Redis.
+#[tokio::test]
+async fn wrong_number_of_arguments_is_rejected() {
+    let addr = start_server().await;
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let response = command(&mut connection, &["GET"]).await;
+    assert_eq!(
+        Frame::Error("ERR wrong number of arguments for 'get' command".into()),
+        response
+    );
+
+    let response = command(&mut connection, &["SET", "k"]).await;
+    assert_eq!(
+        Frame::Error("ERR wrong number of arguments for 'set' command".into()),
+        response
+    );
+
+    let response = command(&mut connection, &["TTL", "a", "b"]).await;
+    assert_eq!(
+        Frame::Error("ERR wrong number of arguments for 'ttl' command".into()),
+        response
+    );
+
+    // The command name is reported in lower case, whatever its case.
+    let response = command(&mut connection, &["Del"]).await;
+    assert_eq!(
+        Frame::Error("ERR wrong number of arguments for 'del' command".into()),
+        response
+    );
+}
+
+/// Other malformed commands are answered with an error as well, rather than
+/// closing the connection.
+#[tokio::test]
+async fn malformed_command_keeps_connection_open() {
+    let addr = start_server().await;
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    // Missing argument
+    let response = command(&mut connection, &["GET"]).await;
+    assert!(matches!(response, Frame::Error(msg) if msg.starts_with("ERR ")));
+
+    // Extra argument
+    let response = command(&mut connection, &["GET", "a", "b"]).await;
+    assert!(matches!(response, Frame::Error(msg) if msg.starts_with("ERR ")));
+
+    // Error codes of the command are kept
+    let response = command(&mut connection, &["INCRBYFLOAT", "k", "abc"]).await;
+    assert_eq!(
+        Frame::Error("ERR value is not a valid float".into()),
+        response
+    );
+
+    let response = command(&mut connection, &["PING"]).await;
+    assert_eq!(Frame::Simple("PONG".into()), response);
+}
+
+/// `COMMAND COUNT` agrees with the commands listed by `COMMAND`, and every
+/// listed command is one the server actually dispatches.
+#[tokio::test]
+async fn command_count_matches_wired_commands() {
+    let addr = start_server().await;
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let count = match command(&mut connection, &["COMMAND", "COUNT"]).await {
+        Frame::Integer(count) => count as usize,
+        frame => panic!("unexpected response {:?}", frame),
+    };
+
+    let infos = match command(&mut connection, &["COMMAND"]).await {
+        Frame::Array(infos) => infos,
+        frame => panic!("unexpected response {:?}", frame),
+    };
+    assert_eq!(count, infos.len());
+
+    for info in infos {
+        let name = match &info {
+            Frame::Array(fields) => fields[0].clone(),
+            frame => panic!("unexpected info {:?}", frame),
+        };
+
+        // Parsing a bare command name fails for commands with required
+        // arguments, but it is only reported as unknown if it isn't wired up.
+        let parsed = Command::from_frame(Frame::Array(vec![name]));
+        assert!(
+            !matches!(parsed, Ok(Command::Unknown(_))),
+            "{:?} is not wired up",
+            info
+        );
+    }
+
+    let response = command(&mut connection, &["COMMAND", "INFO", "get", "nope"]).await;
+    assert_eq!(
+        Frame::Array(vec![
+            Frame::Array(vec![
+                Frame::Bulk("get".into()),
+                Frame::Integer(2),
+                Frame::Array(vec![
+                    Frame::Simple("readonly".into()),
+                    Frame::Simple("fast".into()),
+                ]),
+                Frame::Integer(1),
+                Frame::Integer(1),
+                Frame::Integer(1),
+            ]),
+            Frame::Null,
+        ]),
+        response
+    );
+
+    // Variadic commands report a negative arity, and `-1` as the last key
+    // when their keys run to the end of the arguments.
+    let response = command(&mut connection, &["COMMAND", "INFO", "DEL"]).await;
+    let info = match response {
+        Frame::Array(mut infos) => infos.remove(0),
+        frame => panic!("unexpected response {:?}", frame),
+    };
+    match info {
+        Frame::Array(fields) => {
+            assert_eq!(Frame::Integer(-2), fields[1]);
+            assert_eq!(Frame::Array(vec![Frame::Simple("write".into())]), fields[2]);
+            assert_eq!(
+                &[Frame::Integer(1), Frame::Integer(-1), Frame::Integer(1)],
+                &fields[3..]
+            );
+        }
+        frame => panic!("unexpected info {:?}", frame),
+    }
+
+    let response = command(&mut connection, &["COMMAND", "DOCS", "SET"]).await;
+    assert_eq!(
+        Frame::Array(vec![
+            Frame::Bulk("set".into()),
+            Frame::Array(vec![Frame::Bulk("arity".into()), Frame::Integer(-3)]),
+        ]),
+        response
+    );
+}
+
+#[tokio::test]
+async fn info_used_memory_tracks_stored_bytes() {
+    let addr = start_server().await;
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let before = info_field(&mut connection, "used_memory").await;
+    assert_eq!(0, info_field(&mut connection, "maxmemory").await);
+
+    // Setting a key grows `used_memory` by at least the key and value lengths,
+    // plus a bounded per-entry overhead.
+    let value = "x".repeat(100_000);
+    command(&mut connection, &["SET", "big", &value]).await;
+
+    let after = info_field(&mut connection, "used_memory").await;
+    assert!(after >= before + 100_003, "{} -> {}", before, after);
+    assert!(after <= before + 100_003 + 1024, "{} -> {}", before, after);
+
+    // Overwriting the key only accounts for the new value.
+    command(&mut connection, &["SET", "big", "small"]).await;
+    let overwritten = info_field(&mut connection, "used_memory").await;
+    assert!(overwritten < before + 1024, "{} -> {}", before, overwritten);
+
+    // Deleting the key releases its bytes.
+    command(&mut connection, &["DEL", "big"]).await;
+    assert_eq!(before, info_field(&mut connection, "used_memory").await);
+}
+
+#[tokio::test]
+async fn info_stats_counts_network_bytes() {
+    let addr = start_server().await;
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let value = "x".repeat(100_000);
+    command(&mut connection, &["SET", "big", &value]).await;
+    command(&mut connection, &["GET", "big"]).await;
+
+    // The server has read every byte the client wrote, including the `INFO`
+    // request, and written every byte the client read before it.
+    let input = info_field(&mut connection, "total_net_input_bytes").await;
+    assert_eq!(connection.bytes_written(), input as u64);
+    assert!(input > 100_000, "{}", input);
+
+    let read = connection.bytes_read();
+    let output = info_field(&mut connection, "total_net_output_bytes").await;
+    assert_eq!(read, output as u64);
+    assert!(output > 100_000, "{}", output);
+}
+
+#[tokio::test]
+async fn maxmemory_rejects_writes_once_exceeded() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        ServerBuilder::new()
+            .maxmemory(1000)
+            .run(listener, tokio::signal::ctrl_c())
+            .await
+    });
+
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+    assert_eq!(1000, info_field(&mut connection, "maxmemory").await);
+
+    // The write that crosses the limit is accepted, as its size is unknown
+    // until it is applied.
+    let value = "x".repeat(2000);
+    let response = command(&mut connection, &["SET", "big", &value]).await;
+    assert_eq!(Frame::Simple("OK".into()), response);
+
+    let response = command(&mut connection, &["SET", "other", "value"]).await;
+    assert_eq!(
+        Frame::Error("OOM command not allowed when used memory > 'maxmemory'.".into()),
+        response
+    );
+
+    // Reads and deletes are still allowed, and freeing memory allows writes
+    // again.
+    let response = command(&mut connection, &["GET", "other"]).await;
+    assert_eq!(Frame::Null, response);
+
+    command(&mut connection, &["DEL", "big"]).await;
+
+    let response = command(&mut connection, &["SET", "other", "value"]).await;
+    assert_eq!(Frame::Simple("OK".into()), response);
+}
+
+#[tokio::test]
+async fn max_value_size_rejects_larger_values() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        ServerBuilder::new()
+            .max_value_size(100)
+            .run(listener, tokio::signal::ctrl_c())
+            .await
+    });
+
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let value = "x".repeat(100);
+    let response = command(&mut connection, &["SET", "key", &value]).await;
+    assert_eq!(Frame::Simple("OK".into()), response);
+
+    let value = "y".repeat(101);
+    let response = command(&mut connection, &["SET", "key", &value]).await;
+    assert_eq!(
+        Frame::Error("ERR value exceeds maximum allowed size".into()),
+        response
+    );
+
+    // The rejected value is not stored.
+    let response = command(&mut connection, &["GET", "key"]).await;
+    assert_eq!(Frame::Bulk("x".repeat(100).into()), response);
+}
+
+#[tokio::test]
+async fn read_only_rejects_writes() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        ServerBuilder::new()
+            .read_only(true)
+            .run(listener, tokio::signal::ctrl_c())
+            .await
+    });
+
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let readonly = Frame::Error("READONLY You can't write against a read only replica.".into());
+
+    let response = command(&mut connection, &["SET", "key", "value"]).await;
+    assert_eq!(readonly, response);
+
+    let response = command(&mut connection, &["DEL", "key"]).await;
+    assert_eq!(readonly, response);
+
+    // Reads are still applied.
+    let response = command(&mut connection, &["GET", "key"]).await;
+    assert_eq!(Frame::Null, response);
+}
+
+#[tokio::test]
+async fn config_set_and_get() {
+    let addr = start_server().await;
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let response = command(&mut connection, &["CONFIG", "GET", "maxmemory"]).await;
+    assert_eq!(
+        Frame::Array(vec![
+            Frame::Bulk("maxmemory".into()),
+            Frame::Bulk("0".into())
+        ]),
+        response
+    );
+
+    let response = command(&mut connection, &["CONFIG", "SET", "maxmemory", "1"]).await;
+    assert_eq!(Frame::Simple("OK".into()), response);
+
+    // Patterns are matched against every parameter.
+    let response = command(&mut connection, &["CONFIG", "GET", "max*"]).await;
+    assert_eq!(
+        Frame::Array(vec![
+            Frame::Bulk("maxmemory".into()),
+            Frame::Bulk("1".into()),
+            Frame::Bulk("maxclients".into()),
+            Frame::Bulk("250".into()),
+        ]),
+        response
+    );
+
+    // The new limit applies right away.
+    let response = command(&mut connection, &["SET", "key", "value"]).await;
+    assert_eq!(Frame::Simple("OK".into()), response);
+    let response = command(&mut connection, &["SET", "other", "value"]).await;
+    assert_eq!(
+        Frame::Error("OOM command not allowed when used memory > 'maxmemory'.".into()),
+        response
+    );
+
+    // Memory values may be given with a unit.
+    let response = command(&mut connection, &["CONFIG", "SET", "maxmemory", "1GB"]).await;
+    assert_eq!(Frame::Simple("OK".into()), response);
+    let response = command(&mut connection, &["CONFIG", "GET", "maxmemory"]).await;
+    assert_eq!(
+        Frame::Array(vec![
+            Frame::Bulk("maxmemory".into()),
+            Frame::Bulk("1073741824".into())
+        ]),
+        response
+    );
+
+    let response = command(&mut connection, &["CONFIG", "SET", "maxmemory", "1xb"]).await;
+    assert_eq!(
+        Frame::Error(
+            "ERR CONFIG SET failed (possibly related to argument 'maxmemory') - argument must be a memory value".into()
+        ),
+        response
+    );
+
+    // Idle connections are never closed, so only `0` is accepted.
+    let response = command(&mut connection, &["CONFIG", "SET", "timeout", "0"]).await;
+    assert_eq!(Frame::Simple("OK".into()), response);
+    let response = command(&mut connection, &["CONFIG", "SET", "timeout", "10"]).await;
+    assert_eq!(
+        Frame::Error(
+            "ERR CONFIG SET failed (possibly related to argument 'timeout') - idle connections are never closed, only 0 is supported".into()
+        ),
+        response
+    );
+
+    let response = command(&mut connection, &["CONFIG", "SET", "appendonly", "yes"]).await;
+    assert_eq!(
+        Frame::Error(
+            "ERR CONFIG SET failed (possibly related to argument 'appendonly') - can't set immutable config".into()
+        ),
+        response
+    );
+
+    let response = command(&mut connection, &["CONFIG", "SET", "nosuch", "1"]).await;
+    assert_eq!(
+        Frame::Error("ERR Unknown option or number of arguments for CONFIG SET - 'nosuch'".into()),
+        response
+    );
+}
+
+/// Lowering `maxclients` keeps the connections already open, and new ones
+/// wait until enough of them are closed. Raising it serves them right away.
+#[tokio::test]
+async fn config_set_maxclients() {
+    let addr = start_server().await;
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+    let mut second = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let response = command(&mut second, &["PING"]).await;
+    assert_eq!(Frame::Simple("PONG".into()), response);
+
+    let response = command(&mut connection, &["CONFIG", "SET", "maxclients", "1"]).await;
+    assert_eq!(Frame::Simple("OK".into()), response);
+
+    let response = command(&mut connection, &["CONFIG", "GET", "maxclients"]).await;
+    assert_eq!(
+        Frame::Array(vec![
+            Frame::Bulk("maxclients".into()),
+            Frame::Bulk("1".into())
+        ]),
+        response
+    );
+
+    // Both connections are still served.
+    let response = command(&mut second, &["PING"]).await;
+    assert_eq!(Frame::Simple("PONG".into()), response);
+
+    let mut third = Connection::new(TcpStream::connect(addr).await.unwrap());
+    let ping = Frame::Array(vec![Frame::Bulk("PING".into())]);
+    third.write_frame(&ping).await.unwrap();
+
+    time::timeout(Duration::from_millis(100), third.read_frame())
         .await
-        .unwrap();
+        .unwrap_err();
 
-    let mut response = [0; 34];
+    // Closing one connection leaves one, which is the limit.
+    drop(second);
 
-    stream.read_exact(&mut response).await.unwrap();
+    time::timeout(Duration::from_millis(100), third.read_frame())
+        .await
+        .unwrap_err();
+
+    let response = command(&mut connection, &["CONFIG", "SET", "maxclients", "3"]).await;
+    assert_eq!(Frame::Simple("OK".into()), response);
 
+    let response = third.read_frame().await.unwrap().unwrap();
+    assert_eq!(Frame::Simple("PONG".into()), response);
+
+    let response = command(&mut connection, &["CONFIG", "SET", "maxclients", "0"]).await;
     assert_eq!(
-        &b"*3\r\n$9\r\nsubscribe\r\n$5\r\nhello\r\n:1\r\n"[..],
-        &response[..]
+        Frame::Error(
+            "ERR CONFIG SET failed (possibly related to argument 'maxclients') - argument must be between 1 and 4294967295 inclusive".into()
+        ),
+        response
     );
+}
 
-    stream
-        .write_all(b"*3\r\n$3\r\nSET\r\n$5\r\nhello\r\n$5\r\nworld\r\n")
+#[tokio::test]
+async fn migrate_moves_key_between_servers() {
+    let source = start_server().await;
+    let target = start_server().await;
+    let mut source_conn = Connection::new(TcpStream::connect(source).await.unwrap());
+    let mut target_conn = Connection::new(TcpStream::connect(target).await.unwrap());
+
+    let port = target.port().to_string();
+
+    command(&mut source_conn, &["SET", "moved", "value", "EX", "100"]).await;
+
+    // The key, and its expiration, move to the target.
+    let response = command(
+        &mut source_conn,
+        &["MIGRATE", "127.0.0.1", &port, "moved", "0", "1000"],
+    )
+    .await;
+    assert_eq!(Frame::Simple("OK".into()), response);
+    let response = command(&mut source_conn, &["GET", "moved"]).await;
+    assert_eq!(Frame::Null, response);
+    let response = command(&mut target_conn, &["GET", "moved"]).await;
+    assert_eq!(Frame::Bulk("value".into()), response);
+    match command(&mut target_conn, &["TTL", "moved"]).await {
+        Frame::Integer(ttl) => assert!(ttl > 0 && ttl <= 100, "{}", ttl),
+        frame => panic!("unexpected response {:?}", frame),
+    }
+
+    let response = command(
+        &mut source_conn,
+        &["MIGRATE", "127.0.0.1", &port, "missing", "0", "1000"],
+    )
+    .await;
+    assert_eq!(Frame::Simple("NOKEY".into()), response);
+
+    // With `COPY`, the key is kept.
+    command(&mut source_conn, &["SET", "copied", "value"]).await;
+    let response = command(
+        &mut source_conn,
+        &["MIGRATE", "127.0.0.1", &port, "copied", "0", "1000", "COPY"],
+    )
+    .await;
+    assert_eq!(Frame::Simple("OK".into()), response);
+    let response = command(&mut source_conn, &["GET", "copied"]).await;
+    assert_eq!(Frame::Bulk("value".into()), response);
+
+    // The key now exists on the target, so it is only replaced with
+    // `REPLACE`, and kept on failure.
+    command(&mut source_conn, &["SET", "copied", "new"]).await;
+    let response = command(
+        &mut source_conn,
+        &["MIGRATE", "127.0.0.1", &port, "copied", "0", "1000"],
+    )
+    .await;
+    assert_eq!(
+        Frame::Error(
+            "ERR Target instance replied with error: BUSYKEY Target key name already exists."
+                .into()
+        ),
+        response
+    );
+    let response = command(&mut source_conn, &["GET", "copied"]).await;
+    assert_eq!(Frame::Bulk("new".into()), response);
+
+    let response = command(
+        &mut source_conn,
+        &[
+            "MIGRATE",
+            "127.0.0.1",
+            &port,
+            "copied",
+            "0",
+            "1000",
+            "REPLACE",
+        ],
+    )
+    .await;
+    assert_eq!(Frame::Simple("OK".into()), response);
+    let response = command(&mut target_conn, &["GET", "copied"]).await;
+    assert_eq!(Frame::Bulk("new".into()), response);
+
+    // Nothing listens on the port of a dropped listener, so the connection
+    // fails and the key is kept.
+    let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let closed_port = closed.local_addr().unwrap().port().to_string();
+    drop(closed);
+
+    command(&mut source_conn, &["SET", "kept", "value"]).await;
+    let response = command(
+        &mut source_conn,
+        &["MIGRATE", "127.0.0.1", &closed_port, "kept", "0", "1000"],
+    )
+    .await;
+    assert_eq!(
+        Frame::Error("IOERR error or timeout connecting to the client".into()),
+        response
+    );
+    let response = command(&mut source_conn, &["GET", "kept"]).await;
+    assert_eq!(Frame::Bulk("value".into()), response);
+}
+
+/// A client that does not read its replies does not hold up transactions, nor
+/// the commands queued behind them.
+#[tokio::test]
+async fn client_not_reading_does_not_block_exec() {
+    let addr = start_server().await;
+    let mut stalled = TcpStream::connect(addr).await.unwrap();
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let value = "x".repeat(4 * 1024 * 1024);
+    command(&mut connection, &["SET", "big", &value]).await;
+
+    // More replies than the socket buffers hold, so the server is left
+    // waiting to write them.
+    for _ in 0..16 {
+        stalled
+            .write_all(b"*2\r\n$3\r\nGET\r\n$3\r\nbig\r\n")
+            .await
+            .unwrap();
+    }
+    time::sleep(Duration::from_millis(100)).await;
+
+    let transaction = async {
+        command(&mut connection, &["MULTI"]).await;
+        command(&mut connection, &["SET", "other", "value"]).await;
+        command(&mut connection, &["EXEC"]).await
+    };
+    let response = time::timeout(Duration::from_millis(500), transaction)
         .await
-        .unwrap();
+        .expect("EXEC waited for the stalled client");
+    assert_eq!(Frame::Array(vec![Frame::Simple("OK".into())]), response);
 
-    let mut response = [0; 28];
+    let response = command(&mut connection, &["GET", "other"]).await;
+    assert_eq!(Frame::Bulk("value".into()), response);
 
-    stream.read_exact(&mut response).await.unwrap();
-    assert_eq!(b"-ERR unknown command \'set\'\r\n", &response);
+    drop(stalled);
+}
 
-    stream
-        .write_all(b"*2\r\n$3\r\nGET\r\n$5\r\nhello\r\n")
+/// A target that does not reply does not hold up transactions, nor the
+/// commands queued behind them, while `MIGRATE` waits for it.
+#[tokio::test]
+async fn migrate_waiting_for_target_does_not_block_exec() {
+    let addr = start_server().await;
+    let mut migrating = Connection::new(TcpStream::connect(addr).await.unwrap());
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    // Connections are queued by the kernel, but never accepted or replied to.
+    let silent = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = silent.local_addr().unwrap().port().to_string();
+
+    command(&mut connection, &["SET", "kept", "value"]).await;
+
+    let args = ["MIGRATE", "127.0.0.1", &port, "kept", "0", "1000"];
+    let frame = Frame::Array(
+        args.iter()
+            .map(|arg| Frame::Bulk(Bytes::copy_from_slice(arg.as_bytes())))
+            .collect(),
+    );
+    migrating.write_frame(&frame).await.unwrap();
+    time::sleep(Duration::from_millis(100)).await;
+
+    let transaction = async {
+        command(&mut connection, &["MULTI"]).await;
+        command(&mut connection, &["SET", "other", "value"]).await;
+        command(&mut connection, &["EXEC"]).await
+    };
+    let response = time::timeout(Duration::from_millis(500), transaction)
         .await
-        .unwrap();
+        .expect("EXEC waited for the target");
+    assert_eq!(Frame::Array(vec![Frame::Simple("OK".into())]), response);
 
-    let mut response = [0; 28];
+    let response = migrating.read_frame().await.unwrap().unwrap();
+    assert_eq!(
+        Frame::Error("IOERR error or timeout reading to target instance".into()),
+        response
+    );
+    let response = command(&mut connection, &["GET", "kept"]).await;
+    assert_eq!(Frame::Bulk("value".into()), response);
 
-    stream.read_exact(&mut response).await.unwrap();
-    assert_eq!(b"-ERR unknown command \'get\'\r\n", &response);
+    drop(silent);
+}
+
+/// Functions registered on the server are invoked with `FCALL`, and their
+/// writes, rather than `FCALL`, are logged to the append-only file.
+#[tokio::test]
+async fn fcall_invokes_registered_function() {
+    let path = std::env::temp_dir().join(format!("mini-redis-{}.fcall.aof", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    // Counts a request for `keys[0]`, unless `args[0]` requests were already
+    // counted.
+    let server = ServerBuilder::new().appendonly(&path).register_function(
+        "rate_limit",
+        |store, keys, args| {
+            let limit: u64 = std::str::from_utf8(&args[0])?.parse()?;
+            let count: u64 = match store.get(&keys[0])? {
+                Some(count) => std::str::from_utf8(&count)?.parse()?,
+                None => 0,
+            };
+
+            if count >= limit {
+                return Ok(Frame::Integer(0));
+            }
+
+            store.set(keys[0].clone(), (count + 1).to_string().into(), None);
+            Ok(Frame::Integer(1))
+        },
+    );
+
+    let (shutdown, rx) = oneshot::channel();
+    let handle = tokio::spawn(async move { server.run(listener, rx).await });
+
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    for expected in [1, 1, 0] {
+        let response = command(&mut connection, &["FCALL", "rate_limit", "1", "user", "2"]).await;
+        assert_eq!(Frame::Integer(expected), response);
+    }
+
+    let response = command(&mut connection, &["GET", "user"]).await;
+    assert_eq!(Frame::Bulk("2".into()), response);
+
+    // Errors returned by the function are sent to the client.
+    command(&mut connection, &["SET", "other", "many"]).await;
+    let response = command(&mut connection, &["FCALL", "rate_limit", "1", "other", "2"]).await;
+    assert_eq!(
+        Frame::Error("invalid digit found in string".into()),
+        response
+    );
+
+    let response = command(&mut connection, &["FCALL", "missing", "0"]).await;
+    assert_eq!(Frame::Error("ERR Function not found".into()), response);
+
+    let response = command(&mut connection, &["FCALL", "rate_limit", "3", "user"]).await;
+    assert_eq!(
+        Frame::Error("ERR Number of keys can't be greater than number of args".into()),
+        response
+    );
+
+    shutdown.send(()).unwrap();
+    handle.await.unwrap();
+
+    // The server replaying the file does not have the function.
+    let (addr, _shutdown, _server) = start_appendonly_server(&path).await;
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let response = command(&mut connection, &["GET", "user"]).await;
+    assert_eq!(Frame::Bulk("2".into()), response);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+/// A server started with `server::spawn` runs in the background until its
+/// handle shuts it down.
+#[tokio::test]
+async fn spawn_and_shutdown() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let expected_addr = listener.local_addr().unwrap();
+
+    let (handle, addr) = server::spawn(listener);
+    assert_eq!(expected_addr, addr);
+    assert_eq!(addr, handle.addr());
+
+    let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let response = command(&mut connection, &["SET", "hello", "world"]).await;
+    assert_eq!(Frame::Simple("OK".into()), response);
+    let response = command(&mut connection, &["GET", "hello"]).await;
+    assert_eq!(Frame::Bulk("world".into()), response);
+
+    // Once shut down, open connections are closed and new ones are refused.
+    handle.shutdown().await;
+
+    assert!(connection.read_frame().await.unwrap().is_none());
+    assert!(TcpStream::connect(addr).await.is_err());
+}
+
+/// Sends a command made of `args` on `connection` and returns the response.
+async fn command(connection: &mut Connection, args: &[&str]) -> Frame {
+    let frame = Frame::Array(
+        args.iter()
+            .map(|arg| Frame::Bulk(Bytes::copy_from_slice(arg.as_bytes())))
+            .collect(),
+    );
+
+    connection.write_frame(&frame).await.unwrap();
+    connection.read_frame().await.unwrap().unwrap()
+}
+
+/// Returns the value of the numeric `field` of the `INFO` response.
+async fn info_field(connection: &mut Connection, field: &str) -> usize {
+    let info = match command(connection, &["INFO"]).await {
+        Frame::Bulk(info) => String::from_utf8(info.to_vec()).unwrap(),
+        frame => panic!("unexpected response {:?}", frame),
+    };
+
+    info.lines()
+        .find_map(|line| line.strip_prefix(field)?.strip_prefix(':'))
+        .unwrap_or_else(|| panic!("missing {} in {:?}", field, info))
+        .parse()
+        .unwrap()
+}
+
+/// A subscription confirmation of the given `kind` for `name`.
+fn confirmation(kind: &str, name: &str, count: i64) -> Frame {
+    Frame::Array(vec![
+        Frame::Bulk(Bytes::copy_from_slice(kind.as_bytes())),
+        Frame::Bulk(Bytes::copy_from_slice(name.as_bytes())),
+        Frame::Integer(count),
+    ])
+}
+
+/// The reply of the given `kind` to unsubscribing from all channels or
+/// patterns without holding any.
+fn unsubscribe_none(kind: &str, count: i64) -> Frame {
+    Frame::Array(vec![
+        Frame::Bulk(Bytes::copy_from_slice(kind.as_bytes())),
+        Frame::Null,
+        Frame::Integer(count),
+    ])
+}
+
+/// An array frame holding `parts` as bulk strings.
+fn message(parts: &[&str]) -> Frame {
+    Frame::Array(
+        parts
+            .iter()
+            .map(|part| Frame::Bulk(Bytes::copy_from_slice(part.as_bytes())))
+            .collect(),
+    )
+}
+
+/// Returns the last entry of an array frame.
+fn last_entry(frame: Frame) -> Frame {
+    match frame {
+        Frame::Array(mut entries) => entries.pop().unwrap(),
+        frame => panic!("not an array frame: {:?}", frame),
+    }
 }
 
 async fn start_server() -> SocketAddr {
@@ -405,3 +3027,48 @@ async fn start_server() -> SocketAddr {
 
     addr
 }
+
+async fn start_appendonly_server(path: &Path) -> (SocketAddr, oneshot::Sender<()>, JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let (shutdown, rx) = oneshot::channel();
+    let server = ServerBuilder::new().appendonly(path);
+    let handle = tokio::spawn(async move { server.run(listener, rx).await });
+
+    (addr, shutdown, handle)
+}
+
+/// Polls `GET key` until it returns `expected`, giving up after a few seconds.
+async fn wait_for_value(connection: &mut Connection, key: &str, expected: Frame) {
+    let poll = async {
+        while command(connection, &["GET", key]).await != expected {
+            time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+
+    time::timeout(Duration::from_secs(5), poll)
+        .await
+        .unwrap_or_else(|_| panic!("GET {} never returned {:?}", key, expected));
+}
+
+/// Log output captured by a test subscriber.
+#[derive(Clone, Default)]
+struct Logs(Arc<Mutex<Vec<u8>>>);
+
+impl Logs {
+    fn contents(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+impl std::io::Write for Logs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
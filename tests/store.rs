@@ -0,0 +1,91 @@
+use mini_redis::cmd::{Get, Set};
+use mini_redis::{Connection, Frame, Store};
+
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+
+/// A `Store` keeping values in a plain map, without expiration or pub/sub.
+#[derive(Default)]
+struct MapStore {
+    values: Mutex<HashMap<String, Bytes>>,
+}
+
+impl Store for MapStore {
+    fn get(&self, key: &str) -> mini_redis::Result<Option<Bytes>> {
+        if key == "wrongtype" {
+            return Err("WRONGTYPE Operation against a key holding the wrong kind of value".into());
+        }
+
+        Ok(self.values.lock().unwrap().get(key).cloned())
+    }
+
+    fn set(&self, key: String, value: Bytes, _expire: Option<Duration>) -> Option<Bytes> {
+        self.values.lock().unwrap().insert(key, value)
+    }
+
+    fn del(&self, keys: &[String]) -> usize {
+        let mut values = self.values.lock().unwrap();
+        keys.iter()
+            .filter(|key| values.remove(*key).is_some())
+            .count()
+    }
+
+    fn subscribe(&self, _channel: String) -> broadcast::Receiver<Bytes> {
+        broadcast::channel(1).1
+    }
+
+    fn publish(&self, _channel: &str, _message: Bytes) -> usize {
+        0
+    }
+}
+
+/// `Get` and `Set` can be applied to a `Store` other than the server's, with
+/// their replies written to the connection.
+#[tokio::test]
+async fn commands_apply_to_custom_store() {
+    let (mut server, mut client) = connection_pair().await;
+    let store = MapStore::default();
+
+    Get::new("hello").apply(&store, &mut server).await.unwrap();
+    assert_eq!(Some(Frame::Null), client.read_frame().await.unwrap());
+
+    Set::new("hello", "world".into(), None)
+        .apply(&store, &mut server)
+        .await
+        .unwrap();
+    assert_eq!(
+        Some(Frame::Simple("OK".to_string())),
+        client.read_frame().await.unwrap()
+    );
+
+    Get::new("hello").apply(&store, &mut server).await.unwrap();
+    assert_eq!(
+        Some(Frame::Bulk("world".into())),
+        client.read_frame().await.unwrap()
+    );
+
+    // Errors returned by the store are sent to the client.
+    Get::new("wrongtype")
+        .apply(&store, &mut server)
+        .await
+        .unwrap();
+    assert!(matches!(
+        client.read_frame().await.unwrap(),
+        Some(Frame::Error(msg)) if msg.starts_with("WRONGTYPE")
+    ));
+}
+
+/// Returns the two ends of a local TCP connection.
+async fn connection_pair() -> (Connection, Connection) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let client = TcpStream::connect(addr).await.unwrap();
+    let (server, _) = listener.accept().await.unwrap();
+
+    (Connection::new(server), Connection::new(client))
+}